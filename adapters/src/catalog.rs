@@ -1,8 +1,16 @@
-use crate::{DeCollectionHandle, DeZSetHandle, SerOutputBatchHandle};
-use dbsp::{algebra::ZRingValue, CollectionHandle, DBData, DBWeight};
-use serde::Deserialize;
+use crate::{DeCollectionHandle, DeZSetHandle, Schema, SerOutputBatchHandle};
+use dbsp::{algebra::ZRingValue, CollectionHandle, DBData, DBWeight, OrdZSet, OutputHandle};
+use log::warn;
+use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
+/// Schemas of all streams in a catalog.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CatalogSchemas {
+    pub inputs: BTreeMap<String, Schema>,
+    pub outputs: BTreeMap<String, Schema>,
+}
+
 /// A catalog of input and output stream handles of a circuit.
 ///
 /// An instance of this type is created by the user (or auto-generated code)
@@ -11,10 +19,19 @@ use std::collections::BTreeMap;
 /// to DBSP streams
 /// (See [`InputFormat::new_parser()`](`crate::InputFormat::new_parser`)
 /// method).
+///
+/// Input and output streams can have [schemas](`Schema`) describing the
+/// shape of their records.  Schemas are derived automatically by methods
+/// that register typed handles, such as
+/// [`Self::register_input_zset_handle`], or set explicitly, e.g., by code
+/// generated by the SQL compiler.
 #[derive(Default)]
 pub struct Catalog {
     input_collection_handles: BTreeMap<String, Box<dyn DeCollectionHandle>>,
     output_batch_handles: BTreeMap<String, Box<dyn SerOutputBatchHandle>>,
+    taps: BTreeMap<String, Box<dyn SerOutputBatchHandle>>,
+    tap_schemas: BTreeMap<String, Schema>,
+    schemas: CatalogSchemas,
 }
 
 impl Catalog {
@@ -23,12 +40,21 @@ impl Catalog {
         Self::default()
     }
 
+    /// Add a named input Z-set handle to the catalog.
+    ///
+    /// The schema of the stream is derived from `K`, if possible (see
+    /// [`Schema::from_type`]).  Otherwise, the stream has no schema and the
+    /// reason is logged as a warning.
     pub fn register_input_zset_handle<K, R>(&mut self, name: &str, handle: CollectionHandle<K, R>)
     where
         K: DBData + for<'de> Deserialize<'de>,
         R: DBWeight + ZRingValue,
     {
         self.register_input_collection_handle(name, DeZSetHandle::new(handle));
+        match Schema::from_type::<K>() {
+            Ok(schema) => self.set_input_schema(name, schema),
+            Err(e) => warn!("input stream '{name}' has no schema: {e}"),
+        }
     }
 
     /// Add a named input stream handle to the catalog.
@@ -49,6 +75,73 @@ impl Catalog {
             .insert(name.to_owned(), Box::new(handle));
     }
 
+    /// Add a named output Z-set handle to the catalog.
+    ///
+    /// The schema of the stream is derived from `K`, if possible (see
+    /// [`Schema::from_type`]).  Otherwise, the stream has no schema and the
+    /// reason is logged as a warning.
+    pub fn register_output_zset_handle<K, R>(
+        &mut self,
+        name: &str,
+        handle: OutputHandle<OrdZSet<K, R>>,
+    ) where
+        K: DBData + Serialize + for<'de> Deserialize<'de>,
+        R: DBWeight + Into<i64>,
+    {
+        self.register_output_batch_handle(name, handle);
+        match Schema::from_type::<K>() {
+            Ok(schema) => self.set_output_schema(name, schema),
+            Err(e) => warn!("output stream '{name}' has no schema: {e}"),
+        }
+    }
+
+    /// Set the schema of input stream `name`.
+    pub fn set_input_schema(&mut self, name: &str, schema: Schema) {
+        self.schemas.inputs.insert(name.to_owned(), schema);
+    }
+
+    /// Set the schema of output stream `name`.
+    pub fn set_output_schema(&mut self, name: &str, schema: Schema) {
+        self.schemas.outputs.insert(name.to_owned(), schema);
+    }
+
+    /// Add a named tap to the catalog.
+    ///
+    /// Taps are created with [`Stream::tap`](`dbsp::Stream::tap`) and allow
+    /// clients to inspect internal streams of a running circuit on demand
+    /// (see [`Controller::attach_tap`](`crate::Controller::attach_tap`)).
+    pub fn register_tap<H>(&mut self, name: &str, handle: H)
+    where
+        H: SerOutputBatchHandle + 'static,
+    {
+        self.taps.insert(name.to_owned(), Box::new(handle));
+    }
+
+    /// Add a named tap on a Z-set stream to the catalog.
+    ///
+    /// The schema of the tap is derived from `K`, if possible (see
+    /// [`Schema::from_type`]).  Otherwise, the tap has no schema and the
+    /// reason is logged as a warning.
+    pub fn register_tap_zset_handle<K, R>(
+        &mut self,
+        name: &str,
+        handle: OutputHandle<OrdZSet<K, R>>,
+    ) where
+        K: DBData + Serialize + for<'de> Deserialize<'de>,
+        R: DBWeight + Into<i64>,
+    {
+        self.register_tap(name, handle);
+        match Schema::from_type::<K>() {
+            Ok(schema) => self.set_tap_schema(name, schema),
+            Err(e) => warn!("tap '{name}' has no schema: {e}"),
+        }
+    }
+
+    /// Set the schema of tap `name`.
+    pub fn set_tap_schema(&mut self, name: &str, schema: Schema) {
+        self.tap_schemas.insert(name.to_owned(), schema);
+    }
+
     /// Look up an input stream handle by name.
     pub fn input_collection_handle(&self, name: &str) -> Option<&dyn DeCollectionHandle> {
         self.input_collection_handles.get(name).map(|b| &**b)
@@ -58,4 +151,34 @@ impl Catalog {
     pub fn output_batch_handle(&self, name: &str) -> Option<&dyn SerOutputBatchHandle> {
         self.output_batch_handles.get(name).map(|b| &**b)
     }
+
+    /// Look up the schema of an input stream by name.
+    pub fn input_schema(&self, name: &str) -> Option<&Schema> {
+        self.schemas.inputs.get(name)
+    }
+
+    /// Look up the schema of an output stream by name.
+    pub fn output_schema(&self, name: &str) -> Option<&Schema> {
+        self.schemas.outputs.get(name)
+    }
+
+    /// Schemas of all streams in the catalog.
+    pub fn schemas(&self) -> &CatalogSchemas {
+        &self.schemas
+    }
+
+    /// Look up a tap by name.
+    pub fn tap(&self, name: &str) -> Option<&dyn SerOutputBatchHandle> {
+        self.taps.get(name).map(|b| &**b)
+    }
+
+    /// Look up the schema of a tap by name.
+    pub fn tap_schema(&self, name: &str) -> Option<&Schema> {
+        self.tap_schemas.get(name)
+    }
+
+    /// Names of all taps in the catalog.
+    pub fn tap_names(&self) -> impl Iterator<Item = &str> {
+        self.taps.keys().map(|name| name.as_str())
+    }
 }
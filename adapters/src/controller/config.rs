@@ -21,6 +21,11 @@ const fn default_workers() -> u16 {
     1
 }
 
+/// Default value of `GlobalControllerConfig::operator_metrics_interval_secs`.
+const fn default_operator_metrics_interval_secs() -> u64 {
+    10
+}
+
 /// Controller configuration specified by the user when creating
 /// a new controller instance.
 #[derive(Clone, Serialize, Deserialize)]
@@ -62,6 +67,27 @@ pub struct GlobalControllerConfig {
     /// get buffered by the controller, defaults to 0.
     #[serde(default)]
     pub max_buffering_delay_usecs: u64,
+
+    /// Minimal interval in seconds between refreshes of per-operator metrics
+    /// reported via the Prometheus endpoint, defaults to 10.
+    ///
+    /// Operator metrics are collected by the circuit thread between clock
+    /// cycles.  Collecting them requires computing the size of all operator
+    /// state, which can be expensive for large traces.  Set to 0 to disable
+    /// operator metrics.
+    #[serde(default = "default_operator_metrics_interval_secs")]
+    pub operator_metrics_interval_secs: u64,
+
+    /// Record all inputs received by the pipeline to a journal file at this
+    /// path.
+    ///
+    /// The journal contains every chunk of data received by each input
+    /// endpoint along with clock cycle boundaries.  It can be fed back to an
+    /// identical pipeline using the `replay` input transport to reproduce the
+    /// run.  The journal grows with the total size of the inputs.  Disabled
+    /// by default.
+    #[serde(default)]
+    pub journal_path: Option<String>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -83,6 +109,65 @@ pub struct InputEndpointConfig {
     /// The default is 1 million.
     #[serde(default = "default_max_buffered_records")]
     pub max_buffered_records: u64,
+
+    /// Handling of records that fail to parse.
+    #[serde(default)]
+    pub parse_errors: ParseErrorConfig,
+}
+
+/// Policy for handling records that fail to parse.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParseErrorPolicy {
+    /// Discard the entire input buffer that contains the invalid record and
+    /// fail the endpoint: the controller records a fatal error in the endpoint
+    /// status and pauses the endpoint until it is disconnected.
+    #[default]
+    Fail,
+
+    /// Skip invalid records, push valid records in the same buffer to the
+    /// circuit.
+    Skip,
+
+    /// Skip invalid records, like `Skip`, and write them to the dead-letter
+    /// output transport specified in [`ParseErrorConfig::dead_letter`].
+    DeadLetter,
+}
+
+/// Parse error handling configuration of an input endpoint.
+///
+/// Parse errors are counted in endpoint stats and reported via the controller
+/// error callback under all policies.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ParseErrorConfig {
+    /// What to do with records that fail to parse.  Defaults to `fail`.
+    #[serde(default)]
+    pub policy: ParseErrorPolicy,
+
+    /// Maximal number of parse errors tolerated by the endpoint.
+    ///
+    /// Once the number of parse errors exceeds this threshold, the endpoint
+    /// fails like under the `fail` policy: the controller records a fatal
+    /// error in the endpoint status and pauses the endpoint until it is
+    /// disconnected.  Inputs received before the endpoint pauses are
+    /// discarded.  Unlimited by default.
+    #[serde(default)]
+    pub max_errors: Option<u64>,
+
+    /// Output transport to send invalid records to.  Required when `policy`
+    /// is `dead_letter`.
+    ///
+    /// Each invalid record is written as a single-line JSON object with the
+    /// name of the input endpoint, the offset of the record, the error
+    /// message, and the raw contents of the record.
+    ///
+    /// The offset is relative to the byte stream received by the parser
+    /// instance that parsed the record.  Transports that receive data from
+    /// several threads, e.g., one per Kafka partition, fork a parser per
+    /// thread, so for such transports the offset doesn't identify the record
+    /// within the endpoint's input.
+    #[serde(default)]
+    pub dead_letter: Option<TransportConfig>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -102,6 +187,73 @@ pub struct OutputEndpointConfig {
     /// The default is 1 million.
     #[serde(default = "default_max_buffered_records")]
     pub max_buffered_records: u64,
+
+    /// Output batch delivery settings.
+    #[serde(default)]
+    pub delivery: OutputDeliveryConfig,
+}
+
+/// Representation of output batches sent to an output endpoint.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputMode {
+    /// Z-set deltas: each update is encoded as a record with a positive
+    /// (insert) or negative (delete) weight.
+    #[default]
+    Deltas,
+
+    /// Upserts keyed by primary key.
+    ///
+    /// Each output batch is consolidated and reduced to at most one record
+    /// per key: a record with weight `1` inserts or replaces the value
+    /// associated with its key; a record with weight `-1` deletes the key.
+    /// An update to an existing key, which appears in the delta as a
+    /// delete/insert pair, therefore turns into a single upsert.
+    ///
+    /// The output stream must be an indexed Z-set keyed by primary key, in
+    /// which case upserts contain values of the indexed Z-set.  For
+    /// non-indexed Z-sets, each record acts as its own key.
+    Upserts,
+
+    /// Full snapshots of the output collection.
+    ///
+    /// Instead of changes, each flush (see [`OutputDeliveryConfig`]) that
+    /// follows a change to the output collection sends the complete contents
+    /// of the collection.  Flushes without changes send nothing.
+    ///
+    /// The endpoint reconstructs the collection from the changes it receives
+    /// and keeps it in memory.  It must therefore be connected before the
+    /// circuit evaluates its first clock cycle; connecting a `snapshot`
+    /// endpoint to a running pipeline fails.
+    Snapshot,
+}
+
+/// Output batch delivery configuration of an output endpoint.
+///
+/// By default, outputs produced by each worker thread during each clock
+/// cycle are sent to the endpoint as separate batches, as soon as the clock
+/// cycle completes.  When `max_steps` or `interval_ms` is set, the endpoint
+/// accumulates outputs of multiple clock cycles and flushes them as a single
+/// consolidated batch once either limit is reached or the number of buffered
+/// records reaches `max_buffered_records`, whichever happens first.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct OutputDeliveryConfig {
+    /// Consolidate batches produced by all worker threads during a clock
+    /// cycle into a single batch.  Defaults to `false`.
+    #[serde(default)]
+    pub consolidate: bool,
+
+    /// Flush accumulated outputs every `max_steps` clock cycles.
+    #[serde(default)]
+    pub max_steps: Option<u64>,
+
+    /// Flush accumulated outputs at least every `interval_ms` milliseconds.
+    #[serde(default)]
+    pub interval_ms: Option<u64>,
+
+    /// Representation of output batches.  Defaults to `deltas`.
+    #[serde(default)]
+    pub mode: OutputMode,
 }
 
 /// Transport endpoint configuration.
@@ -0,0 +1,90 @@
+//! Dead-letter queue for input records that fail to parse.
+//!
+//! An input endpoint configured with the `dead_letter` parse error policy
+//! (see [`ParseErrorConfig`](`super::ParseErrorConfig`)) writes each invalid
+//! record to an output transport endpoint as a single-line JSON object.
+
+use crate::{OutputEndpoint, ParseError};
+use anyhow::Result as AnyResult;
+use serde::Serialize;
+use std::{str::from_utf8, sync::Mutex};
+
+/// Raw contents of an invalid record.
+///
+/// Valid UTF-8 data is serialized as a string; other data is serialized as an
+/// array of bytes.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum RawData<'a> {
+    Text(&'a str),
+    Binary(&'a [u8]),
+}
+
+impl<'a> RawData<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        match from_utf8(data) {
+            Ok(text) => Self::Text(text),
+            Err(_) => Self::Binary(data),
+        }
+    }
+}
+
+/// An invalid record written to the dead-letter queue.
+#[derive(Serialize)]
+struct DeadLetterRecord<'a> {
+    /// Name of the input endpoint that received the record.
+    endpoint: &'a str,
+
+    /// Offset of the record from the start of the byte stream received by
+    /// the parser.
+    ///
+    /// Offsets are tracked per parser instance: for transports that fork a
+    /// parser per thread they are relative to that thread's stream.
+    offset: u64,
+
+    /// Parse error message.
+    error: String,
+
+    /// Raw contents of the record.
+    data: RawData<'a>,
+}
+
+impl<'a> DeadLetterRecord<'a> {
+    fn new(endpoint: &'a str, error: &'a ParseError) -> Self {
+        Self {
+            endpoint,
+            offset: error.offset,
+            error: error.error.to_string(),
+            data: RawData::new(&error.data),
+        }
+    }
+}
+
+/// Dead-letter queue of an input endpoint.
+///
+/// The queue is shared by all input probes forked from the endpoint's
+/// original probe.
+pub(crate) struct DeadLetterQueue {
+    endpoint: Mutex<Box<dyn OutputEndpoint>>,
+}
+
+impl DeadLetterQueue {
+    pub(crate) fn new(endpoint: Box<dyn OutputEndpoint>) -> Self {
+        Self {
+            endpoint: Mutex::new(endpoint),
+        }
+    }
+
+    /// Write invalid records received by input endpoint `endpoint_name` to
+    /// the queue.
+    pub(crate) fn push(&self, endpoint_name: &str, errors: &[ParseError]) -> AnyResult<()> {
+        let mut buffer = Vec::new();
+
+        for error in errors.iter() {
+            serde_json::to_writer(&mut buffer, &DeadLetterRecord::new(endpoint_name, error))?;
+            buffer.push(b'\n');
+        }
+
+        self.endpoint.lock().unwrap().push_buffer(&buffer)
+    }
+}
@@ -0,0 +1,381 @@
+//! Output batch delivery.
+//!
+//! The circuit thread hands over outputs of each clock cycle to the output
+//! endpoint thread as a vector of batches.  The endpoint thread accumulates
+//! them in an [`OutputBuffer`], which decides when to flush them to the
+//! encoder and in what form, according to the endpoint's
+//! [`OutputDeliveryConfig`].
+
+use super::{OutputDeliveryConfig, OutputMode};
+use crate::{SerBatch, UpsertBatch};
+use anyhow::{Error as AnyError, Result as AnyResult};
+use std::{
+    mem::take,
+    time::{Duration, Instant},
+};
+
+/// Output batches accumulated by an output endpoint thread.
+pub(crate) struct OutputBuffer {
+    config: OutputDeliveryConfig,
+
+    /// Flush outputs once the number of buffered records reaches this
+    /// threshold to avoid blocking the circuit on a full buffer.
+    max_buffered_records: u64,
+
+    /// Batches received since the last flush.
+    batches: Vec<Box<dyn SerBatch>>,
+
+    /// Total number of records in `batches`.
+    num_records: usize,
+
+    /// Number of clock cycles since the last flush.
+    num_steps: u64,
+
+    /// The last clock cycle received.
+    last_step: u64,
+
+    /// Time when the first clock cycle since the last flush was received.
+    first_step_time: Option<Instant>,
+
+    /// Contents of the output collection as of the last flush (only used in
+    /// the `snapshot` mode).
+    ///
+    /// The batch passed to the encoder is an alias of this one (see
+    /// [`SerBatch::fork`]), so the buffer doesn't keep a second copy.
+    snapshot: Option<Box<dyn SerBatch>>,
+}
+
+impl OutputBuffer {
+    pub(crate) fn new(config: &OutputDeliveryConfig, max_buffered_records: u64) -> Self {
+        Self {
+            config: config.clone(),
+            max_buffered_records,
+            batches: Vec::new(),
+            num_records: 0,
+            num_steps: 0,
+            last_step: 0,
+            first_step_time: None,
+            snapshot: None,
+        }
+    }
+
+    /// `true` if outputs of each clock cycle are flushed as soon as they are
+    /// received, without accumulating them across steps.
+    fn flush_every_step(&self) -> bool {
+        self.config.interval_ms.is_none() && self.config.max_steps.unwrap_or(1) <= 1
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        self.config.interval_ms.map(Duration::from_millis)
+    }
+
+    /// Add outputs of clock cycle `step` to the buffer.
+    pub(crate) fn push(&mut self, step: u64, batches: Vec<Box<dyn SerBatch>>) {
+        self.num_records += batches.iter().map(|b| b.len()).sum::<usize>();
+        self.num_steps += 1;
+        self.last_step = step;
+        self.first_step_time.get_or_insert_with(Instant::now);
+        self.batches.extend(batches);
+    }
+
+    /// `true` if the buffered outputs should be flushed now.
+    pub(crate) fn flush_ready(&self) -> bool {
+        if self.num_steps == 0 {
+            return false;
+        }
+
+        self.flush_every_step()
+            || self.num_records as u64 >= self.max_buffered_records
+            || self
+                .config
+                .max_steps
+                .map_or(false, |max_steps| self.num_steps >= max_steps)
+            || self.timeout() == Some(Duration::ZERO)
+    }
+
+    /// Time left until the buffered outputs must be flushed, if the flush is
+    /// time-driven.
+    pub(crate) fn timeout(&self) -> Option<Duration> {
+        match (self.interval(), self.first_step_time) {
+            (Some(interval), Some(first_step_time)) => {
+                Some(interval.saturating_sub(first_step_time.elapsed()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Take buffered outputs out of the buffer.
+    ///
+    /// Returns the last clock cycle included in the flush, batches to pass
+    /// to the encoder, and the number of records received from the circuit
+    /// since the last flush.
+    ///
+    /// The batches are an error if the output handle produces batches that
+    /// don't support the endpoint's delivery mode (see [`SerBatch::merge`]).
+    /// Buffered outputs are dropped in this case.
+    pub(crate) fn flush(&mut self) -> (u64, AnyResult<Vec<Box<dyn SerBatch>>>, usize) {
+        let batches = take(&mut self.batches);
+        let num_records = take(&mut self.num_records);
+        self.num_steps = 0;
+        self.first_step_time = None;
+
+        if self.config.mode == OutputMode::Deltas && self.flush_every_step() {
+            return (self.last_step, Ok(batches), num_records);
+        }
+
+        (self.last_step, self.flush_merged(batches), num_records)
+    }
+
+    /// Merge `batches` and convert the result according to the delivery
+    /// mode.
+    fn flush_merged(
+        &mut self,
+        batches: Vec<Box<dyn SerBatch>>,
+    ) -> AnyResult<Vec<Box<dyn SerBatch>>> {
+        let delta = match Self::merge(batches)? {
+            Some(delta) => delta,
+            None => return Ok(Vec::new()),
+        };
+
+        let batch: Box<dyn SerBatch> = match self.config.mode {
+            OutputMode::Deltas => delta,
+            OutputMode::Upserts => Box::new(UpsertBatch::new(delta)?),
+            OutputMode::Snapshot => {
+                let snapshot = match self.snapshot.take() {
+                    // Don't resend the snapshot if it hasn't changed.
+                    Some(snapshot) if delta.is_empty() => {
+                        self.snapshot = Some(snapshot);
+                        return Ok(Vec::new());
+                    }
+                    Some(snapshot) => match snapshot.merge(&*delta) {
+                        Ok(merged) => merged,
+                        Err(e) => {
+                            self.snapshot = Some(snapshot);
+                            return Err(e);
+                        }
+                    },
+                    None => delta,
+                };
+                self.snapshot = Some(snapshot.fork().ok_or_else(|| {
+                    AnyError::msg("output batches don't support the 'snapshot' mode")
+                })?);
+                snapshot
+            }
+        };
+
+        Ok(vec![batch])
+    }
+
+    /// Merge `batches` into a single consolidated batch.
+    fn merge(batches: Vec<Box<dyn SerBatch>>) -> AnyResult<Option<Box<dyn SerBatch>>> {
+        let mut batches = batches.into_iter();
+        let mut merged = match batches.next() {
+            Some(batch) => batch,
+            None => return Ok(None),
+        };
+        for batch in batches {
+            merged = merged.merge(&*batch)?;
+        }
+        Ok(Some(merged))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::OutputBuffer;
+    use crate::{seroutput::SerBatchImpl, OutputDeliveryConfig, OutputMode, SerBatch};
+    use dbsp::{trace::Batch, OrdIndexedZSet, OrdZSet};
+    use std::time::Duration;
+
+    fn zset(tuples: &[(u32, i64)]) -> Box<dyn SerBatch> {
+        Box::new(SerBatchImpl::new(OrdZSet::from_keys((), tuples.to_vec())))
+    }
+
+    fn indexed_zset(tuples: &[((u32, u32), i64)]) -> Box<dyn SerBatch> {
+        Box::new(SerBatchImpl::new(OrdIndexedZSet::from_tuples(
+            (),
+            tuples.to_vec(),
+        )))
+    }
+
+    /// Read `(record, weight)` pairs from a batch.
+    fn contents(batches: &[Box<dyn SerBatch>]) -> Vec<(String, i64)> {
+        let mut result = Vec::new();
+
+        for batch in batches.iter() {
+            let mut cursor = batch.cursor();
+            while cursor.key_valid() {
+                while cursor.val_valid() {
+                    let key = serde_json::to_string(cursor.key()).unwrap();
+                    result.push((key, cursor.weight()));
+                    cursor.step_val();
+                }
+                cursor.step_key();
+            }
+        }
+
+        result
+    }
+
+    fn config(
+        max_steps: Option<u64>,
+        interval_ms: Option<u64>,
+        mode: OutputMode,
+    ) -> OutputDeliveryConfig {
+        OutputDeliveryConfig {
+            consolidate: false,
+            max_steps,
+            interval_ms,
+            mode,
+        }
+    }
+
+    #[test]
+    fn test_deltas() {
+        let mut buffer = OutputBuffer::new(&config(None, None, OutputMode::Deltas), 1000);
+        assert!(!buffer.flush_ready());
+
+        buffer.push(1, vec![zset(&[(1, 1)]), zset(&[(1, 1), (2, 1)])]);
+        assert!(buffer.flush_ready());
+
+        // Per-worker batches are passed through as is.
+        let (_, batches, num_records) = buffer.flush();
+        let batches = batches.unwrap();
+        assert_eq!(batches.len(), 2);
+        assert_eq!(num_records, 3);
+        assert!(!buffer.flush_ready());
+    }
+
+    #[test]
+    fn test_max_steps() {
+        let mut buffer = OutputBuffer::new(&config(Some(3), None, OutputMode::Deltas), 1000);
+
+        buffer.push(1, vec![zset(&[(1, 1), (2, 1)])]);
+        buffer.push(2, vec![zset(&[(1, -1)]), zset(&[(3, 1)])]);
+        assert!(!buffer.flush_ready());
+        buffer.push(3, vec![]);
+        assert!(buffer.flush_ready());
+
+        let (step, batches, num_records) = buffer.flush();
+        let batches = batches.unwrap();
+        assert_eq!(step, 3);
+        assert_eq!(num_records, 4);
+        assert_eq!(
+            contents(&batches),
+            vec![("2".to_string(), 1), ("3".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn test_max_buffered_records() {
+        let mut buffer = OutputBuffer::new(&config(Some(100), None, OutputMode::Deltas), 3);
+
+        buffer.push(1, vec![zset(&[(1, 1), (2, 1)])]);
+        assert!(!buffer.flush_ready());
+        buffer.push(2, vec![zset(&[(3, 1)])]);
+        assert!(buffer.flush_ready());
+    }
+
+    #[test]
+    fn test_interval() {
+        let mut buffer = OutputBuffer::new(&config(None, Some(100), OutputMode::Deltas), 1000);
+        assert_eq!(buffer.timeout(), None);
+
+        buffer.push(1, vec![zset(&[(1, 1)])]);
+        assert!(!buffer.flush_ready());
+        assert!(buffer.timeout().unwrap() <= Duration::from_millis(100));
+
+        std::thread::sleep(Duration::from_millis(150));
+        assert!(buffer.flush_ready());
+        assert_eq!(
+            contents(&buffer.flush().1.unwrap()),
+            vec![("1".to_string(), 1)]
+        );
+        assert_eq!(buffer.timeout(), None);
+    }
+
+    #[test]
+    fn test_upserts() {
+        let mut buffer = OutputBuffer::new(&config(None, None, OutputMode::Upserts), 1000);
+
+        // Update key 1, delete key 2, insert key 3.
+        buffer.push(
+            1,
+            vec![
+                indexed_zset(&[((1, 10), -1), ((2, 20), -1)]),
+                indexed_zset(&[((1, 11), 1), ((3, 30), 1)]),
+            ],
+        );
+
+        let (_, batches, num_records) = buffer.flush();
+        let batches = batches.unwrap();
+        assert_eq!(num_records, 4);
+        assert_eq!(batches[0].len(), 3);
+        assert_eq!(
+            contents(&batches),
+            vec![
+                ("11".to_string(), 1),
+                ("20".to_string(), -1),
+                ("30".to_string(), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut buffer = OutputBuffer::new(&config(None, None, OutputMode::Snapshot), 1000);
+
+        buffer.push(1, vec![zset(&[(1, 1), (2, 1)])]);
+        assert_eq!(
+            contents(&buffer.flush().1.unwrap()),
+            vec![("1".to_string(), 1), ("2".to_string(), 1)]
+        );
+
+        buffer.push(2, vec![zset(&[(1, -1), (3, 1)])]);
+        assert_eq!(
+            contents(&buffer.flush().1.unwrap()),
+            vec![("2".to_string(), 1), ("3".to_string(), 1)]
+        );
+
+        // Unchanged snapshots are not resent.
+        buffer.push(3, vec![zset(&[])]);
+        let (step, batches, _) = buffer.flush();
+        let batches = batches.unwrap();
+        assert_eq!(step, 3);
+        assert!(batches.is_empty());
+
+        buffer.push(4, vec![zset(&[(4, 1)])]);
+        assert_eq!(
+            contents(&buffer.flush().1.unwrap()),
+            vec![
+                ("2".to_string(), 1),
+                ("3".to_string(), 1),
+                ("4".to_string(), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_error() {
+        let mut buffer = OutputBuffer::new(&config(None, None, OutputMode::Snapshot), 1000);
+
+        buffer.push(1, vec![zset(&[(1, 1)])]);
+        assert_eq!(
+            contents(&buffer.flush().1.unwrap()),
+            vec![("1".to_string(), 1)]
+        );
+
+        // Batches of different types cannot be merged.
+        buffer.push(2, vec![indexed_zset(&[((2, 20), 1)])]);
+        let (_, batches, num_records) = buffer.flush();
+        assert!(batches.is_err());
+        assert_eq!(num_records, 1);
+
+        // The snapshot survives the error.
+        buffer.push(3, vec![zset(&[(3, 1)])]);
+        assert_eq!(
+            contents(&buffer.flush().1.unwrap()),
+            vec![("1".to_string(), 1), ("3".to_string(), 1)]
+        );
+    }
+}
@@ -36,6 +36,38 @@ pub enum ConfigError {
     /// Controller configuration specifies output stream name
     /// that is not found in the circuit catalog.
     UnknownOutputStream { stream_name: String },
+
+    /// Tap name not found in the circuit catalog.
+    UnknownTap { tap_name: String },
+
+    /// A `replay` input endpoint replays a journal recorded with a different
+    /// number of workers.
+    ReplayWorkersMismatch {
+        endpoint_name: String,
+        journal_workers: u64,
+        workers: usize,
+    },
+
+    /// Input endpoint with this name does not exist.
+    UnknownInputEndpoint { endpoint_name: String },
+
+    /// Output endpoint with this name does not exist.
+    UnknownOutputEndpoint { endpoint_name: String },
+
+    /// Input endpoint configuration specifies the `dead_letter` parse error
+    /// policy without a dead-letter transport.
+    MissingDeadLetterTransport { endpoint_name: String },
+
+    /// An output endpoint in the `snapshot` delivery mode is connected after
+    /// the circuit has started evaluating clock cycles.
+    SnapshotAfterStart { endpoint_name: String },
+
+    /// An output endpoint that sends each record as a separate keyed message
+    /// uses a format that doesn't support keyed output.
+    KeyedOutputNotSupported {
+        endpoint_name: String,
+        format_name: String,
+    },
 }
 
 impl Display for ConfigError {
@@ -69,6 +101,34 @@ impl Display for ConfigError {
             Self::UnknownOutputStream { stream_name } => {
                 write!(f, "unknown output stream '{stream_name}'")
             }
+            Self::UnknownTap { tap_name } => {
+                write!(f, "unknown tap '{tap_name}' (only streams declared as taps when the circuit is built can be tapped; see the 'taps' list in the circuit graph)")
+            }
+            Self::ReplayWorkersMismatch {
+                endpoint_name,
+                journal_workers,
+                workers,
+            } => {
+                write!(f, "input endpoint '{endpoint_name}' replays a journal recorded with {journal_workers} workers, but the circuit has {workers} workers")
+            }
+            Self::UnknownInputEndpoint { endpoint_name } => {
+                write!(f, "unknown input endpoint '{endpoint_name}'")
+            }
+            Self::UnknownOutputEndpoint { endpoint_name } => {
+                write!(f, "unknown output endpoint '{endpoint_name}'")
+            }
+            Self::MissingDeadLetterTransport { endpoint_name } => {
+                write!(f, "input endpoint '{endpoint_name}' uses the 'dead_letter' parse error policy, but does not specify a dead-letter transport")
+            }
+            Self::SnapshotAfterStart { endpoint_name } => {
+                write!(f, "output endpoint '{endpoint_name}' uses the 'snapshot' delivery mode, which is only supported for endpoints connected before the circuit evaluates its first clock cycle")
+            }
+            Self::KeyedOutputNotSupported {
+                endpoint_name,
+                format_name,
+            } => {
+                write!(f, "output endpoint '{endpoint_name}' sends each record as a separate keyed message, which is not supported by format '{format_name}'")
+            }
         }
     }
 }
@@ -127,6 +187,55 @@ impl ConfigError {
             stream_name: stream_name.to_owned(),
         }
     }
+
+    pub fn unknown_tap(tap_name: &str) -> Self {
+        Self::UnknownTap {
+            tap_name: tap_name.to_owned(),
+        }
+    }
+
+    pub fn replay_workers_mismatch(
+        endpoint_name: &str,
+        journal_workers: u64,
+        workers: usize,
+    ) -> Self {
+        Self::ReplayWorkersMismatch {
+            endpoint_name: endpoint_name.to_owned(),
+            journal_workers,
+            workers,
+        }
+    }
+
+    pub fn unknown_input_endpoint(endpoint_name: &str) -> Self {
+        Self::UnknownInputEndpoint {
+            endpoint_name: endpoint_name.to_owned(),
+        }
+    }
+
+    pub fn unknown_output_endpoint(endpoint_name: &str) -> Self {
+        Self::UnknownOutputEndpoint {
+            endpoint_name: endpoint_name.to_owned(),
+        }
+    }
+
+    pub fn missing_dead_letter_transport(endpoint_name: &str) -> Self {
+        Self::MissingDeadLetterTransport {
+            endpoint_name: endpoint_name.to_owned(),
+        }
+    }
+
+    pub fn snapshot_after_start(endpoint_name: &str) -> Self {
+        Self::SnapshotAfterStart {
+            endpoint_name: endpoint_name.to_owned(),
+        }
+    }
+
+    pub fn keyed_output_not_supported(endpoint_name: &str, format_name: &str) -> Self {
+        Self::KeyedOutputNotSupported {
+            endpoint_name: endpoint_name.to_owned(),
+            format_name: format_name.to_owned(),
+        }
+    }
 }
 
 /// Controller error.
@@ -171,6 +280,20 @@ pub enum ControllerError {
 
     /// Error evaluating the DBSP circuit.
     DbspError { error: DBSPError },
+
+    /// Error writing the input journal.
+    JournalError { error: AnyError },
+
+    /// The requested operation is not recorded in the input journal and is
+    /// therefore refused while a journal is being recorded or replayed.
+    UnsupportedWithJournal { operation: String },
+
+    /// Error writing invalid input records to the dead-letter queue of an
+    /// input endpoint.
+    DeadLetterError {
+        endpoint_name: String,
+        error: AnyError,
+    },
 }
 
 impl StdError for ControllerError {}
@@ -224,6 +347,24 @@ impl Display for ControllerError {
             Self::DbspError { error } => {
                 write!(f, "DBSP error: '{error}'")
             }
+            Self::JournalError { error } => {
+                write!(f, "error writing input journal: '{error}'")
+            }
+            Self::UnsupportedWithJournal { operation } => {
+                write!(
+                    f,
+                    "{operation} is not supported while the input journal is being recorded or replayed"
+                )
+            }
+            Self::DeadLetterError {
+                endpoint_name,
+                error,
+            } => {
+                write!(
+                    f,
+                    "error writing to the dead-letter queue of input endpoint '{endpoint_name}': '{error}'"
+                )
+            }
         }
     }
 }
@@ -285,6 +426,56 @@ impl ControllerError {
         }
     }
 
+    pub fn unknown_tap(tap_name: &str) -> Self {
+        Self::Config {
+            config_error: ConfigError::unknown_tap(tap_name),
+        }
+    }
+
+    pub fn replay_workers_mismatch(
+        endpoint_name: &str,
+        journal_workers: u64,
+        workers: usize,
+    ) -> Self {
+        Self::Config {
+            config_error: ConfigError::replay_workers_mismatch(
+                endpoint_name,
+                journal_workers,
+                workers,
+            ),
+        }
+    }
+
+    pub fn unknown_input_endpoint(endpoint_name: &str) -> Self {
+        Self::Config {
+            config_error: ConfigError::unknown_input_endpoint(endpoint_name),
+        }
+    }
+
+    pub fn unknown_output_endpoint(endpoint_name: &str) -> Self {
+        Self::Config {
+            config_error: ConfigError::unknown_output_endpoint(endpoint_name),
+        }
+    }
+
+    pub fn missing_dead_letter_transport(endpoint_name: &str) -> Self {
+        Self::Config {
+            config_error: ConfigError::missing_dead_letter_transport(endpoint_name),
+        }
+    }
+
+    pub fn snapshot_after_start(endpoint_name: &str) -> Self {
+        Self::Config {
+            config_error: ConfigError::snapshot_after_start(endpoint_name),
+        }
+    }
+
+    pub fn keyed_output_not_supported(endpoint_name: &str, format_name: &str) -> Self {
+        Self::Config {
+            config_error: ConfigError::keyed_output_not_supported(endpoint_name, format_name),
+        }
+    }
+
     pub fn input_transport_error(endpoint_name: &str, fatal: bool, error: AnyError) -> Self {
         Self::InputTransportError {
             endpoint_name: endpoint_name.to_owned(),
@@ -318,4 +509,21 @@ impl ControllerError {
     pub fn dbsp_error(error: DBSPError) -> Self {
         Self::DbspError { error }
     }
+
+    pub fn journal_error(error: AnyError) -> Self {
+        Self::JournalError { error }
+    }
+
+    pub fn unsupported_with_journal(operation: &str) -> Self {
+        Self::UnsupportedWithJournal {
+            operation: operation.to_owned(),
+        }
+    }
+
+    pub fn dead_letter_error(endpoint_name: &str, error: AnyError) -> Self {
+        Self::DeadLetterError {
+            endpoint_name: endpoint_name.to_owned(),
+            error,
+        }
+    }
 }
@@ -0,0 +1,166 @@
+//! Live circuit inspection.
+//!
+//! The controller reports the topology of the circuit and allows clients to
+//! attach temporary taps to internal streams of the circuit.  A tap must be
+//! created when building the circuit (see [`dbsp::Stream::tap`]) and
+//! registered in the [`Catalog`](`crate::Catalog`).  Arbitrary nodes listed
+//! in the circuit graph cannot be tapped: the controller has no way to
+//! serialize the contents of a stream whose type it doesn't know, and
+//! attaching a sink to a running circuit would require rebuilding it.
+//! Requests to tap a stream that is not registered as a tap are rejected
+//! with [`ControllerError::unknown_tap`](`crate::ControllerError::unknown_tap`).
+//! Taps are disabled by
+//! default.  Attaching a client to a tap enables it for the next `N` clock
+//! cycles, during which the controller encodes the contents of the stream
+//! using the output format chosen by the client and forwards it to the client.
+//! Once all clients have detached, the tap is disabled again.
+
+use crate::{Encoder, SerOutputBatchHandle};
+use dbsp::{
+    circuit::GlobalNodeId,
+    monitor::{GraphNode, GraphNodeKind},
+};
+use log::error;
+use serde::Serialize;
+use std::{collections::BTreeMap, sync::Mutex};
+
+/// An operator or subcircuit of the circuit.
+#[derive(Clone, Debug, Serialize)]
+pub struct CircuitNode {
+    /// Global node id, e.g., `[3]`, or `[5.1]` for node 1 of subcircuit 5.
+    pub id: String,
+    /// Operator name.
+    pub name: String,
+    /// Source location where the operator was created, if known.
+    pub location: Option<String>,
+    /// `true` if the node is a subcircuit.
+    pub subcircuit: bool,
+    /// `true` if the node is an iterative subcircuit.
+    pub iterative: bool,
+}
+
+/// Topology of the circuit.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct CircuitGraph {
+    /// Operators and subcircuits sorted by id.
+    pub nodes: Vec<CircuitNode>,
+    /// Streams connecting nodes, as `(from, to)` pairs of node ids.
+    pub edges: Vec<(String, String)>,
+    /// Names of taps registered in the catalog.
+    pub taps: Vec<String>,
+}
+
+impl CircuitGraph {
+    pub(super) fn new(
+        nodes: Vec<GraphNode>,
+        edges: Vec<(GlobalNodeId, GlobalNodeId)>,
+        taps: Vec<String>,
+    ) -> Self {
+        let nodes = nodes
+            .into_iter()
+            .map(|node| CircuitNode {
+                id: node.id.to_string(),
+                name: node.name.to_string(),
+                location: node.location.map(|location| {
+                    format!(
+                        "{}:{}:{}",
+                        location.file(),
+                        location.line(),
+                        location.column()
+                    )
+                }),
+                subcircuit: matches!(node.kind, GraphNodeKind::Circuit { .. }),
+                iterative: matches!(node.kind, GraphNodeKind::Circuit { iterative: true }),
+            })
+            .collect();
+
+        let edges = edges
+            .into_iter()
+            .map(|(from, to)| (from.to_string(), to.to_string()))
+            .collect();
+
+        Self { nodes, edges, taps }
+    }
+}
+
+/// A client attached to a tap.
+struct TapClient {
+    tap_name: String,
+    handle: Box<dyn SerOutputBatchHandle>,
+    encoder: Box<dyn Encoder>,
+    /// Number of batches to send to the client before detaching it.
+    remaining: usize,
+}
+
+/// Clients attached to taps.
+#[derive(Default)]
+pub(super) struct TapClients(Mutex<Vec<TapClient>>);
+
+impl TapClients {
+    /// Enable `handle` and forward its contents to `encoder` for the next
+    /// `batches` clock cycles.
+    pub(super) fn attach(
+        &self,
+        tap_name: &str,
+        handle: Box<dyn SerOutputBatchHandle>,
+        encoder: Box<dyn Encoder>,
+        batches: usize,
+    ) {
+        if batches == 0 {
+            return;
+        }
+
+        handle.set_enabled(true);
+        self.0.lock().unwrap().push(TapClient {
+            tap_name: tap_name.to_string(),
+            handle,
+            encoder,
+            remaining: batches,
+        });
+    }
+
+    /// Send the outputs of the last clock cycle to all attached clients;
+    /// detach clients that have received all requested batches.
+    pub(super) fn push(&self) {
+        let mut clients = self.0.lock().unwrap();
+        if clients.is_empty() {
+            return;
+        }
+
+        // Multiple clients can be attached to the same tap.  Read each tap
+        // once and send its contents to all its clients.
+        let mut batches = BTreeMap::new();
+        for client in clients.iter() {
+            batches
+                .entry(client.tap_name.clone())
+                .or_insert_with(|| client.handle.take_from_all());
+        }
+
+        for client in clients.iter_mut() {
+            if let Err(e) = client.encoder.encode(batches[&client.tap_name].as_slice()) {
+                error!(
+                    "error encoding the contents of tap '{}': {e}",
+                    client.tap_name
+                );
+                client.remaining = 0;
+            } else {
+                client.remaining -= 1;
+            }
+        }
+
+        let (detached, attached): (Vec<_>, Vec<_>) =
+            clients.drain(..).partition(|client| client.remaining == 0);
+        *clients = attached;
+
+        // Disable taps without clients.  Dropping the encoder closes the
+        // connection to the client.
+        for client in detached {
+            if !clients
+                .iter()
+                .any(|other| other.tap_name == client.tap_name)
+            {
+                client.handle.set_enabled(false);
+            }
+        }
+    }
+}
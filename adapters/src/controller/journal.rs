@@ -0,0 +1,357 @@
+//! Input journaling for deterministic record and replay.
+//!
+//! When [`GlobalControllerConfig::journal_path`](`super::GlobalControllerConfig::journal_path`)
+//! is set, the controller records every chunk of data received by each input
+//! endpoint, along with clock cycle boundaries, to a journal file.  The
+//! `replay` input transport (see
+//! [`ReplayInputTransport`](`crate::ReplayInputTransport`)) reads the journal
+//! back and feeds the recorded chunks to an identical pipeline, using the same
+//! clock cycle boundaries.  Given the same circuit, input formats, and number
+//! of workers, the replayed pipeline produces the same outputs as the original
+//! one.
+//!
+//! To make step boundaries deterministic, an input probe parses each chunk
+//! without holding the journal lock and takes the lock only to push the
+//! parsed records to the circuit and record the chunk.  Before evaluating a
+//! clock cycle, the circuit thread takes the lock to stage all buffered
+//! inputs (see [`DBSPHandle::stage_inputs`](`dbsp::DBSPHandle::stage_inputs`))
+//! and record the step boundary, and releases it before evaluating the
+//! circuit.  Thus every chunk in the journal is consumed by exactly the clock
+//! cycle that follows it, while input endpoints keep running during the
+//! evaluation.
+//!
+//! The journal does not record transactions or changes to the number of
+//! workers, so the controller refuses both while a journal is being recorded
+//! or replayed.
+//!
+//! # File format
+//!
+//! A journal consists of a [`JournalHeader`] followed by a sequence of
+//! [`JournalEntry`]s, each encoded using `bincode`.
+
+use super::EndpointId;
+use crate::InputMetadata;
+use anyhow::{Error as AnyError, Result as AnyResult};
+use bincode::{config::standard as bincode_config, error::DecodeError, Decode, Encode};
+use crossbeam::sync::Unparker;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, ErrorKind, Write},
+    path::Path,
+    sync::{Condvar, Mutex},
+};
+
+/// Version of the journal file format.
+const JOURNAL_VERSION: u32 = 1;
+
+/// Journal file header.
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode)]
+pub(crate) struct JournalHeader {
+    /// File format version.
+    pub version: u32,
+    /// Number of circuit workers in the recorded run.
+    pub workers: u64,
+}
+
+/// An event recorded in the journal.
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode)]
+pub(crate) enum JournalEntry {
+    /// A chunk of data received by an input endpoint.
+    Input { endpoint: String, data: Vec<u8> },
+    /// End of input on an input endpoint.
+    Eoi { endpoint: String },
+    /// The circuit evaluated a clock cycle.
+    Step,
+    /// A chunk of data received by an input endpoint along with its metadata.
+    InputWithMetadata {
+        endpoint: String,
+        data: Vec<u8>,
+        metadata: InputMetadata,
+    },
+}
+
+/// Writes a journal file.
+pub(crate) struct JournalWriter {
+    writer: BufWriter<File>,
+}
+
+impl JournalWriter {
+    /// Create a new journal at `path`, overwriting any existing file.
+    pub(crate) fn create<P: AsRef<Path>>(path: P, workers: usize) -> AnyResult<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let header = JournalHeader {
+            version: JOURNAL_VERSION,
+            workers: workers as u64,
+        };
+        bincode::encode_into_std_write(header, &mut writer, bincode_config())?;
+
+        Ok(Self { writer })
+    }
+
+    /// Record a chunk of data received by endpoint `endpoint`.
+    pub(crate) fn input(&mut self, endpoint: &str, data: &[u8]) -> AnyResult<()> {
+        self.append(JournalEntry::Input {
+            endpoint: endpoint.to_string(),
+            data: data.to_vec(),
+        })
+    }
+
+    /// Record a chunk of data with metadata received by endpoint `endpoint`.
+    pub(crate) fn input_with_metadata(
+        &mut self,
+        endpoint: &str,
+        data: &[u8],
+        metadata: &InputMetadata,
+    ) -> AnyResult<()> {
+        self.append(JournalEntry::InputWithMetadata {
+            endpoint: endpoint.to_string(),
+            data: data.to_vec(),
+            metadata: metadata.clone(),
+        })
+    }
+
+    /// Record end of input on endpoint `endpoint`.
+    pub(crate) fn eoi(&mut self, endpoint: &str) -> AnyResult<()> {
+        self.append(JournalEntry::Eoi {
+            endpoint: endpoint.to_string(),
+        })
+    }
+
+    /// Record a clock cycle boundary.
+    ///
+    /// Flushes the journal to disk, so that the journal survives a crash of
+    /// the pipeline up to the last completed clock cycle.
+    pub(crate) fn step(&mut self) -> AnyResult<()> {
+        self.append(JournalEntry::Step)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn append(&mut self, entry: JournalEntry) -> AnyResult<()> {
+        bincode::encode_into_std_write(entry, &mut self.writer, bincode_config())?;
+        Ok(())
+    }
+}
+
+/// Reads a journal file.
+pub(crate) struct JournalReader {
+    header: JournalHeader,
+    reader: BufReader<File>,
+}
+
+impl JournalReader {
+    /// Open the journal at `path` and read its header.
+    pub(crate) fn open<P: AsRef<Path>>(path: P) -> AnyResult<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let header: JournalHeader = bincode::decode_from_std_read(&mut reader, bincode_config())?;
+        if header.version != JOURNAL_VERSION {
+            return Err(AnyError::msg(format!(
+                "unsupported journal format version {}",
+                header.version
+            )));
+        }
+
+        Ok(Self { header, reader })
+    }
+
+    pub(crate) fn header(&self) -> &JournalHeader {
+        &self.header
+    }
+
+    /// Read the next entry or return `None` at the end of the journal.
+    ///
+    /// A journal truncated in the middle of an entry, e.g., because the
+    /// recorded pipeline crashed, ends at the last complete entry.
+    pub(crate) fn next_entry(&mut self) -> AnyResult<Option<JournalEntry>> {
+        if self.reader.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+
+        match bincode::decode_from_std_read(&mut self.reader, bincode_config()) {
+            Ok(entry) => Ok(Some(entry)),
+            Err(DecodeError::UnexpectedEnd { .. }) => Ok(None),
+            Err(DecodeError::Io { inner, .. }) if inner.kind() == ErrorKind::UnexpectedEof => {
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Synchronizes clock cycles of the circuit with endpoints that replay a
+/// journal.
+///
+/// While at least one replay endpoint is connected, the circuit thread
+/// evaluates the next clock cycle only after all replay endpoints have pushed
+/// all inputs recorded before this clock cycle, and each endpoint waits for
+/// the clock cycle to complete before pushing more data.
+#[derive(Default)]
+pub(super) struct ReplayBarrier {
+    state: Mutex<ReplayState>,
+    cond: Condvar,
+}
+
+#[derive(Default)]
+struct ReplayState {
+    /// Replay endpoints that haven't reached the end of their journal, with the
+    /// number of clock cycles each endpoint has pushed inputs for.
+    endpoints: BTreeMap<EndpointId, u64>,
+
+    /// Number of clock cycles evaluated by the circuit.
+    steps: u64,
+
+    /// Replay endpoints disconnected from the pipeline.
+    disconnected: BTreeSet<EndpointId>,
+
+    /// The pipeline has been terminated.
+    terminated: bool,
+}
+
+impl ReplayBarrier {
+    pub(super) fn add_endpoint(&self, endpoint_id: EndpointId) {
+        self.state.lock().unwrap().endpoints.insert(endpoint_id, 0);
+    }
+
+    /// Invoked when endpoint `endpoint_id` is disconnected from the
+    /// pipeline.
+    ///
+    /// The endpoint no longer constrains clock cycles, and its subsequent
+    /// [`arrive`](`Self::arrive`) calls return `false`.
+    pub(super) fn remove_endpoint(&self, endpoint_id: EndpointId) {
+        let mut state = self.state.lock().unwrap();
+        state.endpoints.remove(&endpoint_id);
+        state.disconnected.insert(endpoint_id);
+        self.cond.notify_all();
+    }
+
+    /// `true` if endpoint `endpoint_id` replays a journal.
+    pub(super) fn contains(&self, endpoint_id: &EndpointId) -> bool {
+        self.state
+            .lock()
+            .unwrap()
+            .endpoints
+            .contains_key(endpoint_id)
+    }
+
+    /// `true` if clock cycles are controlled by replay endpoints.
+    pub(super) fn active(&self) -> bool {
+        !self.state.lock().unwrap().endpoints.is_empty()
+    }
+
+    /// `true` if all replay endpoints are ready for the next clock cycle.
+    pub(super) fn ready(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        !state.endpoints.is_empty() && state.endpoints.values().all(|steps| *steps > state.steps)
+    }
+
+    /// Invoked by the circuit thread after evaluating a clock cycle.
+    pub(super) fn step_complete(&self) {
+        self.state.lock().unwrap().steps += 1;
+        self.cond.notify_all();
+    }
+
+    /// Invoked by an input probe on behalf of a replay endpoint (see
+    /// [`InputConsumer::replay_barrier`](`crate::InputConsumer::replay_barrier`)).
+    ///
+    /// Returns `false` if the pipeline has been terminated or the endpoint
+    /// has been disconnected.
+    pub(super) fn arrive(
+        &self,
+        endpoint_id: EndpointId,
+        step: Option<u64>,
+        circuit_thread_unparker: &Unparker,
+    ) -> bool {
+        let mut state = self.state.lock().unwrap();
+
+        if state.disconnected.contains(&endpoint_id) {
+            return false;
+        }
+
+        match step {
+            Some(step) => {
+                state.endpoints.insert(endpoint_id, step + 1);
+                circuit_thread_unparker.unpark();
+
+                while state.steps <= step
+                    && !state.terminated
+                    && !state.disconnected.contains(&endpoint_id)
+                {
+                    state = self.cond.wait(state).unwrap();
+                }
+            }
+            None => {
+                state.endpoints.remove(&endpoint_id);
+                circuit_thread_unparker.unpark();
+            }
+        }
+
+        !state.terminated && !state.disconnected.contains(&endpoint_id)
+    }
+
+    /// Release all endpoints waiting for a clock cycle.
+    pub(super) fn terminate(&self) {
+        self.state.lock().unwrap().terminated = true;
+        self.cond.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{JournalEntry, JournalReader, JournalWriter};
+    use crate::InputMetadata;
+
+    #[test]
+    fn test_journal_roundtrip() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+
+        let mut metadata = InputMetadata::new();
+        metadata.push("key", Some("1".to_string()));
+        metadata.push("header:id", None);
+
+        let mut writer = JournalWriter::create(file.path(), 4).unwrap();
+        writer.input("in1", b"1,2,3\n").unwrap();
+        writer.step().unwrap();
+        writer.input("in2", b"foo").unwrap();
+        writer.eoi("in2").unwrap();
+        writer.step().unwrap();
+        writer
+            .input_with_metadata("in1", b"4,5\n", &metadata)
+            .unwrap();
+        writer.step().unwrap();
+        drop(writer);
+
+        let mut reader = JournalReader::open(file.path()).unwrap();
+        assert_eq!(reader.header().workers, 4);
+
+        let mut entries = Vec::new();
+        while let Some(entry) = reader.next_entry().unwrap() {
+            entries.push(entry);
+        }
+
+        assert_eq!(
+            entries,
+            vec![
+                JournalEntry::Input {
+                    endpoint: "in1".to_string(),
+                    data: b"1,2,3\n".to_vec()
+                },
+                JournalEntry::Step,
+                JournalEntry::Input {
+                    endpoint: "in2".to_string(),
+                    data: b"foo".to_vec()
+                },
+                JournalEntry::Eoi {
+                    endpoint: "in2".to_string()
+                },
+                JournalEntry::Step,
+                JournalEntry::InputWithMetadata {
+                    endpoint: "in1".to_string(),
+                    data: b"4,5\n".to_vec(),
+                    metadata,
+                },
+                JournalEntry::Step,
+            ]
+        );
+    }
+}
@@ -32,10 +32,21 @@
 //! The probe passes the data through to the parser, while counting the number
 //! of transmitted bytes and records and updating respective performance
 //! counters in the controller.
+//!
+//! The probe also applies the endpoint's parse error policy (see
+//! [`ParseErrorConfig`]) to records that the parser fails to parse, e.g.,
+//! by sending them to the endpoint's dead-letter queue.
+//!
+//! When input journaling is enabled, the probe also records each chunk of
+//! input data in the journal (see [`journal`]).  Conversely, when the pipeline
+//! replays a journal, the circuit thread evaluates clock cycles at the
+//! boundaries recorded in the journal rather than based on the amount of
+//! buffered data.
 
 use crate::{
-    Catalog, Encoder, InputConsumer, InputEndpoint, InputFormat, InputTransport, OutputConsumer,
-    OutputEndpoint, OutputFormat, OutputTransport, Parser, PipelineState, SerBatch,
+    transport::replay_journal_header, Catalog, CatalogSchemas, Encoder, InputConsumer,
+    InputEndpoint, InputFormat, InputMetadata, InputTransport, OutputConsumer, OutputEndpoint,
+    OutputFormat, OutputTransport, ParseError, Parser, PipelineState, SerBatch,
     SerOutputBatchHandle,
 };
 use anyhow::{Error as AnyError, Result as AnyResult};
@@ -43,13 +54,14 @@ use crossbeam::{
     queue::SegQueue,
     sync::{Parker, ShardedLock, Unparker},
 };
-use dbsp::DBSPHandle;
+use dbsp::{DBSPHandle, Error as DBSPError, RuntimeError};
 use log::{debug, error, info};
 use num_traits::FromPrimitive;
 use std::{
     collections::{BTreeMap, HashSet},
+    mem::take,
     sync::{
-        atomic::{AtomicBool, AtomicU32, Ordering},
+        atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering},
         Arc, Mutex,
     },
     thread::{spawn, JoinHandle},
@@ -57,15 +69,24 @@ use std::{
 };
 
 mod config;
+mod dead_letter;
+mod delivery;
 mod error;
+mod inspect;
+pub(crate) mod journal;
 mod stats;
 
 pub use config::{
     ControllerConfig, FormatConfig, GlobalControllerConfig, InputEndpointConfig,
-    OutputEndpointConfig,
+    OutputDeliveryConfig, OutputEndpointConfig, OutputMode, ParseErrorConfig, ParseErrorPolicy,
 };
+use dead_letter::DeadLetterQueue;
+use delivery::OutputBuffer;
 pub use error::ControllerError;
-pub use stats::{ControllerStatus, InputEndpointStatus, OutputEndpointStatus};
+use inspect::TapClients;
+pub use inspect::{CircuitGraph, CircuitNode};
+use journal::{JournalWriter, ReplayBarrier};
+pub use stats::{CircuitMetrics, ControllerStatus, InputEndpointStatus, OutputEndpointStatus};
 
 pub(crate) type EndpointId = u64;
 
@@ -115,6 +136,8 @@ impl Controller {
     ///   transport or data format.
     ///
     /// * One or more of the endpoints fails to initialize.
+    ///
+    /// * The input journal specified in the global config cannot be created.
     pub fn with_config(
         mut circuit: DBSPHandle,
         catalog: Catalog,
@@ -127,8 +150,24 @@ impl Controller {
         let backpressure_thread_parker = Parker::new();
         let backpressure_thread_unparker = backpressure_thread_parker.unparker().clone();
 
+        let (nodes, edges) = circuit
+            .circuit_graph()
+            .map_err(|e| AnyError::msg(format!("error retrieving circuit graph: {e}")))?;
+        let taps = catalog.tap_names().map(str::to_string).collect();
+        let circuit_graph = CircuitGraph::new(nodes, edges, taps);
+
+        let journal = match &config.global.journal_path {
+            Some(path) => Some(JournalWriter::create(path, circuit.num_workers()).map_err(
+                |e| AnyError::msg(format!("error creating input journal '{path}': {e}")),
+            )?),
+            None => None,
+        };
+
         let inner = Arc::new(ControllerInner::new(
             catalog,
+            circuit_graph,
+            journal,
+            circuit.num_workers(),
             &config.global,
             circuit_thread_unparker,
             backpressure_thread_unparker,
@@ -189,6 +228,80 @@ impl Controller {
         self.inner.connect_input(endpoint_name, config)
     }
 
+    /// Disconnect an existing input endpoint.
+    ///
+    /// Stops the endpoint and removes its stats from the controller status.
+    /// Data received from the endpoint before it was disconnected is
+    /// processed as usual.  This method is asynchronous and may return
+    /// before the endpoint has been fully stopped.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the endpoint doesn't exist.
+    pub fn disconnect_input(&self, endpoint_name: &str) -> AnyResult<()> {
+        self.inner.disconnect_input(endpoint_name)
+    }
+
+    /// Pause an individual input endpoint.
+    ///
+    /// The endpoint remains paused while the pipeline is running until it
+    /// is unpaused via [`Self::start_input`].  Like [`Self::pause`], this
+    /// method is asynchronous.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the endpoint doesn't exist.
+    pub fn pause_input(&self, endpoint_name: &str) -> AnyResult<()> {
+        self.inner.set_input_paused(endpoint_name, true)
+    }
+
+    /// Unpause an input endpoint paused via [`Self::pause_input`].
+    ///
+    /// The endpoint starts streaming data when the pipeline is running.
+    /// An endpoint that has failed due to parse errors (see
+    /// [`ParseErrorConfig`]) remains paused until it is disconnected.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the endpoint doesn't exist.
+    pub fn start_input(&self, endpoint_name: &str) -> AnyResult<()> {
+        self.inner.set_input_paused(endpoint_name, false)
+    }
+
+    /// Connect a new output endpoint with specified name and configuration.
+    ///
+    /// The endpoint receives outputs produced by the circuit starting from
+    /// the next clock cycle.
+    ///
+    /// # Errors
+    ///
+    /// The method may fail for the following reasons:
+    ///
+    /// * The endpoint configuration is invalid, e.g., specifies an unknown
+    ///   transport, data format, or output stream, or an output stream that
+    ///   is already connected to another endpoint.
+    ///
+    /// * The endpoint fails to initialize.
+    pub fn connect_output(
+        &self,
+        endpoint_name: &str,
+        config: &OutputEndpointConfig,
+    ) -> AnyResult<()> {
+        self.inner.connect_output(endpoint_name, config)
+    }
+
+    /// Disconnect an existing output endpoint.
+    ///
+    /// Output batches queued for the endpoint but not yet sent to it are
+    /// discarded.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the endpoint doesn't exist.
+    pub fn disconnect_output(&self, endpoint_name: &str) -> AnyResult<()> {
+        self.inner.disconnect_output(endpoint_name)
+    }
+
     /// Change the state of all input endpoints to running.
     ///
     /// Start streaming data through all connected input endpoints.
@@ -215,6 +328,113 @@ impl Controller {
         self.inner.dump_profile();
     }
 
+    /// Returns the topology of the circuit and the names of all taps
+    /// registered in the catalog.
+    pub fn circuit_graph(&self) -> &CircuitGraph {
+        &self.inner.circuit_graph
+    }
+
+    /// Returns schemas of input and output streams registered in the
+    /// catalog.
+    pub fn schemas(&self) -> CatalogSchemas {
+        self.inner.catalog.lock().unwrap().schemas().clone()
+    }
+
+    /// Attach a client to a tap.
+    ///
+    /// Enables tap `tap_name` registered in the catalog (see
+    /// [`Catalog::register_tap`]) for the next `batches` clock cycles and
+    /// sends the contents of the tapped stream during these cycles to
+    /// `consumer`, encoded using the output format specified by `format` and
+    /// the schema of the tap, if any (see [`Catalog::tap_schema`]).
+    /// After that, the encoder and the consumer are dropped and the tap is
+    /// disabled unless other clients are attached to it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the tap or the format doesn't exist or the encoder fails to
+    /// initialize.
+    pub fn attach_tap(
+        &self,
+        tap_name: &str,
+        format: &FormatConfig,
+        batches: usize,
+        consumer: Box<dyn OutputConsumer>,
+    ) -> AnyResult<()> {
+        let (handle, schema) = {
+            let catalog = self.inner.catalog.lock().unwrap();
+            let handle = catalog
+                .tap(tap_name)
+                .ok_or_else(|| ControllerError::unknown_tap(tap_name))?
+                .fork();
+            (handle, catalog.tap_schema(tap_name).cloned())
+        };
+
+        let encoder = <dyn OutputFormat>::get_format(&format.name)
+            .ok_or_else(|| ControllerError::unknown_output_format(&format.name))?
+            .new_encoder_with_schema(&format.config, schema.as_ref(), consumer)?;
+
+        self.inner.taps.attach(tap_name, handle, encoder, batches);
+        Ok(())
+    }
+
+    /// Change the number of worker threads used by the circuit.
+    ///
+    /// The circuit must be created with
+    /// [`Runtime::init_rescalable_circuit`](`dbsp::Runtime::init_rescalable_circuit`).
+    /// This method is asynchronous: the circuit thread rescales the circuit
+    /// before its next clock cycle.  Errors that occur while rescaling the
+    /// circuit are reported via the error callback.
+    ///
+    /// # Errors
+    ///
+    /// Fails without changing the circuit if `workers` is 0 or if the input
+    /// journal is being recorded or replayed, since the journal does not
+    /// record changes to the number of workers.
+    pub fn rescale(&self, workers: usize) -> Result<(), ControllerError> {
+        if workers == 0 {
+            return Err(ControllerError::dbsp_error(DBSPError::Runtime(
+                RuntimeError::NoWorkers,
+            )));
+        }
+        self.inner.check_not_journaled("rescaling")?;
+        self.inner.rescale(workers);
+        Ok(())
+    }
+
+    /// Start a transaction.
+    ///
+    /// Inputs received by the pipeline are accumulated until the transaction
+    /// commits, at which point output endpoints receive the net change
+    /// produced by all inputs in the transaction.  See
+    /// [`DBSPHandle::start_transaction`] for details.  While the transaction
+    /// is open, an input endpoint that has buffered `max_buffered_records`
+    /// records is paused until the transaction commits or aborts, which
+    /// bounds the size of the transaction.  Like other
+    /// transaction methods, this method is asynchronous: it queues a request
+    /// for the circuit thread.  Errors are reported via the error callback.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the input journal is being recorded or replayed, since the
+    /// journal does not record transactions.
+    pub fn start_transaction(&self) -> Result<(), ControllerError> {
+        self.inner.check_not_journaled("a transaction")?;
+        self.inner.transaction_request(TransactionRequest::Start);
+        Ok(())
+    }
+
+    /// Commit the current transaction.
+    pub fn commit_transaction(&self) {
+        self.inner.transaction_request(TransactionRequest::Commit);
+    }
+
+    /// Abort the current transaction, discarding all inputs received since
+    /// the start of the transaction.
+    pub fn abort_transaction(&self) {
+        self.inner.transaction_request(TransactionRequest::Abort);
+    }
+
     /// Terminate the controller, stop all input endpoints and destroy the
     /// circuit.
     pub fn stop(self) -> AnyResult<()> {
@@ -241,6 +461,14 @@ impl Controller {
         let max_buffering_delay =
             Duration::from_micros(controller.status.global_config.max_buffering_delay_usecs);
         let min_batch_size_records = controller.status.global_config.min_batch_size_records;
+        let operator_metrics_interval = Duration::from_secs(
+            controller
+                .status
+                .global_config
+                .operator_metrics_interval_secs,
+        );
+        // Time when operator metrics were last collected.
+        let mut operator_metrics_time: Option<Instant> = None;
 
         loop {
             let dump_profile = controller
@@ -261,6 +489,49 @@ impl Controller {
                     }
                 }
             }
+            while let Some(request) = controller.transaction_requests.pop() {
+                let result = match request {
+                    TransactionRequest::Start => circuit.start_transaction(),
+                    TransactionRequest::Commit | TransactionRequest::Abort => {
+                        // All inputs buffered during the transaction are about to be
+                        // consumed or discarded: release backpressure.
+                        start = None;
+                        controller.status.consume_buffered_inputs();
+                        controller.unpark_backpressure();
+
+                        // Inputs acknowledged via `on_processed` during the transaction
+                        // are processed by the commit or discarded by the abort.
+                        let processed = controller.take_processed_callbacks();
+                        if let TransactionRequest::Commit = request {
+                            circuit.commit_transaction().map(|()| {
+                                controller.push_outputs();
+                                processed.into_iter().for_each(|callback| callback());
+                            })
+                        } else {
+                            // Inputs buffered before the start of the transaction
+                            // survive the abort; process them right away, since the
+                            // counters of buffered records no longer reflect them.
+                            circuit
+                                .abort_transaction()
+                                .and_then(|()| circuit.step())
+                                .map(|()| controller.push_outputs())
+                        }
+                    }
+                };
+                result.unwrap_or_else(|e| controller.error(ControllerError::dbsp_error(e)));
+            }
+            let rescale = controller.rescale_request.lock().unwrap().take();
+            if let Some(workers) = rescale {
+                match circuit.rescale(workers) {
+                    Ok(()) => {
+                        info!("circuit rescaled to {workers} workers");
+                        controller.workers.store(workers, Ordering::Release);
+                        // Operator metrics of the old set of workers are stale.
+                        operator_metrics_time = None;
+                    }
+                    Err(e) => controller.error(ControllerError::dbsp_error(e)),
+                }
+            }
             match controller.state() {
                 PipelineState::Running | PipelineState::Paused => {
                     // Backpressure in the output pipeline: wait for room in output buffers to
@@ -272,45 +543,88 @@ impl Controller {
                         continue;
                     }
 
+                    // Inside a transaction, inputs accumulate in input handles until the
+                    // transaction commits.  Buffered records are not consumed, so input
+                    // endpoints that exceed their `max_buffered_records` limit stay
+                    // paused by the backpressure thread until the transaction ends.
+                    if circuit.in_transaction() {
+                        debug!("circuit thread: park: transaction in progress");
+                        parker.park();
+                        debug!("circuit thread: unparked");
+                        continue;
+                    }
+
                     let buffered_records = controller.status.num_buffered_input_records();
 
+                    // When replaying a journal, clock cycles are determined by the journal
+                    // rather than the amount of buffered data.
+                    let replaying = controller.replay.active();
+
                     // We have sufficient buffered inputs or the buffering delay has expired --
                     // kick the circuit to consume buffered data.  Use strict inequality in case
                     // `min_batch_size_records` is 0.
-                    if buffered_records > min_batch_size_records
-                        || start
-                            .map(|start| start.elapsed() >= max_buffering_delay)
-                            .unwrap_or(false)
+                    // Endpoints waiting for their inputs to be processed (see
+                    // `InputConsumer::on_processed`) don't wait for more data.
+                    if (replaying && controller.replay.ready())
+                        || (!replaying
+                            && (buffered_records > min_batch_size_records
+                                || controller.has_processed_callbacks()
+                                || start
+                                    .map(|start| start.elapsed() >= max_buffering_delay)
+                                    .unwrap_or(false)))
                     {
                         start = None;
+                        // Callbacks registered so far are waiting for inputs pushed
+                        // before the counters are reset below, which this step consumes.
+                        let processed = controller.take_processed_callbacks();
                         // Reset all counters of buffered records and bytes to 0.
                         controller.status.consume_buffered_inputs();
                         // Wake up the backpressure thread to unpause endpoints blocked due to
                         // backpressure.
                         controller.unpark_backpressure();
                         debug!("circuit thread: calling 'circuit.step'");
-                        circuit
-                            .step()
-                            .unwrap_or_else(|e| controller.error(ControllerError::dbsp_error(e)));
+                        let step_start = Instant::now();
+                        if let Some(journal) = &controller.journal {
+                            // Fix the inputs consumed by this clock cycle and record the
+                            // step boundary atomically with respect to input probes (see
+                            // `journal` module docs).  The lock is released before
+                            // evaluating the circuit.
+                            let mut journal = journal.lock().unwrap();
+                            circuit.stage_inputs().unwrap_or_else(|e| {
+                                controller.error(ControllerError::dbsp_error(e))
+                            });
+                            journal.step().unwrap_or_else(|e| {
+                                controller.error(ControllerError::journal_error(e))
+                            });
+                        }
+                        let step_result = circuit.step();
+                        if replaying {
+                            controller.replay.step_complete();
+                        }
+                        controller.status.circuit_metrics.step(step_start.elapsed());
                         debug!("circuit thread: 'circuit.step' returned");
 
-                        // Push output batches to output pipelines.
-                        let outputs = controller.outputs.read().unwrap();
-                        for (endpoint_id, output) in outputs.iter() {
-                            // TODO: add an endpoint config option to consolidate output batches.
-                            let batch = output.output_handle.take_from_all();
-                            let num_records = batch.iter().map(|b| b.len()).sum();
-
-                            // Increment stats first, so we don't end up with negative counts.
-                            controller.status.enqueue_batch(*endpoint_id, num_records);
-                            output.queue.push(batch);
-
-                            // Wake up the output thread.  We're not trying to be smart here and
-                            // wake up the thread conditionally if it was previously idle, as I
-                            // don't expect this to make any real difference.
-                            output.unparker.unpark();
+                        if !operator_metrics_interval.is_zero()
+                            && operator_metrics_time
+                                .map(|time| time.elapsed() >= operator_metrics_interval)
+                                .unwrap_or(true)
+                        {
+                            operator_metrics_time = Some(Instant::now());
+                            match circuit.profile() {
+                                Ok(profiles) => controller
+                                    .status
+                                    .circuit_metrics
+                                    .set_operator_profiles(profiles),
+                                Err(e) => error!("failed to collect operator metrics: {e}"),
+                            }
                         }
-                    } else if buffered_records > 0 {
+
+                        controller.push_outputs();
+                        match step_result {
+                            Ok(()) => processed.into_iter().for_each(|callback| callback()),
+                            Err(e) => controller.error(ControllerError::dbsp_error(e)),
+                        }
+                    } else if !replaying && buffered_records > 0 {
                         // We have some buffered data, but less than `min_batch_size_records` --
                         // wait up to `max_buffering_delay` for more data to
                         // arrive.
@@ -342,12 +656,15 @@ impl Controller {
         // `Controller::pause()` methods).
         let mut global_pause = true;
 
-        // Endpoints paused due to backpressure.
+        // Endpoints paused due to backpressure or by the user.
         let mut paused_endpoints = HashSet::new();
 
         loop {
             let inputs = controller.inputs.lock().unwrap();
 
+            // Forget disconnected endpoints.
+            paused_endpoints.retain(|epid| inputs.contains_key(epid));
+
             match controller.state() {
                 PipelineState::Paused => {
                     // Pause circuit if not yet paused.
@@ -369,11 +686,19 @@ impl Controller {
                     global_pause = true;
                 }
                 PipelineState::Running => {
-                    // Resume endpoints that have buffer space, pause endpoints with full buffers.
+                    // Resume endpoints that have buffer space, pause endpoints with full buffers,
+                    // endpoints paused by the user, and failed endpoints.  Replay endpoints are
+                    // never paused due to backpressure: they wait for the circuit to consume their
+                    // inputs at each clock cycle boundary, and pausing them could prevent the
+                    // circuit from reaching the next boundary.
                     for (epid, ep) in inputs.iter() {
-                        if controller.status.input_endpoint_full(epid) {
-                            // The endpoint is full and is not yet in the paused state -- pause it
-                            // now.
+                        if controller.status.input_endpoint_paused(epid)
+                            || controller.status.input_endpoint_failed(epid)
+                            || (controller.status.input_endpoint_full(epid)
+                                && !controller.replay.contains(epid))
+                        {
+                            // The endpoint should be paused and is not yet in the paused state --
+                            // pause it now.
                             if !global_pause && !paused_endpoints.contains(epid) {
                                 ep.endpoint.pause().unwrap_or_else(|e| {
                                     controller.input_transport_error(
@@ -428,8 +753,9 @@ impl InputEndpointDescr {
 }
 
 /// A lock-free queue used to send output batches from the circuit thread
-/// to output endpoint threads.
-type BatchQueue = SegQueue<Vec<Box<dyn SerBatch>>>;
+/// to output endpoint threads, along with the number of the clock cycle
+/// that produced them.
+type BatchQueue = SegQueue<(u64, Vec<Box<dyn SerBatch>>)>;
 
 /// State tracked by the controller for each output endpoint.
 struct OutputEndpointDescr {
@@ -450,11 +776,18 @@ struct OutputEndpointDescr {
     /// Handle for the output stream.
     output_handle: Box<dyn SerOutputBatchHandle>,
 
+    /// Consolidate outputs of all workers into a single batch before
+    /// queueing them (see [`OutputDeliveryConfig::consolidate`]).
+    consolidate: bool,
+
     /// FIFO queue of batches read from the stream.
     queue: Arc<BatchQueue>,
 
     /// Unparker for the endpoint thread.
     unparker: Unparker,
+
+    /// Set when the endpoint is disconnected to stop the endpoint thread.
+    disconnected: Arc<AtomicBool>,
 }
 
 impl OutputEndpointDescr {
@@ -462,18 +795,29 @@ impl OutputEndpointDescr {
         endpoint_name: &str,
         stream_name: &str,
         output_handle: Box<dyn SerOutputBatchHandle>,
+        consolidate: bool,
         unparker: Unparker,
     ) -> Self {
         Self {
             endpoint_name: endpoint_name.to_string(),
             stream_name: stream_name.to_string(),
             output_handle,
+            consolidate,
             queue: Arc::new(SegQueue::new()),
             unparker,
+            disconnected: Arc::new(AtomicBool::new(false)),
         }
     }
 }
 
+/// Transaction command queued by [`Controller`] for the circuit thread.
+#[derive(Clone, Copy, Debug)]
+enum TransactionRequest {
+    Start,
+    Commit,
+    Abort,
+}
+
 /// Controller state sharable across threads.
 ///
 /// A reference to this struct is held by each input probe and by both
@@ -482,9 +826,31 @@ struct ControllerInner {
     status: ControllerStatus,
     state: AtomicU32,
     dump_profile_request: AtomicBool,
+    // New number of workers requested via `Controller::rescale`.
+    rescale_request: Mutex<Option<usize>>,
+    // Transaction commands to be executed by the circuit thread, in order.
+    transaction_requests: SegQueue<TransactionRequest>,
+    circuit_graph: CircuitGraph,
+    taps: TapClients,
+    // Input journal, if journaling is enabled.
+    journal: Option<Mutex<JournalWriter>>,
+    // Synchronizes clock cycles with replay endpoints.
+    replay: ReplayBarrier,
+    // Current number of circuit workers.
+    workers: AtomicUsize,
     catalog: Arc<Mutex<Catalog>>,
     inputs: Mutex<BTreeMap<EndpointId, InputEndpointDescr>>,
     outputs: ShardedLock<BTreeMap<EndpointId, OutputEndpointDescr>>,
+    // Callbacks registered via `InputConsumer::on_processed`, waiting for
+    // the next clock cycle to complete.
+    processed_callbacks: Mutex<Vec<Box<dyn FnOnce() + Send>>>,
+    // Set once the circuit thread has pushed outputs of the first clock
+    // cycle to output endpoints (protected by the `outputs` lock).
+    outputs_pushed: AtomicBool,
+    // Endpoint ids are never reused, so that stats and threads of a
+    // disconnected endpoint cannot be confused with a new endpoint.
+    next_input_id: AtomicU64,
+    next_output_id: AtomicU64,
     circuit_thread_unparker: Unparker,
     backpressure_thread_unparker: Unparker,
     error_cb: Box<dyn Fn(ControllerError) + Send + Sync>,
@@ -493,6 +859,9 @@ struct ControllerInner {
 impl ControllerInner {
     fn new(
         catalog: Catalog,
+        circuit_graph: CircuitGraph,
+        journal: Option<JournalWriter>,
+        workers: usize,
         global_config: &GlobalControllerConfig,
         circuit_thread_unparker: Unparker,
         backpressure_thread_unparker: Unparker,
@@ -506,9 +875,20 @@ impl ControllerInner {
             status,
             state,
             dump_profile_request,
+            rescale_request: Mutex::new(None),
+            transaction_requests: SegQueue::new(),
+            circuit_graph,
+            taps: TapClients::default(),
+            journal: journal.map(Mutex::new),
+            replay: ReplayBarrier::default(),
+            workers: AtomicUsize::new(workers),
             catalog: Arc::new(Mutex::new(catalog)),
             inputs: Mutex::new(BTreeMap::new()),
             outputs: ShardedLock::new(BTreeMap::new()),
+            processed_callbacks: Mutex::new(Vec::new()),
+            outputs_pushed: AtomicBool::new(false),
+            next_input_id: AtomicU64::new(0),
+            next_output_id: AtomicU64::new(0),
             circuit_thread_unparker,
             backpressure_thread_unparker,
             error_cb,
@@ -538,12 +918,41 @@ impl ControllerInner {
             .ok_or_else(|| ControllerError::unknown_input_format(&endpoint_config.format.name))?;
         let parser = format.new_parser(&endpoint_config.format.config, &self.catalog)?;
 
+        let endpoint_id = self.next_input_id.fetch_add(1, Ordering::AcqRel);
+
+        // Create dead-letter queue.
+        let parse_error_config = &endpoint_config.parse_errors;
+        let dead_letter = if parse_error_config.policy == ParseErrorPolicy::DeadLetter {
+            let transport_config = parse_error_config
+                .dead_letter
+                .as_ref()
+                .ok_or_else(|| ControllerError::missing_dead_letter_transport(endpoint_name))?;
+            let transport = <dyn OutputTransport>::get_transport(&transport_config.name)
+                .ok_or_else(|| ControllerError::unknown_output_transport(&transport_config.name))?;
+
+            let self_weak = Arc::downgrade(self);
+            let endpoint_name_str = endpoint_name.to_string();
+            let endpoint = transport.new_named_endpoint(
+                endpoint_name,
+                &transport_config.config,
+                Box::new(move |_fatal: bool, e: AnyError| {
+                    if let Some(controller) = self_weak.upgrade() {
+                        controller.error(ControllerError::dead_letter_error(&endpoint_name_str, e))
+                    }
+                }),
+            )?;
+            Some(Arc::new(DeadLetterQueue::new(endpoint)))
+        } else {
+            None
+        };
+
         // Create probe.
-        let endpoint_id = inputs.keys().rev().next().map(|k| k + 1).unwrap_or(0);
         let probe = Box::new(InputProbe::new(
             endpoint_id,
             endpoint_name,
             parser,
+            parse_error_config,
+            dead_letter,
             self.clone(),
             self.circuit_thread_unparker.clone(),
             self.backpressure_thread_unparker.clone(),
@@ -555,8 +964,28 @@ impl ControllerInner {
                 ControllerError::unknown_input_transport(&endpoint_config.transport.name)
             })?;
 
+        // A replay endpoint must run with the same number of workers as the
+        // recorded pipeline; otherwise the circuit may produce different
+        // outputs.
+        let replay = transport.replays_journal();
+        if replay {
+            let header = replay_journal_header(&endpoint_config.transport.config)?;
+            let workers = self.workers.load(Ordering::Acquire);
+            if header.workers != workers as u64 {
+                Err(ControllerError::replay_workers_mismatch(
+                    endpoint_name,
+                    header.workers,
+                    workers,
+                ))?;
+            }
+        }
+
         let endpoint = transport.new_endpoint(&endpoint_config.transport.config, probe)?;
 
+        if replay {
+            self.replay.add_endpoint(endpoint_id);
+        }
+
         inputs.insert(
             endpoint_id,
             InputEndpointDescr::new(endpoint_name, endpoint),
@@ -572,6 +1001,47 @@ impl ControllerInner {
         Ok(())
     }
 
+    /// Lookup input endpoint by name.
+    fn input_endpoint_id(
+        inputs: &BTreeMap<EndpointId, InputEndpointDescr>,
+        endpoint_name: &str,
+    ) -> AnyResult<EndpointId> {
+        inputs
+            .iter()
+            .find(|(_, ep)| ep.endpoint_name == endpoint_name)
+            .map(|(endpoint_id, _)| *endpoint_id)
+            .ok_or_else(|| AnyError::from(ControllerError::unknown_input_endpoint(endpoint_name)))
+    }
+
+    fn disconnect_input(self: &Arc<Self>, endpoint_name: &str) -> AnyResult<()> {
+        let mut inputs = self.inputs.lock().unwrap();
+
+        let endpoint_id = Self::input_endpoint_id(&inputs, endpoint_name)?;
+        let ep = inputs.remove(&endpoint_id).unwrap();
+        ep.endpoint.disconnect();
+
+        drop(inputs);
+
+        // Release the endpoint if it is waiting for a clock cycle.
+        self.replay.remove_endpoint(endpoint_id);
+        self.status.remove_input(&endpoint_id);
+
+        self.unpark_backpressure();
+        Ok(())
+    }
+
+    fn set_input_paused(&self, endpoint_name: &str, paused: bool) -> AnyResult<()> {
+        let inputs = self.inputs.lock().unwrap();
+
+        let endpoint_id = Self::input_endpoint_id(&inputs, endpoint_name)?;
+        self.status.set_input_endpoint_paused(&endpoint_id, paused);
+
+        drop(inputs);
+
+        self.unpark_backpressure();
+        Ok(())
+    }
+
     /// Unpark the circuit thread.
     fn unpark_circuit(&self) {
         self.circuit_thread_unparker.unpark();
@@ -602,6 +1072,17 @@ impl ControllerInner {
             }
         }
 
+        // A snapshot endpoint accumulates the output collection from the
+        // changes it receives, so it must observe all changes since the first
+        // clock cycle.  Holding the `outputs` lock guarantees that the circuit
+        // thread doesn't push outputs of a new step until the endpoint has
+        // been added.
+        if endpoint_config.delivery.mode == OutputMode::Snapshot
+            && self.outputs_pushed.load(Ordering::Acquire)
+        {
+            Err(ControllerError::snapshot_after_start(endpoint_name))?;
+        }
+
         // Create output pipeline, consisting of an encoder, output probe and
         // transport endpoint; run the pipeline in a separate thread.
         //
@@ -609,14 +1090,18 @@ impl ControllerInner {
         // │encoder├──►│OutputProbe├──►│endpoint├──►
         // └───────┘   └───────────┘   └────────┘
 
-        // Lookup output handle in catalog.
-        let collection_handle = self
-            .catalog
-            .lock()
-            .unwrap()
-            .output_batch_handle(&endpoint_config.stream)
-            .ok_or_else(|| ControllerError::unknown_output_stream(&endpoint_config.stream))?
-            .fork();
+        // Lookup output handle and schema in catalog.
+        let (collection_handle, schema) = {
+            let catalog = self.catalog.lock().unwrap();
+            let collection_handle = catalog
+                .output_batch_handle(&endpoint_config.stream)
+                .ok_or_else(|| ControllerError::unknown_output_stream(&endpoint_config.stream))?
+                .fork();
+            (
+                collection_handle,
+                catalog.output_schema(&endpoint_config.stream).cloned(),
+            )
+        };
 
         // Create transport endpoint.
         let transport = <dyn OutputTransport>::get_transport(&endpoint_config.transport.name)
@@ -624,11 +1109,12 @@ impl ControllerInner {
                 ControllerError::unknown_output_transport(&endpoint_config.transport.name)
             })?;
 
-        let endpoint_id = outputs.keys().rev().next().map(|k| k + 1).unwrap_or(0);
+        let endpoint_id = self.next_output_id.fetch_add(1, Ordering::AcqRel);
         let endpoint_name_str = endpoint_name.to_string();
 
         let self_weak = Arc::downgrade(self);
-        let endpoint = transport.new_endpoint(
+        let endpoint = transport.new_named_endpoint(
+            endpoint_name,
             &endpoint_config.transport.config,
             Box::new(move |fatal: bool, e: AnyError| {
                 if let Some(controller) = self_weak.upgrade() {
@@ -636,28 +1122,45 @@ impl ControllerInner {
                 }
             }),
         )?;
+        let keyed = endpoint.is_keyed();
 
         // Create probe.
-        let probe = Box::new(OutputProbe::new(
+        let probe = Arc::new(Mutex::new(OutputProbe::new(
             endpoint_id,
             endpoint_name,
             endpoint,
             self.clone(),
-        ));
+        )));
 
         // Create encoder.
         let format = <dyn OutputFormat>::get_format(&endpoint_config.format.name)
             .ok_or_else(|| ControllerError::unknown_output_format(&endpoint_config.format.name))?;
-        let encoder = format.new_encoder(&endpoint_config.format.config, probe)?;
+        let encoder = format.new_encoder_with_schema(
+            &endpoint_config.format.config,
+            schema.as_ref(),
+            Box::new(SharedOutputProbe(probe.clone())),
+        )?;
+        if keyed && !encoder.supports_keyed_output() {
+            Err(ControllerError::keyed_output_not_supported(
+                endpoint_name,
+                &endpoint_config.format.name,
+            ))?;
+        }
 
         let parker = Parker::new();
         let endpoint_state = OutputEndpointDescr::new(
             endpoint_name,
             &endpoint_config.stream,
             collection_handle,
+            endpoint_config.delivery.consolidate,
             parker.unparker().clone(),
         );
+        let buffer = OutputBuffer::new(
+            &endpoint_config.delivery,
+            endpoint_config.max_buffered_records,
+        );
         let queue = endpoint_state.queue.clone();
+        let disconnected = endpoint_state.disconnected.clone();
         let controller = self.clone();
 
         outputs.insert(endpoint_id, endpoint_state);
@@ -669,8 +1172,11 @@ impl ControllerInner {
                 endpoint_id,
                 endpoint_name_string,
                 encoder,
+                probe,
+                buffer,
                 parker,
                 queue,
+                disconnected,
                 controller,
             )
         });
@@ -688,22 +1194,43 @@ impl ControllerInner {
         endpoint_id: EndpointId,
         endpoint_name: String,
         mut encoder: Box<dyn Encoder>,
+        probe: Arc<Mutex<OutputProbe>>,
+        mut buffer: OutputBuffer,
         parker: Parker,
         queue: Arc<BatchQueue>,
+        disconnected: Arc<AtomicBool>,
         controller: Arc<ControllerInner>,
     ) {
         loop {
-            if controller.state() == PipelineState::Terminated {
+            if controller.state() == PipelineState::Terminated
+                || disconnected.load(Ordering::Acquire)
+            {
                 return;
             }
 
-            // Dequeue the next output batch and push it to the encoder.
-            if let Some(data) = queue.pop() {
-                let num_records = data.iter().map(|b| b.len()).sum();
-
-                encoder
-                    .encode(data.as_slice())
-                    .unwrap_or_else(|e| controller.encode_error(endpoint_id, &endpoint_name, e));
+            // Dequeue the next output batch and add it to the output buffer.
+            let dequeued = if let Some((step, data)) = queue.pop() {
+                buffer.push(step, data);
+                true
+            } else {
+                false
+            };
+
+            // Push buffered outputs to the encoder once the endpoint's
+            // delivery policy says so.
+            if buffer.flush_ready() {
+                let (step, data, num_records) = buffer.flush();
+
+                match data {
+                    Ok(data) => {
+                        probe.lock().unwrap().batch_start(step);
+                        encoder.encode(data.as_slice()).unwrap_or_else(|e| {
+                            controller.encode_error(endpoint_id, &endpoint_name, e)
+                        });
+                        probe.lock().unwrap().batch_end();
+                    }
+                    Err(e) => controller.encode_error(endpoint_id, &endpoint_name, e),
+                }
 
                 // `num_records` output records have been transmitted --
                 // update output stats, wake up the circuit thread if the
@@ -713,14 +1240,41 @@ impl ControllerInner {
                     num_records,
                     &controller.circuit_thread_unparker,
                 );
-            } else {
+            } else if !dequeued {
                 // Queue is empty -- wait for the circuit thread to wake us up when
-                // more data is available.
-                parker.park();
+                // more data is available or until buffered outputs are due.
+                match buffer.timeout() {
+                    Some(timeout) => parker.park_timeout(timeout),
+                    None => parker.park(),
+                }
             }
         }
     }
 
+    fn disconnect_output(&self, endpoint_name: &str) -> AnyResult<()> {
+        let mut outputs = self.outputs.write().unwrap();
+
+        let endpoint_id = outputs
+            .iter()
+            .find(|(_, ep)| ep.endpoint_name == endpoint_name)
+            .map(|(endpoint_id, _)| *endpoint_id)
+            .ok_or_else(|| ControllerError::unknown_output_endpoint(endpoint_name))?;
+        let ep = outputs.remove(&endpoint_id).unwrap();
+
+        // Stop the endpoint thread.
+        ep.disconnected.store(true, Ordering::Release);
+        ep.unparker.unpark();
+
+        drop(outputs);
+
+        self.status.remove_output(&endpoint_id);
+
+        // Wake up the circuit thread in case it is blocked waiting for space in
+        // the endpoint's buffer.
+        self.unpark_circuit();
+        Ok(())
+    }
+
     fn state(self: &Arc<Self>) -> PipelineState {
         PipelineState::from_u32(self.state.load(Ordering::Acquire)).unwrap()
     }
@@ -748,6 +1302,9 @@ impl ControllerInner {
         self.state
             .store(PipelineState::Terminated as u32, Ordering::Release);
 
+        // Release replay endpoints waiting for the next clock cycle.
+        self.replay.terminate();
+
         self.unpark_circuit();
         self.unpark_backpressure();
     }
@@ -757,6 +1314,72 @@ impl ControllerInner {
         self.unpark_circuit();
     }
 
+    fn rescale(&self, workers: usize) {
+        *self.rescale_request.lock().unwrap() = Some(workers);
+        self.unpark_circuit();
+    }
+
+    /// Refuse `operation`, which is not recorded in the input journal, while
+    /// a journal is being recorded or replayed.
+    fn check_not_journaled(&self, operation: &str) -> Result<(), ControllerError> {
+        if self.journal.is_some() || self.replay.active() {
+            Err(ControllerError::unsupported_with_journal(operation))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Invoke `callback` after the next clock cycle completes.
+    fn on_processed(&self, callback: Box<dyn FnOnce() + Send>) {
+        self.processed_callbacks.lock().unwrap().push(callback);
+        self.unpark_circuit();
+    }
+
+    fn has_processed_callbacks(&self) -> bool {
+        !self.processed_callbacks.lock().unwrap().is_empty()
+    }
+
+    fn take_processed_callbacks(&self) -> Vec<Box<dyn FnOnce() + Send>> {
+        take(&mut *self.processed_callbacks.lock().unwrap())
+    }
+
+    fn transaction_request(&self, request: TransactionRequest) {
+        self.transaction_requests.push(request);
+        self.unpark_circuit();
+    }
+
+    /// Push output batches produced by the last clock cycle to output
+    /// pipelines.
+    fn push_outputs(&self) {
+        let step = self
+            .status
+            .circuit_metrics
+            .num_steps
+            .load(Ordering::Acquire);
+        let outputs = self.outputs.read().unwrap();
+        self.outputs_pushed.store(true, Ordering::Release);
+        for (endpoint_id, output) in outputs.iter() {
+            let batch = if output.consolidate {
+                vec![output.output_handle.consolidate()]
+            } else {
+                output.output_handle.take_from_all()
+            };
+            let num_records = batch.iter().map(|b| b.len()).sum();
+
+            // Increment stats first, so we don't end up with negative counts.
+            self.status.enqueue_batch(*endpoint_id, num_records);
+            output.queue.push((step, batch));
+
+            // Wake up the output thread.  We're not trying to be smart here and
+            // wake up the thread conditionally if it was previously idle, as I
+            // don't expect this to make any real difference.
+            output.unparker.unpark();
+        }
+        drop(outputs);
+
+        self.taps.push();
+    }
+
     fn error(&self, error: ControllerError) {
         (self.error_cb)(error);
     }
@@ -780,11 +1403,6 @@ impl ControllerInner {
         ));
     }
 
-    fn parse_error(&self, endpoint_id: EndpointId, endpoint_name: &str, error: AnyError) {
-        self.status.parse_error(endpoint_id);
-        self.error(ControllerError::parse_error(endpoint_name, error));
-    }
-
     fn encode_error(&self, endpoint_id: EndpointId, endpoint_name: &str, error: AnyError) {
         self.status.encode_error(endpoint_id);
         self.error(ControllerError::encode_error(endpoint_name, error));
@@ -820,6 +1438,8 @@ struct InputProbe {
     endpoint_id: EndpointId,
     endpoint_name: String,
     parser: Box<dyn Parser>,
+    parse_error_config: ParseErrorConfig,
+    dead_letter: Option<Arc<DeadLetterQueue>>,
     controller: Arc<ControllerInner>,
     circuit_thread_unparker: Unparker,
     backpressure_thread_unparker: Unparker,
@@ -830,6 +1450,8 @@ impl InputProbe {
         endpoint_id: EndpointId,
         endpoint_name: &str,
         parser: Box<dyn Parser>,
+        parse_error_config: &ParseErrorConfig,
+        dead_letter: Option<Arc<DeadLetterQueue>>,
         controller: Arc<ControllerInner>,
         circuit_thread_unparker: Unparker,
         backpressure_thread_unparker: Unparker,
@@ -838,22 +1460,137 @@ impl InputProbe {
             endpoint_id,
             endpoint_name: endpoint_name.to_owned(),
             parser,
+            parse_error_config: parse_error_config.clone(),
+            dead_letter,
             controller,
             circuit_thread_unparker,
             backpressure_thread_unparker,
         }
     }
-}
 
-/// `InputConsumer` interface exposed to the transport endpoint.
-impl InputConsumer for InputProbe {
-    fn input(&mut self, data: &[u8]) {
+    /// The endpoint has failed and must ignore further inputs until the
+    /// backpressure thread pauses it.
+    fn failed(&self) -> bool {
+        self.controller
+            .status
+            .input_endpoint_failed(&self.endpoint_id)
+    }
+
+    /// Fail the endpoint: record a fatal error in the endpoint status and
+    /// ask the backpressure thread to pause the endpoint, so that the
+    /// transport stops reading (and, e.g., acknowledging) further inputs.
+    fn fail(&self, error: AnyError) {
+        if self
+            .controller
+            .status
+            .fail_input_endpoint(self.endpoint_id, &error)
+        {
+            self.controller
+                .error(ControllerError::parse_error(&self.endpoint_name, error));
+            self.controller.unpark_backpressure();
+        }
+    }
+
+    /// Returns `true` if records parsed from an input buffer that produced
+    /// `errors` should be pushed to the circuit.
+    fn accept(&self, errors: &[ParseError]) -> bool {
+        errors.is_empty() || self.parse_error_config.policy != ParseErrorPolicy::Fail
+    }
+
+    /// Process parse errors: write invalid records to the dead-letter queue,
+    /// update endpoint stats, and notify the error callback.
+    fn parse_errors(&self, errors: Vec<ParseError>) {
+        if errors.is_empty() {
+            return;
+        }
+
+        if let Some(dead_letter) = &self.dead_letter {
+            dead_letter
+                .push(&self.endpoint_name, &errors)
+                .unwrap_or_else(|e| {
+                    self.controller
+                        .error(ControllerError::dead_letter_error(&self.endpoint_name, e))
+                });
+        }
+
+        let num_errors = errors.len() as u64;
+        let total_errors = self
+            .controller
+            .status
+            .parse_errors(self.endpoint_id, num_errors);
+
+        for ParseError { offset, error, .. } in errors.into_iter() {
+            self.controller.error(ControllerError::parse_error(
+                &self.endpoint_name,
+                AnyError::msg(format!("invalid record at offset {offset}: {error}")),
+            ));
+        }
+
+        if self.parse_error_config.policy == ParseErrorPolicy::Fail {
+            self.fail(AnyError::msg(
+                "invalid input with the 'fail' parse error policy; stopping the endpoint",
+            ));
+        } else if let Some(max_errors) = self.parse_error_config.max_errors {
+            if total_errors > max_errors {
+                self.fail(AnyError::msg(format!(
+                    "the number of parse errors exceeded the maximum of {max_errors}; stopping the endpoint"
+                )));
+            }
+        }
+    }
+
+    /// Push records parsed so far to the circuit if `accepted` is `true` or
+    /// discard them otherwise, and record the corresponding journal entry
+    /// using `record`.
+    ///
+    /// When journaling is enabled, holds the journal lock while pushing data
+    /// and writing the entry, so that the journal entry precedes the clock
+    /// cycle that consumes the data (see `journal` module docs).  Every chunk
+    /// is journaled, including rejected ones, since the replayed parser must
+    /// observe the same sequence of chunks.
+    fn flush_and_journal<F>(&mut self, accepted: bool, record: F)
+    where
+        F: FnOnce(&mut JournalWriter, &str) -> AnyResult<()>,
+    {
+        let mut journal = self
+            .controller
+            .journal
+            .as_ref()
+            .map(|journal| journal.lock().unwrap());
+
+        if accepted {
+            self.parser.flush();
+        } else {
+            self.parser.clear();
+        }
+
+        if let Some(journal) = journal.as_mut() {
+            record(journal, &self.endpoint_name)
+                .unwrap_or_else(|e| self.controller.error(ControllerError::journal_error(e)));
+        }
+    }
+
+    /// Parse a chunk of data received by the endpoint, with optional metadata,
+    /// and push it to the circuit.
+    fn input_chunk(&mut self, data: &[u8], metadata: Option<&InputMetadata>) {
         // println!("input consumer {} bytes", data.len());
-        // Pass input buffer to the parser.
-        match self.parser.input(data) {
-            Ok(num_records) => {
-                // Success: push data to the input handle, update stats.
-                self.parser.flush();
+        // Pass input buffer to the parser.  Parsing happens outside of the journal
+        // lock; only pushing parsed data to the circuit and recording the chunk
+        // in the journal are serialized with clock cycles.
+        let parsed = (!self.failed()).then(|| match metadata {
+            None => self.parser.input_with_errors(data),
+            Some(metadata) => self.parser.input_with_metadata(data, metadata),
+        });
+
+        let accepted = matches!(&parsed, Some((_, errors)) if self.accept(errors));
+        self.flush_and_journal(accepted, |journal, endpoint| match metadata {
+            None => journal.input(endpoint, data),
+            Some(metadata) => journal.input_with_metadata(endpoint, data, metadata),
+        });
+
+        if let Some((num_records, errors)) = parsed {
+            if accepted {
+                // Update stats.
                 self.controller.status.input_batch(
                     self.endpoint_id,
                     data.len(),
@@ -863,22 +1600,33 @@ impl InputConsumer for InputProbe {
                     &self.backpressure_thread_unparker,
                 );
             }
-            Err(error) => {
-                self.parser.clear();
-                self.controller
-                    .parse_error(self.endpoint_id, &self.endpoint_name, error);
-            }
+            self.parse_errors(errors);
         }
     }
+}
+
+/// `InputConsumer` interface exposed to the transport endpoint.
+impl InputConsumer for InputProbe {
+    fn input(&mut self, data: &[u8]) {
+        self.input_chunk(data, None)
+    }
+
+    fn input_with_metadata(&mut self, data: &[u8], metadata: &InputMetadata) {
+        self.input_chunk(data, Some(metadata))
+    }
 
     fn eoi(&mut self) {
         // The endpoint reached end-of-file.  Notify and flush the parser (even though
         // no new data has been received, the parser may contain some partially
         // parsed data and may be waiting for, e.g., and end-of-line or
         // end-of-file to finish parsing it).
-        match self.parser.eoi() {
-            Ok(num_records) => {
-                self.parser.flush();
+        let parsed = (!self.failed()).then(|| self.parser.eoi_with_errors());
+
+        let accepted = matches!(&parsed, Some((_, errors)) if self.accept(errors));
+        self.flush_and_journal(accepted, |journal, endpoint| journal.eoi(endpoint));
+
+        if let Some((num_records, errors)) = parsed {
+            if accepted {
                 self.controller.status.eoi(
                     self.endpoint_id,
                     num_records,
@@ -886,11 +1634,7 @@ impl InputConsumer for InputProbe {
                     &self.circuit_thread_unparker,
                 );
             }
-            Err(error) => {
-                self.parser.clear();
-                self.controller
-                    .error(ControllerError::parse_error(&self.endpoint_name, error));
-            }
+            self.parse_errors(errors);
         }
     }
 
@@ -899,11 +1643,23 @@ impl InputConsumer for InputProbe {
             .input_transport_error(self.endpoint_id, &self.endpoint_name, fatal, error);
     }
 
+    fn replay_barrier(&mut self, step: Option<u64>) -> bool {
+        self.controller
+            .replay
+            .arrive(self.endpoint_id, step, &self.circuit_thread_unparker)
+    }
+
+    fn on_processed(&mut self, callback: Box<dyn FnOnce() + Send>) {
+        self.controller.on_processed(callback);
+    }
+
     fn fork(&self) -> Box<dyn InputConsumer> {
         Box::new(Self::new(
             self.endpoint_id,
             &self.endpoint_name,
             self.parser.fork(),
+            &self.parse_error_config,
+            self.dead_letter.clone(),
             self.controller.clone(),
             self.circuit_thread_unparker.clone(),
             self.backpressure_thread_unparker.clone(),
@@ -936,6 +1692,29 @@ impl OutputProbe {
     }
 }
 
+impl OutputProbe {
+    fn transport_error(&self, error: AnyError) {
+        self.controller
+            .output_transport_error(self.endpoint_id, &self.endpoint_name, false, error);
+    }
+
+    /// Notify the endpoint that the encoder is about to push outputs of
+    /// clock cycle `step` (see [`OutputEndpoint::batch_start`]).
+    fn batch_start(&mut self, step: u64) {
+        if let Err(error) = self.endpoint.batch_start(step) {
+            self.transport_error(error);
+        }
+    }
+
+    /// Notify the endpoint that the encoder has pushed all outputs of the
+    /// current clock cycle (see [`OutputEndpoint::batch_end`]).
+    fn batch_end(&mut self) {
+        if let Err(error) = self.endpoint.batch_end() {
+            self.transport_error(error);
+        }
+    }
+}
+
 impl OutputConsumer for OutputProbe {
     fn push_buffer(&mut self, buffer: &[u8]) {
         let num_bytes = buffer.len();
@@ -946,15 +1725,46 @@ impl OutputConsumer for OutputProbe {
                     .status
                     .output_buffer(self.endpoint_id, num_bytes);
             }
-            Err(error) => {
-                self.controller.output_transport_error(
-                    self.endpoint_id,
-                    &self.endpoint_name,
-                    false,
-                    error,
-                );
+            Err(error) => self.transport_error(error),
+        }
+    }
+
+    fn is_keyed(&self) -> bool {
+        self.endpoint.is_keyed()
+    }
+
+    fn push_key(&mut self, key: &[u8], val: Option<&[u8]>) -> AnyResult<()> {
+        let num_bytes = key.len() + val.map_or(0, <[u8]>::len);
+
+        // Transport errors are reported by the probe, same as in
+        // `push_buffer`.
+        match self.endpoint.push_key(key, val) {
+            Ok(()) => {
+                self.controller
+                    .status
+                    .output_buffer(self.endpoint_id, num_bytes);
             }
+            Err(error) => self.transport_error(error),
         }
+        Ok(())
+    }
+}
+
+/// [`OutputProbe`] shared by the encoder, which pushes encoded outputs to it,
+/// and the output endpoint thread, which marks batch boundaries.
+struct SharedOutputProbe(Arc<Mutex<OutputProbe>>);
+
+impl OutputConsumer for SharedOutputProbe {
+    fn push_buffer(&mut self, buffer: &[u8]) {
+        self.0.lock().unwrap().push_buffer(buffer)
+    }
+
+    fn is_keyed(&self) -> bool {
+        self.0.lock().unwrap().is_keyed()
+    }
+
+    fn push_key(&mut self, key: &[u8], val: Option<&[u8]>) -> AnyResult<()> {
+        self.0.lock().unwrap().push_key(key, val)
     }
 }
 
@@ -962,10 +1772,16 @@ impl OutputConsumer for OutputProbe {
 mod test {
     use crate::{
         test::{generate_test_batch, test_circuit, wait, TestStruct},
-        Controller, ControllerConfig,
+        Catalog, Controller, ControllerConfig, FormatConfig, OutputConsumer,
     };
     use csv::{ReaderBuilder as CsvReaderBuilder, WriterBuilder as CsvWriterBuilder};
-    use std::fs::remove_file;
+    use dbsp::Runtime;
+    use std::{
+        borrow::Cow,
+        fs::remove_file,
+        io::Write,
+        sync::{atomic::Ordering, Arc, Mutex},
+    };
     use tempfile::NamedTempFile;
 
     use proptest::prelude::*;
@@ -1072,4 +1888,660 @@ outputs:
             assert_eq!(actual, expected);
         }
     }
+
+    #[derive(Clone, Default)]
+    struct BufferConsumer(Arc<Mutex<Vec<u8>>>);
+
+    impl OutputConsumer for BufferConsumer {
+        fn push_buffer(&mut self, buffer: &[u8]) {
+            self.0.lock().unwrap().extend_from_slice(buffer);
+        }
+    }
+
+    #[test]
+    fn test_tap() {
+        // Like `test_circuit`, but the input stream is also available via the
+        // `test_tap1` tap.
+        let (circuit, (input, output, tap)) = Runtime::init_circuit(4, |circuit| {
+            let (input, hinput) = circuit.add_input_zset::<TestStruct, i32>();
+            (hinput, input.output(), input.tap())
+        })
+        .unwrap();
+
+        let mut catalog = Catalog::new();
+        catalog.register_input_zset_handle("test_input1", input);
+        catalog.register_output_zset_handle("test_output1", output);
+        catalog.register_tap_zset_handle("test_tap1", tap);
+
+        let temp_input_file = NamedTempFile::new().unwrap();
+        let config_str = format!(
+            r#"
+inputs:
+    test_input1:
+        transport:
+            name: file
+            config:
+                path: {:?}
+                follow: false
+        format:
+            name: csv
+            config:
+                input_stream: test_input1
+        "#,
+            temp_input_file.path().to_str().unwrap(),
+        );
+        let config: ControllerConfig = serde_yaml::from_str(&config_str).unwrap();
+
+        let controller = Controller::with_config(
+            circuit,
+            catalog,
+            &config,
+            Box::new(|e| panic!("error: {e}")),
+        )
+        .unwrap();
+
+        let graph = controller.circuit_graph();
+        assert_eq!(graph.taps, vec!["test_tap1".to_string()]);
+        assert!(graph.nodes.iter().any(|node| node.name == "Tap"));
+
+        // The header requires the schema of the tap.
+        let csv = FormatConfig {
+            name: Cow::from("csv"),
+            config: serde_yaml::from_str("header: true").unwrap(),
+        };
+        assert!(controller
+            .attach_tap("no_such_tap", &csv, 1, Box::new(BufferConsumer::default()))
+            .is_err());
+
+        let consumer = BufferConsumer::default();
+        controller
+            .attach_tap("test_tap1", &csv, 1, Box::new(consumer.clone()))
+            .unwrap();
+
+        let data = vec![
+            TestStruct {
+                id: 1,
+                b: true,
+                i: Some(10),
+                s: "foo".to_string(),
+            },
+            TestStruct {
+                id: 2,
+                b: false,
+                i: None,
+                s: "bar".to_string(),
+            },
+        ];
+        let mut writer = CsvWriterBuilder::new()
+            .has_headers(false)
+            .from_writer(temp_input_file.as_file());
+        for val in data.iter().cloned() {
+            writer.serialize(val).unwrap();
+        }
+        writer.flush().unwrap();
+        controller.start();
+
+        // Wait for at least one record after the header row.
+        wait(
+            || {
+                consumer
+                    .0
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .filter(|&&c| c == b'\n')
+                    .count()
+                    > 1
+            },
+            None,
+        );
+        controller.stop().unwrap();
+
+        let buffer = consumer.0.lock().unwrap().clone();
+        let mut reader = CsvReaderBuilder::new()
+            .has_headers(true)
+            .from_reader(buffer.as_slice());
+        assert_eq!(
+            reader.headers().unwrap(),
+            vec!["id", "b", "i", "s", "weight"]
+        );
+        let tapped: Vec<_> = reader
+            .deserialize::<(TestStruct, i32)>()
+            .map(|res| res.unwrap().0)
+            .collect();
+        assert!(!tapped.is_empty());
+        assert!(tapped.iter().all(|val| data.contains(val)));
+    }
+
+    /// Run a pipeline with a single input endpoint described by
+    /// `input_config` until it outputs `num_records` records; return the
+    /// contents of the output file.
+    fn run_to_completion(global_config: &str, input_config: &str, num_records: usize) -> String {
+        let (circuit, catalog) = test_circuit(4);
+
+        let temp_output_path = NamedTempFile::new().unwrap().into_temp_path();
+        let output_path = temp_output_path.to_str().unwrap().to_string();
+        temp_output_path.close().unwrap();
+
+        let config_str = format!(
+            r#"
+{global_config}
+inputs:
+    test_input1:
+{input_config}
+        format:
+            name: csv
+            config:
+                input_stream: test_input1
+outputs:
+    test_output1:
+        stream: test_output1
+        transport:
+            name: file
+            config:
+                path: {output_path:?}
+        format:
+            name: csv
+        "#
+        );
+        let config: ControllerConfig = serde_yaml::from_str(&config_str).unwrap();
+
+        let controller = Controller::with_config(
+            circuit,
+            catalog,
+            &config,
+            Box::new(|e| panic!("error: {e}")),
+        )
+        .unwrap();
+        controller.start();
+
+        wait(
+            || {
+                controller
+                    .status()
+                    .output_status()
+                    .get(&0)
+                    .unwrap()
+                    .transmitted_records()
+                    == num_records as u64
+            },
+            None,
+        );
+        controller.stop().unwrap();
+
+        let output = std::fs::read_to_string(&output_path).unwrap();
+        remove_file(&output_path).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_record_replay() {
+        let data: Vec<_> = (0..1000)
+            .map(|id| TestStruct {
+                id,
+                b: id % 2 == 0,
+                i: Some(id as i64),
+                s: format!("{id}"),
+            })
+            .collect();
+
+        let temp_input_file = NamedTempFile::new().unwrap();
+        let mut writer = CsvWriterBuilder::new()
+            .has_headers(false)
+            .from_writer(temp_input_file.as_file());
+        for val in data.iter().cloned() {
+            writer.serialize(val).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let journal_path = NamedTempFile::new().unwrap().into_temp_path();
+
+        // Record: read inputs from a file in small chunks.
+        let recorded = run_to_completion(
+            &format!(
+                "min_batch_size_records: 10\njournal_path: {:?}",
+                journal_path.to_str().unwrap()
+            ),
+            &format!(
+                r#"        transport:
+            name: file
+            config:
+                path: {:?}
+                buffer_size_bytes: 100
+                follow: false"#,
+                temp_input_file.path().to_str().unwrap()
+            ),
+            data.len(),
+        );
+
+        // Replay: the replayed pipeline must produce identical output batches
+        // regardless of its buffering settings.
+        let replayed = run_to_completion(
+            "min_batch_size_records: 100000",
+            &format!(
+                r#"        transport:
+            name: replay
+            config:
+                path: {:?}
+                endpoint: test_input1"#,
+                journal_path.to_str().unwrap()
+            ),
+            data.len(),
+        );
+
+        assert_eq!(recorded, replayed);
+    }
+
+    #[test]
+    fn test_connect_disconnect() {
+        let (circuit, catalog) = test_circuit(4);
+
+        let data: Vec<_> = (0..100)
+            .map(|id| TestStruct {
+                id,
+                b: id % 2 == 0,
+                i: Some(id as i64),
+                s: format!("{id}"),
+            })
+            .collect();
+
+        let temp_input_file = NamedTempFile::new().unwrap();
+        let mut writer = CsvWriterBuilder::new()
+            .has_headers(false)
+            .from_writer(temp_input_file.as_file());
+        for val in data.iter().cloned() {
+            writer.serialize(val).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let output_path = NamedTempFile::new().unwrap().into_temp_path();
+
+        // Start with no endpoints.
+        let config: ControllerConfig = serde_yaml::from_str("inputs: {}").unwrap();
+        let controller = Controller::with_config(
+            circuit,
+            catalog,
+            &config,
+            Box::new(|e| panic!("error: {e}")),
+        )
+        .unwrap();
+        controller.start();
+
+        let output_config = serde_yaml::from_str(&format!(
+            r#"
+stream: test_output1
+transport:
+    name: file
+    config:
+        path: {:?}
+format:
+    name: csv"#,
+            output_path.to_str().unwrap()
+        ))
+        .unwrap();
+        controller
+            .connect_output("test_output1", &output_config)
+            .unwrap();
+
+        let input_config = serde_yaml::from_str(&format!(
+            r#"
+transport:
+    name: file
+    config:
+        path: {:?}
+        follow: false
+format:
+    name: csv
+    config:
+        input_stream: test_input1"#,
+            temp_input_file.path().to_str().unwrap()
+        ))
+        .unwrap();
+        controller
+            .connect_input("test_input1", &input_config)
+            .unwrap();
+
+        wait(
+            || {
+                controller
+                    .status()
+                    .output_status()
+                    .get(&0)
+                    .map(|status| status.transmitted_records() == data.len() as u64)
+                    .unwrap_or(false)
+            },
+            None,
+        );
+
+        // Snapshot endpoints can't be connected after the circuit has started
+        // producing outputs.
+        let snapshot_config = serde_yaml::from_str(&format!(
+            r#"
+stream: test_output1
+transport:
+    name: file
+    config:
+        path: {:?}
+format:
+    name: csv
+delivery:
+    mode: snapshot"#,
+            output_path.to_str().unwrap()
+        ))
+        .unwrap();
+        controller.disconnect_output("test_output1").unwrap();
+        assert!(controller
+            .connect_output("test_snapshot", &snapshot_config)
+            .is_err());
+        controller
+            .connect_output("test_output1", &output_config)
+            .unwrap();
+
+        controller.pause_input("test_input1").unwrap();
+        assert!(controller
+            .status()
+            .input_status()
+            .get(&0)
+            .unwrap()
+            .paused
+            .load(Ordering::Acquire));
+        controller.start_input("test_input1").unwrap();
+        assert!(controller.pause_input("unknown").is_err());
+
+        controller.disconnect_input("test_input1").unwrap();
+        assert!(controller.status().input_status().is_empty());
+        assert!(controller.disconnect_input("test_input1").is_err());
+
+        controller.disconnect_output("test_output1").unwrap();
+        assert!(controller.status().output_status().is_empty());
+        assert!(controller.disconnect_output("test_output1").is_err());
+
+        // Endpoint ids are not reused.
+        controller
+            .connect_input("test_input1", &input_config)
+            .unwrap();
+        assert!(controller.status().input_status().contains_key(&1));
+
+        controller.stop().unwrap();
+    }
+
+    #[test]
+    fn test_dead_letter() {
+        let (circuit, catalog) = test_circuit(4);
+
+        let data: Vec<_> = (0..10)
+            .map(|id| TestStruct {
+                id,
+                b: id % 2 == 0,
+                i: Some(id as i64),
+                s: format!("{id}"),
+            })
+            .collect();
+        let invalid = ["foo\n", "x,true,1,1\n"];
+
+        // Interleave valid and invalid records; remember the offset of each
+        // invalid record.
+        let mut input = Vec::new();
+        let mut expected_errors = Vec::new();
+        for (n, val) in data.iter().enumerate() {
+            let mut writer = CsvWriterBuilder::new()
+                .has_headers(false)
+                .from_writer(Vec::new());
+            writer.serialize(val).unwrap();
+            input.extend_from_slice(&writer.into_inner().unwrap());
+
+            if n % 4 == 0 {
+                let record = invalid[(n / 4) % 2];
+                expected_errors.push((input.len() as u64, record));
+                input.extend_from_slice(record.as_bytes());
+            }
+        }
+
+        let mut temp_input_file = NamedTempFile::new().unwrap();
+        temp_input_file.write_all(&input).unwrap();
+        let dead_letter_path = NamedTempFile::new().unwrap().into_temp_path();
+        let output_path = NamedTempFile::new().unwrap().into_temp_path();
+
+        // Use a small buffer size to test records split across buffers.
+        let config_str = format!(
+            r#"
+inputs:
+    test_input1:
+        transport:
+            name: file
+            config:
+                path: {:?}
+                buffer_size_bytes: 7
+                follow: false
+        format:
+            name: csv
+            config:
+                input_stream: test_input1
+        parse_errors:
+            policy: dead_letter
+            dead_letter:
+                name: file
+                config:
+                    path: {:?}
+outputs:
+    test_output1:
+        stream: test_output1
+        transport:
+            name: file
+            config:
+                path: {:?}
+        format:
+            name: csv
+        "#,
+            temp_input_file.path().to_str().unwrap(),
+            dead_letter_path.to_str().unwrap(),
+            output_path.to_str().unwrap(),
+        );
+        let config: ControllerConfig = serde_yaml::from_str(&config_str).unwrap();
+
+        let controller =
+            Controller::with_config(circuit, catalog, &config, Box::new(|_| {})).unwrap();
+        controller.start();
+
+        // Valid records must reach the output despite parse errors.
+        wait(
+            || {
+                let status = controller.status();
+                status
+                    .output_status()
+                    .get(&0)
+                    .unwrap()
+                    .transmitted_records()
+                    == data.len() as u64
+                    && status
+                        .input_status()
+                        .get(&0)
+                        .unwrap()
+                        .metrics
+                        .num_parse_errors
+                        .load(Ordering::Acquire)
+                        == expected_errors.len() as u64
+            },
+            None,
+        );
+        controller.stop().unwrap();
+
+        let dead_letters: Vec<serde_json::Value> = std::fs::read_to_string(&dead_letter_path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(dead_letters.len(), expected_errors.len());
+
+        for (dead_letter, (offset, record)) in dead_letters.iter().zip(expected_errors.iter()) {
+            assert_eq!(dead_letter["endpoint"], "test_input1");
+            assert_eq!(dead_letter["offset"], *offset);
+            assert_eq!(dead_letter["data"], *record);
+            assert!(dead_letter["error"].is_string());
+        }
+    }
+
+    #[test]
+    fn test_parse_error_fail() {
+        let (circuit, catalog) = test_circuit(4);
+
+        let mut temp_input_file = NamedTempFile::new().unwrap();
+        temp_input_file
+            .write_all(b"1,true,1,1\nfoo\n2,false,2,2\n")
+            .unwrap();
+
+        let config_str = format!(
+            r#"
+inputs:
+    test_input1:
+        transport:
+            name: file
+            config:
+                path: {:?}
+                follow: false
+        format:
+            name: csv
+            config:
+                input_stream: test_input1
+        "#,
+            temp_input_file.path().to_str().unwrap(),
+        );
+        let config: ControllerConfig = serde_yaml::from_str(&config_str).unwrap();
+
+        let controller =
+            Controller::with_config(circuit, catalog, &config, Box::new(|_| {})).unwrap();
+        controller.start();
+
+        // The default `fail` policy fails the endpoint on the first error.
+        wait(
+            || {
+                controller
+                    .status()
+                    .input_status()
+                    .get(&0)
+                    .unwrap()
+                    .failed
+                    .load(Ordering::Acquire)
+            },
+            None,
+        );
+
+        {
+            let input_status = controller.status().input_status();
+            let endpoint_status = input_status.get(&0).unwrap();
+            assert!(endpoint_status.fatal_error.lock().unwrap().is_some());
+            assert_eq!(
+                endpoint_status
+                    .metrics
+                    .num_parse_errors
+                    .load(Ordering::Acquire),
+                1
+            );
+        }
+
+        // A failed endpoint stays paused.
+        controller.start_input("test_input1").unwrap();
+        assert!(controller.status().input_endpoint_failed(&0));
+
+        controller.stop().unwrap();
+    }
+
+    #[test]
+    fn test_keyed_output_not_supported() {
+        use crate::{
+            register_output_format, register_output_transport, Encoder, OutputEndpoint,
+            OutputFormat, OutputTransport, SerBatch,
+        };
+        use anyhow::{Error as AnyError, Result as AnyResult};
+        use serde_yaml::Value as YamlValue;
+
+        struct KeyedTransport;
+        struct KeyedEndpoint;
+
+        impl OutputTransport for KeyedTransport {
+            fn name(&self) -> Cow<'static, str> {
+                Cow::Borrowed("test_keyed")
+            }
+
+            fn new_endpoint(
+                &self,
+                _config: &YamlValue,
+                _async_error_callback: Box<dyn Fn(bool, AnyError) + Send + Sync>,
+            ) -> AnyResult<Box<dyn OutputEndpoint>> {
+                Ok(Box::new(KeyedEndpoint))
+            }
+        }
+
+        impl OutputEndpoint for KeyedEndpoint {
+            fn push_buffer(&mut self, _buffer: &[u8]) -> AnyResult<()> {
+                Ok(())
+            }
+
+            fn is_keyed(&self) -> bool {
+                true
+            }
+        }
+
+        /// Format whose encoder doesn't support keyed output.
+        struct UnkeyedFormat;
+        struct UnkeyedEncoder;
+
+        impl OutputFormat for UnkeyedFormat {
+            fn name(&self) -> Cow<'static, str> {
+                Cow::Borrowed("test_unkeyed")
+            }
+
+            fn new_encoder(
+                &self,
+                _config: &YamlValue,
+                _consumer: Box<dyn OutputConsumer>,
+            ) -> AnyResult<Box<dyn Encoder>> {
+                Ok(Box::new(UnkeyedEncoder))
+            }
+        }
+
+        impl Encoder for UnkeyedEncoder {
+            fn encode(&mut self, _batches: &[Box<dyn SerBatch>]) -> AnyResult<()> {
+                Ok(())
+            }
+        }
+
+        register_output_transport(Box::new(KeyedTransport));
+        register_output_format(Box::new(UnkeyedFormat));
+
+        let (circuit, catalog) = test_circuit(1);
+        let config: ControllerConfig = serde_yaml::from_str("inputs: {}").unwrap();
+        let controller = Controller::with_config(
+            circuit,
+            catalog,
+            &config,
+            Box::new(|e| panic!("error: {e}")),
+        )
+        .unwrap();
+
+        let output_config = |format: &str| {
+            serde_yaml::from_str(&format!(
+                r#"
+stream: test_output1
+transport:
+    name: test_keyed
+format:
+    name: {format}"#
+            ))
+            .unwrap()
+        };
+
+        let error = controller
+            .connect_output("test_output1", &output_config("test_unkeyed"))
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("not supported by format 'test_unkeyed'"));
+
+        controller
+            .connect_output("test_output2", &output_config("csv"))
+            .unwrap();
+
+        controller.stop().unwrap();
+    }
 }
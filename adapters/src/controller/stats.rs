@@ -33,21 +33,91 @@
 use super::{EndpointId, GlobalControllerConfig, InputEndpointConfig, OutputEndpointConfig};
 use anyhow::Error as AnyError;
 use crossbeam::sync::{ShardedLock, ShardedLockReadGuard, Unparker};
+use dbsp::profile::WorkerProfile;
 use serde::{Serialize, Serializer};
 use std::{
     collections::BTreeMap,
     sync::{
-        atomic::{AtomicU64, Ordering},
-        Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex, MutexGuard,
     },
+    time::Duration,
 };
 
+/// Upper bounds, in seconds, of the buckets of the step latency histogram.
+pub const STEP_LATENCY_BUCKETS: [f64; 12] = [
+    0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 60.0,
+];
+
 #[derive(Default, Serialize)]
 pub struct GlobalControllerMetrics {
     /// Total number of records buffered by all endpoints.
     pub buffered_input_records: AtomicU64,
 }
 
+/// Performance counters of the circuit.
+#[derive(Default, Serialize)]
+pub struct CircuitMetrics {
+    /// Number of clock cycles evaluated by the circuit.
+    pub num_steps: AtomicU64,
+
+    /// Total time spent evaluating the circuit in microseconds.
+    pub total_step_usecs: AtomicU64,
+
+    /// Number of clock cycles in each bucket of `STEP_LATENCY_BUCKETS`.
+    /// The last element counts clock cycles that took longer than the
+    /// largest bucket.  Unlike Prometheus buckets, these counts are not
+    /// cumulative.
+    #[serde(skip)]
+    step_latency_buckets: [AtomicU64; STEP_LATENCY_BUCKETS.len() + 1],
+
+    /// Operator profiles of all workers, refreshed periodically by the
+    /// circuit thread.
+    #[serde(skip)]
+    operator_profiles: Mutex<Vec<WorkerProfile>>,
+}
+
+impl CircuitMetrics {
+    /// Record the completion of a clock cycle that took `latency`.
+    pub fn step(&self, latency: Duration) {
+        let bucket = STEP_LATENCY_BUCKETS
+            .iter()
+            .position(|bound| latency.as_secs_f64() <= *bound)
+            .unwrap_or(STEP_LATENCY_BUCKETS.len());
+
+        self.step_latency_buckets[bucket].fetch_add(1, Ordering::AcqRel);
+        self.total_step_usecs
+            .fetch_add(latency.as_micros() as u64, Ordering::AcqRel);
+        self.num_steps.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Cumulative counts of clock cycles whose latency does not exceed each
+    /// of `STEP_LATENCY_BUCKETS`.
+    pub fn step_latency_histogram(&self) -> Vec<(f64, u64)> {
+        let mut count = 0;
+
+        STEP_LATENCY_BUCKETS
+            .iter()
+            .zip(self.step_latency_buckets.iter())
+            .map(|(bound, bucket)| {
+                count += bucket.load(Ordering::Acquire);
+                (*bound, count)
+            })
+            .collect()
+    }
+
+    /// Replace operator profiles with a fresh snapshot.
+    pub fn set_operator_profiles(&self, profiles: Vec<WorkerProfile>) {
+        *self.operator_profiles.lock().unwrap() = profiles;
+    }
+
+    /// The most recent snapshot of operator profiles; empty if operator
+    /// metrics are disabled or haven't been collected yet.
+    pub fn operator_profiles(&self) -> MutexGuard<'_, Vec<WorkerProfile>> {
+        self.operator_profiles.lock().unwrap()
+    }
+}
+
 type InputsStatus = ShardedLock<BTreeMap<EndpointId, InputEndpointStatus>>;
 type OutputsStatus = ShardedLock<BTreeMap<EndpointId, OutputEndpointStatus>>;
 
@@ -82,6 +152,9 @@ pub struct ControllerStatus {
     /// Global controller metrics.
     pub global_metrics: GlobalControllerMetrics,
 
+    /// Circuit metrics.
+    pub circuit_metrics: CircuitMetrics,
+
     /// Input endpoint configs and metrics.
     // `ShardedLock` is a read/write lock optimized for fast reads.
     // Write access is only required when adding or removing an endpoint.
@@ -100,6 +173,7 @@ impl ControllerStatus {
         Self {
             global_config: global_config.clone(),
             global_metrics: Default::default(),
+            circuit_metrics: Default::default(),
             inputs: ShardedLock::new(BTreeMap::new()),
             outputs: ShardedLock::new(BTreeMap::new()),
         }
@@ -131,6 +205,16 @@ impl ControllerStatus {
         );
     }
 
+    /// Remove stats of a disconnected input endpoint.
+    pub fn remove_input(&self, endpoint_id: &EndpointId) {
+        self.inputs.write().unwrap().remove(endpoint_id);
+    }
+
+    /// Remove stats of a disconnected output endpoint.
+    pub fn remove_output(&self, endpoint_id: &EndpointId) {
+        self.outputs.write().unwrap().remove(endpoint_id);
+    }
+
     /// Total number of records buffered by all input endpoints.
     pub fn num_buffered_input_records(&self) -> u64 {
         self.global_metrics
@@ -190,6 +274,21 @@ impl ControllerStatus {
         buffered_records >= max_buffered_records
     }
 
+    /// True if the endpoint has been paused by the user.
+    pub fn input_endpoint_paused(&self, endpoint_id: &EndpointId) -> bool {
+        match self.inputs.read().unwrap().get(endpoint_id) {
+            None => false,
+            Some(endpoint_stats) => endpoint_stats.paused.load(Ordering::Acquire),
+        }
+    }
+
+    /// Pause or unpause the endpoint on behalf of the user.
+    pub fn set_input_endpoint_paused(&self, endpoint_id: &EndpointId, paused: bool) {
+        if let Some(endpoint_stats) = self.inputs.read().unwrap().get(endpoint_id) {
+            endpoint_stats.paused.store(paused, Ordering::Release);
+        }
+    }
+
     /// Update counters after receiving a new input batch.
     ///
     /// # Arguments
@@ -253,7 +352,7 @@ impl ControllerStatus {
     /// # Arguments
     ///
     /// * `endpoint_id` - id of the input endpoint.
-    /// * `num_records` - number of records returned by `Parser::eoi`.
+    /// * `num_records` - number of records returned by `Parser::eoi_with_errors`.
     /// * `global_config` - global controller config.
     /// * `circuit_thread_unparker` - unparker used to wake up the circuit
     ///   thread if the total number of buffered records exceeds
@@ -325,9 +424,49 @@ impl ControllerStatus {
         })
     }
 
-    pub fn parse_error(&self, endpoint_id: EndpointId) {
-        if let Some(endpoint_stats) = self.input_status().get(&endpoint_id) {
-            endpoint_stats.parse_error();
+    /// Add `num_errors` to the parse error counter of the endpoint.
+    ///
+    /// Returns the updated number of parse errors.
+    pub fn parse_errors(&self, endpoint_id: EndpointId, num_errors: u64) -> u64 {
+        self.input_status()
+            .get(&endpoint_id)
+            .map(|endpoint_stats| endpoint_stats.parse_errors(num_errors))
+            .unwrap_or(0)
+    }
+
+    /// Number of parse errors encountered by the endpoint.
+    pub fn num_parse_errors(&self, endpoint_id: EndpointId) -> u64 {
+        self.input_status()
+            .get(&endpoint_id)
+            .map(|endpoint_stats| {
+                endpoint_stats
+                    .metrics
+                    .num_parse_errors
+                    .load(Ordering::Acquire)
+            })
+            .unwrap_or(0)
+    }
+
+    /// Mark the endpoint as failed due to an error that is not caused by the
+    /// transport, e.g., exceeding the maximal number of parse errors, and
+    /// record the error.
+    ///
+    /// Returns `true` if the endpoint wasn't already in the failed state.
+    pub fn fail_input_endpoint(&self, endpoint_id: EndpointId, error: &AnyError) -> bool {
+        match self.input_status().get(&endpoint_id) {
+            Some(endpoint_stats) => {
+                endpoint_stats.fatal_error(error);
+                !endpoint_stats.failed.swap(true, Ordering::AcqRel)
+            }
+            None => false,
+        }
+    }
+
+    /// True if the endpoint has failed (see [`Self::fail_input_endpoint`]).
+    pub fn input_endpoint_failed(&self, endpoint_id: &EndpointId) -> bool {
+        match self.inputs.read().unwrap().get(endpoint_id) {
+            None => false,
+            Some(endpoint_stats) => endpoint_stats.failed.load(Ordering::Acquire),
         }
     }
 
@@ -384,6 +523,17 @@ pub struct InputEndpointStatus {
 
     /// The first fatal error that occurred at the endpoint.
     pub fatal_error: Mutex<Option<String>>,
+
+    /// The endpoint has been paused by the user.
+    ///
+    /// A paused endpoint remains paused while the pipeline is running.
+    pub paused: AtomicBool,
+
+    /// The endpoint has failed, e.g., because it exceeded the maximal
+    /// number of parse errors.
+    ///
+    /// A failed endpoint remains paused until it is disconnected.
+    pub failed: AtomicBool,
 }
 
 impl InputEndpointStatus {
@@ -393,6 +543,8 @@ impl InputEndpointStatus {
             config: config.clone(),
             metrics: Default::default(),
             fatal_error: Mutex::new(None),
+            paused: AtomicBool::new(false),
+            failed: AtomicBool::new(false),
         }
     }
 
@@ -424,8 +576,19 @@ impl InputEndpointStatus {
     }
 
     /// Increment parser error counter.
-    fn parse_error(&self) {
-        self.metrics.num_parse_errors.fetch_add(1, Ordering::AcqRel);
+    fn parse_errors(&self, num_errors: u64) -> u64 {
+        self.metrics
+            .num_parse_errors
+            .fetch_add(num_errors, Ordering::AcqRel)
+            + num_errors
+    }
+
+    /// Save `error` in `self.fatal_error` if this is the first fatal error.
+    fn fatal_error(&self, error: &AnyError) {
+        let mut fatal_error = self.fatal_error.lock().unwrap();
+        if fatal_error.is_none() {
+            *fatal_error = Some(error.to_string());
+        }
     }
 
     /// Increment transport error counter.  If this is the first fatal error,
@@ -435,10 +598,7 @@ impl InputEndpointStatus {
             .num_transport_errors
             .fetch_add(1, Ordering::AcqRel);
         if fatal {
-            let mut fatal_error = self.fatal_error.lock().unwrap();
-            if fatal_error.is_none() {
-                *fatal_error = Some(error.to_string());
-            }
+            self.fatal_error(error);
         }
     }
 }
@@ -1,18 +1,17 @@
 use crate::{
-    format::{Encoder, InputFormat, OutputFormat, Parser},
-    Catalog, DeCollectionHandle, OutputConsumer, SerBatch,
+    format::{Encoder, InputFormat, OutputFormat, ParseError, Parser},
+    Catalog, DeCollectionHandle, InputMetadata, OutputConsumer, Schema, SerBatch,
 };
 use anyhow::{Error as AnyError, Result as AnyResult};
 use csv::{
-    byte_record_deserializer, Reader as CsvReader, ReaderBuilder as CsvReaderBuilder,
+    byte_record_deserializer, ByteRecord, ReaderBuilder as CsvReaderBuilder,
     WriterBuilder as CsvWriterBuilder,
 };
-use erased_serde::Deserializer as ErasedDeserializer;
+use erased_serde::{Deserializer as ErasedDeserializer, Serialize as ErasedSerialize};
 use serde::Deserialize;
 use serde_yaml::Value as YamlValue;
 use std::{
     borrow::Cow,
-    io::Read,
     mem::take,
     sync::{Arc, Mutex},
 };
@@ -24,6 +23,27 @@ pub struct CsvInputFormat;
 struct CsvParserConfig {
     /// Input stream to feed parsed records to.
     input_stream: String,
+
+    /// Names of the columns of input records, in order.
+    ///
+    /// When specified, the list is checked against the schema of the input
+    /// stream when the endpoint is connected, so that a mismatch between the
+    /// data source and the stream fails the connection instead of producing
+    /// a parse error for every record.
+    #[serde(default)]
+    columns: Option<Vec<String>>,
+
+    /// Input metadata items to prepend to each record as leading columns,
+    /// in order.
+    ///
+    /// Item names are defined by the transport, e.g., `key`, `topic`,
+    /// `partition`, `offset`, `timestamp`, or `header:<name>` for the Kafka
+    /// transport, which must be configured to supply these items.  Missing
+    /// items and items without a value produce empty columns.  Data received
+    /// without metadata, e.g., from transports that don't support metadata,
+    /// is parsed as is.
+    #[serde(default)]
+    metadata_columns: Vec<String>,
 }
 
 impl InputFormat for CsvInputFormat {
@@ -37,12 +57,25 @@ impl InputFormat for CsvInputFormat {
         catalog: &Arc<Mutex<Catalog>>,
     ) -> AnyResult<Box<dyn Parser>> {
         let config = CsvParserConfig::deserialize(config)?;
-        catalog
-            .lock()
-            .unwrap()
+        let catalog = catalog.lock().unwrap();
+        let stream = catalog
             .input_collection_handle(&config.input_stream)
-            .map(|stream| Box::new(CsvParser::new(stream)) as Box<dyn Parser>)
-            .ok_or_else(|| AnyError::msg(format!("unknown stream '{}'", config.input_stream)))
+            .ok_or_else(|| AnyError::msg(format!("unknown stream '{}'", config.input_stream)))?;
+
+        if let Some(columns) = &config.columns {
+            catalog
+                .input_schema(&config.input_stream)
+                .ok_or_else(|| {
+                    AnyError::msg(format!(
+                        "cannot validate 'columns': stream '{}' has no schema",
+                        config.input_stream
+                    ))
+                })?
+                .check_columns(columns)
+                .map_err(|e| AnyError::msg(format!("stream '{}': {e}", config.input_stream)))?;
+        }
+
+        Ok(Box::new(CsvParser::new(stream, config.metadata_columns)))
     }
 }
 
@@ -55,46 +88,102 @@ struct CsvParser {
     /// character and prepend it to the next input buffer.
     leftover: Vec<u8>,
 
+    /// Offset of the first byte of `leftover` from the start of the input
+    /// stream.
+    offset: u64,
+
     /// Builder used to create a new CSV reader for each received data
     /// buffer.
     builder: CsvReaderBuilder,
+
+    /// Input metadata items to prepend to records received with metadata.
+    metadata_columns: Vec<String>,
 }
 
 impl CsvParser {
-    fn new(input_stream: &dyn DeCollectionHandle) -> Self {
+    fn new(input_stream: &dyn DeCollectionHandle, metadata_columns: Vec<String>) -> Self {
         let mut builder = CsvReaderBuilder::new();
         builder.has_headers(false);
 
         Self {
             input_stream: input_stream.fork(),
             leftover: Vec::new(),
+            offset: 0,
             builder,
+            metadata_columns,
         }
     }
 
-    fn parse_from_reader<R>(
+    /// Parse complete CSV records in `data`, skipping invalid records.
+    ///
+    /// `offset` is the offset of `data` from the start of the input stream.
+    /// Fields of `prefix` are prepended to each record.
+    fn parse(
         input_stream: &mut dyn DeCollectionHandle,
-        mut reader: CsvReader<R>,
-    ) -> AnyResult<usize>
-    where
-        R: Read,
-    {
+        builder: &CsvReaderBuilder,
+        data: &[u8],
+        offset: u64,
+        prefix: &ByteRecord,
+    ) -> (usize, Vec<ParseError>) {
+        let mut reader = builder.from_reader(data);
+        let mut record = ByteRecord::new();
+        let mut prefixed_record = ByteRecord::new();
         let mut num_records = 0;
+        let mut errors = Vec::new();
+
+        loop {
+            let start = reader.position().byte() as usize;
+            let result = reader.read_byte_record(&mut record);
+            let end = reader.position().byte() as usize;
+
+            let error = match result {
+                Ok(false) => break,
+                Ok(true) => {
+                    let record = if prefix.is_empty() {
+                        &record
+                    } else {
+                        prefixed_record.clear();
+                        prefixed_record.extend(prefix.iter());
+                        prefixed_record.extend(record.iter());
+                        &prefixed_record
+                    };
+                    let mut deserializer = byte_record_deserializer(record, None);
+                    let mut deserializer = <dyn ErasedDeserializer>::erase(&mut deserializer);
+                    match input_stream.insert(&mut deserializer) {
+                        Ok(()) => {
+                            num_records += 1;
+                            continue;
+                        }
+                        Err(e) => AnyError::msg(format!(
+                            "failed to deserialize csv record '{record:?}': {e}"
+                        )),
+                    }
+                }
+                Err(e) => AnyError::from(e),
+            };
+
+            errors.push(ParseError::new(
+                &data[start..end],
+                offset + start as u64,
+                error,
+            ));
+
+            // Don't loop forever if the reader fails without making progress.
+            if end == start {
+                break;
+            }
+        }
 
-        for record in reader.byte_records() {
-            let record = record?;
+        (num_records, errors)
+    }
 
-            let mut deserializer = byte_record_deserializer(&record, None);
-            let mut deserializer = <dyn ErasedDeserializer>::erase(&mut deserializer);
-            input_stream.insert(&mut deserializer).map_err(|e| {
-                AnyError::msg(format!(
-                    "failed to deserialize csv record '{record:?}': {e}"
-                ))
-            })?;
-            num_records += 1;
+    /// Converts the result of [`Parser::input_with_errors`] to the result of
+    /// [`Parser::input`], failing with the first parse error, if any.
+    fn first_error((num_records, errors): (usize, Vec<ParseError>)) -> AnyResult<usize> {
+        match errors.into_iter().next() {
+            None => Ok(num_records),
+            Some(e) => Err(e.error),
         }
-
-        Ok(num_records)
     }
 
     /// Returns the index of the first character following the last newline
@@ -113,6 +202,10 @@ impl CsvParser {
 
 impl Parser for CsvParser {
     fn input(&mut self, data: &[u8]) -> AnyResult<usize> {
+        Self::first_error(self.input_with_errors(data))
+    }
+
+    fn input_with_errors(&mut self, data: &[u8]) -> (usize, Vec<ParseError>) {
         // println!("input {} bytes:\n{}\nself.leftover:\n{}", data.len(),
         //    std::str::from_utf8(data).map(|s| s.to_string()).unwrap_or_else(|e|
         // format!("invalid csv: {e}")),    std::str::from_utf8(&self.leftover).
@@ -127,15 +220,33 @@ impl Parser for CsvParser {
             // the `leftover` buffer so it gets processed with the next input
             // buffer.
             self.leftover.extend_from_slice(data);
-            Ok(0)
+            (0, Vec::new())
         } else {
-            let reader = self
-                .builder
-                .from_reader(Read::chain(&*self.leftover, &data[0..leftover]));
+            let consumed = (self.leftover.len() + leftover) as u64;
 
-            let res = Self::parse_from_reader(&mut *self.input_stream, reader);
+            // Avoid copying `data` unless there is a leftover from the previous
+            // buffer.
+            let res = if self.leftover.is_empty() {
+                Self::parse(
+                    &mut *self.input_stream,
+                    &self.builder,
+                    &data[0..leftover],
+                    self.offset,
+                    &ByteRecord::new(),
+                )
+            } else {
+                self.leftover.extend_from_slice(&data[0..leftover]);
+                Self::parse(
+                    &mut *self.input_stream,
+                    &self.builder,
+                    &self.leftover,
+                    self.offset,
+                    &ByteRecord::new(),
+                )
+            };
             // println!("parse returned: {res:?}");
 
+            self.offset += consumed;
             self.leftover.clear();
             self.leftover.extend_from_slice(&data[leftover..]);
 
@@ -143,15 +254,57 @@ impl Parser for CsvParser {
         }
     }
 
+    fn input_with_metadata(
+        &mut self,
+        data: &[u8],
+        metadata: &InputMetadata,
+    ) -> (usize, Vec<ParseError>) {
+        // `data` consists of complete records; finish the leftover from
+        // previous chunks first.
+        let (mut num_records, mut errors) = self.eoi_with_errors();
+
+        let prefix = self
+            .metadata_columns
+            .iter()
+            .map(|item| metadata.get(item).unwrap_or_default())
+            .collect::<ByteRecord>();
+
+        let (n, mut e) = Self::parse(
+            &mut *self.input_stream,
+            &self.builder,
+            data,
+            self.offset,
+            &prefix,
+        );
+        self.offset += data.len() as u64;
+
+        num_records += n;
+        errors.append(&mut e);
+        (num_records, errors)
+    }
+
     fn eoi(&mut self) -> AnyResult<usize> {
+        Self::first_error(self.eoi_with_errors())
+    }
+
+    fn eoi_with_errors(&mut self) -> (usize, Vec<ParseError>) {
         if self.leftover.is_empty() {
-            return Ok(0);
+            return (0, Vec::new());
         }
 
         // Try to interpret the leftover chunk as a complete CSV line.
-        let reader = self.builder.from_reader(&*self.leftover);
-
-        Self::parse_from_reader(&mut *self.input_stream, reader)
+        let res = Self::parse(
+            &mut *self.input_stream,
+            &self.builder,
+            &self.leftover,
+            self.offset,
+            &ByteRecord::new(),
+        );
+
+        self.offset += self.leftover.len() as u64;
+        self.leftover.clear();
+
+        res
     }
 
     fn flush(&mut self) {
@@ -163,7 +316,10 @@ impl Parser for CsvParser {
     }
 
     fn fork(&self) -> Box<dyn Parser> {
-        Box::new(Self::new(&*self.input_stream))
+        Box::new(Self::new(
+            &*self.input_stream,
+            self.metadata_columns.clone(),
+        ))
     }
 }
 
@@ -178,6 +334,14 @@ const fn default_buffer_size_records() -> usize {
 struct CsvEncoderConfig {
     #[serde(default = "default_buffer_size_records")]
     buffer_size_records: usize,
+
+    /// Start the output with a header row that contains field names of the
+    /// output stream followed by `weight`.
+    ///
+    /// Requires the schema of the output stream.  Not used when the
+    /// transport endpoint consumes one record at a time.  Default: `false`.
+    #[serde(default)]
+    header: bool,
 }
 
 impl OutputFormat for CsvOutputFormat {
@@ -189,10 +353,34 @@ impl OutputFormat for CsvOutputFormat {
         &self,
         config: &YamlValue,
         consumer: Box<dyn OutputConsumer>,
+    ) -> AnyResult<Box<dyn Encoder>> {
+        self.new_encoder_with_schema(config, None, consumer)
+    }
+
+    fn new_encoder_with_schema(
+        &self,
+        config: &YamlValue,
+        schema: Option<&Schema>,
+        consumer: Box<dyn OutputConsumer>,
     ) -> AnyResult<Box<dyn Encoder>> {
         let config = CsvEncoderConfig::deserialize(config)?;
 
-        Ok(Box::new(CsvEncoder::new(consumer, config)))
+        let header = if config.header {
+            let schema = schema.ok_or_else(|| {
+                AnyError::msg("CSV 'header' option requires the schema of the output stream")
+            })?;
+            Some(
+                schema
+                    .field_names()
+                    .chain(["weight"])
+                    .map(str::to_string)
+                    .collect(),
+            )
+        } else {
+            None
+        };
+
+        Ok(Box::new(CsvEncoder::new(consumer, config, header)))
     }
 }
 
@@ -206,11 +394,18 @@ struct CsvEncoder {
 
     config: CsvEncoderConfig,
 
+    /// Header row that hasn't been sent yet.
+    header: Option<Vec<String>>,
+
     buffer: Vec<u8>,
 }
 
 impl CsvEncoder {
-    fn new(output_consumer: Box<dyn OutputConsumer>, config: CsvEncoderConfig) -> Self {
+    fn new(
+        output_consumer: Box<dyn OutputConsumer>,
+        config: CsvEncoderConfig,
+        header: Option<Vec<String>>,
+    ) -> Self {
         let mut builder = CsvWriterBuilder::new();
         builder.has_headers(false);
 
@@ -218,17 +413,83 @@ impl CsvEncoder {
             output_consumer,
             builder,
             config,
+            header,
             buffer: Vec::new(),
         }
     }
+
+    /// Encode `val` as a single CSV record without the record terminator.
+    fn encode_record(
+        builder: &CsvWriterBuilder,
+        val: &dyn ErasedSerialize,
+        buffer: &mut Vec<u8>,
+    ) -> AnyResult<()> {
+        buffer.clear();
+        let mut writer = builder.from_writer(&mut *buffer);
+        writer.serialize(val)?;
+        writer.flush()?;
+        drop(writer);
+
+        while matches!(buffer.last(), Some(b'\n' | b'\r')) {
+            buffer.pop();
+        }
+        Ok(())
+    }
+
+    /// Push each record to a keyed consumer as a separate message.
+    ///
+    /// The message key is the CSV encoding of the record key (see
+    /// [`SerCursor::record_key`](`crate::SerCursor::record_key`)); the value
+    /// is the CSV encoding of the record, or `None` if the record was
+    /// deleted.  Weights are not encoded: a record with a positive weight is
+    /// pushed once.
+    fn encode_keyed(&mut self, batches: &[Box<dyn SerBatch>]) -> AnyResult<()> {
+        let mut key_buffer = Vec::new();
+        let mut val_buffer = Vec::new();
+
+        for batch in batches.iter() {
+            let mut cursor = batch.cursor();
+
+            while cursor.key_valid() {
+                let w = cursor.weight();
+                if w != 0 {
+                    Self::encode_record(&self.builder, cursor.record_key(), &mut key_buffer)?;
+                    if w > 0 {
+                        Self::encode_record(&self.builder, cursor.key(), &mut val_buffer)?;
+                        self.output_consumer
+                            .push_key(&key_buffer, Some(&val_buffer))?;
+                    } else {
+                        self.output_consumer.push_key(&key_buffer, None)?;
+                    }
+                }
+
+                cursor.step_key();
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl Encoder for CsvEncoder {
+    fn supports_keyed_output(&self) -> bool {
+        true
+    }
+
     fn encode(&mut self, batches: &[Box<dyn SerBatch>]) -> AnyResult<()> {
+        if self.output_consumer.is_keyed() {
+            return self.encode_keyed(batches);
+        }
+
         let buffer = take(&mut self.buffer);
         let mut writer = self.builder.from_writer(buffer);
         let mut num_records = 0;
 
+        let header = self.header.take();
+        if let Some(header) = &header {
+            writer.write_record(header)?;
+        }
+
         for batch in batches.iter() {
             let mut cursor = batch.cursor();
 
@@ -252,7 +513,7 @@ impl Encoder for CsvEncoder {
 
         let mut buffer = writer.into_inner()?;
 
-        if num_records > 0 {
+        if num_records > 0 || header.is_some() {
             self.output_consumer.push_buffer(&buffer);
             buffer.clear();
         }
@@ -262,3 +523,250 @@ impl Encoder for CsvEncoder {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{CsvEncoder, CsvEncoderConfig, CsvInputFormat, CsvOutputFormat};
+    use crate::{
+        format::{Encoder, InputFormat, OutputFormat},
+        seroutput::SerBatchImpl,
+        test::{MockDeZSet, TestStruct},
+        Catalog, InputMetadata, OutputConsumer, Schema, SerBatch, UpsertBatch,
+    };
+    use dbsp::{trace::Batch, OrdIndexedZSet, OrdZSet};
+    use std::sync::{Arc, Mutex};
+
+    type Messages = Arc<Mutex<Vec<(String, Option<String>)>>>;
+
+    /// Consumer that concatenates received buffers.
+    struct BufferConsumer(Arc<Mutex<String>>);
+
+    impl OutputConsumer for BufferConsumer {
+        fn push_buffer(&mut self, buffer: &[u8]) {
+            self.0
+                .lock()
+                .unwrap()
+                .push_str(std::str::from_utf8(buffer).unwrap());
+        }
+    }
+
+    /// Keyed consumer that records received messages.
+    struct KeyedConsumer(Messages);
+
+    impl OutputConsumer for KeyedConsumer {
+        fn push_buffer(&mut self, _buffer: &[u8]) {
+            panic!("unexpected push_buffer call on a keyed consumer");
+        }
+
+        fn is_keyed(&self) -> bool {
+            true
+        }
+
+        fn push_key(&mut self, key: &[u8], val: Option<&[u8]>) -> AnyResult<()> {
+            self.0.lock().unwrap().push((
+                String::from_utf8(key.to_vec()).unwrap(),
+                val.map(|val| String::from_utf8(val.to_vec()).unwrap()),
+            ));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_keyed_encoder() {
+        let messages: Messages = Arc::new(Mutex::new(Vec::new()));
+        let mut encoder = CsvEncoder::new(
+            Box::new(KeyedConsumer(messages.clone())),
+            CsvEncoderConfig {
+                buffer_size_records: 10,
+                header: false,
+            },
+            None,
+        );
+
+        // Update key 1, delete key 2, insert key 3.
+        let batch: Box<dyn SerBatch> = Box::new(SerBatchImpl::new(OrdIndexedZSet::from_tuples(
+            (),
+            vec![
+                ((1u32, (1u32, "a".to_string())), -1),
+                ((1, (1, "b,c".to_string())), 1),
+                ((2, (2, "d".to_string())), -1),
+                ((3, (3, "e".to_string())), 1),
+            ],
+        )));
+        encoder
+            .encode(&[Box::new(UpsertBatch::new(batch).unwrap())])
+            .unwrap();
+
+        assert_eq!(
+            &*messages.lock().unwrap(),
+            &[
+                ("1".to_string(), Some("1,\"b,c\"".to_string())),
+                ("2".to_string(), None),
+                ("3".to_string(), Some("3,e".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_header() {
+        let schema = Schema::from_type::<TestStruct>().unwrap();
+        let config = serde_yaml::from_str("header: true").unwrap();
+
+        // A header row requires a schema.
+        assert!(CsvOutputFormat
+            .new_encoder(
+                &config,
+                Box::new(BufferConsumer(Arc::new(Mutex::new(String::new()))))
+            )
+            .is_err());
+
+        let output = Arc::new(Mutex::new(String::new()));
+        let mut encoder = CsvOutputFormat
+            .new_encoder_with_schema(
+                &config,
+                Some(&schema),
+                Box::new(BufferConsumer(output.clone())),
+            )
+            .unwrap();
+
+        let batch = || -> Box<dyn SerBatch> {
+            Box::new(SerBatchImpl::new(OrdZSet::from_keys(
+                (),
+                vec![(
+                    TestStruct {
+                        id: 1,
+                        b: true,
+                        i: None,
+                        s: "foo".to_string(),
+                    },
+                    1i64,
+                )],
+            )))
+        };
+
+        // The header row is only sent once.
+        encoder.encode(&[batch()]).unwrap();
+        encoder.encode(&[batch()]).unwrap();
+        assert_eq!(
+            &*output.lock().unwrap(),
+            "id,b,i,s,weight\n1,true,,foo,1\n1,true,,foo,1\n"
+        );
+    }
+
+    #[test]
+    fn test_columns() {
+        let mut catalog = Catalog::new();
+        catalog.register_input_collection_handle("test_input", <MockDeZSet<TestStruct>>::new());
+        let catalog = Arc::new(Mutex::new(catalog));
+
+        let config = |columns: &str| {
+            serde_yaml::from_str(&format!("input_stream: test_input\ncolumns: {columns}")).unwrap()
+        };
+
+        // Columns can't be validated without a schema.
+        assert!(CsvInputFormat
+            .new_parser(&config("[id, b, i, s]"), &catalog)
+            .is_err());
+
+        catalog
+            .lock()
+            .unwrap()
+            .set_input_schema("test_input", Schema::from_type::<TestStruct>().unwrap());
+
+        CsvInputFormat
+            .new_parser(&config("[id, b, i, s]"), &catalog)
+            .unwrap();
+
+        // Mismatch is reported when the parser is created.
+        let error = CsvInputFormat
+            .new_parser(&config("[id, s, i, b]"), &catalog)
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("don't match the schema"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let zset = <MockDeZSet<TestStruct>>::new();
+        let mut catalog = Catalog::new();
+        catalog.register_input_collection_handle("test_input", zset.clone());
+        let catalog = Arc::new(Mutex::new(catalog));
+
+        let mut parser = CsvInputFormat
+            .new_parser(
+                &serde_yaml::from_str("input_stream: test_input").unwrap(),
+                &catalog,
+            )
+            .unwrap();
+
+        // Malformed records are reported individually.
+        let (num_records, errors) = parser.input_with_errors(b"1,true,5,foo\nbad\n2,false,,bar\n");
+        assert_eq!(num_records, 2);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].data.starts_with(b"bad"));
+        assert_eq!(errors[0].offset, 13);
+
+        // `input` fails on the first malformed record.
+        assert!(parser.input(b"bad\n3,true,,baz\n").is_err());
+        assert_eq!(parser.input(b"4,true,,baz\n").unwrap(), 1);
+    }
+
+    #[test]
+    fn test_metadata_columns() {
+        let zset = <MockDeZSet<TestStruct>>::new();
+        let mut catalog = Catalog::new();
+        catalog.register_input_collection_handle("test_input", zset.clone());
+        let catalog = Arc::new(Mutex::new(catalog));
+
+        let mut parser = CsvInputFormat
+            .new_parser(
+                &serde_yaml::from_str("input_stream: test_input\nmetadata_columns: [key]").unwrap(),
+                &catalog,
+            )
+            .unwrap();
+
+        let mut metadata = InputMetadata::new();
+        metadata.push("key", Some("1".to_string()));
+
+        // Metadata is added to each record, including records with quoted
+        // line breaks.
+        let (num_records, errors) =
+            parser.input_with_metadata(b"true,,\"foo\nbar\"\nfalse,3,baz", &metadata);
+        assert_eq!(num_records, 2);
+        assert!(errors.is_empty());
+
+        // Data without metadata is parsed as is.
+        let (num_records, errors) = parser.input_with_errors(b"2,true,5,qux\n");
+        assert_eq!(num_records, 1);
+        assert!(errors.is_empty());
+        parser.flush();
+
+        assert_eq!(
+            zset.state()
+                .flushed
+                .iter()
+                .map(|(val, _)| val.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                TestStruct {
+                    id: 1,
+                    b: true,
+                    i: None,
+                    s: "foo\nbar".to_string(),
+                },
+                TestStruct {
+                    id: 1,
+                    b: false,
+                    i: Some(3),
+                    s: "baz".to_string(),
+                },
+                TestStruct {
+                    id: 2,
+                    b: true,
+                    i: Some(5),
+                    s: "qux".to_string(),
+                },
+            ]
+        );
+    }
+}
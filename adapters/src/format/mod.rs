@@ -1,26 +1,65 @@
-use crate::{Catalog, SerBatch};
-use anyhow::Result as AnyResult;
+use crate::{Catalog, InputMetadata, Schema, SerBatch};
+use anyhow::{Error as AnyError, Result as AnyResult};
 use once_cell::sync::Lazy;
 use serde_yaml::Value as YamlValue;
 use std::{
     borrow::Cow,
     collections::BTreeMap,
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, RwLock},
 };
 
 mod csv;
 
 use self::csv::{CsvInputFormat, CsvOutputFormat};
 
-/// Static map of supported input formats.
-// TODO: support for registering new formats at runtime in order to allow
-// external crates to implement new formats.
-static INPUT_FORMATS: Lazy<BTreeMap<&'static str, Box<dyn InputFormat>>> =
-    Lazy::new(|| BTreeMap::from([("csv", Box::new(CsvInputFormat) as Box<dyn InputFormat>)]));
+/// Registry of supported input formats.
+///
+/// Initialized with built-in formats.  External crates add new formats
+/// using [`register_input_format`].
+static INPUT_FORMATS: Lazy<RwLock<BTreeMap<String, &'static dyn InputFormat>>> = Lazy::new(|| {
+    RwLock::new(BTreeMap::from([(
+        "csv".to_string(),
+        &CsvInputFormat as &'static dyn InputFormat,
+    )]))
+});
+
+/// Registry of supported output formats.
+static OUTPUT_FORMATS: Lazy<RwLock<BTreeMap<String, &'static dyn OutputFormat>>> =
+    Lazy::new(|| {
+        RwLock::new(BTreeMap::from([(
+            "csv".to_string(),
+            &CsvOutputFormat as &'static dyn OutputFormat,
+        )]))
+    });
+
+/// Register a new input format.
+///
+/// Makes the format available to all controllers under the name returned
+/// by [`InputFormat::name`], replacing any previously registered format
+/// with the same name, including built-in formats.  Formats must be
+/// registered before creating a controller whose configuration refers to
+/// them.
+///
+/// Registered formats are never deallocated.
+pub fn register_input_format(format: Box<dyn InputFormat>) {
+    let format: &'static dyn InputFormat = Box::leak(format);
+    INPUT_FORMATS
+        .write()
+        .unwrap()
+        .insert(format.name().into_owned(), format);
+}
 
-/// Static map of supported output formats.
-static OUTPUT_FORMATS: Lazy<BTreeMap<&'static str, Box<dyn OutputFormat>>> =
-    Lazy::new(|| BTreeMap::from([("csv", Box::new(CsvOutputFormat) as Box<dyn OutputFormat>)]));
+/// Register a new output format.
+///
+/// See [`register_input_format`].  Output formats are also used to encode
+/// the contents of taps.
+pub fn register_output_format(format: Box<dyn OutputFormat>) {
+    let format: &'static dyn OutputFormat = Box::leak(format);
+    OUTPUT_FORMATS
+        .write()
+        .unwrap()
+        .insert(format.name().into_owned(), format);
+}
 
 /// Trait that represents a specific data format.
 ///
@@ -45,7 +84,9 @@ pub trait InputFormat: Send + Sync {
     ///   multiple input tables.  Its associated data format includes metadata
     ///   about the destination table of each record.  The associated parser
     ///   uses this metadata to locate corresponding steams in the circuit
-    ///   catalog.
+    ///   catalog.  Parsers should validate their configuration against the
+    ///   [schemas](`Catalog::input_schema`) of these streams and fail on a
+    ///   mismatch rather than report it for each record.
     fn new_parser(
         &self,
         config: &YamlValue,
@@ -56,7 +97,31 @@ pub trait InputFormat: Send + Sync {
 impl dyn InputFormat {
     /// Lookup input format by name.
     pub fn get_format(name: &str) -> Option<&'static dyn InputFormat> {
-        INPUT_FORMATS.get(name).map(|f| &**f)
+        INPUT_FORMATS.read().unwrap().get(name).copied()
+    }
+}
+
+/// An error parsing an individual input record.
+#[derive(Debug)]
+pub struct ParseError {
+    /// Raw bytes of the invalid record.
+    pub data: Vec<u8>,
+
+    /// Offset of the invalid record in bytes from the start of the byte
+    /// stream received by the parser.
+    pub offset: u64,
+
+    /// Error message.
+    pub error: AnyError,
+}
+
+impl ParseError {
+    pub fn new(data: &[u8], offset: u64, error: AnyError) -> Self {
+        Self {
+            data: data.to_vec(),
+            offset,
+            error,
+        }
     }
 }
 
@@ -76,6 +141,47 @@ pub trait Parser: Send {
     /// if parsing fails.
     fn input(&mut self, data: &[u8]) -> AnyResult<usize>;
 
+    /// Push a chunk of data to the parser, reporting malformed records
+    /// individually.
+    ///
+    /// Like [`Parser::input`], but a malformed record must not prevent the
+    /// parser from parsing subsequent records in `data`.  The parser skips
+    /// such records and reports them in the returned vector of errors.
+    /// Formats that cannot identify individual records in an invalid input
+    /// may report the entire buffer as a single error.  The caller decides
+    /// whether to push the successfully parsed records to the circuit or
+    /// discard the entire buffer (see [`Parser::clear`]).
+    ///
+    /// Returns the number of successfully parsed records and the list of
+    /// parse errors.
+    ///
+    /// The default implementation invokes [`Parser::input`] and reports its
+    /// error, if any, as a single error covering all of `data`.  Since
+    /// `input` does not track stream offsets, the offset of such an error is
+    /// always 0.
+    fn input_with_errors(&mut self, data: &[u8]) -> (usize, Vec<ParseError>) {
+        match self.input(data) {
+            Ok(num_records) => (num_records, Vec::new()),
+            Err(e) => (0, vec![ParseError::new(data, 0, e)]),
+        }
+    }
+
+    /// Push a chunk of complete records that share the same `metadata` to
+    /// the parser.
+    ///
+    /// Formats that support input metadata add metadata items to the parsed
+    /// records as configured, e.g., as additional columns.  Any incomplete
+    /// record left over from previous chunks is completed before parsing
+    /// `data`.  The default implementation ignores `metadata` and invokes
+    /// [`Parser::input_with_errors`].
+    fn input_with_metadata(
+        &mut self,
+        data: &[u8],
+        _metadata: &InputMetadata,
+    ) -> (usize, Vec<ParseError>) {
+        self.input_with_errors(data)
+    }
+
     /// End-of-input-stream notification.
     ///
     /// No more data will be received from the stream.  The parser uses this
@@ -85,6 +191,20 @@ pub trait Parser: Send {
     /// error if parsing fails.
     fn eoi(&mut self) -> AnyResult<usize>;
 
+    /// End-of-input-stream notification, reporting malformed records
+    /// individually.
+    ///
+    /// Returns the number of additional records pushed to the circuit and
+    /// the list of parse errors, same as [`Parser::input_with_errors`].  The
+    /// default implementation invokes [`Parser::eoi`] and reports its error,
+    /// if any, as a single error with no data and offset 0.
+    fn eoi_with_errors(&mut self) -> (usize, Vec<ParseError>) {
+        match self.eoi() {
+            Ok(num_records) => (num_records, Vec::new()),
+            Err(e) => (0, vec![ParseError::new(&[], 0, e)]),
+        }
+    }
+
     /// Flush input handles.
     ///
     /// The implementation must call
@@ -122,19 +242,62 @@ pub trait OutputFormat: Send + Sync {
         config: &YamlValue,
         consumer: Box<dyn OutputConsumer>,
     ) -> AnyResult<Box<dyn Encoder>>;
+
+    /// Create a new encoder for an output stream with the given schema.
+    ///
+    /// The controller creates encoders for output endpoints via this method.
+    /// `schema` is the schema of the output stream, if known.  Encoders use
+    /// the schema to generate metadata, such as header rows, and must fail if
+    /// `config` is inconsistent with the schema or requires a schema that is
+    /// not available, so that the mismatch is reported when the endpoint is
+    /// connected.  The default implementation ignores the schema and invokes
+    /// [`Self::new_encoder`].
+    fn new_encoder_with_schema(
+        &self,
+        config: &YamlValue,
+        _schema: Option<&Schema>,
+        consumer: Box<dyn OutputConsumer>,
+    ) -> AnyResult<Box<dyn Encoder>> {
+        self.new_encoder(config, consumer)
+    }
 }
 
 impl dyn OutputFormat {
     /// Lookup output format by name.
     pub fn get_format(name: &str) -> Option<&'static dyn OutputFormat> {
-        OUTPUT_FORMATS.get(name).map(|f| &**f)
+        OUTPUT_FORMATS.read().unwrap().get(name).copied()
     }
 }
 
 pub trait Encoder: Send {
     fn encode(&mut self, batches: &[Box<dyn SerBatch>]) -> AnyResult<()>;
+
+    /// Returns `true` if the encoder pushes records one at a time to keyed
+    /// consumers (see [`OutputConsumer::is_keyed`]).
+    ///
+    /// The controller refuses to connect an encoder that returns `false` to
+    /// a keyed endpoint.  The default implementation returns `false`.
+    fn supports_keyed_output(&self) -> bool {
+        false
+    }
 }
 
 pub trait OutputConsumer: Send {
     fn push_buffer(&mut self, buffer: &[u8]);
+
+    /// Returns `true` if the consumer expects records to be pushed one at a
+    /// time via [`Self::push_key`] (see
+    /// [`OutputEndpoint::is_keyed`](`crate::OutputEndpoint::is_keyed`)).
+    fn is_keyed(&self) -> bool {
+        false
+    }
+
+    /// Push a single encoded record with encoded key `key`.  `val` is `None`
+    /// for deleted records.
+    ///
+    /// Only invoked if [`Self::is_keyed`] returns `true`.  The default
+    /// implementation returns an error.
+    fn push_key(&mut self, _key: &[u8], _val: Option<&[u8]>) -> AnyResult<()> {
+        Err(AnyError::msg("consumer does not support keyed output"))
+    }
 }
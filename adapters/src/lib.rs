@@ -96,6 +96,12 @@
 //! * [`Encoder`] - an encoder that consumes batches of records and serializes
 //!   them into binary buffers.
 //!
+//! Transports and formats are looked up by name in global registries, which
+//! are initialized with the built-in adapters.  External crates can add new
+//! adapters or replace built-in ones using [`register_input_transport`],
+//! [`register_output_transport`], [`register_input_format`], and
+//! [`register_output_format`].
+//!
 //! ## Controller API
 //!
 //! A [`Controller`] is instantiated with
@@ -104,7 +110,7 @@
 //!   configurations as well as global controller configuration settings, and
 //!
 //! * a [`Catalog`] object, which stores dictionaries of input and output
-//!   streams of the circuit.
+//!   streams of the circuit and their [schemas](`Schema`).
 
 use num_derive::FromPrimitive;
 
@@ -112,6 +118,7 @@ mod catalog;
 mod controller;
 mod deinput;
 mod format;
+mod schema;
 mod seroutput;
 #[cfg(feature = "server")]
 pub mod server;
@@ -132,15 +139,26 @@ pub enum PipelineState {
     Terminated = 2,
 }
 
-pub use catalog::Catalog;
+pub use catalog::{Catalog, CatalogSchemas};
 pub use deinput::{
     DeCollectionHandle, DeMapHandle, DeScalarHandle, DeScalarHandleImpl, DeSetHandle, DeZSetHandle,
 };
-pub use format::{Encoder, InputFormat, OutputConsumer, OutputFormat, Parser};
-pub use seroutput::{SerBatch, SerCursor, SerOutputBatchHandle};
+pub use format::{
+    register_input_format, register_output_format, Encoder, InputFormat, OutputConsumer,
+    OutputFormat, ParseError, Parser,
+};
+pub use schema::{ColumnType, Field, Schema};
+pub use seroutput::{SerBatch, SerCursor, SerOutputBatchHandle, UpsertBatch};
 
-pub use controller::{Controller, ControllerConfig, ControllerError, ControllerStatus};
+pub use controller::{
+    CircuitGraph, CircuitNode, Controller, ControllerConfig, ControllerError, ControllerStatus,
+    FormatConfig, InputEndpointConfig, OutputDeliveryConfig, OutputEndpointConfig, OutputMode,
+};
 pub use transport::{
-    FileInputTransport, InputConsumer, InputEndpoint, InputTransport, OutputEndpoint,
-    OutputTransport,
+    register_input_transport, register_output_transport, DirectoryInputTransport,
+    FileInputTransport, InputConsumer, InputEndpoint, InputMetadata, InputTransport,
+    OutputEndpoint, OutputTransport, ReplayInputTransport, SocketInputTransport,
 };
+
+#[cfg(feature = "with-http")]
+pub use transport::HttpInputTransport;
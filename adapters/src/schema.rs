@@ -0,0 +1,800 @@
+//! Schemas of input and output streams.
+//!
+//! A [`Schema`] describes the shape of records in a stream: names, types, and
+//! nullability of their fields and the subset of fields that form the key of
+//! the record.  Schemas are either derived from Rust types using
+//! [`Schema::from_type`] or supplied by the code that constructs the circuit,
+//! e.g., the SQL compiler, in serialized form.
+//!
+//! Schemas are registered in the [`Catalog`](`crate::Catalog`) along with
+//! stream handles.  Formats use them to validate their configuration when an
+//! endpoint is connected and to generate metadata such as CSV header rows;
+//! external tools can use the Avro and Parquet schemas generated by
+//! [`Schema::avro_schema`] and [`Schema::parquet_schema`].
+//!
+//! Currently only the CSV format uses schemas (its `columns` and `header`
+//! options).  There is no JSON format, so JSON field names are not validated.
+//! Key columns are never derived from types; they must be set with
+//! [`Schema::with_key`] or supplied with the schema.
+
+use anyhow::{Error as AnyError, Result as AnyResult};
+use serde::{
+    de::{
+        self, value::StrDeserializer, DeserializeSeed, Deserializer, IntoDeserializer, MapAccess,
+        SeqAccess, Visitor,
+    },
+    Deserialize, Serialize,
+};
+use serde_json::{json, Value as JsonValue};
+use std::{collections::BTreeSet, fmt, fmt::Write};
+
+/// Type of a field.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnType {
+    Boolean,
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    F32,
+    F64,
+    String,
+    Bytes,
+    /// Nested record.
+    Struct(Vec<Field>),
+    /// Variable-length array.
+    Array(Box<Field>),
+}
+
+/// A named field of a record.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Field {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: ColumnType,
+    #[serde(default)]
+    pub nullable: bool,
+}
+
+impl Field {
+    pub fn new(name: &str, ty: ColumnType, nullable: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            ty,
+            nullable,
+        }
+    }
+}
+
+/// Schema of records in a stream.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Schema {
+    /// Top-level fields of the record, in the order in which they are
+    /// serialized.
+    pub fields: Vec<Field>,
+
+    /// Names of fields that form the key of the record.  Empty if the
+    /// record is keyed by all of its fields.
+    #[serde(default)]
+    pub key: Vec<String>,
+}
+
+impl Schema {
+    pub fn new(fields: Vec<Field>) -> Self {
+        Self {
+            fields,
+            key: Vec::new(),
+        }
+    }
+
+    /// Derive the schema of a struct or tuple type `T` from its
+    /// `Deserialize` implementation.
+    ///
+    /// Field names and order are those expected by the deserializer,
+    /// including any `#[serde(rename)]` attributes.  `Option` fields are
+    /// nullable.  Fails for types whose schema cannot be determined without
+    /// data, e.g., enums, maps, or types that use `deserialize_any`, and for
+    /// types whose `Deserialize` implementation rejects the placeholder
+    /// values used to trace it (zero, `false`, and empty strings and byte
+    /// arrays).
+    ///
+    /// The derived schema has no key columns, since keys are not part of the
+    /// type; use [`Self::with_key`] to set them.
+    pub fn from_type<T>() -> AnyResult<Self>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        let mut traced = TracedType::default();
+        T::deserialize(Tracer {
+            traced: &mut traced,
+        })
+        .map_err(|e| {
+            AnyError::msg(format!(
+                "cannot derive schema of type '{}': {e}",
+                std::any::type_name::<T>()
+            ))
+        })?;
+
+        match traced.ty {
+            Some(ColumnType::Struct(fields)) => Ok(Self::new(fields)),
+            _ => Err(AnyError::msg(format!(
+                "cannot derive schema of type '{}': only struct and tuple types are supported",
+                std::any::type_name::<T>()
+            ))),
+        }
+    }
+
+    /// Set key columns of the schema.
+    pub fn with_key(mut self, key: &[&str]) -> AnyResult<Self> {
+        self.key = key.iter().map(|name| name.to_string()).collect();
+        self.validate()?;
+        Ok(self)
+    }
+
+    /// Check that field names are unique and key columns exist.
+    pub fn validate(&self) -> AnyResult<()> {
+        let mut names = BTreeSet::new();
+        for field in self.fields.iter() {
+            if !names.insert(field.name.as_str()) {
+                return Err(AnyError::msg(format!(
+                    "duplicate field name '{}'",
+                    field.name
+                )));
+            }
+        }
+
+        for key in self.key.iter() {
+            if !names.contains(key.as_str()) {
+                return Err(AnyError::msg(format!("unknown key column '{key}'")));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Names of top-level fields.
+    pub fn field_names(&self) -> impl Iterator<Item = &str> {
+        self.fields.iter().map(|field| field.name.as_str())
+    }
+
+    /// Look up a top-level field by name.
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    /// Check that `columns` lists top-level fields of the schema in order.
+    pub fn check_columns(&self, columns: &[String]) -> AnyResult<()> {
+        if !self.field_names().eq(columns.iter().map(String::as_str)) {
+            return Err(AnyError::msg(format!(
+                "columns [{}] don't match the schema of the stream: [{}]",
+                columns.join(", "),
+                self.field_names().collect::<Vec<_>>().join(", ")
+            )));
+        }
+        Ok(())
+    }
+
+    /// Generate an Avro record schema named `name`.
+    ///
+    /// `name` can be a full name, including a namespace.  Characters that are
+    /// not valid in Avro names are replaced with `_`, and names that start
+    /// with a digit, such as tuple fields `0`, `1`, etc., are prefixed with
+    /// `_`.  Distinct field names can therefore map to the same Avro name,
+    /// which makes the generated schema invalid.
+    ///
+    /// Nested records are named after their path in the schema, e.g.,
+    /// `name.field.subfield`.  A nested record with the same fields as a
+    /// record defined earlier in the schema refers to the earlier record by
+    /// its full name instead.
+    ///
+    /// Unsigned integers are mapped to the smallest signed Avro type that
+    /// fits them.  `u64` values don't fit in `long` and are mapped to
+    /// `decimal` with precision 20 and scale 0.  Nullable fields are unions
+    /// of `null` and the field type.
+    pub fn avro_schema(&self, name: &str) -> JsonValue {
+        let name = name.split('.').map(avro_name).collect::<Vec<_>>().join(".");
+        AvroSchemaBuilder::default().record(&name, &self.fields)
+    }
+
+    /// Generate a Parquet message type named `name` in the textual format
+    /// used by Parquet tools.
+    pub fn parquet_schema(&self, name: &str) -> String {
+        let mut result = format!("message {name} {{\n");
+        for field in self.fields.iter() {
+            parquet_field(&mut result, field, 1);
+        }
+        result.push_str("}\n");
+        result
+    }
+}
+
+/// Convert `name` to a valid Avro name (see [`Schema::avro_schema`]).
+fn avro_name(name: &str) -> String {
+    let mut result: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if !result.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        result.insert(0, '_');
+    }
+    result
+}
+
+/// Generates the Avro schema of a record and its nested records.
+#[derive(Default)]
+struct AvroSchemaBuilder<'a> {
+    /// Fields and full names of records defined so far.
+    records: Vec<(&'a [Field], String)>,
+}
+
+impl<'a> AvroSchemaBuilder<'a> {
+    fn record(&mut self, full_name: &str, fields: &'a [Field]) -> JsonValue {
+        self.records.push((fields, full_name.to_string()));
+
+        let mut avro_fields = Vec::with_capacity(fields.len());
+        for field in fields.iter() {
+            let name = avro_name(&field.name);
+            let ty = self.field_type(&format!("{full_name}.{name}"), field);
+            avro_fields.push(if field.nullable {
+                json!({"name": name, "type": ty, "default": null})
+            } else {
+                json!({"name": name, "type": ty})
+            });
+        }
+
+        json!({
+            "type": "record",
+            "name": full_name,
+            "fields": avro_fields,
+        })
+    }
+
+    /// Avro type of `field`, whose path in the schema is `path`.
+    fn field_type(&mut self, path: &str, field: &'a Field) -> JsonValue {
+        let ty = match &field.ty {
+            ColumnType::Boolean => json!("boolean"),
+            ColumnType::I8
+            | ColumnType::I16
+            | ColumnType::I32
+            | ColumnType::U8
+            | ColumnType::U16 => json!("int"),
+            ColumnType::I64 | ColumnType::U32 => json!("long"),
+            ColumnType::U64 => {
+                json!({"type": "bytes", "logicalType": "decimal", "precision": 20, "scale": 0})
+            }
+            ColumnType::F32 => json!("float"),
+            ColumnType::F64 => json!("double"),
+            ColumnType::String => json!("string"),
+            ColumnType::Bytes => json!("bytes"),
+            ColumnType::Struct(fields) => {
+                let defined = self
+                    .records
+                    .iter()
+                    .find(|(defined, _)| *defined == fields.as_slice())
+                    .map(|(_, full_name)| full_name.clone());
+                match defined {
+                    Some(full_name) => json!(full_name),
+                    None => self.record(path, fields),
+                }
+            }
+            ColumnType::Array(element) => {
+                let element_path = format!("{path}.{}", avro_name(&element.name));
+                json!({"type": "array", "items": self.field_type(&element_path, element)})
+            }
+        };
+
+        if field.nullable {
+            json!(["null", ty])
+        } else {
+            ty
+        }
+    }
+}
+
+fn parquet_field(result: &mut String, field: &Field, indent: usize) {
+    let repetition = if field.nullable {
+        "optional"
+    } else {
+        "required"
+    };
+    let padding = "  ".repeat(indent);
+    let name = &field.name;
+
+    let primitive = match &field.ty {
+        ColumnType::Boolean => "boolean",
+        ColumnType::I8 => "int32 (INTEGER(8,true))",
+        ColumnType::I16 => "int32 (INTEGER(16,true))",
+        ColumnType::I32 => "int32",
+        ColumnType::I64 => "int64",
+        ColumnType::U8 => "int32 (INTEGER(8,false))",
+        ColumnType::U16 => "int32 (INTEGER(16,false))",
+        ColumnType::U32 => "int32 (INTEGER(32,false))",
+        ColumnType::U64 => "int64 (INTEGER(64,false))",
+        ColumnType::F32 => "float",
+        ColumnType::F64 => "double",
+        ColumnType::String => "binary (STRING)",
+        ColumnType::Bytes => "binary",
+        ColumnType::Struct(fields) => {
+            let _ = writeln!(result, "{padding}{repetition} group {name} {{");
+            for field in fields.iter() {
+                parquet_field(result, field, indent + 1);
+            }
+            let _ = writeln!(result, "{padding}}}");
+            return;
+        }
+        ColumnType::Array(element) => {
+            // Standard three-level list representation.
+            let _ = writeln!(result, "{padding}{repetition} group {name} (LIST) {{");
+            let _ = writeln!(result, "{padding}  repeated group list {{");
+            parquet_field(result, element, indent + 2);
+            let _ = writeln!(result, "{padding}  }}");
+            let _ = writeln!(result, "{padding}}}");
+            return;
+        }
+    };
+
+    match primitive.split_once(' ') {
+        Some((ty, annotation)) => {
+            let _ = writeln!(result, "{padding}{repetition} {ty} {name} {annotation};");
+        }
+        None => {
+            let _ = writeln!(result, "{padding}{repetition} {primitive} {name};");
+        }
+    }
+}
+
+/// Error returned by [`Tracer`].
+#[derive(Debug)]
+struct TraceError(String);
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for TraceError {}
+
+impl de::Error for TraceError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+/// Type of a value recorded by [`Tracer`].
+#[derive(Default)]
+struct TracedType {
+    ty: Option<ColumnType>,
+    nullable: bool,
+}
+
+impl TracedType {
+    fn into_field(self, name: &str) -> Result<Field, TraceError> {
+        let ty = self
+            .ty
+            .ok_or_else(|| TraceError(format!("cannot determine the type of field '{name}'")))?;
+        Ok(Field::new(name, ty, self.nullable))
+    }
+}
+
+/// Deserializer that records the type requested by a `Deserialize`
+/// implementation and feeds it a placeholder value of this type.
+struct Tracer<'a> {
+    traced: &'a mut TracedType,
+}
+
+impl<'a> Tracer<'a> {
+    fn unsupported<T>(what: &str) -> Result<T, TraceError> {
+        Err(TraceError(format!("{what} are not supported")))
+    }
+
+    fn set(self, ty: ColumnType) {
+        self.traced.ty = Some(ty);
+    }
+}
+
+macro_rules! trace_primitive {
+    ($method:ident, $visit:ident, $val:expr, $ty:expr) => {
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+            self.set($ty);
+            visitor.$visit($val)
+        }
+    };
+}
+
+impl<'de, 'a> Deserializer<'de> for Tracer<'a> {
+    type Error = TraceError;
+
+    trace_primitive!(deserialize_bool, visit_bool, false, ColumnType::Boolean);
+    trace_primitive!(deserialize_i8, visit_i8, 0, ColumnType::I8);
+    trace_primitive!(deserialize_i16, visit_i16, 0, ColumnType::I16);
+    trace_primitive!(deserialize_i32, visit_i32, 0, ColumnType::I32);
+    trace_primitive!(deserialize_i64, visit_i64, 0, ColumnType::I64);
+    trace_primitive!(deserialize_u8, visit_u8, 0, ColumnType::U8);
+    trace_primitive!(deserialize_u16, visit_u16, 0, ColumnType::U16);
+    trace_primitive!(deserialize_u32, visit_u32, 0, ColumnType::U32);
+    trace_primitive!(deserialize_u64, visit_u64, 0, ColumnType::U64);
+    trace_primitive!(deserialize_f32, visit_f32, 0.0, ColumnType::F32);
+    trace_primitive!(deserialize_f64, visit_f64, 0.0, ColumnType::F64);
+    trace_primitive!(deserialize_char, visit_char, ' ', ColumnType::String);
+    trace_primitive!(deserialize_str, visit_str, "", ColumnType::String);
+    trace_primitive!(deserialize_string, visit_str, "", ColumnType::String);
+    trace_primitive!(deserialize_bytes, visit_bytes, &[], ColumnType::Bytes);
+    trace_primitive!(deserialize_byte_buf, visit_bytes, &[], ColumnType::Bytes);
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        self.traced.nullable = true;
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        let mut elements = Vec::new();
+        let value = visitor.visit_seq(SeqTracer {
+            remaining: 1,
+            elements: &mut elements,
+        })?;
+        let element = elements.pop().unwrap_or_default().into_field("element")?;
+        self.set(ColumnType::Array(Box::new(element)));
+        Ok(value)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        let mut elements = Vec::with_capacity(len);
+        let value = visitor.visit_seq(SeqTracer {
+            remaining: len,
+            elements: &mut elements,
+        })?;
+        let fields = elements
+            .into_iter()
+            .enumerate()
+            .map(|(i, element)| element.into_field(&i.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.set(ColumnType::Struct(fields));
+        Ok(value)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        let mut traced = Vec::with_capacity(fields.len());
+        let value = visitor.visit_map(StructTracer {
+            fields,
+            next: 0,
+            traced: &mut traced,
+        })?;
+        self.set(ColumnType::Struct(traced));
+        Ok(value)
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, TraceError> {
+        Self::unsupported("self-describing types")
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, TraceError> {
+        Self::unsupported("unit types")
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        Self::unsupported("unit structs")
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, TraceError> {
+        Self::unsupported("maps")
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        Self::unsupported("enums")
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, TraceError> {
+        Self::unsupported("identifiers")
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, TraceError> {
+        Self::unsupported("ignored values")
+    }
+}
+
+/// Feeds `remaining` traced elements to a sequence visitor.
+struct SeqTracer<'a> {
+    remaining: usize,
+    elements: &'a mut Vec<TracedType>,
+}
+
+impl<'de, 'a> SeqAccess<'de> for SeqTracer<'a> {
+    type Error = TraceError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, TraceError> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+
+        let mut traced = TracedType::default();
+        let value = seed.deserialize(Tracer {
+            traced: &mut traced,
+        })?;
+        self.elements.push(traced);
+        Ok(Some(value))
+    }
+}
+
+/// Feeds traced values of all `fields` of a struct to a map visitor.
+struct StructTracer<'a> {
+    fields: &'static [&'static str],
+    next: usize,
+    traced: &'a mut Vec<Field>,
+}
+
+impl<'de, 'a> MapAccess<'de> for StructTracer<'a> {
+    type Error = TraceError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, TraceError> {
+        match self.fields.get(self.next) {
+            None => Ok(None),
+            Some(field) => {
+                let deserializer: StrDeserializer<'_, TraceError> = (*field).into_deserializer();
+                seed.deserialize(deserializer).map(Some)
+            }
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, TraceError> {
+        let name = self.fields[self.next];
+        self.next += 1;
+
+        let mut traced = TracedType::default();
+        let value = seed.deserialize(Tracer {
+            traced: &mut traced,
+        })?;
+        self.traced.push(traced.into_field(name)?);
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ColumnType, Field, Schema};
+    use crate::test::TestStruct;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Nested {
+        #[serde(rename = "ID")]
+        id: u64,
+        point: Option<(f64, f64)>,
+        tags: Vec<Option<String>>,
+    }
+
+    #[test]
+    fn test_from_type() {
+        assert_eq!(
+            Schema::from_type::<TestStruct>().unwrap(),
+            Schema::new(vec![
+                Field::new("id", ColumnType::U32, false),
+                Field::new("b", ColumnType::Boolean, false),
+                Field::new("i", ColumnType::I64, true),
+                Field::new("s", ColumnType::String, false),
+            ])
+        );
+
+        assert_eq!(
+            Schema::from_type::<Nested>().unwrap().fields,
+            vec![
+                Field::new("ID", ColumnType::U64, false),
+                Field::new(
+                    "point",
+                    ColumnType::Struct(vec![
+                        Field::new("0", ColumnType::F64, false),
+                        Field::new("1", ColumnType::F64, false),
+                    ]),
+                    true
+                ),
+                Field::new(
+                    "tags",
+                    ColumnType::Array(Box::new(Field::new("element", ColumnType::String, true))),
+                    false
+                ),
+            ]
+        );
+
+        // Field names in the schema are the names that the type accepts.
+        assert_eq!(
+            serde_json::from_value::<Nested>(
+                json!({"ID": 1, "point": [0.5, 1.5], "tags": ["a", null]})
+            )
+            .unwrap(),
+            Nested {
+                id: 1,
+                point: Some((0.5, 1.5)),
+                tags: vec![Some("a".to_string()), None],
+            }
+        );
+
+        assert!(Schema::from_type::<u32>().is_err());
+        assert!(Schema::from_type::<std::collections::BTreeMap<u32, u32>>().is_err());
+    }
+
+    #[test]
+    fn test_key() {
+        let schema = Schema::from_type::<TestStruct>().unwrap();
+        assert_eq!(schema.clone().with_key(&["id"]).unwrap().key, vec!["id"]);
+        assert!(schema.with_key(&["foo"]).is_err());
+    }
+
+    #[test]
+    fn test_check_columns() {
+        let schema = Schema::from_type::<TestStruct>().unwrap();
+        let columns = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+        schema
+            .check_columns(&columns(&["id", "b", "i", "s"]))
+            .unwrap();
+        assert!(schema
+            .check_columns(&columns(&["id", "i", "b", "s"]))
+            .is_err());
+        assert!(schema.check_columns(&columns(&["id", "b", "i"])).is_err());
+    }
+
+    #[test]
+    fn test_avro_schema() {
+        let schema = Schema::from_type::<TestStruct>().unwrap();
+        assert_eq!(
+            schema.avro_schema("test"),
+            json!({
+                "type": "record",
+                "name": "test",
+                "fields": [
+                    {"name": "id", "type": "long"},
+                    {"name": "b", "type": "boolean"},
+                    {"name": "i", "type": ["null", "long"], "default": null},
+                    {"name": "s", "type": "string"},
+                ]
+            })
+        );
+    }
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct Point {
+        x: f64,
+        y: f64,
+    }
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct Segment {
+        from: Point,
+        to: Option<Point>,
+        id: u64,
+        weights: (i32, Vec<Point>),
+    }
+
+    #[test]
+    fn test_avro_schema_nested() {
+        let schema = Schema::from_type::<Segment>().unwrap();
+        let point = json!({
+            "type": "record",
+            "name": "ns.segment.from",
+            "fields": [
+                {"name": "x", "type": "double"},
+                {"name": "y", "type": "double"},
+            ]
+        });
+        assert_eq!(
+            schema.avro_schema("ns.segment"),
+            json!({
+                "type": "record",
+                "name": "ns.segment",
+                "fields": [
+                    // The first occurrence of a struct type defines the record...
+                    {"name": "from", "type": point},
+                    // ...that subsequent occurrences refer to by name.
+                    {"name": "to", "type": ["null", "ns.segment.from"], "default": null},
+                    {
+                        "name": "id",
+                        "type": {
+                            "type": "bytes",
+                            "logicalType": "decimal",
+                            "precision": 20,
+                            "scale": 0
+                        }
+                    },
+                    {
+                        "name": "weights",
+                        "type": {
+                            "type": "record",
+                            "name": "ns.segment.weights",
+                            "fields": [
+                                {"name": "_0", "type": "int"},
+                                {
+                                    "name": "_1",
+                                    "type": {"type": "array", "items": "ns.segment.from"}
+                                },
+                            ]
+                        }
+                    },
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_parquet_schema() {
+        let schema = Schema::from_type::<Nested>().unwrap();
+        assert_eq!(
+            schema.parquet_schema("test"),
+            r#"message test {
+  required int64 ID (INTEGER(64,false));
+  optional group point {
+    required double 0;
+    required double 1;
+  }
+  required group tags (LIST) {
+    repeated group list {
+      optional binary element (STRING);
+    }
+  }
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_serialize() {
+        let schema = Schema::from_type::<TestStruct>()
+            .unwrap()
+            .with_key(&["id"])
+            .unwrap();
+        let json = serde_json::to_string(&schema).unwrap();
+        assert_eq!(serde_json::from_str::<Schema>(&json).unwrap(), schema);
+    }
+}
@@ -1,10 +1,14 @@
+use anyhow::{Error as AnyError, Result as AnyResult};
 use dbsp::{
     trace::{Batch, BatchReader, Cursor},
     OutputHandle,
 };
 use erased_serde::Serialize as ErasedSerialize;
 use serde::Serialize;
-use std::sync::Arc;
+use std::{
+    any::{Any, TypeId},
+    sync::Arc,
+};
 
 /// A type-erased batch whose contents can be serialized.
 ///
@@ -25,7 +29,59 @@ pub trait SerBatch: Send {
     /// Cursor over the batch.
     fn cursor<'a>(&'a self) -> Box<dyn SerCursor + 'a>;
 
-    // fn fork(&self) -> Box<dyn SerBatch>;
+    /// Cursor over the upsert view of the batch.
+    ///
+    /// The upsert view contains at most one record per key of a consolidated
+    /// batch:
+    ///
+    /// * If the key has a value with positive weight, the view contains this
+    ///   value with weight `1` (upsert).
+    ///
+    /// * Otherwise, the view contains the first value of the key with weight
+    ///   `-1` (delete).
+    ///
+    /// Records of the view are yielded as keys of the cursor: for indexed
+    /// batches these are the values associated with each key; for batches
+    /// without values (i.e., with value type `()`) these are the keys
+    /// themselves.  This way encoders that only serialize keys produce
+    /// complete records.
+    ///
+    /// Returns `None` if the batch doesn't support the upsert view.  The
+    /// default implementation returns `None`.
+    fn upsert_cursor<'a>(&'a self) -> Option<Box<dyn SerCursor + 'a>> {
+        None
+    }
+
+    /// Returns an alias to `self`, or `None` if the batch cannot be aliased.
+    /// The default implementation returns `None`.
+    fn fork(&self) -> Option<Box<dyn SerBatch>> {
+        None
+    }
+
+    /// Merges `self` with `other` into a single consolidated batch.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `other` has a different concrete type than `self`, i.e.,
+    /// both batches must be produced by the same output handle.  The default
+    /// implementation always fails.
+    fn merge(&self, _other: &dyn SerBatch) -> AnyResult<Box<dyn SerBatch>> {
+        Err(AnyError::msg("output batches don't support merging"))
+    }
+
+    /// Casts `self` to `Any` to support downcasting in [`Self::merge`].
+    /// The default implementation returns `None`.
+    fn as_any(&self) -> Option<&dyn Any> {
+        None
+    }
+}
+
+/// Downcasts `batch` to `T` in [`SerBatch::merge`] implementations.
+fn downcast_batch<T: 'static>(batch: &dyn SerBatch) -> AnyResult<&T> {
+    batch
+        .as_any()
+        .and_then(|batch| batch.downcast_ref::<T>())
+        .ok_or_else(|| AnyError::msg("cannot merge output batches of different types"))
 }
 
 /// Cursor that allows serializing the contents of a type-erased batch.
@@ -50,6 +106,15 @@ pub trait SerCursor {
     /// A reference to the current value. Panics if invalid.
     fn val(&self) -> &dyn ErasedSerialize;
 
+    /// Key that identifies the current record in keyed outputs, e.g., the
+    /// key of a Kafka message.  Panics if invalid.
+    ///
+    /// Encoders treat [`Self::key`] as the record.  The default
+    /// implementation returns the record itself.
+    fn record_key(&self) -> &dyn ErasedSerialize {
+        self.key()
+    }
+
     /// Returns a reference to the current key, if valid.
     fn get_key(&self) -> Option<&dyn ErasedSerialize> {
         if self.key_valid() {
@@ -106,7 +171,7 @@ where
 
 impl<B> SerBatch for SerBatchImpl<B>
 where
-    B: BatchReader<Time = ()> + Clone + Send + Sync,
+    B: Batch<Time = ()> + Send + Sync,
     B::Key: Serialize,
     B::Val: Serialize,
     B::R: Into<i64>,
@@ -123,11 +188,84 @@ where
         Box::new(SerBatchCursor::new(&*self.batch))
     }
 
-    /*fn fork(&self) -> Box<dyn SerBatch> {
-        Box::new(Self {
+    fn upsert_cursor<'a>(&'a self) -> Option<Box<dyn SerCursor + 'a>> {
+        Some(Box::new(UpsertCursor::new(&*self.batch)))
+    }
+
+    fn fork(&self) -> Option<Box<dyn SerBatch>> {
+        Some(Box::new(Self {
             batch: self.batch.clone(),
-        })
-    }*/
+        }))
+    }
+
+    fn merge(&self, other: &dyn SerBatch) -> AnyResult<Box<dyn SerBatch>> {
+        let other = downcast_batch::<Self>(other)?;
+
+        Ok(Box::new(Self::new(self.batch.merge(&other.batch))))
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+/// Type-erased upsert view of a batch (see [`SerBatch::upsert_cursor`]).
+///
+/// Wraps a consolidated [`SerBatch`], substituting its upsert cursor for the
+/// regular cursor, so that the view can be passed to any [`Encoder`](`crate::Encoder`).
+pub struct UpsertBatch {
+    batch: Box<dyn SerBatch>,
+}
+
+impl UpsertBatch {
+    /// Create the upsert view of `batch`.
+    ///
+    /// Fails if `batch` doesn't support the upsert view (see
+    /// [`SerBatch::upsert_cursor`]).
+    pub fn new(batch: Box<dyn SerBatch>) -> AnyResult<Self> {
+        if batch.upsert_cursor().is_none() {
+            return Err(AnyError::msg(
+                "output batches don't support the 'upserts' mode",
+            ));
+        }
+        Ok(Self { batch })
+    }
+}
+
+impl SerBatch for UpsertBatch {
+    fn key_count(&self) -> usize {
+        self.batch.key_count()
+    }
+
+    fn len(&self) -> usize {
+        // One record per key.
+        self.batch.key_count()
+    }
+
+    fn cursor<'a>(&'a self) -> Box<dyn SerCursor + 'a> {
+        // Checked in `UpsertBatch::new`.
+        self.batch.upsert_cursor().unwrap()
+    }
+
+    fn upsert_cursor<'a>(&'a self) -> Option<Box<dyn SerCursor + 'a>> {
+        self.batch.upsert_cursor()
+    }
+
+    fn fork(&self) -> Option<Box<dyn SerBatch>> {
+        Some(Box::new(Self {
+            batch: self.batch.fork()?,
+        }))
+    }
+
+    fn merge(&self, other: &dyn SerBatch) -> AnyResult<Box<dyn SerBatch>> {
+        let other = downcast_batch::<Self>(other)?;
+
+        Ok(Box::new(Self::new(self.batch.merge(&*other.batch)?)?))
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
 }
 
 /// [`SerCursor`] implementation that wraps a [`Cursor`].
@@ -196,6 +334,119 @@ where
     }
 }
 
+/// [`SerCursor`] over the upsert view of a batch (see
+/// [`SerBatch::upsert_cursor`]).
+pub struct UpsertCursor<'a, B>
+where
+    B: BatchReader,
+{
+    cursor: B::Cursor<'a>,
+
+    /// `true` if the batch has non-unit values, in which case records of the
+    /// view are values rather than keys of the batch.
+    indexed: bool,
+
+    /// Weight of the current record: `1` for upserts, `-1` for deletes.
+    weight: i64,
+
+    /// The upsert view has exactly one value per key.  This flag is cleared
+    /// by `step_val`.
+    val_valid: bool,
+}
+
+impl<'a, B> UpsertCursor<'a, B>
+where
+    B: BatchReader<Time = ()>,
+    B::R: Into<i64>,
+{
+    pub fn new(batch: &'a B) -> Self {
+        let mut result = Self {
+            cursor: batch.cursor(),
+            indexed: TypeId::of::<B::Val>() != TypeId::of::<()>(),
+            weight: 0,
+            val_valid: true,
+        };
+        result.seek_record();
+        result
+    }
+
+    /// Position the underlying cursor on the record of the view for the
+    /// current key: the first value with positive weight, if any, or the
+    /// first value otherwise.
+    fn seek_record(&mut self) {
+        self.val_valid = true;
+
+        if !self.cursor.key_valid() {
+            return;
+        }
+
+        while self.cursor.val_valid() {
+            if self.cursor.weight().into() > 0 {
+                self.weight = 1;
+                return;
+            }
+            self.cursor.step_val();
+        }
+
+        self.cursor.rewind_vals();
+        self.weight = -1;
+    }
+}
+
+impl<'a, B> SerCursor for UpsertCursor<'a, B>
+where
+    B: BatchReader<Time = ()>,
+    B::Key: Serialize,
+    B::Val: Serialize,
+    B::R: Into<i64>,
+{
+    fn key_valid(&self) -> bool {
+        self.cursor.key_valid()
+    }
+
+    fn val_valid(&self) -> bool {
+        self.cursor.key_valid() && self.val_valid
+    }
+
+    fn key(&self) -> &dyn ErasedSerialize {
+        if self.indexed {
+            self.cursor.val()
+        } else {
+            self.cursor.key()
+        }
+    }
+
+    fn val(&self) -> &dyn ErasedSerialize {
+        &()
+    }
+
+    fn record_key(&self) -> &dyn ErasedSerialize {
+        self.cursor.key()
+    }
+
+    fn weight(&mut self) -> i64 {
+        self.weight
+    }
+
+    fn step_key(&mut self) {
+        self.cursor.step_key();
+        self.seek_record();
+    }
+
+    fn step_val(&mut self) {
+        self.val_valid = false;
+    }
+
+    fn rewind_keys(&mut self) {
+        self.cursor.rewind_keys();
+        self.seek_record();
+    }
+
+    fn rewind_vals(&mut self) {
+        self.val_valid = true;
+    }
+}
+
 /// A handle to an output stream of a circuit that yields type-erased
 /// output batches.
 ///
@@ -214,6 +465,14 @@ pub trait SerOutputBatchHandle: Send + Sync {
     /// [`SerBatch`] trait object.
     fn consolidate(&self) -> Box<dyn SerBatch>;
 
+    /// Like [`OutputHandle::set_enabled`].
+    ///
+    /// Used to enable taps (see
+    /// [`Catalog::register_tap`](`crate::Catalog::register_tap`)) only while
+    /// clients are attached to them.  The default implementation does
+    /// nothing, i.e., the handle always receives outputs.
+    fn set_enabled(&self, _enabled: bool) {}
+
     /// Returns an alias to `self`.
     fn fork(&self) -> Box<dyn SerOutputBatchHandle>;
 }
@@ -242,6 +501,10 @@ where
         Box::new(SerBatchImpl::new(batch))
     }
 
+    fn set_enabled(&self, enabled: bool) {
+        self.set_enabled(enabled)
+    }
+
     fn fork(&self) -> Box<dyn SerOutputBatchHandle> {
         Box::new(self.clone())
     }
@@ -1,10 +1,14 @@
-use crate::{Catalog, Controller, ControllerConfig, ControllerError};
+use crate::{
+    Catalog, Controller, ControllerConfig, ControllerError, FormatConfig, InputEndpointConfig,
+    OutputConsumer, OutputEndpointConfig,
+};
 use actix_web::{
+    delete,
     dev::{Server, ServiceFactory, ServiceRequest},
     get,
     middleware::Logger,
-    rt, web,
-    web::Data as WebData,
+    post, rt, web,
+    web::{Bytes, Data as WebData},
     App, Error as ActixError, HttpResponse, HttpServer, Responder,
 };
 use actix_web_static_files::ResourceFiles;
@@ -12,8 +16,11 @@ use anyhow::{Error as AnyError, Result as AnyResult};
 use clap::Parser;
 use dbsp::DBSPHandle;
 use env_logger::Env;
-use log::{error, info};
-use std::{net::TcpListener, sync::Mutex};
+use futures::{channel::mpsc, StreamExt};
+use log::{error, info, warn};
+use serde::Deserialize;
+use serde_yaml::Value as YamlValue;
+use std::{borrow::Cow, net::TcpListener, sync::Mutex};
 use tokio::{
     spawn,
     sync::mpsc::{channel, Receiver, Sender},
@@ -25,7 +32,7 @@ use self::prometheus::PrometheusMetrics;
 struct ServerState {
     metadata: String,
     controller: Mutex<Option<Controller>>,
-    prometheus: PrometheusMetrics,
+    prometheus: Mutex<PrometheusMetrics>,
     /// Channel used to send a `kill` command to
     /// the self-destruct task when shutting down
     /// the server.
@@ -42,7 +49,7 @@ impl ServerState {
         Self {
             metadata: meta,
             controller: Mutex::new(Some(controller)),
-            prometheus,
+            prometheus: Mutex::new(prometheus),
             terminate_sender,
         }
     }
@@ -81,6 +88,10 @@ struct Args {
 /// * `circuit_factory` - a function that creates a circuit and builds an
 ///   input/output stream
 /// catalog.
+///
+/// Custom transports and formats used by the pipeline configuration must be
+/// registered (see [`register_input_transport`](`crate::register_input_transport`)
+/// and related functions) before calling this function.
 pub fn server_main<F>(circuit_factory: &F) -> AnyResult<()>
 where
     F: Fn(usize) -> (DBSPHandle, Catalog),
@@ -223,6 +234,21 @@ where
         .service(metrics)
         .service(metadata)
         .service(dump_profile)
+        .service(rescale)
+        .service(start_transaction)
+        .service(commit_transaction)
+        .service(abort_transaction)
+        .service(circuit_graph)
+        .service(schema)
+        .service(tap)
+        .service(connect_input)
+        .service(disconnect_input)
+        .service(input_endpoint_status)
+        .service(start_input)
+        .service(pause_input)
+        .service(connect_output)
+        .service(disconnect_output)
+        .service(output_endpoint_status)
         .service(kill)
 }
 
@@ -265,7 +291,7 @@ async fn status(state: WebData<ServerState>) -> impl Responder {
 #[get("/metrics")]
 async fn metrics(state: WebData<ServerState>) -> impl Responder {
     match &*state.controller.lock().unwrap() {
-        Some(controller) => match state.prometheus.metrics(controller) {
+        Some(controller) => match state.prometheus.lock().unwrap().metrics(controller) {
             Ok(metrics) => HttpResponse::Ok()
                 .content_type(mime::TEXT_PLAIN)
                 .body(metrics),
@@ -295,6 +321,344 @@ async fn dump_profile(state: WebData<ServerState>) -> impl Responder {
     }
 }
 
+/// Change the number of worker threads.  Requires a circuit created with
+/// `Runtime::init_rescalable_circuit`.
+#[get("/rescale/{workers}")]
+async fn rescale(state: WebData<ServerState>, workers: web::Path<usize>) -> impl Responder {
+    let workers = workers.into_inner();
+
+    match &*state.controller.lock().unwrap() {
+        Some(controller) => match controller.rescale(workers) {
+            Ok(()) => HttpResponse::Ok().body(format!("Rescaling to {workers} workers initiated")),
+            Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+        },
+        None => HttpResponse::Conflict().body("The pipeline has been terminated"),
+    }
+}
+
+#[get("/start_transaction")]
+async fn start_transaction(state: WebData<ServerState>) -> impl Responder {
+    match &*state.controller.lock().unwrap() {
+        Some(controller) => match controller.start_transaction() {
+            Ok(()) => HttpResponse::Ok().body("Transaction started"),
+            Err(e) => HttpResponse::Conflict().body(e.to_string()),
+        },
+        None => HttpResponse::Conflict().body("The pipeline has been terminated"),
+    }
+}
+
+#[get("/commit_transaction")]
+async fn commit_transaction(state: WebData<ServerState>) -> impl Responder {
+    match &*state.controller.lock().unwrap() {
+        Some(controller) => {
+            controller.commit_transaction();
+            HttpResponse::Ok().body("Transaction commit initiated")
+        }
+        None => HttpResponse::Conflict().body("The pipeline has been terminated"),
+    }
+}
+
+#[get("/abort_transaction")]
+async fn abort_transaction(state: WebData<ServerState>) -> impl Responder {
+    match &*state.controller.lock().unwrap() {
+        Some(controller) => {
+            controller.abort_transaction();
+            HttpResponse::Ok().body("Transaction abort initiated")
+        }
+        None => HttpResponse::Conflict().body("The pipeline has been terminated"),
+    }
+}
+
+/// Circuit topology and the list of taps.
+#[get("/circuit_graph")]
+async fn circuit_graph(state: WebData<ServerState>) -> impl Responder {
+    match &*state.controller.lock().unwrap() {
+        Some(controller) => {
+            let json_string = serde_json::to_string(controller.circuit_graph()).unwrap();
+            HttpResponse::Ok()
+                .content_type(mime::APPLICATION_JSON)
+                .body(json_string)
+        }
+        None => HttpResponse::Conflict().body("The pipeline has been terminated"),
+    }
+}
+
+/// Schemas of input and output streams.
+#[get("/schema")]
+async fn schema(state: WebData<ServerState>) -> impl Responder {
+    match &*state.controller.lock().unwrap() {
+        Some(controller) => {
+            let json_string = serde_json::to_string(&controller.schemas()).unwrap();
+            HttpResponse::Ok()
+                .content_type(mime::APPLICATION_JSON)
+                .body(json_string)
+        }
+        None => HttpResponse::Conflict().body("The pipeline has been terminated"),
+    }
+}
+
+fn default_tap_format() -> String {
+    "csv".to_string()
+}
+
+const fn default_tap_batches() -> usize {
+    1
+}
+
+/// Query arguments of the `/tap` endpoint.
+#[derive(Deserialize)]
+struct TapArgs {
+    /// Output format name, defaults to "csv".
+    #[serde(default = "default_tap_format")]
+    format: String,
+
+    /// Number of clock cycles to stream, defaults to 1.
+    #[serde(default = "default_tap_batches")]
+    batches: usize,
+}
+
+/// Maximal number of encoded buffers queued for a tap client.
+const TAP_CHANNEL_CAPACITY: usize = 64;
+
+/// Output consumer that forwards encoded tap contents to an HTTP response.
+///
+/// Buffers are queued in a bounded channel.  If the client falls behind and
+/// the channel fills up, the consumer closes the response and drops the rest
+/// of the data, so that a slow client cannot make the pipeline run out of
+/// memory.
+struct TapConsumer {
+    tap_name: String,
+    sender: Option<mpsc::Sender<Bytes>>,
+}
+
+impl OutputConsumer for TapConsumer {
+    fn push_buffer(&mut self, buffer: &[u8]) {
+        let sender = match &mut self.sender {
+            Some(sender) => sender,
+            None => return,
+        };
+
+        // The client may have disconnected, in which case the tap will detach
+        // after sending the requested number of batches.
+        if let Err(e) = sender.try_send(Bytes::copy_from_slice(buffer)) {
+            if e.is_full() {
+                warn!(
+                    "client of tap '{}' is not keeping up with the pipeline, disconnecting",
+                    self.tap_name
+                );
+            }
+            self.sender = None;
+        }
+    }
+}
+
+/// Stream the contents of a tap during the next `batches` clock cycles.
+///
+/// The response ends once the tap detaches.
+#[get("/tap/{tap_name}")]
+async fn tap(
+    state: WebData<ServerState>,
+    tap_name: web::Path<String>,
+    args: web::Query<TapArgs>,
+) -> impl Responder {
+    match &*state.controller.lock().unwrap() {
+        Some(controller) => {
+            let format = FormatConfig {
+                name: Cow::Owned(args.format.clone()),
+                config: YamlValue::Null,
+            };
+            let (sender, receiver) = mpsc::channel(TAP_CHANNEL_CAPACITY);
+            let consumer = TapConsumer {
+                tap_name: tap_name.to_string(),
+                sender: Some(sender),
+            };
+
+            match controller.attach_tap(&tap_name, &format, args.batches, Box::new(consumer)) {
+                Ok(()) => HttpResponse::Ok().streaming(receiver.map(Ok::<_, ActixError>)),
+                Err(e) => HttpResponse::BadRequest().body(format!("Failed to attach tap: {e}")),
+            }
+        }
+        None => HttpResponse::Conflict().body("The pipeline has been terminated"),
+    }
+}
+
+/// Connect a new input endpoint.
+///
+/// The request body contains the endpoint configuration in YAML or JSON
+/// format (a JSON document is also a valid YAML document).
+#[post("/input_endpoints/{endpoint_name}")]
+async fn connect_input(
+    state: WebData<ServerState>,
+    endpoint_name: web::Path<String>,
+    body: Bytes,
+) -> impl Responder {
+    let config: InputEndpointConfig = match serde_yaml::from_slice(&body) {
+        Ok(config) => config,
+        Err(e) => {
+            return HttpResponse::BadRequest().body(format!("Invalid endpoint configuration: {e}"));
+        }
+    };
+
+    match &*state.controller.lock().unwrap() {
+        Some(controller) => match controller.connect_input(&endpoint_name, &config) {
+            Ok(()) => {
+                HttpResponse::Ok().body(format!("Input endpoint '{endpoint_name}' connected"))
+            }
+            Err(e) => HttpResponse::BadRequest().body(format!(
+                "Failed to connect input endpoint '{endpoint_name}': {e}"
+            )),
+        },
+        None => HttpResponse::Conflict().body("The pipeline has been terminated"),
+    }
+}
+
+#[delete("/input_endpoints/{endpoint_name}")]
+async fn disconnect_input(
+    state: WebData<ServerState>,
+    endpoint_name: web::Path<String>,
+) -> impl Responder {
+    match &*state.controller.lock().unwrap() {
+        Some(controller) => match controller.disconnect_input(&endpoint_name) {
+            Ok(()) => {
+                HttpResponse::Ok().body(format!("Input endpoint '{endpoint_name}' disconnected"))
+            }
+            Err(e) => HttpResponse::NotFound().body(e.to_string()),
+        },
+        None => HttpResponse::Conflict().body("The pipeline has been terminated"),
+    }
+}
+
+/// Input endpoint configuration and metrics.
+#[get("/input_endpoints/{endpoint_name}")]
+async fn input_endpoint_status(
+    state: WebData<ServerState>,
+    endpoint_name: web::Path<String>,
+) -> impl Responder {
+    match &*state.controller.lock().unwrap() {
+        Some(controller) => {
+            let inputs = controller.status().input_status();
+            match inputs
+                .values()
+                .find(|status| status.endpoint_name == *endpoint_name)
+            {
+                Some(status) => {
+                    let json_string = serde_json::to_string(status).unwrap();
+                    HttpResponse::Ok()
+                        .content_type(mime::APPLICATION_JSON)
+                        .body(json_string)
+                }
+                None => HttpResponse::NotFound()
+                    .body(format!("Unknown input endpoint '{endpoint_name}'")),
+            }
+        }
+        None => HttpResponse::Conflict().body("The pipeline has been terminated"),
+    }
+}
+
+/// Unpause an input endpoint paused via `/input_endpoints/{endpoint_name}/pause`.
+#[get("/input_endpoints/{endpoint_name}/start")]
+async fn start_input(
+    state: WebData<ServerState>,
+    endpoint_name: web::Path<String>,
+) -> impl Responder {
+    match &*state.controller.lock().unwrap() {
+        Some(controller) => match controller.start_input(&endpoint_name) {
+            Ok(()) => HttpResponse::Ok().body(format!("Input endpoint '{endpoint_name}' started")),
+            Err(e) => HttpResponse::NotFound().body(e.to_string()),
+        },
+        None => HttpResponse::Conflict().body("The pipeline has been terminated"),
+    }
+}
+
+/// Pause an individual input endpoint.  The endpoint remains paused while
+/// the pipeline is running.
+#[get("/input_endpoints/{endpoint_name}/pause")]
+async fn pause_input(
+    state: WebData<ServerState>,
+    endpoint_name: web::Path<String>,
+) -> impl Responder {
+    match &*state.controller.lock().unwrap() {
+        Some(controller) => match controller.pause_input(&endpoint_name) {
+            Ok(()) => HttpResponse::Ok().body(format!("Input endpoint '{endpoint_name}' paused")),
+            Err(e) => HttpResponse::NotFound().body(e.to_string()),
+        },
+        None => HttpResponse::Conflict().body("The pipeline has been terminated"),
+    }
+}
+
+/// Connect a new output endpoint.
+///
+/// The request body contains the endpoint configuration in YAML or JSON
+/// format.
+#[post("/output_endpoints/{endpoint_name}")]
+async fn connect_output(
+    state: WebData<ServerState>,
+    endpoint_name: web::Path<String>,
+    body: Bytes,
+) -> impl Responder {
+    let config: OutputEndpointConfig = match serde_yaml::from_slice(&body) {
+        Ok(config) => config,
+        Err(e) => {
+            return HttpResponse::BadRequest().body(format!("Invalid endpoint configuration: {e}"));
+        }
+    };
+
+    match &*state.controller.lock().unwrap() {
+        Some(controller) => match controller.connect_output(&endpoint_name, &config) {
+            Ok(()) => {
+                HttpResponse::Ok().body(format!("Output endpoint '{endpoint_name}' connected"))
+            }
+            Err(e) => HttpResponse::BadRequest().body(format!(
+                "Failed to connect output endpoint '{endpoint_name}': {e}"
+            )),
+        },
+        None => HttpResponse::Conflict().body("The pipeline has been terminated"),
+    }
+}
+
+#[delete("/output_endpoints/{endpoint_name}")]
+async fn disconnect_output(
+    state: WebData<ServerState>,
+    endpoint_name: web::Path<String>,
+) -> impl Responder {
+    match &*state.controller.lock().unwrap() {
+        Some(controller) => match controller.disconnect_output(&endpoint_name) {
+            Ok(()) => {
+                HttpResponse::Ok().body(format!("Output endpoint '{endpoint_name}' disconnected"))
+            }
+            Err(e) => HttpResponse::NotFound().body(e.to_string()),
+        },
+        None => HttpResponse::Conflict().body("The pipeline has been terminated"),
+    }
+}
+
+/// Output endpoint configuration and metrics.
+#[get("/output_endpoints/{endpoint_name}")]
+async fn output_endpoint_status(
+    state: WebData<ServerState>,
+    endpoint_name: web::Path<String>,
+) -> impl Responder {
+    match &*state.controller.lock().unwrap() {
+        Some(controller) => {
+            let outputs = controller.status().output_status();
+            match outputs
+                .values()
+                .find(|status| status.endpoint_name == *endpoint_name)
+            {
+                Some(status) => {
+                    let json_string = serde_json::to_string(status).unwrap();
+                    HttpResponse::Ok()
+                        .content_type(mime::APPLICATION_JSON)
+                        .body(json_string)
+                }
+                None => HttpResponse::NotFound()
+                    .body(format!("Unknown output endpoint '{endpoint_name}'")),
+            }
+        }
+        None => HttpResponse::Conflict().body("The pipeline has been terminated"),
+    }
+}
+
 #[get("/shutdown")]
 async fn shutdown(state: WebData<ServerState>) -> impl Responder {
     let controller = state.controller.lock().unwrap().take();
@@ -325,9 +689,9 @@ mod test_with_kafka {
         test::{
             generate_test_batches,
             kafka::{BufferConsumer, KafkaResources, TestProducer},
-            test_circuit, wait, TEST_LOGGER,
+            test_circuit, wait, TestStruct, TEST_LOGGER,
         },
-        Controller, ControllerConfig, ControllerError,
+        CatalogSchemas, Controller, ControllerConfig, ControllerError, Schema,
     };
     use actix_web::{http::StatusCode, middleware::Logger, test, web::Data as WebData, App};
     use crossbeam::queue::SegQueue;
@@ -449,6 +813,19 @@ outputs:
         let resp = test::call_service(&app, req).await;
         assert!(resp.status().is_success());
 
+        println!("/schema");
+        let req = test::TestRequest::get().uri("/schema").to_request();
+        let schemas: CatalogSchemas = test::call_and_read_body_json(&app, req).await;
+        let expected = Schema::from_type::<TestStruct>().unwrap();
+        assert_eq!(schemas.inputs.get("test_input1"), Some(&expected));
+        assert_eq!(schemas.outputs.get("test_output1"), Some(&expected));
+
+        println!("/metrics");
+        let req = test::TestRequest::get().uri("/metrics").to_request();
+        let resp = test::call_and_read_body(&app, req).await;
+        let metrics = String::from_utf8(resp.to_vec()).unwrap();
+        assert!(metrics.contains("circuit_step_latency_seconds_bucket"));
+
         // Pause command; send more data, receive none.
         println!("/pause");
         let req = test::TestRequest::get().uri("/pause").to_request();
@@ -471,7 +848,8 @@ outputs:
 
         println!("Testing invalid input");
         producer.send_string("invalid\n", "test_server_input_topic");
-        wait(|| errors.len() == 1, None);
+        // The parse error and the resulting endpoint failure.
+        wait(|| errors.len() == 2, None);
 
         // Shutdown
         println!("/shutdown");
@@ -1,15 +1,29 @@
 use crate::{
-    controller::{EndpointId, InputEndpointStatus, OutputEndpointStatus},
+    controller::{ControllerStatus, EndpointId, InputEndpointStatus, OutputEndpointStatus},
     Controller,
 };
 use anyhow::{Error as AnyError, Result as AnyResult};
-use prometheus::{Encoder, IntGauge, Opts, Registry, TextEncoder};
+use dbsp::{
+    circuit::metadata::{MetaItem, OperatorMeta},
+    profile::NodeProfile,
+};
+use prometheus::{
+    proto::{Bucket, Counter, Gauge, Histogram, LabelPair, Metric, MetricFamily, MetricType},
+    Encoder, IntGauge, Opts, Registry, TextEncoder,
+};
 use std::{collections::BTreeMap, sync::atomic::Ordering};
 
 /// Prometheus metrics of the controller.
 ///
 /// The primary metrics are stored in `controller.status` and are mirrored
 /// to Prometheus metrics on demand.
+///
+/// Endpoint metrics are registered in `registry` when the endpoint is
+/// created and unregistered when it is disconnected.  Endpoints connected or
+/// disconnected at runtime are picked up on the next scrape.  Circuit and
+/// per-operator metrics are generated from scratch on
+/// each scrape, since the set of operators changes when the circuit is
+/// rescaled.
 pub(crate) struct PrometheusMetrics {
     registry: Registry,
     input_metrics: BTreeMap<EndpointId, InputMetrics>,
@@ -158,10 +172,56 @@ impl PrometheusMetrics {
         Ok(())
     }
 
+    /// Register metrics for newly connected endpoints and unregister metrics
+    /// of disconnected endpoints.
+    fn sync_endpoints(&mut self, status: &ControllerStatus) -> AnyResult<()> {
+        // Unregister first, so that a new endpoint can reuse the name of a
+        // disconnected one.
+        let inputs = status.input_status();
+        let removed_inputs: Vec<_> = self
+            .input_metrics
+            .keys()
+            .filter(|endpoint_id| !inputs.contains_key(endpoint_id))
+            .cloned()
+            .collect();
+        for endpoint_id in removed_inputs.iter() {
+            let metrics = self.input_metrics.remove(endpoint_id).unwrap();
+            self.remove_gauges(&metrics.gauges())?;
+        }
+
+        let outputs = status.output_status();
+        let removed_outputs: Vec<_> = self
+            .output_metrics
+            .keys()
+            .filter(|endpoint_id| !outputs.contains_key(endpoint_id))
+            .cloned()
+            .collect();
+        for endpoint_id in removed_outputs.iter() {
+            let metrics = self.output_metrics.remove(endpoint_id).unwrap();
+            self.remove_gauges(&metrics.gauges())?;
+        }
+
+        for (endpoint_id, endpoint_status) in inputs.iter() {
+            if !self.input_metrics.contains_key(endpoint_id) {
+                self.add_input_endpoint(*endpoint_id, endpoint_status)?;
+            }
+        }
+
+        for (endpoint_id, endpoint_status) in outputs.iter() {
+            if !self.output_metrics.contains_key(endpoint_id) {
+                self.add_output_endpoint(*endpoint_id, endpoint_status)?;
+            }
+        }
+
+        Ok(())
+    }
+
     /// Extract metrics in the format expected by the Prometheus server.
-    pub(crate) fn metrics(&self, controller: &Controller) -> AnyResult<Vec<u8>> {
+    pub(crate) fn metrics(&mut self, controller: &Controller) -> AnyResult<Vec<u8>> {
         let status = controller.status();
 
+        self.sync_endpoints(status)?;
+
         for (endpoint_id, endpoint_status) in status.input_status().iter() {
             self.update_input_metrics(*endpoint_id, endpoint_status)?;
         }
@@ -172,12 +232,127 @@ impl PrometheusMetrics {
 
         let mut buffer = vec![];
         let encoder = TextEncoder::new();
-        let metric_families = self.registry.gather();
+        let mut metric_families = self.registry.gather();
+        metric_families.extend(Self::circuit_metrics(status));
         encoder.encode(&metric_families, &mut buffer)?;
 
         Ok(buffer)
     }
 
+    /// Circuit-level step latency histogram and per-operator metrics
+    /// extracted from the most recent operator profiles.
+    fn circuit_metrics(status: &ControllerStatus) -> Vec<MetricFamily> {
+        let circuit_metrics = &status.circuit_metrics;
+
+        let mut histogram = Histogram::default();
+        histogram.set_sample_count(circuit_metrics.num_steps.load(Ordering::Acquire));
+        histogram.set_sample_sum(
+            circuit_metrics.total_step_usecs.load(Ordering::Acquire) as f64 / 1_000_000.0,
+        );
+        histogram.set_bucket(
+            circuit_metrics
+                .step_latency_histogram()
+                .into_iter()
+                .map(|(upper_bound, count)| {
+                    let mut bucket = Bucket::default();
+                    bucket.set_upper_bound(upper_bound);
+                    bucket.set_cumulative_count(count);
+                    bucket
+                })
+                .collect::<Vec<_>>()
+                .into(),
+        );
+        let mut step_latency = Metric::default();
+        step_latency.set_histogram(histogram);
+
+        let mut records = Vec::new();
+        let mut batches = Vec::new();
+        let mut used_bytes = Vec::new();
+        let mut allocated_bytes = Vec::new();
+        let mut invocations = Vec::new();
+        let mut eval_time = Vec::new();
+
+        for profile in circuit_metrics.operator_profiles().iter() {
+            for node in profile.nodes.iter() {
+                let labels = operator_labels(profile.worker, node);
+
+                if let Some(value) = meta_value(&node.meta, "total size") {
+                    records.push(gauge(&labels, value));
+                }
+                if let Some(MetaItem::Array(sizes)) = meta_item(&node.meta, "batch sizes") {
+                    batches.push(gauge(&labels, sizes.len() as f64));
+                }
+                if let Some(value) = meta_value(&node.meta, "used bytes") {
+                    used_bytes.push(gauge(&labels, value));
+                }
+                if let Some(value) = meta_value(&node.meta, "allocated bytes") {
+                    allocated_bytes.push(gauge(&labels, value));
+                }
+                if let Some(cpu) = &node.cpu {
+                    invocations.push(counter(&labels, cpu.invocations() as f64));
+                    eval_time.push(counter(&labels, cpu.total_time().as_secs_f64()));
+                }
+            }
+        }
+
+        [
+            (
+                "circuit_step_latency_seconds",
+                "Time to evaluate one clock cycle of the circuit.",
+                MetricType::HISTOGRAM,
+                vec![step_latency],
+            ),
+            (
+                "operator_records",
+                "Number of records stored by the operator, e.g., in a trace.",
+                MetricType::GAUGE,
+                records,
+            ),
+            (
+                "operator_batches",
+                "Number of batches stored by the operator.",
+                MetricType::GAUGE,
+                batches,
+            ),
+            (
+                "operator_used_bytes",
+                "Memory used by the operator state.",
+                MetricType::GAUGE,
+                used_bytes,
+            ),
+            (
+                "operator_allocated_bytes",
+                "Memory allocated for the operator state.",
+                MetricType::GAUGE,
+                allocated_bytes,
+            ),
+            (
+                "operator_invocations_total",
+                "Number of times the operator was evaluated; requires CPU profiler.",
+                MetricType::COUNTER,
+                invocations,
+            ),
+            (
+                "operator_eval_seconds_total",
+                "Total time spent evaluating the operator; requires CPU profiler.",
+                MetricType::COUNTER,
+                eval_time,
+            ),
+        ]
+        .into_iter()
+        // The text encoder rejects families without samples.
+        .filter(|(_, _, _, metrics)| !metrics.is_empty())
+        .map(|(name, help, metric_type, metrics)| {
+            let mut family = MetricFamily::default();
+            family.set_name(name.to_string());
+            family.set_help(help.to_string());
+            family.set_field_type(metric_type);
+            family.set_metric(metrics.into());
+            family
+        })
+        .collect()
+    }
+
     fn create_gauge(&self, name: &str, endpoint: &str) -> AnyResult<IntGauge> {
         let opts = Opts::new(name, name).const_label("endpoint", endpoint);
         let gauge = IntGauge::with_opts(opts)?;
@@ -185,6 +360,14 @@ impl PrometheusMetrics {
 
         Ok(gauge)
     }
+
+    fn remove_gauges(&self, gauges: &[&IntGauge]) -> AnyResult<()> {
+        for gauge in gauges.iter() {
+            self.registry.unregister(Box::new((*gauge).clone()))?;
+        }
+
+        Ok(())
+    }
 }
 
 struct InputMetrics {
@@ -196,6 +379,19 @@ struct InputMetrics {
     num_parse_errors: IntGauge,
 }
 
+impl InputMetrics {
+    fn gauges(&self) -> [&IntGauge; 6] {
+        [
+            &self.total_bytes,
+            &self.total_records,
+            &self.buffered_bytes,
+            &self.buffered_records,
+            &self.num_transport_errors,
+            &self.num_parse_errors,
+        ]
+    }
+}
+
 struct OutputMetrics {
     transmitted_bytes: IntGauge,
     transmitted_records: IntGauge,
@@ -204,3 +400,77 @@ struct OutputMetrics {
     num_transport_errors: IntGauge,
     num_encode_errors: IntGauge,
 }
+
+impl OutputMetrics {
+    fn gauges(&self) -> [&IntGauge; 6] {
+        [
+            &self.transmitted_bytes,
+            &self.transmitted_records,
+            &self.buffered_records,
+            &self.buffered_batches,
+            &self.num_transport_errors,
+            &self.num_encode_errors,
+        ]
+    }
+}
+
+/// Labels that identify an operator instance: operator name, global node
+/// id, source location, and worker.
+fn operator_labels(worker: usize, node: &NodeProfile) -> Vec<LabelPair> {
+    let node_id = node
+        .id
+        .iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>()
+        .join(".");
+
+    [
+        ("operator", node.name.clone()),
+        ("node_id", format!("[{node_id}]")),
+        ("location", node.location.clone().unwrap_or_default()),
+        ("worker", worker.to_string()),
+    ]
+    .into_iter()
+    .map(|(name, value)| {
+        let mut label = LabelPair::default();
+        label.set_name(name.to_string());
+        label.set_value(value);
+        label
+    })
+    .collect()
+}
+
+fn meta_item<'a>(meta: &'a OperatorMeta, label: &str) -> Option<&'a MetaItem> {
+    meta.iter()
+        .find(|(item_label, _)| item_label == label)
+        .map(|(_, item)| item)
+}
+
+/// Numeric value of metadata item `label`.
+fn meta_value(meta: &OperatorMeta, label: &str) -> Option<f64> {
+    match meta_item(meta, label)? {
+        MetaItem::Int(value) => Some(*value as f64),
+        MetaItem::Bytes(bytes) => Some(bytes.bytes as f64),
+        _ => None,
+    }
+}
+
+fn gauge(labels: &[LabelPair], value: f64) -> Metric {
+    let mut gauge = Gauge::default();
+    gauge.set_value(value);
+
+    let mut metric = Metric::default();
+    metric.set_label(labels.to_vec().into());
+    metric.set_gauge(gauge);
+    metric
+}
+
+fn counter(labels: &[LabelPair], value: f64) -> Metric {
+    let mut counter = Counter::default();
+    counter.set_value(value);
+
+    let mut metric = Metric::default();
+    metric.set_label(labels.to_vec().into());
+    metric.set_counter(counter);
+    metric
+}
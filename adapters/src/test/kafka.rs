@@ -6,6 +6,7 @@ use rdkafka::{
     client::DefaultClientContext,
     config::{FromClientConfig, RDKafkaLogLevel},
     consumer::{BaseConsumer, Consumer},
+    message::{Header, OwnedHeaders},
     producer::{BaseRecord, DefaultProducerContext, Producer, ThreadedProducer},
     util::Timeout,
     ClientConfig, Message,
@@ -132,6 +133,34 @@ impl TestProducer {
         self.producer.send(record).unwrap();
         self.producer.flush(Timeout::Never).unwrap();
     }
+
+    /// Send a single message with optional `key` and `headers` to
+    /// partition 0 of `topic`.
+    pub fn send_message(
+        &self,
+        payload: &str,
+        key: Option<&str>,
+        headers: &[(&str, &str)],
+        topic: &str,
+    ) {
+        let mut owned_headers = OwnedHeaders::new();
+        for (key, value) in headers.iter() {
+            owned_headers = owned_headers.insert(Header {
+                key,
+                value: Some(*value),
+            });
+        }
+
+        let mut record = <BaseRecord<str, str, ()>>::to(topic)
+            .payload(payload)
+            .partition(0)
+            .headers(owned_headers);
+        if let Some(key) = key {
+            record = record.key(key);
+        }
+        self.producer.send(record).unwrap();
+        self.producer.flush(Timeout::Never).unwrap();
+    }
 }
 
 /// Consumer thread: read from output topic, deserialize to a shared buffer.
@@ -1,5 +1,8 @@
-use crate::{controller::FormatConfig, Catalog, InputConsumer, InputFormat, Parser};
-use anyhow::{Error as AnyError, Result as AnyResult};
+use crate::{
+    controller::FormatConfig, Catalog, InputConsumer, InputFormat, InputMetadata, ParseError,
+    Parser,
+};
+use anyhow::Error as AnyError;
 use std::sync::{Arc, Mutex, MutexGuard};
 
 pub type ErrorCallback = Box<dyn FnMut(&AnyError) + Send>;
@@ -16,7 +19,7 @@ pub struct MockInputConsumerState {
     pub endpoint_error: Option<AnyError>,
 
     /// The last result returned by the parser.
-    pub parser_result: Option<AnyResult<usize>>,
+    pub parser_result: Option<(usize, Vec<ParseError>)>,
 
     /// Parser to push data to.
     parser: Box<dyn Parser>,
@@ -77,26 +80,37 @@ impl MockInputConsumer {
     pub fn on_error(&self, error_cb: Option<ErrorCallback>) {
         self.state().error_cb = error_cb;
     }
-}
 
-impl InputConsumer for MockInputConsumer {
-    fn input(&mut self, data: &[u8]) {
+    fn input_chunk(&mut self, data: &[u8], metadata: Option<&InputMetadata>) {
         // println!("input");
         let mut state = self.state();
 
         state.data.extend_from_slice(data);
-        let parser_result = state.parser.input(data);
+        let parser_result = match metadata {
+            None => state.parser.input_with_errors(data),
+            Some(metadata) => state.parser.input_with_metadata(data, metadata),
+        };
         // println!("parser returned '{:?}'", state.parser_result);
-        if let Err(e) = &parser_result {
+        for ParseError { error, .. } in parser_result.1.iter() {
             if let Some(error_cb) = &mut state.error_cb {
-                error_cb(e);
+                error_cb(error);
             } else {
-                panic!("mock_input_consumer: parse error '{e}'");
+                panic!("mock_input_consumer: parse error '{error}'");
             }
         }
         state.parser_result = Some(parser_result);
         state.parser.flush();
     }
+}
+
+impl InputConsumer for MockInputConsumer {
+    fn input(&mut self, data: &[u8]) {
+        self.input_chunk(data, None)
+    }
+
+    fn input_with_metadata(&mut self, data: &[u8], metadata: &InputMetadata) {
+        self.input_chunk(data, Some(metadata))
+    }
 
     fn error(&mut self, _fatal: bool, error: AnyError) {
         let mut state = self.state();
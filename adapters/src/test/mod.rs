@@ -1,6 +1,6 @@
 //! Test framework for the `adapters` crate.
 
-use crate::{controller::InputEndpointConfig, Catalog, InputEndpoint, InputTransport};
+use crate::{controller::InputEndpointConfig, Catalog, InputEndpoint, InputTransport, Schema};
 use dbsp::{DBSPHandle, Runtime};
 use log::{Log, Metadata, Record};
 use serde::Deserialize;
@@ -79,6 +79,10 @@ where
     let mut catalog = Catalog::new();
     let input_handle = <MockDeZSet<T>>::new();
     catalog.register_input_collection_handle(name, input_handle.clone());
+    match Schema::from_type::<T>() {
+        Ok(schema) => catalog.set_input_schema(name, schema),
+        Err(e) => log::warn!("input stream '{name}' has no schema: {e}"),
+    }
 
     let consumer = MockInputConsumer::from_config(&config.format, &Arc::new(Mutex::new(catalog)));
 
@@ -104,7 +108,7 @@ pub fn test_circuit(workers: usize) -> (DBSPHandle, Catalog) {
 
     let mut catalog = Catalog::new();
     catalog.register_input_zset_handle("test_input1", input);
-    catalog.register_output_batch_handle("test_output1", output);
+    catalog.register_output_zset_handle("test_output1", output);
 
     (circuit, catalog)
 }
@@ -0,0 +1,729 @@
+//! Input transport that ingests files dropped into a directory.
+
+use super::{InputConsumer, InputEndpoint, InputTransport};
+use crate::PipelineState;
+use anyhow::{Error as AnyError, Result as AnyResult};
+use crossbeam::channel::{unbounded, Receiver, Sender};
+use flate2::read::MultiGzDecoder;
+use glob::{MatchOptions, Pattern};
+use num_traits::FromPrimitive;
+use serde::Deserialize;
+use serde_yaml::Value as YamlValue;
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    fs::{read_dir, File, OpenOptions},
+    io::{BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    thread::{sleep, spawn},
+    time::{Duration, SystemTime},
+};
+
+const SLEEP_MS: u64 = 200;
+
+/// Default value of `DirectoryInputConfig::pattern`.
+fn default_pattern() -> String {
+    "*".to_string()
+}
+
+/// Default value of `DirectoryInputConfig::workers`.
+const fn default_workers() -> usize {
+    1
+}
+
+/// `InputTransport` implementation that ingests files that appear in a
+/// directory.
+///
+/// The endpoint scans the directory for files whose names match a glob
+/// pattern and dispatches them to worker threads in the configured order.
+/// Each worker feeds its own instance of the parser created with
+/// [`InputConsumer::fork`] and sends an [`eoi`](`InputConsumer::eoi`)
+/// message at the end of each file, so records must not straddle files.
+///
+/// Files must be complete by the time they appear in the directory, e.g.,
+/// producers should write them under a different name or in a different
+/// directory and then atomically rename them.
+pub struct DirectoryInputTransport;
+
+impl InputTransport for DirectoryInputTransport {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("directory")
+    }
+
+    fn new_endpoint(
+        &self,
+        config: &YamlValue,
+        consumer: Box<dyn InputConsumer>,
+    ) -> AnyResult<Box<dyn InputEndpoint>> {
+        let config = DirectoryInputConfig::deserialize(config)?;
+        let mut ep = DirectoryInputEndpoint::new();
+        ep.connect(config, consumer)?;
+        Ok(Box::new(ep))
+    }
+}
+
+/// Order in which files are ingested.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum FileOrder {
+    /// Lexicographic order of file names.
+    #[default]
+    Name,
+
+    /// Order of modification times; files with identical modification times
+    /// are ordered by name.
+    Mtime,
+}
+
+/// File compression.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Compression {
+    /// Detect compression from file extension: `.gz` for gzip; `.zst` or
+    /// `.zstd` for zstd; anything else is read as is.
+    #[default]
+    Auto,
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    fn for_path(self, path: &Path) -> Self {
+        match self {
+            Self::Auto => match path.extension().and_then(|ext| ext.to_str()) {
+                Some("gz") => Self::Gzip,
+                Some("zst") | Some("zstd") => Self::Zstd,
+                _ => Self::None,
+            },
+            compression => compression,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct DirectoryInputConfig {
+    /// Directory to read files from.
+    path: String,
+
+    /// Glob pattern that file names must match to be ingested, e.g.,
+    /// `"*.csv.gz"`.  Hidden files (whose names start with `.`) only match
+    /// patterns that start with `.`.
+    ///
+    /// Default: `"*"`.
+    #[serde(default = "default_pattern")]
+    pattern: String,
+
+    /// Order in which files are ingested: `name` (default) or `mtime`.
+    ///
+    /// Files are dispatched to workers in this order.  With a single worker,
+    /// each file is ingested completely before the next one is started.
+    #[serde(default)]
+    order: FileOrder,
+
+    /// File compression: `auto` (default), `none`, `gzip`, or `zstd`.
+    #[serde(default)]
+    compression: Compression,
+
+    /// Number of threads that read files in parallel.
+    ///
+    /// Default: 1.
+    #[serde(default = "default_workers")]
+    workers: usize,
+
+    /// Keep watching the directory for new files.
+    ///
+    /// When `false`, the endpoint ingests files present in the directory
+    /// when it is started and stops.  When `true`, the endpoint keeps
+    /// ingesting new files as they appear.
+    #[serde(default)]
+    follow: bool,
+
+    /// File that tracks ingested files.
+    ///
+    /// The endpoint appends the name of each file to this file once the file
+    /// has been ingested completely and the circuit has finished the clock
+    /// cycle that consumed its contents, and skips files listed there,
+    /// including across restarts.  A file whose ingestion or processing was
+    /// interrupted, e.g., by a crash, is ingested again from the start.
+    ///
+    /// Default: when this parameter is not specified, ingested files are only
+    /// tracked in memory.
+    progress_file: Option<String>,
+
+    /// Read buffer size.
+    ///
+    /// Default: when this parameter is not specified, a platform-specific
+    /// default is used.
+    buffer_size_bytes: Option<usize>,
+}
+
+/// Files claimed by the endpoint.
+struct Progress {
+    /// Files that have been ingested completely.
+    done: HashSet<String>,
+
+    /// Files dispatched to workers but not yet ingested and processed
+    /// completely, and files that failed to ingest; the latter are not
+    /// retried until the endpoint is restarted.
+    claimed: HashSet<String>,
+
+    /// Open `progress_file`, if any.
+    progress_file: Option<File>,
+}
+
+impl Progress {
+    fn new(progress_file: Option<&str>) -> AnyResult<Self> {
+        let mut done = HashSet::new();
+
+        let progress_file = match progress_file {
+            None => None,
+            Some(path) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .read(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|e| {
+                        AnyError::msg(format!("error opening progress file '{path}': {e}"))
+                    })?;
+                for line in BufReader::new(&file).lines() {
+                    let line = line?;
+                    if !line.is_empty() {
+                        done.insert(line);
+                    }
+                }
+                Some(file)
+            }
+        };
+
+        Ok(Self {
+            done,
+            claimed: HashSet::new(),
+            progress_file,
+        })
+    }
+
+    fn is_new(&self, name: &str) -> bool {
+        !self.done.contains(name) && !self.claimed.contains(name)
+    }
+
+    /// Record that file `name` has been ingested and processed completely.
+    fn complete(&mut self, name: &str) -> AnyResult<()> {
+        self.claimed.remove(name);
+        self.done.insert(name.to_string());
+
+        if let Some(file) = &mut self.progress_file {
+            writeln!(file, "{name}")?;
+            file.sync_data()?;
+        }
+
+        Ok(())
+    }
+}
+
+struct DirectoryInputEndpoint {
+    status: Arc<AtomicU32>,
+}
+
+impl DirectoryInputEndpoint {
+    fn new() -> Self {
+        Self {
+            status: Arc::new(AtomicU32::new(PipelineState::Paused as u32)),
+        }
+    }
+
+    fn connect(
+        &mut self,
+        config: DirectoryInputConfig,
+        consumer: Box<dyn InputConsumer>,
+    ) -> AnyResult<()> {
+        let pattern = Pattern::new(&config.pattern).map_err(|e| {
+            AnyError::msg(format!("invalid file pattern '{}': {e}", config.pattern))
+        })?;
+
+        // Fail early if the directory is not readable.
+        read_dir(&config.path).map_err(|e| {
+            AnyError::msg(format!("error reading directory '{}': {e}", config.path))
+        })?;
+
+        let progress = Arc::new(Mutex::new(Progress::new(config.progress_file.as_deref())?));
+        let config = Arc::new(config);
+        let (sender, receiver) = unbounded();
+
+        // Consumer used to report errors outside of worker threads: directory
+        // scan errors and failures to record progress.
+        let errors = Arc::new(Mutex::new(consumer.fork()));
+
+        for _ in 1..config.workers.max(1) {
+            let consumer = consumer.fork();
+            let receiver = receiver.clone();
+            let progress = progress.clone();
+            let errors = errors.clone();
+            let status = self.status.clone();
+            let config = config.clone();
+            spawn(move || {
+                Self::worker_thread(config, receiver, consumer, progress, errors, status)
+            });
+        }
+
+        let status = self.status.clone();
+        let worker_progress = progress.clone();
+        let worker_errors = errors.clone();
+        let worker_config = config.clone();
+        spawn(move || {
+            Self::worker_thread(
+                worker_config,
+                receiver,
+                consumer,
+                worker_progress,
+                worker_errors,
+                status,
+            )
+        });
+
+        let status = self.status.clone();
+        spawn(move || Self::scanner_thread(config, pattern, sender, progress, errors, status));
+
+        Ok(())
+    }
+
+    /// Periodically scan the directory and dispatch new files to workers.
+    ///
+    /// Drops `sender` and exits after the first scan unless `config.follow`
+    /// is set, which makes workers exit once they have processed all
+    /// dispatched files.
+    ///
+    /// A failed scan is a fatal error unless `config.follow` is set, in which
+    /// case the scanner reports the first error of each run of failed scans
+    /// and keeps retrying.
+    fn scanner_thread(
+        config: Arc<DirectoryInputConfig>,
+        pattern: Pattern,
+        sender: Sender<(String, PathBuf)>,
+        progress: Arc<Mutex<Progress>>,
+        errors: Arc<Mutex<Box<dyn InputConsumer>>>,
+        status: Arc<AtomicU32>,
+    ) {
+        let mut scan_failed = false;
+
+        loop {
+            match PipelineState::from_u32(status.load(Ordering::Acquire)) {
+                Some(PipelineState::Paused) => sleep(Duration::from_millis(SLEEP_MS)),
+                Some(PipelineState::Running) => {
+                    match Self::scan(&config, &pattern, &progress) {
+                        Ok(files) => {
+                            scan_failed = false;
+                            for file in files.into_iter() {
+                                if sender.send(file).is_err() {
+                                    return;
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            let error = AnyError::msg(format!(
+                                "error scanning directory '{}': {e}",
+                                config.path
+                            ));
+                            if !config.follow {
+                                errors.lock().unwrap().error(true, error);
+                                return;
+                            }
+                            // The directory may be temporarily unavailable;
+                            // try again later.
+                            if !scan_failed {
+                                errors.lock().unwrap().error(false, error);
+                            }
+                            scan_failed = true;
+                        }
+                    }
+
+                    if !config.follow {
+                        return;
+                    }
+                    sleep(Duration::from_millis(SLEEP_MS));
+                }
+                Some(PipelineState::Terminated) => return,
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    /// List new files in the directory in ingestion order and mark them as
+    /// claimed.
+    fn scan(
+        config: &DirectoryInputConfig,
+        pattern: &Pattern,
+        progress: &Mutex<Progress>,
+    ) -> AnyResult<Vec<(String, PathBuf)>> {
+        let options = MatchOptions {
+            require_literal_leading_dot: true,
+            ..MatchOptions::new()
+        };
+        let mut progress = progress.lock().unwrap();
+        let mut files = Vec::new();
+
+        for entry in read_dir(&config.path)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_file() {
+                continue;
+            }
+
+            let name = match entry.file_name().into_string() {
+                Ok(name) => name,
+                Err(_) => continue,
+            };
+
+            if pattern.matches_with(&name, options) && progress.is_new(&name) {
+                let mtime = match config.order {
+                    FileOrder::Name => SystemTime::UNIX_EPOCH,
+                    FileOrder::Mtime => entry.metadata()?.modified()?,
+                };
+                files.push((mtime, name, entry.path()));
+            }
+        }
+
+        files.sort();
+
+        Ok(files
+            .into_iter()
+            .map(|(_, name, path)| {
+                progress.claimed.insert(name.clone());
+                (name, path)
+            })
+            .collect())
+    }
+
+    fn worker_thread(
+        config: Arc<DirectoryInputConfig>,
+        receiver: Receiver<(String, PathBuf)>,
+        mut consumer: Box<dyn InputConsumer>,
+        progress: Arc<Mutex<Progress>>,
+        errors: Arc<Mutex<Box<dyn InputConsumer>>>,
+        status: Arc<AtomicU32>,
+    ) {
+        loop {
+            match PipelineState::from_u32(status.load(Ordering::Acquire)) {
+                Some(PipelineState::Paused) => sleep(Duration::from_millis(SLEEP_MS)),
+                Some(PipelineState::Running) => {
+                    match receiver.recv_timeout(Duration::from_millis(SLEEP_MS)) {
+                        Ok((name, path)) => {
+                            match Self::read_file(&config, &path, &mut *consumer, &status) {
+                                Ok(true) => {
+                                    consumer.eoi();
+                                    // Record progress only once the circuit has
+                                    // processed the contents of the file.
+                                    let progress = progress.clone();
+                                    let errors = errors.clone();
+                                    consumer.on_processed(Box::new(move || {
+                                        if let Err(e) = progress.lock().unwrap().complete(&name) {
+                                            errors.lock().unwrap().error(
+                                                true,
+                                                AnyError::msg(format!(
+                                                    "error recording progress of file '{name}': {e}"
+                                                )),
+                                            );
+                                        }
+                                    }));
+                                }
+                                // The endpoint has been disconnected.
+                                Ok(false) => return,
+                                Err(e) => consumer.error(
+                                    false,
+                                    AnyError::msg(format!("error reading file '{name}': {e}")),
+                                ),
+                            }
+                        }
+                        Err(e) if e.is_timeout() => {}
+                        // The scanner is done and all files have been processed.
+                        Err(_) => return,
+                    }
+                }
+                Some(PipelineState::Terminated) => return,
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    /// Push the contents of file `path` to `consumer`.
+    ///
+    /// Returns `false` if the endpoint was disconnected before reaching the
+    /// end of the file.
+    fn read_file(
+        config: &DirectoryInputConfig,
+        path: &Path,
+        consumer: &mut dyn InputConsumer,
+        status: &AtomicU32,
+    ) -> AnyResult<bool> {
+        let file = File::open(path)?;
+        let reader: Box<dyn Read> = match config.compression.for_path(path) {
+            Compression::Gzip => Box::new(MultiGzDecoder::new(file)),
+            Compression::Zstd => Box::new(zstd::stream::read::Decoder::new(file)?),
+            _ => Box::new(file),
+        };
+        let mut reader = match config.buffer_size_bytes {
+            Some(buffer_size) if buffer_size > 0 => BufReader::with_capacity(buffer_size, reader),
+            _ => BufReader::new(reader),
+        };
+
+        loop {
+            match PipelineState::from_u32(status.load(Ordering::Acquire)) {
+                Some(PipelineState::Paused) => sleep(Duration::from_millis(SLEEP_MS)),
+                Some(PipelineState::Running) => {
+                    let data = reader.fill_buf()?;
+                    if data.is_empty() {
+                        return Ok(true);
+                    }
+                    consumer.input(data);
+                    let len = data.len();
+                    reader.consume(len);
+                }
+                Some(PipelineState::Terminated) => return Ok(false),
+                _ => unreachable!(),
+            }
+        }
+    }
+}
+
+impl InputEndpoint for DirectoryInputEndpoint {
+    fn pause(&self) -> AnyResult<()> {
+        // Notify worker threads via the status flag.  A worker may send
+        // another buffer downstream before the flag takes effect.
+        self.status
+            .store(PipelineState::Paused as u32, Ordering::Release);
+        Ok(())
+    }
+
+    fn start(&self) -> AnyResult<()> {
+        self.status
+            .store(PipelineState::Running as u32, Ordering::Release);
+        Ok(())
+    }
+
+    fn disconnect(&self) {
+        self.status
+            .store(PipelineState::Terminated as u32, Ordering::Release);
+    }
+}
+
+impl Drop for DirectoryInputEndpoint {
+    fn drop(&mut self) {
+        self.disconnect();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::{mock_input_pipeline, wait, TestStruct};
+    use flate2::{write::GzEncoder, Compression};
+    use std::{
+        fs::{read_to_string, File},
+        io::Write,
+        path::Path,
+    };
+
+    fn record(id: u32) -> TestStruct {
+        TestStruct {
+            id,
+            b: id % 2 == 0,
+            i: Some(id as i64),
+            s: format!("foo{id}"),
+        }
+    }
+
+    fn csv(records: &[TestStruct]) -> Vec<u8> {
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(Vec::new());
+        for record in records.iter() {
+            writer.serialize(record).unwrap();
+        }
+        writer.into_inner().unwrap()
+    }
+
+    fn config(dir: &Path, extra: &str) -> String {
+        format!(
+            r#"
+transport:
+    name: directory
+    config:
+        path: {:?}
+        pattern: "*.csv*"
+        progress_file: {:?}
+{extra}
+format:
+    name: csv
+    config:
+        input_stream: test_input
+"#,
+            dir.to_str().unwrap(),
+            dir.join(".progress").to_str().unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_directory_nofollow() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let plain = [record(1), record(2)];
+        let gzipped = [record(3), record(4)];
+        let zstd_records = [record(5), record(6)];
+
+        File::create(dir.path().join("1.csv"))
+            .unwrap()
+            .write_all(&csv(&plain))
+            .unwrap();
+
+        let mut encoder = GzEncoder::new(
+            File::create(dir.path().join("2.csv.gz")).unwrap(),
+            Compression::default(),
+        );
+        encoder.write_all(&csv(&gzipped)).unwrap();
+        encoder.finish().unwrap();
+
+        File::create(dir.path().join("3.csv.zst"))
+            .unwrap()
+            .write_all(&zstd::encode_all(&csv(&zstd_records)[..], 0).unwrap())
+            .unwrap();
+
+        // Doesn't match the pattern.
+        File::create(dir.path().join("4.txt"))
+            .unwrap()
+            .write_all(&csv(&[record(7)]))
+            .unwrap();
+
+        let expected: Vec<TestStruct> = plain
+            .iter()
+            .chain(gzipped.iter())
+            .chain(zstd_records.iter())
+            .cloned()
+            .collect();
+
+        // Use a very small buffer size for testing.
+        let (endpoint, _consumer, zset) = mock_input_pipeline::<TestStruct>(
+            "test_input",
+            serde_yaml::from_str(&config(dir.path(), "        buffer_size_bytes: 5")).unwrap(),
+        );
+        endpoint.start().unwrap();
+
+        wait(|| zset.state().flushed.len() == expected.len(), None);
+        for (i, (val, polarity)) in zset.state().flushed.iter().enumerate() {
+            assert!(polarity);
+            assert_eq!(val, &expected[i]);
+        }
+
+        // Wait for all files to be recorded in the progress file.
+        wait(
+            || {
+                read_to_string(dir.path().join(".progress"))
+                    .unwrap()
+                    .lines()
+                    .count()
+                    == 3
+            },
+            None,
+        );
+        endpoint.disconnect();
+        drop(endpoint);
+
+        // Files ingested by the previous endpoint are skipped after a restart.
+        File::create(dir.path().join("5.csv"))
+            .unwrap()
+            .write_all(&csv(&[record(8)]))
+            .unwrap();
+
+        let (endpoint, consumer, zset) = mock_input_pipeline::<TestStruct>(
+            "test_input",
+            serde_yaml::from_str(&config(dir.path(), "")).unwrap(),
+        );
+        endpoint.start().unwrap();
+
+        wait(|| consumer.state().eoi, None);
+        assert_eq!(
+            zset.state()
+                .flushed
+                .iter()
+                .map(|(val, _)| val.clone())
+                .collect::<Vec<_>>(),
+            vec![record(8)]
+        );
+        endpoint.disconnect();
+    }
+
+    #[test]
+    fn test_directory_follow() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // Note: we use the default buffer size, so that each file is pushed
+        // to the parser in a single chunk, since forks of the mock consumer
+        // share the same parser.
+
+        let (endpoint, _consumer, zset) = mock_input_pipeline::<TestStruct>(
+            "test_input",
+            serde_yaml::from_str(&config(
+                dir.path(),
+                "        follow: true\n        workers: 3",
+            ))
+            .unwrap(),
+        );
+        endpoint.start().unwrap();
+
+        for i in 0..10 {
+            // Write the file under a name that doesn't match the pattern
+            // and rename it once it's complete.
+            let tmp_path = dir.path().join(format!("{i}.tmp"));
+            File::create(&tmp_path)
+                .unwrap()
+                .write_all(&csv(&[record(i)]))
+                .unwrap();
+            std::fs::rename(&tmp_path, dir.path().join(format!("{i}.csv"))).unwrap();
+        }
+
+        wait(|| zset.state().flushed.len() == 10, None);
+
+        // Files can be ingested in any order by multiple workers.
+        let mut ids: Vec<u32> = zset.state().flushed.iter().map(|(val, _)| val.id).collect();
+        ids.sort();
+        assert_eq!(ids, (0..10).collect::<Vec<_>>());
+
+        endpoint.disconnect();
+    }
+
+    #[test]
+    fn test_directory_scan_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("input");
+        std::fs::create_dir(&path).unwrap();
+
+        let (endpoint, consumer, zset) = mock_input_pipeline::<TestStruct>(
+            "test_input",
+            serde_yaml::from_str(&config(&path, "        follow: true")).unwrap(),
+        );
+        consumer.on_error(Some(Box::new(|_| {})));
+        endpoint.start().unwrap();
+
+        // Scan errors are reported, but don't stop the endpoint in follow mode.
+        std::fs::remove_dir_all(&path).unwrap();
+        wait(|| consumer.state().endpoint_error.is_some(), None);
+        assert!(consumer
+            .state()
+            .endpoint_error
+            .as_ref()
+            .unwrap()
+            .to_string()
+            .starts_with("error scanning directory"));
+
+        std::fs::create_dir(&path).unwrap();
+        File::create(path.join("1.csv"))
+            .unwrap()
+            .write_all(&csv(&[record(1)]))
+            .unwrap();
+        wait(|| zset.state().flushed.len() == 1, None);
+
+        endpoint.disconnect();
+    }
+}
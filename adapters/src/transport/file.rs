@@ -1,21 +1,29 @@
 use super::{InputConsumer, InputEndpoint, InputTransport, OutputEndpoint, OutputTransport};
 use crate::PipelineState;
 use anyhow::{Error as AnyError, Result as AnyResult};
-use crossbeam::sync::{Parker, Unparker};
+use crossbeam::{
+    channel::{bounded, Receiver, RecvTimeoutError, Sender},
+    sync::{Parker, Unparker},
+};
+use flate2::{write::GzEncoder, Compression as GzCompression};
+use log::error;
 use num_traits::FromPrimitive;
 use serde::Deserialize;
 use serde_yaml::Value as YamlValue;
 use std::{
     borrow::Cow,
-    fs::File,
+    ffi::OsString,
+    fs::{rename, File},
     io::{BufRead, BufReader, Result as IoResult, Write},
+    path::PathBuf,
     sync::{
         atomic::{AtomicU32, Ordering},
-        Arc,
+        Arc, Mutex,
     },
     thread::{sleep, spawn},
-    time::Duration,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
+use zstd::stream::write::Encoder as ZstdEncoder;
 
 const SLEEP_MS: u64 = 200;
 
@@ -170,6 +178,12 @@ impl Drop for FileInputEndpoint {
 }
 
 /// `OutputTransport` implementation that writes data to file.
+///
+/// By default, the endpoint writes all outputs to a single file.  It can
+/// also be configured to roll over to a new file when the current file
+/// reaches a size, age, or clock cycle limit.  Files are only rotated at
+/// output batch boundaries, so each output batch is contained in a single
+/// file.
 pub struct FileOutputTransport;
 
 impl OutputTransport for FileOutputTransport {
@@ -180,45 +194,409 @@ impl OutputTransport for FileOutputTransport {
     fn new_endpoint(
         &self,
         config: &YamlValue,
-        _async_error_callback: Box<dyn Fn(bool, AnyError) + Send + Sync>,
+        async_error_callback: Box<dyn Fn(bool, AnyError) + Send + Sync>,
+    ) -> AnyResult<Box<dyn OutputEndpoint>> {
+        let config = FileOutputConfig::deserialize(config)?;
+        let ep = FileOutputEndpoint::new(None, config, async_error_callback)?;
+
+        Ok(Box::new(ep))
+    }
+
+    fn new_named_endpoint(
+        &self,
+        endpoint_name: &str,
+        config: &YamlValue,
+        async_error_callback: Box<dyn Fn(bool, AnyError) + Send + Sync>,
     ) -> AnyResult<Box<dyn OutputEndpoint>> {
         let config = FileOutputConfig::deserialize(config)?;
-        let ep = FileOutputEndpoint::new(config)?;
+        let ep = FileOutputEndpoint::new(Some(endpoint_name), config, async_error_callback)?;
 
         Ok(Box::new(ep))
     }
 }
 
+/// Output file compression.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum FileCompression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
 #[derive(Deserialize)]
 struct FileOutputConfig {
     /// File path.
+    ///
+    /// The path can contain the following placeholders, which are
+    /// substituted when a file is created:
+    ///
+    /// * `{endpoint}` - endpoint name.  Only available for endpoints created
+    ///   by the controller, which knows the endpoint name.
+    /// * `{step}` - the clock cycle whose outputs are written to the file
+    ///   first (0 if the file is created before the first output batch).
+    /// * `{timestamp}` - file creation time in milliseconds since UNIX epoch.
+    /// * `{seq}` - sequence number of the file, starting from 0.
+    ///
+    /// When file rotation is enabled, the path must contain `{step}` or
+    /// `{seq}` to produce unique file names; the endpoint fails to initialize
+    /// otherwise.  `{timestamp}` alone is not sufficient, since several files
+    /// can be created within the same millisecond.
     path: String,
+
+    /// Start a new file once the current file contains at least this many
+    /// bytes (before compression).
+    max_file_bytes: Option<u64>,
+
+    /// Start a new file once the current file is this many seconds old.
+    ///
+    /// The file is closed (and renamed if `atomic_rename` is set) when it
+    /// reaches this age, even if the endpoint receives no more outputs.
+    max_file_secs: Option<u64>,
+
+    /// Start a new file once the current file contains this many output
+    /// batches.
+    max_file_batches: Option<u64>,
+
+    /// Output file compression: `none` (default), `gzip`, or `zstd`.
+    #[serde(default)]
+    compression: FileCompression,
+
+    /// Write each file under a temporary name and rename it to its final name
+    /// once it is complete, so that downstream consumers only ever observe
+    /// complete files.
+    ///
+    /// The temporary name is the final file name prefixed with `.` and
+    /// suffixed with `.tmp` in the same directory.  Defaults to `false`.
+    #[serde(default)]
+    atomic_rename: bool,
+}
+
+impl FileOutputConfig {
+    fn rotation_enabled(&self) -> bool {
+        self.max_file_bytes.is_some()
+            || self.max_file_secs.is_some()
+            || self.max_file_batches.is_some()
+    }
+}
+
+/// Output file writer that optionally compresses the data.
+enum FileWriter {
+    Plain(File),
+    Gzip(GzEncoder<File>),
+    Zstd(ZstdEncoder<'static, File>),
+}
+
+impl FileWriter {
+    fn new(file: File, compression: FileCompression) -> IoResult<Self> {
+        Ok(match compression {
+            FileCompression::None => Self::Plain(file),
+            FileCompression::Gzip => Self::Gzip(GzEncoder::new(file, GzCompression::default())),
+            FileCompression::Zstd => Self::Zstd(ZstdEncoder::new(file, 0)?),
+        })
+    }
+
+    fn write_all(&mut self, buffer: &[u8]) -> IoResult<()> {
+        match self {
+            Self::Plain(file) => file.write_all(buffer),
+            Self::Gzip(encoder) => encoder.write_all(buffer),
+            Self::Zstd(encoder) => encoder.write_all(buffer),
+        }
+    }
+
+    /// Write compression trailer, if any, and flush the file to disk.
+    fn finish(self) -> IoResult<()> {
+        let file = match self {
+            Self::Plain(file) => file,
+            Self::Gzip(encoder) => encoder.finish()?,
+            Self::Zstd(encoder) => encoder.finish()?,
+        };
+        file.sync_all()
+    }
+}
+
+/// The file currently being written.
+struct OutputFile {
+    writer: FileWriter,
+
+    /// Path the file is written to.
+    path: PathBuf,
+
+    /// Final path of the file if it is written under a temporary name.
+    final_path: Option<PathBuf>,
+
+    created: Instant,
+    num_bytes: u64,
+    num_batches: u64,
+}
+
+impl OutputFile {
+    /// Finish writing the file and rename it to its final name.
+    fn close(self) -> IoResult<()> {
+        self.writer.finish()?;
+        if let Some(final_path) = &self.final_path {
+            rename(&self.path, final_path)?;
+        }
+        Ok(())
+    }
+}
+
+/// State of the file output endpoint, shared with the thread that closes
+/// files that reach their age limit.
+struct FileOutputInner {
+    /// Endpoint name, if known.
+    endpoint_name: Option<String>,
+    config: FileOutputConfig,
+
+    /// The file currently being written.  Files are created lazily when the
+    /// first buffer after a rotation is received.
+    file: Option<OutputFile>,
+
+    /// Sequence number of the next file.
+    seq: u64,
+
+    /// Clock cycle of the current output batch.
+    step: u64,
+
+    /// `true` between `batch_start` and `batch_end`.  Files are never closed
+    /// in the middle of a batch.
+    in_batch: bool,
+}
+
+impl FileOutputInner {
+    /// Substitute placeholders in the path template.
+    fn file_path(&self) -> PathBuf {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+
+        PathBuf::from(
+            self.config
+                .path
+                .replace(
+                    "{endpoint}",
+                    self.endpoint_name.as_deref().unwrap_or_default(),
+                )
+                .replace("{step}", &self.step.to_string())
+                .replace("{timestamp}", &timestamp.to_string())
+                .replace("{seq}", &self.seq.to_string()),
+        )
+    }
+
+    /// Create the next output file.
+    fn open(&mut self) -> IoResult<()> {
+        let final_path = self.file_path();
+        let (path, final_path) = if self.config.atomic_rename {
+            let mut tmp_name = OsString::from(".");
+            tmp_name.push(final_path.file_name().unwrap_or_default());
+            tmp_name.push(".tmp");
+            (final_path.with_file_name(tmp_name), Some(final_path))
+        } else {
+            (final_path, None)
+        };
+
+        let file = File::create(&path)?;
+        self.seq += 1;
+
+        self.file = Some(OutputFile {
+            writer: FileWriter::new(file, self.config.compression)?,
+            path,
+            final_path,
+            created: Instant::now(),
+            num_bytes: 0,
+            num_batches: 0,
+        });
+        Ok(())
+    }
+
+    /// Close the current file, if any.
+    fn close(&mut self) -> AnyResult<()> {
+        if let Some(file) = self.file.take() {
+            let path = file.path.clone();
+            file.close().map_err(|e| {
+                AnyError::msg(format!(
+                    "error closing output file '{}': {e}",
+                    path.display()
+                ))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// `true` if the current file has reached its age limit.
+    fn expired(&self) -> bool {
+        match (&self.file, self.config.max_file_secs) {
+            (Some(file), Some(max)) => file.created.elapsed().as_secs() >= max,
+            _ => false,
+        }
+    }
+
+    /// Close the current file if it has reached any of the configured limits.
+    fn maybe_rotate(&mut self) -> AnyResult<()> {
+        let rotate = match &self.file {
+            None => false,
+            Some(file) => {
+                self.config
+                    .max_file_bytes
+                    .map_or(false, |max| file.num_bytes >= max)
+                    || self
+                        .config
+                        .max_file_batches
+                        .map_or(false, |max| file.num_batches >= max)
+                    || self.expired()
+            }
+        };
+
+        if rotate {
+            self.close()?;
+        }
+
+        Ok(())
+    }
 }
 
 struct FileOutputEndpoint {
-    file: File,
+    inner: Arc<Mutex<FileOutputInner>>,
+
+    /// Dropping this sender stops the thread that closes files that reach
+    /// their age limit.
+    _timer: Option<Sender<()>>,
 }
 
 impl FileOutputEndpoint {
-    fn new(config: FileOutputConfig) -> IoResult<Self> {
-        let file = File::create(config.path)?;
-        Ok(Self { file })
+    fn new(
+        endpoint_name: Option<&str>,
+        config: FileOutputConfig,
+        async_error_callback: Box<dyn Fn(bool, AnyError) + Send + Sync>,
+    ) -> AnyResult<Self> {
+        // Without a unique name, every new file would overwrite the previous
+        // one.  Timestamps are not unique, as files can be rotated more than
+        // once per millisecond.
+        if config.rotation_enabled()
+            && !["{step}", "{seq}"]
+                .iter()
+                .any(|placeholder| config.path.contains(placeholder))
+        {
+            return Err(AnyError::msg(format!(
+                "output file path '{}' must contain '{{step}}' or '{{seq}}' when file rotation is enabled",
+                config.path
+            )));
+        }
+
+        if endpoint_name.is_none() && config.path.contains("{endpoint}") {
+            return Err(AnyError::msg(format!(
+                "output file path '{}' contains '{{endpoint}}', but the endpoint name is not known",
+                config.path
+            )));
+        }
+
+        let max_file_secs = config.max_file_secs;
+        let mut inner = FileOutputInner {
+            endpoint_name: endpoint_name.map(str::to_string),
+            config,
+            file: None,
+            seq: 0,
+            step: 0,
+            in_batch: false,
+        };
+
+        // Without rotation, the output file is created upfront, so it exists
+        // even if the endpoint never receives any outputs.
+        if !inner.config.rotation_enabled() {
+            inner.open()?;
+        }
+
+        let inner = Arc::new(Mutex::new(inner));
+
+        // Close files that reach their age limit even if no more output
+        // batches arrive.
+        let timer = max_file_secs.map(|_| {
+            let (sender, receiver) = bounded(0);
+            let inner = inner.clone();
+            spawn(move || Self::timer_thread(inner, receiver, async_error_callback));
+            sender
+        });
+
+        Ok(Self {
+            inner,
+            _timer: timer,
+        })
+    }
+
+    fn timer_thread(
+        inner: Arc<Mutex<FileOutputInner>>,
+        receiver: Receiver<()>,
+        async_error_callback: Box<dyn Fn(bool, AnyError) + Send + Sync>,
+    ) {
+        while let Err(RecvTimeoutError::Timeout) =
+            receiver.recv_timeout(Duration::from_millis(SLEEP_MS))
+        {
+            let mut inner = inner.lock().unwrap();
+            if !inner.in_batch && inner.expired() {
+                if let Err(e) = inner.close() {
+                    async_error_callback(false, e);
+                }
+            }
+        }
     }
 }
 
 impl OutputEndpoint for FileOutputEndpoint {
     fn push_buffer(&mut self, buffer: &[u8]) -> AnyResult<()> {
-        self.file.write_all(buffer)?;
+        let mut inner = self.inner.lock().unwrap();
+        if inner.file.is_none() {
+            inner.open()?;
+        }
+
+        let file = inner.file.as_mut().unwrap();
+        file.writer.write_all(buffer)?;
+        file.num_bytes += buffer.len() as u64;
+        Ok(())
+    }
+
+    fn batch_start(&mut self, step: u64) -> AnyResult<()> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.step = step;
+        inner.in_batch = true;
+        inner.maybe_rotate()
+    }
+
+    fn batch_end(&mut self) -> AnyResult<()> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.in_batch = false;
+        if let Some(file) = &mut inner.file {
+            file.num_batches += 1;
+        }
         Ok(())
     }
 }
 
+impl Drop for FileOutputEndpoint {
+    fn drop(&mut self) {
+        if let Err(e) = self.inner.lock().unwrap().close() {
+            error!("{e}");
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use crate::test::{mock_input_pipeline, wait};
+    use super::FileOutputTransport;
+    use crate::{
+        test::{mock_input_pipeline, wait},
+        OutputTransport,
+    };
     use csv::WriterBuilder as CsvWriterBuilder;
+    use flate2::read::GzDecoder;
     use serde::{Deserialize, Serialize};
-    use std::{io::Write, thread::sleep, time::Duration};
+    use std::{
+        fs::{read_dir, File},
+        io::{Read, Write},
+        thread::sleep,
+        time::Duration,
+    };
     use tempfile::NamedTempFile;
 
     #[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
@@ -362,7 +740,7 @@ format:
             || {
                 let state = consumer.state();
                 // println!("result: {:?}", state.parser_result);
-                state.parser_result.is_some() && state.parser_result.as_ref().unwrap().is_err()
+                state.parser_result.is_some() && !state.parser_result.as_ref().unwrap().1.is_empty()
             },
             None,
         );
@@ -372,4 +750,165 @@ format:
 
         endpoint.disconnect();
     }
+
+    #[test]
+    fn test_rolling_file_output() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let config_str = format!(
+            r#"
+path: {:?}
+max_file_batches: 2
+compression: gzip
+atomic_rename: true
+"#,
+            dir.path().join("{endpoint}-{seq}-{step}.csv.gz")
+        );
+
+        let mut endpoint = FileOutputTransport
+            .new_named_endpoint(
+                "test_output",
+                &serde_yaml::from_str(&config_str).unwrap(),
+                Box::new(|_, _| {}),
+            )
+            .unwrap();
+
+        let file_names = || {
+            let mut names: Vec<String> = read_dir(dir.path())
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                .collect();
+            names.sort();
+            names
+        };
+
+        // Files are created lazily.
+        assert!(file_names().is_empty());
+
+        for step in 1..=5 {
+            endpoint.batch_start(step).unwrap();
+            endpoint
+                .push_buffer(format!("step {step}\n").as_bytes())
+                .unwrap();
+            endpoint.batch_end().unwrap();
+        }
+
+        // The last file is incomplete and still has a temporary name.
+        assert_eq!(
+            file_names(),
+            vec![
+                ".test_output-2-5.csv.gz.tmp".to_string(),
+                "test_output-0-1.csv.gz".to_string(),
+                "test_output-1-3.csv.gz".to_string(),
+            ]
+        );
+
+        drop(endpoint);
+
+        let expected = [
+            ("test_output-0-1.csv.gz", "step 1\nstep 2\n"),
+            ("test_output-1-3.csv.gz", "step 3\nstep 4\n"),
+            ("test_output-2-5.csv.gz", "step 5\n"),
+        ];
+        assert_eq!(
+            file_names(),
+            expected
+                .iter()
+                .map(|(name, _)| name.to_string())
+                .collect::<Vec<_>>()
+        );
+
+        for (name, contents) in expected.iter() {
+            let mut actual = String::new();
+            GzDecoder::new(File::open(dir.path().join(name)).unwrap())
+                .read_to_string(&mut actual)
+                .unwrap();
+            assert_eq!(&actual, contents);
+        }
+    }
+
+    #[test]
+    fn test_file_output_max_file_secs() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let config_str = format!(
+            r#"
+path: {:?}
+max_file_secs: 1
+atomic_rename: true
+"#,
+            dir.path().join("{seq}.csv")
+        );
+
+        let mut endpoint = FileOutputTransport
+            .new_endpoint(
+                &serde_yaml::from_str(&config_str).unwrap(),
+                Box::new(|_, _| panic!("unexpected error")),
+            )
+            .unwrap();
+
+        endpoint.batch_start(1).unwrap();
+        endpoint.push_buffer(b"step 1\n").unwrap();
+        endpoint.batch_end().unwrap();
+
+        // The file is closed and renamed once it expires, without waiting
+        // for the next batch.
+        wait(
+            || {
+                read_dir(dir.path())
+                    .unwrap()
+                    .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                    .collect::<Vec<_>>()
+                    == vec!["0.csv".to_string()]
+            },
+            None,
+        );
+
+        let mut contents = String::new();
+        File::open(dir.path().join("0.csv"))
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "step 1\n");
+    }
+
+    #[test]
+    fn test_file_output_invalid_path() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // Rotated files need unique names.
+        let config_str = format!(
+            "path: {:?}\nmax_file_batches: 1",
+            dir.path().join("{endpoint}.csv")
+        );
+        assert!(FileOutputTransport
+            .new_named_endpoint(
+                "test_output",
+                &serde_yaml::from_str(&config_str).unwrap(),
+                Box::new(|_, _| {}),
+            )
+            .is_err());
+
+        // Timestamps are not unique.
+        let config_str = format!(
+            "path: {:?}\nmax_file_batches: 1",
+            dir.path().join("{timestamp}.csv")
+        );
+        assert!(FileOutputTransport
+            .new_named_endpoint(
+                "test_output",
+                &serde_yaml::from_str(&config_str).unwrap(),
+                Box::new(|_, _| {}),
+            )
+            .is_err());
+
+        // The endpoint name is only known to endpoints created by name.
+        let config_str = format!("path: {:?}", dir.path().join("{endpoint}.csv"));
+        assert!(FileOutputTransport
+            .new_endpoint(
+                &serde_yaml::from_str(&config_str).unwrap(),
+                Box::new(|_, _| {}),
+            )
+            .is_err());
+    }
 }
@@ -0,0 +1,547 @@
+//! Input transport that receives data over HTTP.
+
+use super::{InputConsumer, InputEndpoint, InputTransport};
+use crate::PipelineState;
+use actix_web::{
+    http::Method,
+    rt,
+    web::{self, Bytes, Data as WebData, PayloadConfig},
+    App, HttpRequest, HttpResponse, HttpServer,
+};
+use anyhow::{Error as AnyError, Result as AnyResult};
+use num_traits::FromPrimitive;
+use serde::Deserialize;
+use serde_yaml::Value as YamlValue;
+use std::{
+    borrow::Cow,
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    io::Read,
+    net::TcpListener,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    thread::{sleep, spawn},
+    time::{Duration, Instant},
+};
+
+const SLEEP_MS: u64 = 200;
+
+/// Default value of `HttpInputConfig::Poll::interval_ms`.
+const fn default_poll_interval_ms() -> u64 {
+    1_000
+}
+
+/// Default value of `HttpInputConfig::Listen::max_body_bytes`.
+const fn default_max_body_bytes() -> usize {
+    16 * 1024 * 1024
+}
+
+/// `InputTransport` implementation that receives data over HTTP.
+///
+/// The endpoint either runs an HTTP server that accepts data pushed by
+/// clients or periodically polls a URL.  In both modes, the bodies of
+/// consecutive requests or responses are concatenated into a single byte
+/// stream fed to the parser, so each of them should contain complete
+/// records.
+pub struct HttpInputTransport;
+
+impl InputTransport for HttpInputTransport {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("http")
+    }
+
+    fn new_endpoint(
+        &self,
+        config: &YamlValue,
+        consumer: Box<dyn InputConsumer>,
+    ) -> AnyResult<Box<dyn InputEndpoint>> {
+        let config = HttpInputConfig::deserialize(config)?;
+        let mut ep = HttpInputEndpoint::new();
+        ep.connect(config, consumer)?;
+        Ok(Box::new(ep))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+enum HttpInputConfig {
+    /// Listen for `POST` or `PUT` requests on `address` (`host:port`) and
+    /// push the body of each request to the parser.
+    ///
+    /// While the endpoint is paused, requests are rejected with status
+    /// `503 Service Unavailable`, so that clients can retry later.  Requests
+    /// with bodies larger than `max_body_bytes` (default: 16 MiB) are
+    /// rejected with status `413 Payload Too Large`.
+    Listen {
+        address: String,
+        #[serde(default = "default_max_body_bytes")]
+        max_body_bytes: usize,
+    },
+
+    /// Send a `GET` request to `url` every `interval_ms` milliseconds
+    /// (default: 1000) and push the body of each successful response whose
+    /// contents changed since the previous one to the parser.
+    ///
+    /// The endpoint sends conditional requests (`If-None-Match`,
+    /// `If-Modified-Since`) when the server supplies `ETag` or
+    /// `Last-Modified` headers, and otherwise compares the body with the
+    /// previous one.  A changed body is pushed in full: records it shares
+    /// with the previous version are inserted again, and records that no
+    /// longer appear in it are not deleted.
+    ///
+    /// Failed requests are reported as non-fatal endpoint errors and retried
+    /// after the next interval.
+    Poll {
+        url: String,
+        #[serde(default = "default_poll_interval_ms")]
+        interval_ms: u64,
+    },
+}
+
+/// State shared by the request handlers of a listening endpoint.
+struct ListenState {
+    consumer: Mutex<Box<dyn InputConsumer>>,
+    status: Arc<AtomicU32>,
+}
+
+/// Polls a URL, skipping responses that haven't changed since the previous
+/// poll.
+struct Poller {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    /// Hash of the last body pushed to the parser.
+    last_body_hash: Option<u64>,
+}
+
+impl Poller {
+    fn new(url: String) -> Self {
+        Self {
+            url,
+            etag: None,
+            last_modified: None,
+            last_body_hash: None,
+        }
+    }
+
+    /// Returns the body of the response if it changed since the last poll.
+    fn poll(&mut self) -> AnyResult<Option<Vec<u8>>> {
+        let mut request = ureq::get(&self.url);
+        if let Some(etag) = &self.etag {
+            request = request.set("If-None-Match", etag);
+        }
+        if let Some(last_modified) = &self.last_modified {
+            request = request.set("If-Modified-Since", last_modified);
+        }
+
+        let response = request.call()?;
+        if response.status() == 304 {
+            return Ok(None);
+        }
+        self.etag = response.header("ETag").map(str::to_owned);
+        self.last_modified = response.header("Last-Modified").map(str::to_owned);
+
+        let mut body = Vec::new();
+        response.into_reader().read_to_end(&mut body)?;
+
+        let mut hasher = DefaultHasher::new();
+        body.hash(&mut hasher);
+        let hash = hasher.finish();
+        if self.last_body_hash == Some(hash) {
+            return Ok(None);
+        }
+        self.last_body_hash = Some(hash);
+
+        Ok(Some(body))
+    }
+}
+
+struct HttpInputEndpoint {
+    status: Arc<AtomicU32>,
+}
+
+impl HttpInputEndpoint {
+    fn new() -> Self {
+        Self {
+            status: Arc::new(AtomicU32::new(PipelineState::Paused as u32)),
+        }
+    }
+
+    fn connect(
+        &mut self,
+        config: HttpInputConfig,
+        consumer: Box<dyn InputConsumer>,
+    ) -> AnyResult<()> {
+        let status = self.status.clone();
+
+        match config {
+            HttpInputConfig::Listen {
+                address,
+                max_body_bytes,
+            } => {
+                let listener = TcpListener::bind(&address)
+                    .map_err(|e| AnyError::msg(format!("failed to listen on '{address}': {e}")))?;
+                let state = WebData::new(ListenState {
+                    consumer: Mutex::new(consumer),
+                    status,
+                });
+                let _worker = spawn(move || Self::server_thread(listener, state, max_body_bytes));
+            }
+            HttpInputConfig::Poll { url, interval_ms } => {
+                let interval = Duration::from_millis(interval_ms);
+                let _worker = spawn(move || {
+                    Self::poller_thread(Poller::new(url), interval, consumer, status)
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run an HTTP server on `listener` until the endpoint is disconnected.
+    fn server_thread(listener: TcpListener, state: WebData<ListenState>, max_body_bytes: usize) {
+        let server_state = state.clone();
+        let result = rt::System::new().block_on(async move {
+            let server = HttpServer::new(move || {
+                App::new()
+                    .app_data(PayloadConfig::new(max_body_bytes))
+                    .app_data(server_state.clone())
+                    .default_service(web::to(Self::handle_request))
+            })
+            .workers(1)
+            .disable_signals()
+            .listen(listener)?
+            .run();
+
+            // Stop the server once the endpoint is disconnected.
+            let server_handle = server.handle();
+            let status = state.status.clone();
+            rt::spawn(async move {
+                while PipelineState::from_u32(status.load(Ordering::Acquire))
+                    != Some(PipelineState::Terminated)
+                {
+                    rt::time::sleep(Duration::from_millis(SLEEP_MS)).await;
+                }
+                server_handle.stop(false).await
+            });
+
+            server.await
+        });
+
+        if let Err(e) = result {
+            state
+                .consumer
+                .lock()
+                .unwrap()
+                .error(true, AnyError::from(e));
+        }
+    }
+
+    async fn handle_request(
+        request: HttpRequest,
+        body: Bytes,
+        state: WebData<ListenState>,
+    ) -> HttpResponse {
+        if !matches!(*request.method(), Method::POST | Method::PUT) {
+            return HttpResponse::MethodNotAllowed()
+                .body("only POST and PUT requests are supported");
+        }
+        if PipelineState::from_u32(state.status.load(Ordering::Acquire))
+            != Some(PipelineState::Running)
+        {
+            return HttpResponse::ServiceUnavailable().body("endpoint is paused");
+        }
+
+        state.consumer.lock().unwrap().input(&body);
+        HttpResponse::Ok().finish()
+    }
+
+    fn poller_thread(
+        mut poller: Poller,
+        interval: Duration,
+        mut consumer: Box<dyn InputConsumer>,
+        status: Arc<AtomicU32>,
+    ) {
+        let mut last_poll: Option<Instant> = None;
+
+        loop {
+            match PipelineState::from_u32(status.load(Ordering::Acquire)) {
+                Some(PipelineState::Paused) => sleep(Duration::from_millis(SLEEP_MS)),
+                Some(PipelineState::Running) => {
+                    let timeout = last_poll
+                        .map(|last_poll| interval.saturating_sub(last_poll.elapsed()))
+                        .unwrap_or(Duration::ZERO);
+                    if !timeout.is_zero() {
+                        // Sleep in small increments to react to status changes.
+                        sleep(timeout.min(Duration::from_millis(SLEEP_MS)));
+                        continue;
+                    }
+
+                    last_poll = Some(Instant::now());
+                    match poller.poll() {
+                        Ok(Some(body)) => consumer.input(&body),
+                        Ok(None) => {}
+                        Err(e) => consumer.error(false, e),
+                    }
+                }
+                Some(PipelineState::Terminated) => return,
+                _ => unreachable!(),
+            }
+        }
+    }
+}
+
+impl InputEndpoint for HttpInputEndpoint {
+    fn pause(&self) -> AnyResult<()> {
+        self.status
+            .store(PipelineState::Paused as u32, Ordering::Release);
+        Ok(())
+    }
+
+    fn start(&self) -> AnyResult<()> {
+        self.status
+            .store(PipelineState::Running as u32, Ordering::Release);
+        Ok(())
+    }
+
+    fn disconnect(&self) {
+        self.status
+            .store(PipelineState::Terminated as u32, Ordering::Release);
+    }
+}
+
+impl Drop for HttpInputEndpoint {
+    fn drop(&mut self) {
+        self.disconnect();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::{mock_input_pipeline, wait, TestStruct};
+    use actix_web::{
+        dev::ServerHandle,
+        rt,
+        web::{self, Data as WebData},
+        App, HttpRequest, HttpResponse, HttpServer,
+    };
+    use std::{
+        net::TcpListener,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Mutex,
+        },
+        thread::{spawn, JoinHandle},
+    };
+
+    fn test_data() -> (Vec<TestStruct>, String) {
+        let data = vec![
+            TestStruct {
+                id: 1,
+                b: true,
+                i: Some(10),
+                s: "foo".to_string(),
+            },
+            TestStruct {
+                id: 2,
+                b: false,
+                i: Some(-10),
+                s: "bar".to_string(),
+            },
+        ];
+
+        (data, "1,true,10,foo\n2,false,-10,bar\n".to_string())
+    }
+
+    fn free_address() -> String {
+        TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_http_listen() {
+        let (test_data, csv) = test_data();
+        let address = free_address();
+
+        let config_str = format!(
+            r#"
+transport:
+    name: http
+    config:
+        mode: listen
+        address: {address:?}
+        max_body_bytes: 1000
+format:
+    name: csv
+    config:
+        input_stream: test_input
+"#
+        );
+
+        let (endpoint, _consumer, zset) = mock_input_pipeline::<TestStruct>(
+            "test_input",
+            serde_yaml::from_str(&config_str).unwrap(),
+        );
+
+        let url = format!("http://{address}/");
+
+        // Requests are rejected while the endpoint is paused.
+        match ureq::post(&url).send_string(&csv) {
+            Err(ureq::Error::Status(503, _)) => {}
+            _ => panic!("expected 503 response from a paused endpoint"),
+        }
+        assert!(zset.state().flushed.is_empty());
+
+        endpoint.start().unwrap();
+        ureq::post(&url).send_string(&csv).unwrap();
+        ureq::put(&url).send_string(&csv).unwrap();
+
+        wait(|| zset.state().flushed.len() == 2 * test_data.len(), None);
+        for (i, (val, polarity)) in zset.state().flushed.iter().enumerate() {
+            assert!(polarity);
+            assert_eq!(val, &test_data[i % test_data.len()]);
+        }
+
+        // Bodies over `max_body_bytes` are rejected.
+        match ureq::post(&url).send_string(&csv.repeat(100)) {
+            Err(ureq::Error::Status(413, _)) => {}
+            _ => panic!("expected 413 response to an oversized request"),
+        }
+        assert_eq!(zset.state().flushed.len(), 2 * test_data.len());
+
+        endpoint.disconnect();
+    }
+
+    /// In-process HTTP server that serves `body`, optionally with an `ETag`
+    /// derived from `version`.
+    struct TestServerState {
+        body: Mutex<(String, u64)>,
+        etag: bool,
+        requests: AtomicUsize,
+    }
+
+    async fn serve(request: HttpRequest, state: WebData<TestServerState>) -> HttpResponse {
+        state.requests.fetch_add(1, Ordering::AcqRel);
+        let (body, version) = state.body.lock().unwrap().clone();
+
+        if !state.etag {
+            return HttpResponse::Ok().body(body);
+        }
+
+        let etag = format!("\"{version}\"");
+        let if_none_match = request
+            .headers()
+            .get("If-None-Match")
+            .and_then(|value| value.to_str().ok());
+        if if_none_match == Some(etag.as_str()) {
+            HttpResponse::NotModified().finish()
+        } else {
+            HttpResponse::Ok().insert_header(("ETag", etag)).body(body)
+        }
+    }
+
+    fn start_test_server(
+        state: WebData<TestServerState>,
+    ) -> (String, ServerHandle, JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(state.clone())
+                .default_service(web::to(serve))
+        })
+        .workers(1)
+        .disable_signals()
+        .listen(listener)
+        .unwrap()
+        .run();
+        let handle = server.handle();
+        let server_thread = spawn(move || rt::System::new().block_on(server).unwrap());
+
+        (address.to_string(), handle, server_thread)
+    }
+
+    fn test_http_poll(etag: bool) {
+        let (test_data, csv) = test_data();
+
+        let state = WebData::new(TestServerState {
+            body: Mutex::new((csv, 1)),
+            etag,
+            requests: AtomicUsize::new(0),
+        });
+        let (address, server_handle, server_thread) = start_test_server(state.clone());
+
+        let config_str = format!(
+            r#"
+transport:
+    name: http
+    config:
+        mode: poll
+        url: "http://{address}/data"
+        interval_ms: 10
+format:
+    name: csv
+    config:
+        input_stream: test_input
+"#
+        );
+
+        let (endpoint, _consumer, zset) = mock_input_pipeline::<TestStruct>(
+            "test_input",
+            serde_yaml::from_str(&config_str).unwrap(),
+        );
+        endpoint.start().unwrap();
+
+        // Unchanged contents are pushed only once.
+        wait(|| state.requests.load(Ordering::Acquire) >= 5, None);
+        wait(|| zset.state().flushed.len() == test_data.len(), None);
+        assert_eq!(zset.state().flushed.len(), test_data.len());
+
+        // New contents are pushed on the next poll.
+        *state.body.lock().unwrap() = ("3,true,30,baz\n".to_string(), 2);
+        wait(|| zset.state().flushed.len() == test_data.len() + 1, None);
+        let requests = state.requests.load(Ordering::Acquire);
+        wait(
+            || state.requests.load(Ordering::Acquire) >= requests + 5,
+            None,
+        );
+        assert_eq!(zset.state().flushed.len(), test_data.len() + 1);
+
+        let flushed = zset.state().flushed.clone();
+        for (i, (val, polarity)) in flushed.iter().take(test_data.len()).enumerate() {
+            assert!(polarity);
+            assert_eq!(val, &test_data[i]);
+        }
+        assert_eq!(
+            flushed[test_data.len()],
+            (
+                TestStruct {
+                    id: 3,
+                    b: true,
+                    i: Some(30),
+                    s: "baz".to_string(),
+                },
+                true
+            )
+        );
+
+        endpoint.disconnect();
+        rt::System::new().block_on(server_handle.stop(true));
+        server_thread.join().unwrap();
+    }
+
+    #[test]
+    fn test_http_poll_etag() {
+        test_http_poll(true);
+    }
+
+    #[test]
+    fn test_http_poll_no_etag() {
+        test_http_poll(false);
+    }
+}
@@ -1,28 +1,40 @@
 use super::{refine_kafka_error, KafkaLogLevel};
-use crate::{InputConsumer, InputEndpoint, InputTransport, PipelineState};
+use crate::{InputConsumer, InputEndpoint, InputMetadata, InputTransport, PipelineState};
 use anyhow::{Error as AnyError, Result as AnyResult};
 use num_traits::FromPrimitive;
 use rdkafka::{
     config::{FromClientConfigAndContext, RDKafkaLogLevel},
-    consumer::{BaseConsumer, Consumer, ConsumerContext, Rebalance, RebalanceProtocol},
+    consumer::{
+        base_consumer::PartitionQueue, BaseConsumer, Consumer, ConsumerContext, Rebalance,
+        RebalanceProtocol,
+    },
     error::{KafkaError, KafkaResult},
-    ClientConfig, ClientContext, Message,
+    message::{BorrowedMessage, Headers},
+    ClientConfig, ClientContext, Message, Offset, TopicPartitionList,
 };
 use serde::Deserialize;
 use serde_yaml::Value as YamlValue;
 use std::{
     borrow::Cow,
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     sync::{
-        atomic::{AtomicU32, Ordering},
+        atomic::{AtomicBool, AtomicU32, Ordering},
         Arc, Mutex, Weak,
     },
-    thread::spawn,
+    thread::{sleep, spawn, JoinHandle},
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 const POLL_TIMEOUT: Duration = Duration::from_millis(100);
 
+/// Timeout of metadata requests issued while positioning newly assigned
+/// partitions.
+const METADATA_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Number of attempts to seek a newly assigned partition to its start
+/// position.
+const SEEK_ATTEMPTS: usize = 50;
+
 /// On startup, the endpoint waits to join the consumer group.
 /// This constant defines the default wait timeout.
 const fn default_group_join_timeout_secs() -> u32 {
@@ -76,6 +88,107 @@ pub struct KafkaInputConfig {
     /// consumer group during initialization.
     #[serde(default = "default_group_join_timeout_secs")]
     group_join_timeout_secs: u32,
+
+    /// Position to start reading from.
+    ///
+    /// When not specified, the start position is determined by the
+    /// `auto.offset.reset` option and the offsets committed by the consumer
+    /// group, if any.
+    start_from: Option<KafkaStartFrom>,
+
+    /// Message metadata to pass to the parser along with each message.
+    ///
+    /// A list of `key`, `topic`, `partition`, `offset`, `timestamp`, and
+    /// `header:<name>` items.  When non-empty, the payload of each message is
+    /// passed to the parser as a chunk of complete records along with the
+    /// values of the listed items (see [`InputMetadata`]), which the format
+    /// maps to columns, e.g., via the `metadata_columns` option of the CSV
+    /// format.  Keys and header values are converted to strings, replacing
+    /// invalid UTF-8 sequences.  Missing keys and headers have no value.
+    #[serde(default)]
+    metadata: Vec<KafkaMetadataField>,
+}
+
+/// Start position of a Kafka input endpoint.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KafkaStartFrom {
+    /// Start from the earliest available message in partitions that don't
+    /// have a committed offset.  Sets `auto.offset.reset` to `earliest`.
+    Earliest,
+
+    /// Start from the end of partitions that don't have a committed offset,
+    /// i.e., only receive messages produced after the endpoint has joined
+    /// the consumer group.  Sets `auto.offset.reset` to `latest`.
+    Latest,
+
+    /// Start from the first message whose timestamp, in milliseconds since
+    /// the UNIX epoch, is greater than or equal to the specified value.
+    ///
+    /// Applies to each partition the first time it is assigned to the
+    /// endpoint, overriding committed offsets.
+    Timestamp(i64),
+
+    /// Start from explicit per-partition offsets.
+    ///
+    /// Applies to each listed partition the first time it is assigned to
+    /// the endpoint, overriding committed offsets.  Partitions that are not
+    /// listed are read from the earliest available message.
+    Offsets(Vec<KafkaPartitionOffset>),
+}
+
+/// Start offset of a single partition.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct KafkaPartitionOffset {
+    pub topic: String,
+    pub partition: i32,
+    pub offset: i64,
+}
+
+/// Message metadata item that can be added to each record.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub enum KafkaMetadataField {
+    Key,
+    Topic,
+    Partition,
+    Offset,
+    Timestamp,
+    Header(String),
+}
+
+impl KafkaMetadataField {
+    /// Name of the item in [`InputMetadata`].
+    fn name(&self) -> Cow<'static, str> {
+        match self {
+            Self::Key => Cow::Borrowed("key"),
+            Self::Topic => Cow::Borrowed("topic"),
+            Self::Partition => Cow::Borrowed("partition"),
+            Self::Offset => Cow::Borrowed("offset"),
+            Self::Timestamp => Cow::Borrowed("timestamp"),
+            Self::Header(header) => Cow::Owned(format!("header:{header}")),
+        }
+    }
+}
+
+impl TryFrom<String> for KafkaMetadataField {
+    type Error = String;
+
+    fn try_from(field: String) -> Result<Self, Self::Error> {
+        match field.as_str() {
+            "key" => Ok(Self::Key),
+            "topic" => Ok(Self::Topic),
+            "partition" => Ok(Self::Partition),
+            "offset" => Ok(Self::Offset),
+            "timestamp" => Ok(Self::Timestamp),
+            _ => match field.strip_prefix("header:") {
+                Some(header) if !header.is_empty() => Ok(Self::Header(header.to_string())),
+                _ => Err(format!(
+                    "invalid Kafka metadata field '{field}': expected one of 'key', 'topic', 'partition', 'offset', 'timestamp', or 'header:<name>'"
+                )),
+            },
+        }
+    }
 }
 
 impl KafkaInputConfig {
@@ -87,7 +200,7 @@ impl KafkaInputConfig {
             .entry(option.to_string())
             .or_insert_with(|| val.to_string());
         if option_val != val {
-            Err(AnyError::msg("cannot override '{option}' option: the Kafka transport adapter sets this option to '{val}'"))?;
+            Err(AnyError::msg(format!("cannot override '{option}' option: the Kafka transport adapter sets this option to '{val}'")))?;
         }
         Ok(())
     }
@@ -117,6 +230,16 @@ impl KafkaInputConfig {
         self.set_option_if_missing("group.id", &group_id);
         self.set_option_if_missing("enable.partition.eof", "false");
 
+        match &self.start_from {
+            None => {}
+            Some(KafkaStartFrom::Latest) => self.enforce_option("auto.offset.reset", "latest")?,
+            // When seeking to a timestamp or an explicit offset, messages
+            // fetched before the seek takes effect are filtered out by
+            // offset, which only works if reading starts before the target
+            // offset.
+            Some(_) => self.enforce_option("auto.offset.reset", "earliest")?,
+        }
+
         Ok(())
     }
 }
@@ -132,10 +255,15 @@ impl KafkaInputEndpoint {
 /// Client context used to intercept rebalancing events.
 ///
 /// `rdkafka` allows consumers to register callbacks invoked on various
-/// Kafka events.  We need to intercept rebalancing events, when the
-/// consumer gets assigned new partitions, since these new partitions are
-/// may not be in the paused/unpaused state required by the endpoint,
-/// so we may need to pause or unpause them as appropriate.
+/// Kafka events.  We need to intercept rebalancing events for two reasons:
+///
+/// * When the consumer gets assigned new partitions, we split each of them
+///   into a separate queue processed by a dedicated worker thread, and
+///   stop these threads when the partitions are revoked.
+///
+/// * Newly assigned partitions may not be in the paused/unpaused state
+///   required by the endpoint, so we may need to pause or unpause them as
+///   appropriate.
 ///
 /// See https://github.com/edenhill/librdkafka/issues/1849 for a discussion
 /// of the pause/unpause behavior.
@@ -156,27 +284,75 @@ impl KafkaInputContext {
 impl ClientContext for KafkaInputContext {}
 
 impl ConsumerContext for KafkaInputContext {
+    fn pre_rebalance(&self, rebalance: &Rebalance<'_>) {
+        // Signal workers of revoked partitions to stop without waiting for
+        // them, so that a worker blocked under backpressure doesn't stall
+        // the rebalance.
+        if let Rebalance::Revoke(partitions) = rebalance {
+            if let Some(endpoint) = self.endpoint.lock().unwrap().upgrade() {
+                endpoint.stop_partition_workers(partitions);
+            }
+        }
+    }
+
     fn post_rebalance(&self, rebalance: &Rebalance<'_>) {
-        // println!("Rebalance: {rebalance:?}");
-        if matches!(rebalance, Rebalance::Assign(_)) {
+        if let Rebalance::Assign(partitions) = rebalance {
             if let Some(endpoint) = self.endpoint.lock().unwrap().upgrade() {
-                if endpoint.state() == PipelineState::Running {
-                    // TODO: handle errors by storing them inside `endpoint`
-                    // for later processing in `poll`.
-                    let _ = endpoint.resume_partitions();
+                KafkaInputEndpointInner::start_partition_workers(&endpoint, partitions);
+
+                let result = if endpoint.state() == PipelineState::Running {
+                    endpoint.resume_partitions()
                 } else {
-                    let _ = endpoint.pause_partitions();
+                    endpoint.pause_partitions()
+                };
+                if let Err(e) = result {
+                    endpoint.report_error(e);
                 }
             }
         }
-
-        // println!("Rebalance complete");
     }
 }
 
+/// Worker thread that processes messages from a single partition.
+///
+/// Workers of revoked partitions are signaled to stop, but not joined in the
+/// rebalance callback, since they may be blocked pushing data downstream.
+/// If the partition is assigned to the endpoint again, the new worker waits
+/// for the old one to exit before processing any messages.
+struct PartitionWorker {
+    /// Signals the worker thread to exit.
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
 struct KafkaInputEndpointInner {
     state: AtomicU32,
-    kafka_consumer: BaseConsumer<KafkaInputContext>,
+    kafka_consumer: Arc<BaseConsumer<KafkaInputContext>>,
+
+    /// Consumer that receives messages polled from the main consumer queue
+    /// and errors; partition workers use consumers forked from it.
+    consumer: Mutex<Box<dyn InputConsumer>>,
+
+    start_from: Option<KafkaStartFrom>,
+    metadata: Vec<KafkaMetadataField>,
+
+    /// Partitions that have been assigned to the endpoint at least once.
+    ///
+    /// `start_from` only applies to the first assignment of each partition;
+    /// after a rebalance, reading resumes from the committed offset.
+    positioned: Mutex<BTreeSet<(String, i32)>>,
+
+    /// Start offsets of partitions positioned by timestamp or explicit
+    /// offset.  Messages below the start offset fetched before the seek
+    /// takes effect are skipped.
+    start_offsets: Mutex<BTreeMap<(String, i32), i64>>,
+
+    /// Worker threads of currently assigned partitions.
+    workers: Mutex<BTreeMap<(String, i32), PartitionWorker>>,
+
+    /// Worker threads of revoked partitions that have been signaled to stop,
+    /// but may not have exited yet.
+    stopped_workers: Mutex<BTreeMap<(String, i32), JoinHandle<()>>>,
 }
 
 impl KafkaInputEndpointInner {
@@ -202,7 +378,14 @@ impl KafkaInputEndpointInner {
 
         let endpoint = Arc::new(Self {
             state: AtomicU32::new(PipelineState::Paused as u32),
-            kafka_consumer,
+            kafka_consumer: Arc::new(kafka_consumer),
+            consumer: Mutex::new(consumer),
+            start_from: config.start_from.clone(),
+            metadata: config.metadata.clone(),
+            positioned: Mutex::new(BTreeSet::new()),
+            start_offsets: Mutex::new(BTreeMap::new()),
+            workers: Mutex::new(BTreeMap::new()),
+            stopped_workers: Mutex::new(BTreeMap::new()),
         });
 
         *endpoint.kafka_consumer.context().endpoint.lock().unwrap() = Arc::downgrade(&endpoint);
@@ -235,7 +418,7 @@ impl KafkaInputEndpointInner {
         }
 
         let endpoint_clone = endpoint.clone();
-        spawn(move || Self::worker_thread(endpoint_clone, consumer));
+        spawn(move || Self::worker_thread(endpoint_clone));
 
         Ok(endpoint)
     }
@@ -282,7 +465,284 @@ impl KafkaInputEndpointInner {
         refine_kafka_error(self.kafka_consumer.client(), e)
     }
 
-    fn worker_thread(endpoint: Arc<KafkaInputEndpointInner>, mut consumer: Box<dyn InputConsumer>) {
+    /// Report an error to the endpoint's consumer.  Returns `true` if the
+    /// error is fatal.
+    fn report_error(&self, e: KafkaError) -> bool {
+        let (fatal, e) = self.refine_error(e);
+        self.consumer.lock().unwrap().error(fatal, e);
+        fatal
+    }
+
+    /// Compute start offsets of newly assigned `partitions` according to
+    /// `start_from`.
+    ///
+    /// Returns offsets of the subset of partitions that are assigned to the
+    /// endpoint for the first time and must be positioned explicitly.
+    fn start_offsets(
+        &self,
+        partitions: &TopicPartitionList,
+    ) -> KafkaResult<BTreeMap<(String, i32), i64>> {
+        let mut positioned = self.positioned.lock().unwrap();
+        let new_partitions = partitions
+            .elements()
+            .iter()
+            .map(|elem| (elem.topic().to_string(), elem.partition()))
+            .filter(|partition| positioned.insert(partition.clone()))
+            .collect::<Vec<_>>();
+
+        let mut offsets = BTreeMap::new();
+
+        match &self.start_from {
+            Some(KafkaStartFrom::Timestamp(timestamp)) if !new_partitions.is_empty() => {
+                let mut timestamps = TopicPartitionList::new();
+                for (topic, partition) in new_partitions.iter() {
+                    timestamps.add_partition_offset(
+                        topic,
+                        *partition,
+                        Offset::Offset(*timestamp),
+                    )?;
+                }
+
+                for elem in self
+                    .kafka_consumer
+                    .offsets_for_times(timestamps, METADATA_TIMEOUT)?
+                    .elements()
+                {
+                    let offset = match elem.offset() {
+                        Offset::Offset(offset) => offset,
+                        // No messages at or after `timestamp`: start from
+                        // the end of the partition.
+                        _ => {
+                            self.kafka_consumer
+                                .fetch_watermarks(elem.topic(), elem.partition(), METADATA_TIMEOUT)?
+                                .1
+                        }
+                    };
+                    offsets.insert((elem.topic().to_string(), elem.partition()), offset);
+                }
+            }
+            Some(KafkaStartFrom::Offsets(start_offsets)) => {
+                for start_offset in start_offsets.iter() {
+                    let partition = (start_offset.topic.clone(), start_offset.partition);
+                    if new_partitions.contains(&partition) {
+                        offsets.insert(partition, start_offset.offset);
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        Ok(offsets)
+    }
+
+    /// Start worker threads for newly assigned `partitions`.
+    fn start_partition_workers(endpoint: &Arc<Self>, partitions: &TopicPartitionList) {
+        let offsets = match endpoint.start_offsets(partitions) {
+            Ok(offsets) => offsets,
+            Err(e) => {
+                endpoint.report_error(e);
+                BTreeMap::new()
+            }
+        };
+        endpoint
+            .start_offsets
+            .lock()
+            .unwrap()
+            .extend(offsets.clone());
+
+        let mut workers = endpoint.workers.lock().unwrap();
+
+        for elem in partitions.elements() {
+            let partition = (elem.topic().to_string(), elem.partition());
+            if workers.contains_key(&partition) {
+                continue;
+            }
+
+            // The worker of a previous assignment of the partition may still
+            // be running and own the partition queue.  In this case, the new
+            // worker splits the queue once the previous worker has exited,
+            // so that the partition is never processed by two threads at the
+            // same time.
+            //
+            // If the partition cannot be split, its messages keep arriving
+            // to the main queue.
+            let previous = endpoint.stopped_workers.lock().unwrap().remove(&partition);
+            let queue = match previous {
+                Some(_) => None,
+                None => match endpoint
+                    .kafka_consumer
+                    .split_partition_queue(elem.topic(), elem.partition())
+                {
+                    Some(queue) => Some(queue),
+                    None => continue,
+                },
+            };
+
+            let stop = Arc::new(AtomicBool::new(false));
+            let stop_clone = stop.clone();
+            let endpoint_clone = endpoint.clone();
+            let consumer = endpoint.consumer.lock().unwrap().fork();
+            let start_offset = offsets.get(&partition).cloned();
+            let (topic, partition_id) = partition.clone();
+
+            let handle = spawn(move || {
+                if let Some(previous) = previous {
+                    let _ = previous.join();
+                }
+                let queue = match queue.or_else(|| {
+                    endpoint_clone
+                        .kafka_consumer
+                        .split_partition_queue(&topic, partition_id)
+                }) {
+                    Some(queue) => queue,
+                    None => return,
+                };
+                Self::partition_thread(
+                    endpoint_clone,
+                    queue,
+                    consumer,
+                    stop_clone,
+                    topic,
+                    partition_id,
+                    start_offset,
+                )
+            });
+
+            workers.insert(partition, PartitionWorker { stop, handle });
+        }
+    }
+
+    /// Signal worker threads of revoked `partitions` to stop.
+    fn stop_partition_workers(&self, partitions: &TopicPartitionList) {
+        let mut workers = self.workers.lock().unwrap();
+        let mut stopped_workers = self.stopped_workers.lock().unwrap();
+
+        for elem in partitions.elements() {
+            let partition = (elem.topic().to_string(), elem.partition());
+            if let Some(worker) = workers.remove(&partition) {
+                worker.stop.store(true, Ordering::Release);
+                stopped_workers.insert(partition, worker.handle);
+            }
+        }
+    }
+
+    /// Worker thread that processes messages from a single partition.
+    fn partition_thread(
+        endpoint: Arc<KafkaInputEndpointInner>,
+        queue: PartitionQueue<KafkaInputContext>,
+        mut consumer: Box<dyn InputConsumer>,
+        stop: Arc<AtomicBool>,
+        topic: String,
+        partition: i32,
+        start_offset: Option<i64>,
+    ) {
+        if let Some(offset) = start_offset {
+            // The partition may not be ready to seek immediately after
+            // assignment.  If seeking keeps failing, we still skip messages
+            // below `offset`.
+            let mut seek_error = None;
+            for _ in 0..SEEK_ATTEMPTS {
+                if stop.load(Ordering::Acquire) || endpoint.state() == PipelineState::Terminated {
+                    return;
+                }
+                match endpoint.kafka_consumer.seek(
+                    &topic,
+                    partition,
+                    Offset::Offset(offset),
+                    POLL_TIMEOUT,
+                ) {
+                    Ok(()) => {
+                        seek_error = None;
+                        break;
+                    }
+                    Err(e) => {
+                        seek_error = Some(e);
+                        sleep(POLL_TIMEOUT);
+                    }
+                }
+            }
+            if let Some(e) = seek_error {
+                consumer.error(
+                    false,
+                    AnyError::msg(format!(
+                        "failed to seek partition {partition} of topic '{topic}' to offset {offset} after {SEEK_ATTEMPTS} attempts; reading from the current position and skipping messages below offset {offset}: {e}"
+                    )),
+                );
+            }
+        }
+
+        loop {
+            if stop.load(Ordering::Acquire) || endpoint.state() == PipelineState::Terminated {
+                return;
+            }
+
+            // Paused partitions don't receive any messages, so we can keep
+            // polling while the endpoint is paused.
+            match queue.poll(POLL_TIMEOUT) {
+                None => {}
+                Some(Err(e)) => {
+                    let (fatal, e) = endpoint.refine_error(e);
+                    consumer.error(fatal, e);
+                    if fatal {
+                        return;
+                    }
+                }
+                Some(Ok(message)) => {
+                    if start_offset.map_or(true, |offset| message.offset() >= offset) {
+                        endpoint.process_message(&message, &mut *consumer);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Push the payload of `message`, along with requested metadata, to
+    /// `consumer`.
+    fn process_message(&self, message: &BorrowedMessage, consumer: &mut dyn InputConsumer) {
+        let payload = match message.payload() {
+            Some(payload) => payload,
+            None => return,
+        };
+
+        if self.metadata.is_empty() {
+            consumer.input(payload);
+        } else {
+            consumer.input_with_metadata(payload, &self.message_metadata(message));
+        }
+    }
+
+    /// Extract `self.metadata` items from `message`.
+    fn message_metadata(&self, message: &BorrowedMessage) -> InputMetadata {
+        let mut metadata = InputMetadata::new();
+
+        for field in self.metadata.iter() {
+            let value = match field {
+                KafkaMetadataField::Key => message
+                    .key()
+                    .map(|key| String::from_utf8_lossy(key).into_owned()),
+                KafkaMetadataField::Topic => Some(message.topic().to_string()),
+                KafkaMetadataField::Partition => Some(message.partition().to_string()),
+                KafkaMetadataField::Offset => Some(message.offset().to_string()),
+                KafkaMetadataField::Timestamp => {
+                    message.timestamp().to_millis().map(|ts| ts.to_string())
+                }
+                KafkaMetadataField::Header(name) => message
+                    .headers()
+                    .and_then(|headers| {
+                        headers
+                            .iter()
+                            .find(|header| header.key == name.as_str())
+                            .and_then(|header| header.value)
+                    })
+                    .map(|value| String::from_utf8_lossy(value).into_owned()),
+            };
+            metadata.push(&field.name(), value);
+        }
+
+        metadata
+    }
+
+    fn worker_thread(endpoint: Arc<KafkaInputEndpointInner>) {
         let mut actual_state = PipelineState::Paused;
         loop {
             // endpoint.debug_consumer();
@@ -291,7 +751,7 @@ impl KafkaInputEndpointInner {
                     actual_state = PipelineState::Paused;
                     if let Err(e) = endpoint.pause_partitions() {
                         let (_fatal, e) = endpoint.refine_error(e);
-                        consumer.error(true, e);
+                        endpoint.consumer.lock().unwrap().error(true, e);
                         return;
                     }
                 }
@@ -299,11 +759,29 @@ impl KafkaInputEndpointInner {
                     actual_state = PipelineState::Running;
                     if let Err(e) = endpoint.resume_partitions() {
                         let (_fatal, e) = endpoint.refine_error(e);
-                        consumer.error(true, e);
+                        endpoint.consumer.lock().unwrap().error(true, e);
                         return;
                     };
                 }
-                PipelineState::Terminated => return,
+                PipelineState::Terminated => {
+                    // Partition workers exit on their own; wait for them
+                    // so that no data is pushed after disconnect returns
+                    // control to the pipeline.
+                    let workers = std::mem::take(&mut *endpoint.workers.lock().unwrap());
+                    for worker in workers.values() {
+                        worker.stop.store(true, Ordering::Release);
+                    }
+                    let stopped_workers =
+                        std::mem::take(&mut *endpoint.stopped_workers.lock().unwrap());
+                    for handle in workers
+                        .into_values()
+                        .map(|worker| worker.handle)
+                        .chain(stopped_workers.into_values())
+                    {
+                        let _ = handle.join();
+                    }
+                    return;
+                }
                 _ => {}
             }
 
@@ -313,24 +791,30 @@ impl KafkaInputEndpointInner {
             //
             // `POLL_TIMEOUT` makes sure that the thread will periodically
             // check for termination and pause commands.
+            //
+            // Messages of partitions split into their own queues are
+            // processed by partition workers.  Only messages received
+            // before the split arrive here.
             match endpoint.kafka_consumer.poll(POLL_TIMEOUT) {
                 None => {
                     // println!("poll returned None");
                 }
                 Some(Err(e)) => {
                     // println!("poll returned error");
-                    let (fatal, e) = endpoint.refine_error(e);
-                    consumer.error(fatal, e);
-                    if fatal {
+                    if endpoint.report_error(e) {
                         return;
                     }
                 }
                 Some(Ok(message)) => {
-                    // println!("received {} bytes", message.payload().unwrap().len());
-                    // message.payload().map(|payload| consumer.input(payload));
-
-                    if let Some(payload) = message.payload() {
-                        consumer.input(payload);
+                    let start_offset = endpoint
+                        .start_offsets
+                        .lock()
+                        .unwrap()
+                        .get(&(message.topic().to_string(), message.partition()))
+                        .cloned();
+                    if start_offset.map_or(true, |offset| message.offset() >= offset) {
+                        let mut consumer = endpoint.consumer.lock().unwrap();
+                        endpoint.process_message(&message, &mut **consumer);
                     }
                 }
             }
@@ -68,6 +68,24 @@ pub struct KafkaOutputConfig {
     /// Defaults to 1000.
     #[serde(default = "default_max_inflight_messages")]
     max_inflight_messages: u32,
+
+    /// Send each output record as a separate message.
+    ///
+    /// When `true`, the message key is the encoded key of the record and the
+    /// message value is the encoded record, or null (a tombstone) if the
+    /// record was deleted.  Combined with the `upserts` delivery mode over an
+    /// indexed view, this allows a log-compacted topic to hold the current
+    /// contents of the view.  When `false`, each message contains a buffer of
+    /// encoded records.
+    ///
+    /// Record weights are not encoded: a record inserted with a weight
+    /// greater than 1 is sent as a single message, so multiplicities are
+    /// lost.  The output format must support keyed output (e.g., `csv`);
+    /// otherwise connecting the endpoint fails.
+    ///
+    /// Defaults to `false`.
+    #[serde(default)]
+    message_per_record: bool,
 }
 
 /// Producer context object used to handle async delivery notifications from
@@ -117,6 +135,7 @@ struct KafkaOutputEndpoint {
     kafka_producer: ThreadedProducer<KafkaOutputContext>,
     topic: String,
     max_inflight_messages: u32,
+    message_per_record: bool,
     parker: Parker,
 }
 
@@ -148,15 +167,14 @@ impl KafkaOutputEndpoint {
             kafka_producer,
             topic: config.topic,
             max_inflight_messages: config.max_inflight_messages,
+            message_per_record: config.message_per_record,
             parker,
         })
     }
-}
 
-impl OutputEndpoint for KafkaOutputEndpoint {
-    fn push_buffer(&mut self, buffer: &[u8]) -> AnyResult<()> {
-        // Wait for the number of unacknowledged messages to drop
-        // below `max_inflight_messages`.
+    /// Wait for the number of unacknowledged messages to drop
+    /// below `max_inflight_messages`.
+    fn wait_for_inflight_messages(&self) {
         while self.kafka_producer.in_flight_count() as i64 > self.max_inflight_messages as i64 {
             // FIXME: It appears that the delivery callback can be invoked before the
             // in-flight counter is decremented, in which case we may never get
@@ -166,6 +184,12 @@ impl OutputEndpoint for KafkaOutputEndpoint {
             // thread _after_ the in-flight counter has been decremented.
             self.parker.park_timeout(OUTPUT_POLLING_INTERVAL);
         }
+    }
+}
+
+impl OutputEndpoint for KafkaOutputEndpoint {
+    fn push_buffer(&mut self, buffer: &[u8]) -> AnyResult<()> {
+        self.wait_for_inflight_messages();
 
         let record = <BaseRecord<(), [u8], ()>>::to(&self.topic).payload(buffer);
         self.kafka_producer
@@ -173,4 +197,21 @@ impl OutputEndpoint for KafkaOutputEndpoint {
             .map_err(|(err, _record)| err)?;
         Ok(())
     }
+
+    fn is_keyed(&self) -> bool {
+        self.message_per_record
+    }
+
+    fn push_key(&mut self, key: &[u8], val: Option<&[u8]>) -> AnyResult<()> {
+        self.wait_for_inflight_messages();
+
+        let mut record = <BaseRecord<[u8], [u8], ()>>::to(&self.topic).key(key);
+        if let Some(val) = val {
+            record = record.payload(val);
+        }
+        self.kafka_producer
+            .send(record)
+            .map_err(|(err, _record)| err)?;
+        Ok(())
+    }
 }
@@ -6,9 +6,17 @@ use crate::{
     },
     Controller, ControllerConfig,
 };
+use csv::ReaderBuilder as CsvReaderBuilder;
 use log::LevelFilter;
 use proptest::prelude::*;
-use std::{thread::sleep, time::Duration};
+use rdkafka::{
+    consumer::{BaseConsumer, Consumer},
+    ClientConfig, Message,
+};
+use std::{
+    thread::sleep,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 /// Wait to receive all records in `data` in the same order.
 fn wait_for_output_ordered(zset: &MockDeZSet<TestStruct>, data: &[Vec<TestStruct>]) {
@@ -215,3 +223,306 @@ outputs:
         drop(kafka_resources);
     }
 }
+
+/// Generate `num_batches` batches of `batch_size` distinct records.
+fn test_batches(num_batches: u32, batch_size: u32) -> Vec<Vec<TestStruct>> {
+    (0..num_batches)
+        .map(|batch| {
+            (0..batch_size)
+                .map(|i| {
+                    let id = batch * batch_size + i;
+                    TestStruct {
+                        id,
+                        b: id % 2 == 0,
+                        i: Some(id as i64),
+                        s: format!("s{id}"),
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn test_kafka_input_partitions() {
+    let _ = log::set_logger(&TEST_LOGGER);
+    log::set_max_level(LevelFilter::Debug);
+
+    let kafka_resources = KafkaResources::create_topics(&[("input_partitions_test_topic", 32)]);
+
+    let config_str = r#"
+transport:
+    name: kafka
+    config:
+        bootstrap.servers: "localhost"
+        start_from: earliest
+        topics: [input_partitions_test_topic]
+        log_level: debug
+format:
+    name: csv
+    config:
+        input_stream: test_input
+"#;
+
+    let (endpoint, _consumer, zset) =
+        mock_input_pipeline::<TestStruct>("test_input", serde_yaml::from_str(config_str).unwrap());
+
+    let data = test_batches(500, 100);
+    let producer = TestProducer::new();
+    producer.send_to_topic(&data, "input_partitions_test_topic");
+
+    // Each partition is processed by its own worker thread.  Records are
+    // delivered exactly once, but not in the original order.
+    endpoint.start().unwrap();
+    wait_for_output_unordered(&zset, &data);
+    zset.reset();
+
+    // Pause/resume applies to all partitions.
+    endpoint.pause().unwrap();
+    sleep(Duration::from_millis(1000));
+    producer.send_to_topic(&data, "input_partitions_test_topic");
+    sleep(Duration::from_millis(1000));
+    assert_eq!(zset.state().flushed.len(), 0);
+
+    endpoint.start().unwrap();
+    wait_for_output_unordered(&zset, &data);
+
+    endpoint.disconnect();
+    drop(kafka_resources);
+}
+
+#[test]
+fn test_kafka_input_start_offsets() {
+    let _ = log::set_logger(&TEST_LOGGER);
+    log::set_max_level(LevelFilter::Debug);
+
+    let kafka_resources = KafkaResources::create_topics(&[("input_offsets_test_topic", 1)]);
+
+    let producer = TestProducer::new();
+    for (id, payload) in [(1, "true,1,foo"), (2, "false,2,bar"), (3, "true,3,\"a,b\"")] {
+        producer.send_message(
+            payload,
+            Some(&id.to_string()),
+            &[],
+            "input_offsets_test_topic",
+        );
+    }
+
+    // Skip the first message; take the `id` column from message keys.
+    let config_str = r#"
+transport:
+    name: kafka
+    config:
+        bootstrap.servers: "localhost"
+        start_from:
+            offsets:
+                - topic: input_offsets_test_topic
+                  partition: 0
+                  offset: 1
+        metadata: [key]
+        topics: [input_offsets_test_topic]
+        log_level: debug
+format:
+    name: csv
+    config:
+        input_stream: test_input
+        metadata_columns: [key]
+"#;
+
+    let (endpoint, _consumer, zset) =
+        mock_input_pipeline::<TestStruct>("test_input", serde_yaml::from_str(config_str).unwrap());
+    endpoint.start().unwrap();
+
+    wait_for_output_ordered(
+        &zset,
+        &[vec![
+            TestStruct {
+                id: 2,
+                b: false,
+                i: Some(2),
+                s: "bar".to_string(),
+            },
+            TestStruct {
+                id: 3,
+                b: true,
+                i: Some(3),
+                s: "a,b".to_string(),
+            },
+        ]],
+    );
+
+    endpoint.disconnect();
+    drop(kafka_resources);
+}
+
+#[test]
+fn test_kafka_input_start_timestamp() {
+    let _ = log::set_logger(&TEST_LOGGER);
+    log::set_max_level(LevelFilter::Debug);
+
+    let kafka_resources = KafkaResources::create_topics(&[("input_timestamp_test_topic", 1)]);
+
+    let producer = TestProducer::new();
+    producer.send_message(
+        "true,1,foo",
+        None,
+        &[("id", "1")],
+        "input_timestamp_test_topic",
+    );
+
+    sleep(Duration::from_millis(100));
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    sleep(Duration::from_millis(100));
+
+    producer.send_message(
+        "false,2,bar",
+        None,
+        &[("trace", "x"), ("id", "2")],
+        "input_timestamp_test_topic",
+    );
+
+    // Only receive messages produced after `timestamp`; take the `id`
+    // column from the `id` header.
+    let config_str = format!(
+        r#"
+transport:
+    name: kafka
+    config:
+        bootstrap.servers: "localhost"
+        start_from:
+            timestamp: {timestamp}
+        metadata: ["header:id"]
+        topics: [input_timestamp_test_topic]
+        log_level: debug
+format:
+    name: csv
+    config:
+        input_stream: test_input
+        metadata_columns: ["header:id"]
+"#
+    );
+
+    let (endpoint, _consumer, zset) =
+        mock_input_pipeline::<TestStruct>("test_input", serde_yaml::from_str(&config_str).unwrap());
+    endpoint.start().unwrap();
+
+    let expected = vec![TestStruct {
+        id: 2,
+        b: false,
+        i: Some(2),
+        s: "bar".to_string(),
+    }];
+    wait_for_output_ordered(&zset, &[expected]);
+
+    // Make sure no older messages show up later.
+    sleep(Duration::from_millis(1000));
+    assert_eq!(zset.state().flushed.len(), 1);
+
+    endpoint.disconnect();
+    drop(kafka_resources);
+}
+
+#[test]
+fn test_kafka_output_message_per_record() {
+    let _ = log::set_logger(&TEST_LOGGER);
+    log::set_max_level(LevelFilter::Debug);
+
+    let kafka_resources = KafkaResources::create_topics(&[
+        ("per_record_test_input_topic", 1),
+        ("per_record_test_output_topic", 1),
+    ]);
+
+    let config_str = r#"
+inputs:
+    test_input1:
+        transport:
+            name: kafka
+            config:
+                bootstrap.servers: "localhost"
+                start_from: earliest
+                topics: [per_record_test_input_topic]
+                log_level: debug
+        format:
+            name: csv
+            config:
+                input_stream: test_input1
+outputs:
+    test_output2:
+        stream: test_output1
+        transport:
+            name: kafka
+            config:
+                bootstrap.servers: "localhost"
+                topic: per_record_test_output_topic
+                message_per_record: true
+        format:
+            name: csv
+"#;
+
+    let (circuit, catalog) = test_circuit(4);
+    let config: ControllerConfig = serde_yaml::from_str(config_str).unwrap();
+    let controller = Controller::with_config(
+        circuit,
+        catalog,
+        &config,
+        Box::new(|e| panic!("error: {e}")),
+    )
+    .unwrap();
+
+    let data = test_batches(10, 100);
+    let producer = TestProducer::new();
+    producer.send_to_topic(&data, "per_record_test_input_topic");
+    controller.start();
+
+    let kafka_consumer = ClientConfig::new()
+        .set("bootstrap.servers", "localhost")
+        .set("auto.offset.reset", "earliest")
+        .set(
+            "group.id",
+            &format!(
+                "per_record_test_group_{}",
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_millis()
+            ),
+        )
+        .create::<BaseConsumer>()
+        .unwrap();
+    kafka_consumer
+        .subscribe(&["per_record_test_output_topic"])
+        .unwrap();
+
+    // Each record arrives in a separate message keyed by the record itself.
+    let mut received = Vec::new();
+    let num_records: usize = data.iter().map(Vec::len).sum();
+    while received.len() < num_records {
+        if let Some(message) = kafka_consumer.poll(Duration::from_millis(100)) {
+            let message = message.unwrap();
+            let key = message.key().unwrap();
+            let payload = message.payload().unwrap();
+            assert_eq!(key, payload);
+
+            let mut reader = CsvReaderBuilder::new()
+                .has_headers(false)
+                .from_reader(payload);
+            let records = reader
+                .deserialize::<TestStruct>()
+                .map(Result::unwrap)
+                .collect::<Vec<_>>();
+            assert_eq!(records.len(), 1);
+            received.extend(records);
+        }
+    }
+
+    let mut expected = data.into_iter().flatten().collect::<Vec<_>>();
+    expected.sort();
+    received.sort();
+    assert_eq!(received, expected);
+
+    controller.stop().unwrap();
+    drop(kafka_resources);
+}
@@ -1,50 +1,113 @@
 use anyhow::{Error as AnyError, Result as AnyResult};
+use bincode::{Decode, Encode};
 use once_cell::sync::Lazy;
 use serde_yaml::Value as YamlValue;
 use std::borrow::Cow;
 use std::collections::BTreeMap;
+use std::sync::RwLock;
 
+mod directory;
 mod file;
+mod replay;
+mod socket;
+
+#[cfg(feature = "with-http")]
+mod http;
 
 #[cfg(feature = "with-kafka")]
 mod kafka;
 
+pub use directory::DirectoryInputTransport;
 pub use file::{FileInputTransport, FileOutputTransport};
+pub(crate) use replay::replay_journal_header;
+pub use replay::ReplayInputTransport;
+pub use socket::SocketInputTransport;
+
+#[cfg(feature = "with-http")]
+pub use http::HttpInputTransport;
 
 #[cfg(feature = "with-kafka")]
 pub use kafka::{KafkaInputTransport, KafkaOutputTransport};
 
-/// Static map of supported input transports.
-// TODO: support for registering new transports at runtime in order to allow
-// external crates to implement new transports.
-static INPUT_TRANSPORT: Lazy<BTreeMap<&'static str, Box<dyn InputTransport>>> = Lazy::new(|| {
-    BTreeMap::from([
-        (
-            "file",
-            Box::new(FileInputTransport) as Box<dyn InputTransport>,
-        ),
-        #[cfg(feature = "with-kafka")]
-        (
-            "kafka",
-            Box::new(KafkaInputTransport) as Box<dyn InputTransport>,
-        ),
-    ])
-});
-
-/// Static map of supported output transports.
-static OUTPUT_TRANSPORT: Lazy<BTreeMap<&'static str, Box<dyn OutputTransport>>> = Lazy::new(|| {
-    BTreeMap::from([
-        (
-            "file",
-            Box::new(FileOutputTransport) as Box<dyn OutputTransport>,
-        ),
-        #[cfg(feature = "with-kafka")]
-        (
-            "kafka",
-            Box::new(KafkaOutputTransport) as Box<dyn OutputTransport>,
-        ),
-    ])
-});
+/// Registry of supported input transports.
+///
+/// Initialized with built-in transports.  External crates add new transports
+/// using [`register_input_transport`].
+static INPUT_TRANSPORT: Lazy<RwLock<BTreeMap<String, &'static dyn InputTransport>>> =
+    Lazy::new(|| {
+        RwLock::new(BTreeMap::from([
+            (
+                "file".to_string(),
+                &FileInputTransport as &'static dyn InputTransport,
+            ),
+            (
+                "directory".to_string(),
+                &DirectoryInputTransport as &'static dyn InputTransport,
+            ),
+            (
+                "replay".to_string(),
+                &ReplayInputTransport as &'static dyn InputTransport,
+            ),
+            (
+                "socket".to_string(),
+                &SocketInputTransport as &'static dyn InputTransport,
+            ),
+            #[cfg(feature = "with-http")]
+            (
+                "http".to_string(),
+                &HttpInputTransport as &'static dyn InputTransport,
+            ),
+            #[cfg(feature = "with-kafka")]
+            (
+                "kafka".to_string(),
+                &KafkaInputTransport as &'static dyn InputTransport,
+            ),
+        ]))
+    });
+
+/// Registry of supported output transports.
+static OUTPUT_TRANSPORT: Lazy<RwLock<BTreeMap<String, &'static dyn OutputTransport>>> =
+    Lazy::new(|| {
+        RwLock::new(BTreeMap::from([
+            (
+                "file".to_string(),
+                &FileOutputTransport as &'static dyn OutputTransport,
+            ),
+            #[cfg(feature = "with-kafka")]
+            (
+                "kafka".to_string(),
+                &KafkaOutputTransport as &'static dyn OutputTransport,
+            ),
+        ]))
+    });
+
+/// Register a new input transport.
+///
+/// Makes the transport available to all controllers under the name returned
+/// by [`InputTransport::name`], replacing any previously registered
+/// transport with the same name, including built-in transports.  Transports
+/// must be registered before creating a controller whose configuration
+/// refers to them, e.g., before calling `server_main`.
+///
+/// Registered transports are never deallocated.
+pub fn register_input_transport(transport: Box<dyn InputTransport>) {
+    let transport: &'static dyn InputTransport = Box::leak(transport);
+    INPUT_TRANSPORT
+        .write()
+        .unwrap()
+        .insert(transport.name().into_owned(), transport);
+}
+
+/// Register a new output transport.
+///
+/// See [`register_input_transport`].
+pub fn register_output_transport(transport: Box<dyn OutputTransport>) {
+    let transport: &'static dyn OutputTransport = Box::leak(transport);
+    OUTPUT_TRANSPORT
+        .write()
+        .unwrap()
+        .insert(transport.name().into_owned(), transport);
+}
 
 /// Trait that represents a specific data transport.
 ///
@@ -76,12 +139,21 @@ pub trait InputTransport: Send + Sync {
         config: &YamlValue,
         consumer: Box<dyn InputConsumer>,
     ) -> AnyResult<Box<dyn InputEndpoint>>;
+
+    /// `true` if endpoints created by this transport replay an input journal
+    /// (see [`ReplayInputTransport`](`crate::ReplayInputTransport`)).
+    ///
+    /// The controller synchronizes clock cycles with such endpoints using
+    /// [`InputConsumer::replay_barrier`].  Returns `false` by default.
+    fn replays_journal(&self) -> bool {
+        false
+    }
 }
 
 impl dyn InputTransport {
     /// Lookup input transport by name.
     pub fn get_transport(name: &str) -> Option<&'static dyn InputTransport> {
-        INPUT_TRANSPORT.get(name).map(|f| &**f)
+        INPUT_TRANSPORT.read().unwrap().get(name).copied()
     }
 }
 
@@ -115,10 +187,52 @@ pub trait InputEndpoint: Send {
 /// A transport endpoint pushes binary data downstream via an instance of this
 /// trait.
 // TODO: `input_owned`.
+/// Metadata of a chunk of input data, e.g., the key, headers, and position
+/// of a message received from a message bus.
+///
+/// An ordered list of named items.  Item names are defined by the transport
+/// that produces the metadata.  Formats map items to columns of parsed
+/// records according to their configuration.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Encode, Decode)]
+pub struct InputMetadata {
+    items: Vec<(String, Option<String>)>,
+}
+
+impl InputMetadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append item `name`.  `None` represents a missing value, e.g., the key
+    /// of a message without a key.
+    pub fn push(&mut self, name: &str, value: Option<String>) {
+        self.items.push((name.to_string(), value));
+    }
+
+    /// Value of item `name`.  Returns `None` if the item is missing or has no
+    /// value.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.items
+            .iter()
+            .find(|(item, _)| item == name)
+            .and_then(|(_, value)| value.as_deref())
+    }
+}
+
 pub trait InputConsumer: Send {
     /// Push a chunk of data to the consumer.
     fn input(&mut self, data: &[u8]);
 
+    /// Push a chunk of data along with its metadata to the consumer.
+    ///
+    /// Unlike [`Self::input`], `data` must consist of complete records, all
+    /// of which share the same `metadata`.  The consumer passes `metadata`
+    /// to the parser (see [`Parser::input_with_metadata`]).  The default
+    /// implementation ignores `metadata`.
+    fn input_with_metadata(&mut self, data: &[u8], _metadata: &InputMetadata) {
+        self.input(data)
+    }
+
     /// Endpoint failed.
     ///
     /// Endpoint failed; no more data will be received from this endpoint.
@@ -129,6 +243,36 @@ pub trait InputConsumer: Send {
     /// No more data will be received from the endpoint.
     fn eoi(&mut self);
 
+    /// Clock cycle boundary of a recorded run.
+    ///
+    /// Used by endpoints that replay recorded inputs (see
+    /// [`ReplayInputTransport`]) to reproduce the clock cycle boundaries of
+    /// the original run.  `Some(step)` signals that the endpoint has pushed
+    /// all data received before clock cycle `step` of the recorded run and
+    /// blocks until the circuit has evaluated this clock cycle.  `None`
+    /// signals that the endpoint has reached the end of the recording and no
+    /// longer constrains clock cycles.
+    ///
+    /// Returns `false` if the pipeline has been terminated, in which case the
+    /// endpoint must stop.  The default implementation returns `true`
+    /// immediately.
+    fn replay_barrier(&mut self, _step: Option<u64>) -> bool {
+        true
+    }
+
+    /// Invoke `callback` once the circuit has finished a clock cycle that
+    /// consumed all data pushed to this consumer so far.
+    ///
+    /// Endpoints use this to acknowledge inputs, e.g., record ingestion
+    /// progress, only after the circuit has processed them, so that inputs
+    /// are not lost if the pipeline stops in between.  The callback is
+    /// invoked from the circuit thread and must not block.  It is never
+    /// invoked if the inputs are discarded, e.g., by an aborted transaction.
+    /// The default implementation invokes `callback` immediately.
+    fn on_processed(&mut self, callback: Box<dyn FnOnce() + Send>) {
+        callback()
+    }
+
     /// Create a new consumer instance.
     ///
     /// Used by multithreaded transport endpoints to create multiple parallel
@@ -172,15 +316,116 @@ pub trait OutputTransport: Send + Sync {
         config: &YamlValue,
         async_error_callback: Box<dyn Fn(bool, AnyError) + Send + Sync>,
     ) -> AnyResult<Box<dyn OutputEndpoint>>;
+
+    /// Create a new transport endpoint with the given name.
+    ///
+    /// The controller creates output endpoints via this method, passing the
+    /// name of the endpoint in the controller configuration, which transports
+    /// can use, e.g., in file names or log messages.  The default
+    /// implementation ignores the name and invokes [`Self::new_endpoint`].
+    fn new_named_endpoint(
+        &self,
+        _endpoint_name: &str,
+        config: &YamlValue,
+        async_error_callback: Box<dyn Fn(bool, AnyError) + Send + Sync>,
+    ) -> AnyResult<Box<dyn OutputEndpoint>> {
+        self.new_endpoint(config, async_error_callback)
+    }
 }
 
 impl dyn OutputTransport {
     /// Lookup output transport by name.
     pub fn get_transport(name: &str) -> Option<&'static dyn OutputTransport> {
-        OUTPUT_TRANSPORT.get(name).map(|f| &**f)
+        OUTPUT_TRANSPORT.read().unwrap().get(name).copied()
     }
 }
 
 pub trait OutputEndpoint: Send {
     fn push_buffer(&mut self, buffer: &[u8]) -> AnyResult<()>;
+
+    /// Returns `true` if the endpoint sends each output record as a separate
+    /// keyed message.
+    ///
+    /// Encoders push records to such endpoints one at a time via
+    /// [`Self::push_key`] instead of [`Self::push_buffer`].  The default
+    /// implementation returns `false`.
+    fn is_keyed(&self) -> bool {
+        false
+    }
+
+    /// Push a single encoded record with encoded key `key`.  `val` is `None`
+    /// for deleted records.
+    ///
+    /// Only invoked if [`Self::is_keyed`] returns `true`.
+    fn push_key(&mut self, _key: &[u8], _val: Option<&[u8]>) -> AnyResult<()> {
+        Err(AnyError::msg("endpoint does not support keyed output"))
+    }
+
+    /// Notifies the endpoint that the following buffers contain an output
+    /// batch produced by the circuit during clock cycles up to and including
+    /// `step`.
+    ///
+    /// Together with [`Self::batch_end`], allows endpoints to align their
+    /// own units of output, e.g., files, with output batches.  Endpoints
+    /// that don't receive these notifications, e.g., dead-letter queues,
+    /// must work without them.  The default implementation does nothing.
+    fn batch_start(&mut self, _step: u64) -> AnyResult<()> {
+        Ok(())
+    }
+
+    /// Notifies the endpoint that all buffers of the current output batch
+    /// have been pushed.  The default implementation does nothing.
+    fn batch_end(&mut self) -> AnyResult<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{register_output_transport, OutputEndpoint, OutputTransport};
+    use anyhow::{Error as AnyError, Result as AnyResult};
+    use serde_yaml::Value as YamlValue;
+    use std::borrow::Cow;
+
+    struct NullOutputTransport;
+
+    struct NullOutputEndpoint;
+
+    impl OutputTransport for NullOutputTransport {
+        fn name(&self) -> Cow<'static, str> {
+            Cow::Borrowed("test_null")
+        }
+
+        fn new_endpoint(
+            &self,
+            _config: &YamlValue,
+            _async_error_callback: Box<dyn Fn(bool, AnyError) + Send + Sync>,
+        ) -> AnyResult<Box<dyn OutputEndpoint>> {
+            Ok(Box::new(NullOutputEndpoint))
+        }
+    }
+
+    impl OutputEndpoint for NullOutputEndpoint {
+        fn push_buffer(&mut self, _buffer: &[u8]) -> AnyResult<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_register_transport() {
+        assert!(<dyn OutputTransport>::get_transport("test_null").is_none());
+
+        register_output_transport(Box::new(NullOutputTransport));
+
+        let transport = <dyn OutputTransport>::get_transport("test_null").unwrap();
+        assert_eq!(transport.name(), "test_null");
+        transport
+            .new_named_endpoint("test", &YamlValue::Null, Box::new(|_, _| {}))
+            .unwrap()
+            .push_buffer(b"data")
+            .unwrap();
+
+        // Built-in transports are still available.
+        assert!(<dyn OutputTransport>::get_transport("file").is_some());
+    }
 }
@@ -0,0 +1,187 @@
+use super::{InputConsumer, InputEndpoint, InputTransport};
+use crate::{
+    controller::journal::{JournalEntry, JournalHeader, JournalReader},
+    PipelineState,
+};
+use anyhow::Result as AnyResult;
+use crossbeam::sync::{Parker, Unparker};
+use num_traits::FromPrimitive;
+use serde::Deserialize;
+use serde_yaml::Value as YamlValue;
+use std::{
+    borrow::Cow,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    thread::spawn,
+};
+
+/// `InputTransport` implementation that replays inputs recorded in a
+/// controller journal (see the `journal_path` setting in
+/// [`ControllerConfig`](`crate::ControllerConfig`)).
+///
+/// The endpoint feeds chunks of data received by one endpoint of the recorded
+/// pipeline to its parser, which must be configured with the same format as
+/// the original endpoint.  The controller evaluates the circuit exactly at the
+/// clock cycle boundaries recorded in the journal: while replay endpoints are
+/// connected, the controller only starts a new clock cycle once all of them
+/// have pushed the inputs recorded before this cycle, and each endpoint waits
+/// for the cycle to complete before pushing more data.  Replay endpoints must
+/// be specified in the controller configuration, so that they are connected
+/// before the circuit evaluates its first clock cycle.
+pub struct ReplayInputTransport;
+
+impl InputTransport for ReplayInputTransport {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("replay")
+    }
+
+    fn new_endpoint(
+        &self,
+        config: &YamlValue,
+        consumer: Box<dyn InputConsumer>,
+    ) -> AnyResult<Box<dyn InputEndpoint>> {
+        let config = ReplayInputConfig::deserialize(config)?;
+        let mut ep = ReplayInputEndpoint::new();
+        ep.connect(config, consumer)?;
+        Ok(Box::new(ep))
+    }
+
+    fn replays_journal(&self) -> bool {
+        true
+    }
+}
+
+/// Read the header of the journal replayed by a `replay` endpoint with
+/// configuration `config`.
+pub(crate) fn replay_journal_header(config: &YamlValue) -> AnyResult<JournalHeader> {
+    let config = ReplayInputConfig::deserialize(config)?;
+    Ok(JournalReader::open(config.path)?.header().clone())
+}
+
+#[derive(Deserialize)]
+struct ReplayInputConfig {
+    /// Journal file path.
+    path: String,
+
+    /// Name of the input endpoint of the recorded pipeline whose inputs
+    /// this endpoint replays.
+    endpoint: String,
+}
+
+struct ReplayInputEndpoint {
+    status: Arc<AtomicU32>,
+    unparker: Option<Unparker>,
+}
+
+impl ReplayInputEndpoint {
+    fn new() -> Self {
+        Self {
+            status: Arc::new(AtomicU32::new(PipelineState::Paused as u32)),
+            unparker: None,
+        }
+    }
+
+    fn connect(
+        &mut self,
+        config: ReplayInputConfig,
+        consumer: Box<dyn InputConsumer>,
+    ) -> AnyResult<()> {
+        let journal = JournalReader::open(&config.path)?;
+
+        let parker = Parker::new();
+        self.unparker = Some(parker.unparker().clone());
+        let status = self.status.clone();
+        let _worker =
+            spawn(move || Self::worker_thread(journal, config.endpoint, consumer, parker, status));
+        Ok(())
+    }
+
+    fn unpark(&self) {
+        if let Some(unparker) = &self.unparker {
+            unparker.unpark();
+        }
+    }
+
+    fn worker_thread(
+        mut journal: JournalReader,
+        endpoint: String,
+        mut consumer: Box<dyn InputConsumer>,
+        parker: Parker,
+        status: Arc<AtomicU32>,
+    ) {
+        // Index of the next clock cycle in the recorded run.
+        let mut step = 0;
+
+        loop {
+            match PipelineState::from_u32(status.load(Ordering::Acquire)) {
+                Some(PipelineState::Paused) => parker.park(),
+                Some(PipelineState::Running) => match journal.next_entry() {
+                    Err(e) => {
+                        consumer.error(true, e);
+                        consumer.replay_barrier(None);
+                        return;
+                    }
+                    Ok(None) => {
+                        consumer.replay_barrier(None);
+                        return;
+                    }
+                    Ok(Some(JournalEntry::Input {
+                        endpoint: name,
+                        data,
+                    })) if name == endpoint => consumer.input(&data),
+                    Ok(Some(JournalEntry::InputWithMetadata {
+                        endpoint: name,
+                        data,
+                        metadata,
+                    })) if name == endpoint => consumer.input_with_metadata(&data, &metadata),
+                    Ok(Some(JournalEntry::Eoi { endpoint: name })) if name == endpoint => {
+                        consumer.eoi()
+                    }
+                    Ok(Some(JournalEntry::Step)) => {
+                        if !consumer.replay_barrier(Some(step)) {
+                            return;
+                        }
+                        step += 1;
+                    }
+                    // Inputs of other endpoints.
+                    Ok(Some(_)) => {}
+                },
+                Some(PipelineState::Terminated) => return,
+                _ => unreachable!(),
+            }
+        }
+    }
+}
+
+impl InputEndpoint for ReplayInputEndpoint {
+    fn pause(&self) -> AnyResult<()> {
+        self.status
+            .store(PipelineState::Paused as u32, Ordering::Release);
+        Ok(())
+    }
+
+    fn start(&self) -> AnyResult<()> {
+        self.status
+            .store(PipelineState::Running as u32, Ordering::Release);
+
+        // Wake up the worker if it's paused.
+        self.unpark();
+        Ok(())
+    }
+
+    fn disconnect(&self) {
+        self.status
+            .store(PipelineState::Terminated as u32, Ordering::Release);
+
+        // Wake up the worker if it's paused.
+        self.unpark();
+    }
+}
+
+impl Drop for ReplayInputEndpoint {
+    fn drop(&mut self) {
+        self.disconnect();
+    }
+}
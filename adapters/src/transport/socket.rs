@@ -0,0 +1,568 @@
+//! Input transport that reads data from a TCP or Unix socket.
+
+use super::{InputConsumer, InputEndpoint, InputTransport};
+use crate::PipelineState;
+use anyhow::{Error as AnyError, Result as AnyResult};
+use num_traits::FromPrimitive;
+use serde::Deserialize;
+use serde_yaml::Value as YamlValue;
+use std::{
+    borrow::Cow,
+    io::{ErrorKind, Read, Result as IoResult},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    thread::{sleep, spawn},
+    time::Duration,
+};
+
+#[cfg(unix)]
+use std::{
+    fs,
+    os::unix::{
+        fs::MetadataExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::PathBuf,
+};
+
+const SLEEP_MS: u64 = 200;
+
+/// Default size of the buffer used to read from a socket.
+const DEFAULT_BUFFER_SIZE: usize = 8192;
+
+/// `InputTransport` implementation that reads data from a TCP or Unix
+/// socket.
+///
+/// The endpoint either connects to a socket or listens on a socket and
+/// accepts any number of connections.  In the latter case each connection is
+/// served by a separate thread that feeds its own instance of the parser
+/// created with [`InputConsumer::fork`], so records must not straddle
+/// connections.
+pub struct SocketInputTransport;
+
+impl InputTransport for SocketInputTransport {
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("socket")
+    }
+
+    fn new_endpoint(
+        &self,
+        config: &YamlValue,
+        consumer: Box<dyn InputConsumer>,
+    ) -> AnyResult<Box<dyn InputEndpoint>> {
+        let config = SocketInputConfig::deserialize(config)?;
+        let mut ep = SocketInputEndpoint::new(config);
+        ep.connect(consumer)?;
+        Ok(Box::new(ep))
+    }
+}
+
+/// Socket type.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SocketProtocol {
+    #[default]
+    Tcp,
+    Unix,
+}
+
+/// Whether the endpoint connects to a socket or listens on it.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SocketMode {
+    #[default]
+    Connect,
+    Listen,
+}
+
+#[derive(Deserialize)]
+struct SocketInputConfig {
+    /// Socket address: `host:port` for TCP sockets, file system path for
+    /// Unix sockets.
+    address: String,
+
+    /// Socket type: `tcp` (default) or `unix`.
+    #[serde(default)]
+    protocol: SocketProtocol,
+
+    /// `connect` (default) to connect to `address`; `listen` to accept
+    /// connections on `address`.
+    ///
+    /// In the `connect` mode, the endpoint outputs an
+    /// [`eoi`](`InputConsumer::eoi`) message and stops when the peer closes
+    /// the connection.  In the `listen` mode, the endpoint keeps accepting
+    /// new connections until it is disconnected.
+    #[serde(default)]
+    mode: SocketMode,
+
+    /// Read buffer size.
+    ///
+    /// Default: 8 KiB.
+    buffer_size_bytes: Option<usize>,
+}
+
+/// A connected TCP or Unix socket.
+enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Stream {
+    fn connect(protocol: SocketProtocol, address: &str) -> IoResult<Self> {
+        match protocol {
+            SocketProtocol::Tcp => TcpStream::connect(address).map(Self::Tcp),
+            #[cfg(unix)]
+            SocketProtocol::Unix => UnixStream::connect(address).map(Self::Unix),
+            #[cfg(not(unix))]
+            SocketProtocol::Unix => Err(unix_unsupported()),
+        }
+    }
+
+    /// Make reads time out periodically, so the reader thread can check for
+    /// endpoint status changes.
+    fn set_read_timeout(&self, timeout: Duration) -> IoResult<()> {
+        match self {
+            Self::Tcp(stream) => {
+                stream.set_nonblocking(false)?;
+                stream.set_read_timeout(Some(timeout))
+            }
+            #[cfg(unix)]
+            Self::Unix(stream) => {
+                stream.set_nonblocking(false)?;
+                stream.set_read_timeout(Some(timeout))
+            }
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        match self {
+            Self::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+/// File created by binding a Unix socket.
+///
+/// Binding a Unix socket creates a file at the socket's path, which must be
+/// removed before the path can be bound again.
+#[cfg(unix)]
+struct UnixSocketFile {
+    path: PathBuf,
+    /// Device and inode of the file, used to avoid removing a file created
+    /// by a different listener bound to the same path after this one.
+    id: (u64, u64),
+}
+
+#[cfg(unix)]
+impl UnixSocketFile {
+    fn new(path: &str) -> IoResult<Self> {
+        let metadata = fs::metadata(path)?;
+        Ok(Self {
+            path: PathBuf::from(path),
+            id: (metadata.dev(), metadata.ino()),
+        })
+    }
+
+    /// Remove the file unless it has already been removed or replaced.
+    fn remove(&self) {
+        if let Ok(metadata) = fs::metadata(&self.path) {
+            if (metadata.dev(), metadata.ino()) == self.id {
+                let _ = fs::remove_file(&self.path);
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for UnixSocketFile {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
+
+/// A listening TCP or Unix socket.
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener, Arc<UnixSocketFile>),
+}
+
+impl Listener {
+    /// Bind a non-blocking listener to `address`.
+    fn bind(protocol: SocketProtocol, address: &str) -> IoResult<Self> {
+        let listener = match protocol {
+            SocketProtocol::Tcp => TcpListener::bind(address).map(Self::Tcp)?,
+            #[cfg(unix)]
+            SocketProtocol::Unix => {
+                let listener = UnixListener::bind(address)?;
+                Self::Unix(listener, Arc::new(UnixSocketFile::new(address)?))
+            }
+            #[cfg(not(unix))]
+            SocketProtocol::Unix => return Err(unix_unsupported()),
+        };
+
+        match &listener {
+            Self::Tcp(listener) => listener.set_nonblocking(true)?,
+            #[cfg(unix)]
+            Self::Unix(listener, _) => listener.set_nonblocking(true)?,
+        }
+
+        Ok(listener)
+    }
+
+    fn accept(&self) -> IoResult<Stream> {
+        match self {
+            Self::Tcp(listener) => listener.accept().map(|(stream, _)| Stream::Tcp(stream)),
+            #[cfg(unix)]
+            Self::Unix(listener, _) => listener.accept().map(|(stream, _)| Stream::Unix(stream)),
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn unix_unsupported() -> std::io::Error {
+    std::io::Error::new(
+        ErrorKind::Unsupported,
+        "Unix sockets are not supported on this platform",
+    )
+}
+
+/// `true` for errors that indicate that a non-blocking or timed-out socket
+/// operation should be retried.
+fn is_retryable(error: &std::io::Error) -> bool {
+    matches!(
+        error.kind(),
+        ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted
+    )
+}
+
+struct SocketInputEndpoint {
+    config: SocketInputConfig,
+    status: Arc<AtomicU32>,
+    /// Socket file of a listening Unix socket, removed when the endpoint is
+    /// disconnected, so that the path can be reused right away.
+    #[cfg(unix)]
+    socket_file: Option<Arc<UnixSocketFile>>,
+}
+
+impl SocketInputEndpoint {
+    fn new(config: SocketInputConfig) -> Self {
+        Self {
+            config,
+            status: Arc::new(AtomicU32::new(PipelineState::Paused as u32)),
+            #[cfg(unix)]
+            socket_file: None,
+        }
+    }
+
+    fn connect(&mut self, consumer: Box<dyn InputConsumer>) -> AnyResult<()> {
+        let status = self.status.clone();
+        let buffer_size = match self.config.buffer_size_bytes {
+            Some(buffer_size) if buffer_size > 0 => buffer_size,
+            _ => DEFAULT_BUFFER_SIZE,
+        };
+
+        match self.config.mode {
+            SocketMode::Connect => {
+                let stream = Stream::connect(self.config.protocol, &self.config.address)?;
+                stream.set_read_timeout(Duration::from_millis(SLEEP_MS))?;
+                let _worker =
+                    spawn(move || Self::reader_thread(stream, consumer, status, true, buffer_size));
+            }
+            SocketMode::Listen => {
+                let listener = Listener::bind(self.config.protocol, &self.config.address)?;
+                #[cfg(unix)]
+                if let Listener::Unix(_, socket_file) = &listener {
+                    self.socket_file = Some(socket_file.clone());
+                }
+                let _worker =
+                    spawn(move || Self::listener_thread(listener, consumer, status, buffer_size));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Accept connections and spawn a reader thread for each of them.
+    fn listener_thread(
+        listener: Listener,
+        mut consumer: Box<dyn InputConsumer>,
+        status: Arc<AtomicU32>,
+        buffer_size: usize,
+    ) {
+        loop {
+            if PipelineState::from_u32(status.load(Ordering::Acquire))
+                == Some(PipelineState::Terminated)
+            {
+                return;
+            }
+
+            match listener.accept().and_then(|stream| {
+                stream
+                    .set_read_timeout(Duration::from_millis(SLEEP_MS))
+                    .map(|_| stream)
+            }) {
+                Ok(stream) => {
+                    let consumer = consumer.fork();
+                    let status = status.clone();
+                    spawn(move || {
+                        Self::reader_thread(stream, consumer, status, false, buffer_size)
+                    });
+                }
+                Err(e) if is_retryable(&e) => sleep(Duration::from_millis(SLEEP_MS)),
+                Err(e) => {
+                    consumer.error(true, AnyError::from(e));
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Read data from a connected socket until the connection is closed or
+    /// the endpoint is disconnected.
+    ///
+    /// Errors are fatal for the endpoint only when `fatal` is `true`, i.e.,
+    /// when this is the only connection of the endpoint.
+    fn reader_thread(
+        mut stream: Stream,
+        mut consumer: Box<dyn InputConsumer>,
+        status: Arc<AtomicU32>,
+        fatal: bool,
+        buffer_size: usize,
+    ) {
+        let mut buffer = vec![0; buffer_size];
+
+        loop {
+            match PipelineState::from_u32(status.load(Ordering::Acquire)) {
+                Some(PipelineState::Paused) => sleep(Duration::from_millis(SLEEP_MS)),
+                Some(PipelineState::Running) => match stream.read(&mut buffer) {
+                    Ok(0) => {
+                        consumer.eoi();
+                        return;
+                    }
+                    Ok(len) => consumer.input(&buffer[0..len]),
+                    Err(e) if is_retryable(&e) => {}
+                    Err(e) => {
+                        consumer.error(fatal, AnyError::from(e));
+                        return;
+                    }
+                },
+                Some(PipelineState::Terminated) => return,
+                _ => unreachable!(),
+            }
+        }
+    }
+}
+
+impl InputEndpoint for SocketInputEndpoint {
+    fn pause(&self) -> AnyResult<()> {
+        // Notify reader threads via the status flag.  A reader may send
+        // another buffer downstream before the flag takes effect.
+        self.status
+            .store(PipelineState::Paused as u32, Ordering::Release);
+        Ok(())
+    }
+
+    fn start(&self) -> AnyResult<()> {
+        self.status
+            .store(PipelineState::Running as u32, Ordering::Release);
+        Ok(())
+    }
+
+    fn disconnect(&self) {
+        self.status
+            .store(PipelineState::Terminated as u32, Ordering::Release);
+        #[cfg(unix)]
+        if let Some(socket_file) = &self.socket_file {
+            socket_file.remove();
+        }
+    }
+}
+
+impl Drop for SocketInputEndpoint {
+    fn drop(&mut self) {
+        self.disconnect();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::{mock_input_pipeline, wait, TestStruct};
+    use csv::WriterBuilder as CsvWriterBuilder;
+    use std::{
+        io::Write,
+        net::{TcpListener, TcpStream},
+        thread::sleep,
+        time::Duration,
+    };
+
+    fn test_data() -> Vec<TestStruct> {
+        vec![
+            TestStruct {
+                id: 1,
+                b: true,
+                i: Some(10),
+                s: "foo".to_string(),
+            },
+            TestStruct {
+                id: 2,
+                b: false,
+                i: Some(-10),
+                s: "bar".to_string(),
+            },
+        ]
+    }
+
+    fn write_csv<W: Write>(writer: W, data: &[TestStruct]) {
+        let mut writer = CsvWriterBuilder::new()
+            .has_headers(false)
+            .from_writer(writer);
+        for val in data.iter() {
+            writer.serialize(val).unwrap();
+        }
+        writer.flush().unwrap();
+    }
+
+    fn config(address: &str, protocol: &str, mode: &str) -> String {
+        format!(
+            r#"
+transport:
+    name: socket
+    config:
+        address: {address:?}
+        protocol: {protocol}
+        mode: {mode}
+        buffer_size_bytes: 5
+format:
+    name: csv
+    config:
+        input_stream: test_input
+"#
+        )
+    }
+
+    #[test]
+    fn test_socket_connect() {
+        let test_data = test_data();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        let (endpoint, consumer, zset) = mock_input_pipeline::<TestStruct>(
+            "test_input",
+            serde_yaml::from_str(&config(&address, "tcp", "connect")).unwrap(),
+        );
+
+        let (stream, _) = listener.accept().unwrap();
+        write_csv(&stream, &test_data);
+
+        sleep(Duration::from_millis(10));
+
+        // No outputs should be produced while the endpoint is paused.
+        assert!(consumer.state().data.is_empty());
+
+        endpoint.start().unwrap();
+        wait(|| zset.state().flushed.len() == test_data.len(), None);
+        for (i, (val, polarity)) in zset.state().flushed.iter().enumerate() {
+            assert!(polarity);
+            assert_eq!(val, &test_data[i]);
+        }
+
+        // Closing the connection ends the input stream.
+        drop(stream);
+        wait(|| consumer.state().eoi, None);
+
+        endpoint.disconnect();
+    }
+
+    #[test]
+    fn test_socket_listen() {
+        let test_data = test_data();
+
+        // Find a free port.
+        let address = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+
+        let (endpoint, _consumer, zset) = mock_input_pipeline::<TestStruct>(
+            "test_input",
+            serde_yaml::from_str(&config(&address, "tcp", "listen")).unwrap(),
+        );
+        endpoint.start().unwrap();
+
+        // Send data over several consecutive connections.
+        for i in 0..3 {
+            let stream = TcpStream::connect(&address).unwrap();
+            write_csv(&stream, &test_data);
+            drop(stream);
+
+            wait(
+                || zset.state().flushed.len() == test_data.len() * (i + 1),
+                None,
+            );
+        }
+
+        endpoint.disconnect();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_socket_listen() {
+        use std::os::unix::net::UnixStream;
+
+        let test_data = test_data();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("socket");
+        let address = path.to_str().unwrap();
+
+        let (endpoint, _consumer, zset) = mock_input_pipeline::<TestStruct>(
+            "test_input",
+            serde_yaml::from_str(&config(address, "unix", "listen")).unwrap(),
+        );
+        endpoint.start().unwrap();
+
+        let stream = UnixStream::connect(&path).unwrap();
+        write_csv(&stream, &test_data);
+        drop(stream);
+
+        wait(|| zset.state().flushed.len() == test_data.len(), None);
+        for (i, (val, polarity)) in zset.state().flushed.iter().enumerate() {
+            assert!(polarity);
+            assert_eq!(val, &test_data[i]);
+        }
+
+        // Disconnecting removes the socket file, so the path can be bound
+        // again right away.
+        endpoint.disconnect();
+        assert!(!path.exists());
+
+        let (endpoint, _consumer, zset) = mock_input_pipeline::<TestStruct>(
+            "test_input",
+            serde_yaml::from_str(&config(address, "unix", "listen")).unwrap(),
+        );
+        endpoint.start().unwrap();
+
+        let stream = UnixStream::connect(&path).unwrap();
+        write_csv(&stream, &test_data);
+        drop(stream);
+
+        wait(|| zset.state().flushed.len() == test_data.len(), None);
+
+        // The old listener thread must not remove the new socket file when it
+        // exits.
+        sleep(Duration::from_millis(500));
+        assert!(path.exists());
+
+        endpoint.disconnect();
+        assert!(!path.exists());
+    }
+}
@@ -11,8 +11,9 @@ use anyhow::{anyhow, Result};
 use ascii_table::AsciiTable;
 use clap::Parser;
 use dbsp::{
+    circuit::schedule::{AdaptiveScheduler, DynamicScheduler, StaticScheduler},
     nexmark::{
-        config::{Config as NexmarkConfig, Query as NexmarkQuery},
+        config::{Config as NexmarkConfig, Query as NexmarkQuery, Scheduler as NexmarkScheduler},
         model::Event,
         queries::{
             q0, q1, q12, q13, q13_side_input, q14, q15, q16, q17, q18, q19, q2, q20, q21, q22, q3,
@@ -192,10 +193,11 @@ fn coordinate_input_and_steps(
 
 fn create_ascii_table() -> AsciiTable {
     /// Reported metrics (per query) for the benchmark.
-    const RESULT_COLUMNS: [&str; 13] = [
+    const RESULT_COLUMNS: [&str; 14] = [
         "Query",
         "#Events",
         "Cores",
+        "Scheduler",
         "Elapsed",
         "Cores * Elapsed",
         "Throughput/Cores",
@@ -233,6 +235,7 @@ fn main() -> Result<()> {
     let max_events = nexmark_config.max_events;
     let queries_to_run = nexmark_config.query.clone();
     let cpu_cores = nexmark_config.cpu_cores;
+    let scheduler = nexmark_config.scheduler;
 
     let results = run_queries!(
         nexmark_config,
@@ -271,6 +274,7 @@ fn main() -> Result<()> {
             result.name.clone(),
             format!("{}", result.num_events.to_formatted_string(&Locale::en)),
             format!("{cpu_cores}"),
+            format!("{scheduler:?}"),
             format!("{:#.3?}", result.elapsed),
             format!("{:#.3?}", result.elapsed * cpu_cores as u32),
             format!(
@@ -5,7 +5,18 @@ macro_rules! run_queries {
 
         let num_cores = $nexmark_config.cpu_cores;
         let expected_num_events = $nexmark_config.max_events;
-        let (dbsp, input_handle) = Runtime::init_circuit(num_cores, circuit_closure).unwrap();
+        let (dbsp, input_handle) = match $nexmark_config.scheduler {
+            NexmarkScheduler::Static => {
+                Runtime::init_circuit_with_scheduler::<_, _, StaticScheduler>(num_cores, circuit_closure)
+            }
+            NexmarkScheduler::Dynamic => {
+                Runtime::init_circuit_with_scheduler::<_, _, DynamicScheduler>(num_cores, circuit_closure)
+            }
+            NexmarkScheduler::Adaptive => {
+                Runtime::init_circuit_with_scheduler::<_, _, AdaptiveScheduler>(num_cores, circuit_closure)
+            }
+        }
+        .unwrap();
 
         // Create a channel for the coordinating thread to determine whether the
         // producer or consumer step is completed first.
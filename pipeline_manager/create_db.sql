@@ -14,13 +14,36 @@ CREATE TABLE project (
 
 CREATE SEQUENCE project_id_seq AS bigint;
 
+-- Code of all versions of each project, including the current one.
+CREATE TABLE project_history (
+    project_id bigint,
+    version bigint,
+    code varchar,
+    created timestamp with time zone,
+    PRIMARY KEY (project_id, version),
+    FOREIGN KEY (project_id) REFERENCES project(id) ON DELETE CASCADE
+);
+
 CREATE TABLE pipeline (
     id bigint,
     project_id bigint,
     project_version bigint,
-    -- TODO: add 'host' field when we support remote pipelines.
+    -- Config and config version that the pipeline was created from.  Not a
+    -- foreign key: the pipeline outlives the config.
+    config_id bigint,
+    config_version bigint,
+    -- Name of the runner backend that started the pipeline.
+    runner varchar NOT NULL,
+    host varchar NOT NULL,
     port int NOT NULL,
+    -- Pipeline config used to start (and restart) the pipeline.
+    config varchar NOT NULL,
     killed bool NOT NULL,
+    status varchar NOT NULL,
+    error varchar,
+    status_since timestamp with time zone,
+    -- Number of automatic restarts.
+    restarts int NOT NULL,
     created timestamp with time zone,
     PRIMARY KEY (id),
     FOREIGN KEY (project_id) REFERENCES project(id) ON DELETE CASCADE
@@ -28,6 +51,15 @@ CREATE TABLE pipeline (
 
 CREATE SEQUENCE pipeline_id_seq AS bigint;
 
+-- Log of pipeline status transitions.
+CREATE TABLE pipeline_history (
+    pipeline_id bigint,
+    status varchar NOT NULL,
+    error varchar,
+    time timestamp with time zone NOT NULL,
+    FOREIGN KEY (pipeline_id) REFERENCES pipeline(id) ON DELETE CASCADE
+);
+
 CREATE TABLE project_config (
     id bigint,
     project_id bigint,
@@ -39,3 +71,14 @@ CREATE TABLE project_config (
 );
 
 CREATE SEQUENCE project_config_id_seq AS bigint;
+
+-- All versions of each project config, including the current one.
+CREATE TABLE project_config_history (
+    config_id bigint,
+    version bigint,
+    name varchar,
+    config varchar,
+    created timestamp with time zone,
+    PRIMARY KEY (config_id, version),
+    FOREIGN KEY (config_id) REFERENCES project_config(id) ON DELETE CASCADE
+);
@@ -11,9 +11,10 @@ fi
 
 response=$(curl -s -X POST http://localhost:8080/pipelines -H 'Content-Type: application/json' -d '{"project_id":'$1',"project_version":'$2',"config_id":'$3',"config_version":'$4'}')
 
-port=$(echo ${response} | jq '.port')
 id=$(echo ${response} | jq '.pipeline_id')
+host=$(echo ${response} | jq -r '.host')
+port=$(echo ${response} | jq '.port')
 
-curl -s -X GET http://localhost:${port}/start
+curl -s -X POST http://localhost:8080/pipelines/${id}/start
 
-echo Started pipeline ${id} on port ${port}
+echo Started pipeline ${id} on ${host}:${port}
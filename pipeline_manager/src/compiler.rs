@@ -1,9 +1,10 @@
 use crate::{ManagerConfig, ProjectDB, ProjectId, Version};
 use anyhow::{Error as AnyError, Result as AnyResult};
 use fs_extra::{dir, dir::CopyOptions};
-use log::{debug, error, trace};
+use log::{debug, error, trace, warn};
 use serde::Serialize;
 use std::{
+    collections::HashSet,
     process::{ExitStatus, Stdio},
     sync::Arc,
 };
@@ -142,8 +143,19 @@ impl Compiler {
                             job = Some(CompilationJob::rust(&config, project_id, version).await?);
                         }
                         Ok(status) if status.success() && job.as_ref().unwrap().is_rust() => {
-                            // Rust compiler succeeded -- declare victory.
-                            db.set_project_status_guarded(project_id, version, ProjectStatus::Success).await?;
+                            // Rust compiler succeeded -- keep a copy of the executable,
+                            // so this version can still run after the project is modified,
+                            // and declare victory.
+                            let status = match Self::save_executable(&config, project_id, version).await {
+                                Ok(()) => ProjectStatus::Success,
+                                Err(e) => ProjectStatus::RustError(format!("failed to save compiled executable: {e}")),
+                            };
+                            if status == ProjectStatus::Success {
+                                if let Err(e) = Self::prune_project_executables(&config, &db, project_id, version).await {
+                                    warn!("failed to delete old executables of project '{project_id}': {e}");
+                                }
+                            }
+                            db.set_project_status_guarded(project_id, version, status).await?;
                             job = None;
                         }
                         Ok(status) => {
@@ -182,6 +194,73 @@ impl Compiler {
             }
         }
     }
+
+    /// Copy the compiled executable of the project to
+    /// [`ManagerConfig::versioned_executable`].
+    async fn save_executable(
+        config: &ManagerConfig,
+        project_id: ProjectId,
+        version: Version,
+    ) -> AnyResult<()> {
+        fs::create_dir_all(config.project_executables_dir(project_id)).await?;
+        fs::copy(
+            config.project_executable(project_id),
+            config.versioned_executable(project_id, version),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Delete executables of project versions older than
+    /// [`ManagerConfig::executable_retention`] versions before `version`,
+    /// except those used by pipelines that haven't been shut down.
+    async fn prune_project_executables(
+        config: &ManagerConfig,
+        db: &ProjectDB,
+        project_id: ProjectId,
+        version: Version,
+    ) -> AnyResult<()> {
+        let in_use: HashSet<Version> = db
+            .list_project_pipelines(project_id)
+            .await?
+            .into_iter()
+            .filter(|pipeline| !pipeline.killed)
+            .map(|pipeline| pipeline.project_version)
+            .collect();
+
+        Self::prune_executables(config, project_id, version, &in_use).await
+    }
+
+    async fn prune_executables(
+        config: &ManagerConfig,
+        project_id: ProjectId,
+        version: Version,
+        in_use: &HashSet<Version>,
+    ) -> AnyResult<()> {
+        let prefix = format!("{}_v", ManagerConfig::crate_name(project_id));
+        let oldest_kept = version.number() - config.executable_retention as i64 + 1;
+
+        let mut entries = fs::read_dir(config.project_executables_dir(project_id)).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let old_version = match entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.strip_prefix(&prefix))
+                .and_then(|version| version.parse::<i64>().ok())
+            {
+                Some(old_version) => Version::new(old_version),
+                None => continue,
+            };
+
+            if old_version.number() < oldest_kept && !in_use.contains(&old_version) {
+                debug!("deleting executable of project '{project_id}', version '{old_version}'");
+                fs::remove_file(entry.path()).await?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Eq, PartialEq)]
@@ -377,3 +456,46 @@ impl CompilationJob {
         let _ = self.compiler_process.kill().await;
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::Compiler;
+    use crate::{ManagerConfig, ProjectId, Version};
+    use std::{collections::HashSet, fs};
+
+    #[actix_web::test]
+    async fn test_prune_executables() {
+        let dir = tempfile::tempdir().unwrap();
+        let config: ManagerConfig = serde_yaml::from_str(&format!(
+            "working_directory: {}\nsql_compiler_home: {}\nexecutable_retention: 2\n",
+            dir.path().display(),
+            dir.path().display()
+        ))
+        .unwrap();
+
+        let project_id = ProjectId(1);
+        fs::create_dir_all(config.project_executables_dir(project_id)).unwrap();
+        for version in 1..=5 {
+            fs::write(
+                config.versioned_executable(project_id, Version::new(version)),
+                "",
+            )
+            .unwrap();
+        }
+
+        // Version 1 is still used by a pipeline.
+        let in_use = HashSet::from([Version::new(1)]);
+        Compiler::prune_executables(&config, project_id, Version::new(5), &in_use)
+            .await
+            .unwrap();
+
+        let remaining: Vec<bool> = (1..=5)
+            .map(|version| {
+                config
+                    .versioned_executable(project_id, Version::new(version))
+                    .exists()
+            })
+            .collect();
+        assert_eq!(remaining, vec![true, false, false, true, true]);
+    }
+}
@@ -1,7 +1,11 @@
-use crate::{PipelineId, ProjectId};
+use crate::{PipelineId, ProjectId, Version};
 use anyhow::{Error as AnyError, Result as AnyResult};
 use serde::Deserialize;
-use std::path::{Path, PathBuf};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 use tokio::fs::{canonicalize, create_dir_all};
 
 const fn default_server_port() -> u16 {
@@ -20,6 +24,113 @@ fn default_working_directory() -> String {
     ".".to_string()
 }
 
+fn default_advertised_host() -> String {
+    "localhost".to_string()
+}
+
+fn default_runners() -> BTreeMap<String, RunnerConfig> {
+    BTreeMap::from([(default_runner(), RunnerConfig::Local)])
+}
+
+fn default_runner() -> String {
+    "local".to_string()
+}
+
+const fn default_executable_retention() -> u32 {
+    10
+}
+
+/// Pipeline runner backend configuration.
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum RunnerConfig {
+    /// Run pipelines as child processes of the manager.
+    Local,
+    /// Run pipelines via a runner agent.
+    Remote {
+        /// Agent URL, e.g., `http://agent:8080`.
+        url: String,
+        /// Secret shared with the agent (see [`ManagerConfig::agent_token`]).
+        token: String,
+    },
+}
+
+/// Pipeline health monitoring and restart policy.
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub(crate) struct PipelineMonitorConfig {
+    /// Interval between health checks of live pipelines in milliseconds.
+    ///
+    /// Defaults to 5000.
+    pub health_check_interval_ms: u64,
+
+    /// Number of consecutive failed health checks after which a pipeline is
+    /// considered failed.
+    ///
+    /// Defaults to 3.
+    pub failure_threshold: u32,
+
+    /// Restart pipelines that crashed or became unreachable.
+    ///
+    /// The default is `false`.
+    pub auto_restart: bool,
+
+    /// Maximal number of automatic restarts of a pipeline.
+    ///
+    /// Defaults to 3.
+    pub max_restarts: u32,
+
+    /// Delay between the failure of a pipeline and the first restart
+    /// attempt in milliseconds.  The delay doubles with each subsequent
+    /// restart of the same pipeline.
+    ///
+    /// Defaults to 1000.
+    pub restart_backoff_ms: u64,
+
+    /// Upper bound on the delay before a restart attempt in milliseconds.
+    ///
+    /// Defaults to 60000.
+    pub max_restart_backoff_ms: u64,
+}
+
+impl Default for PipelineMonitorConfig {
+    fn default() -> Self {
+        Self {
+            health_check_interval_ms: 5_000,
+            failure_threshold: 3,
+            auto_restart: false,
+            max_restarts: 3,
+            restart_backoff_ms: 1_000,
+            max_restart_backoff_ms: 60_000,
+        }
+    }
+}
+
+impl PipelineMonitorConfig {
+    pub(crate) fn health_check_interval(&self) -> Duration {
+        Duration::from_millis(self.health_check_interval_ms)
+    }
+
+    /// Delay before restarting a pipeline that has already been restarted
+    /// `restarts` times.
+    pub(crate) fn restart_backoff(&self, restarts: u32) -> Duration {
+        let backoff = self
+            .restart_backoff_ms
+            .saturating_mul(1u64.checked_shl(restarts).unwrap_or(u64::MAX));
+
+        Duration::from_millis(backoff.min(self.max_restart_backoff_ms))
+    }
+
+    /// `true` if a pipeline that has already been restarted `restarts` times
+    /// and has been in the failed state for `failed_for` should be restarted
+    /// now.
+    pub(crate) fn restart_due(&self, restarts: u32, failed_for: Duration) -> bool {
+        self.auto_restart
+            && restarts < self.max_restarts
+            && failed_for >= self.restart_backoff(restarts)
+    }
+}
+
 /// Pipeline manager configuration read from a YAML config file.
 #[derive(Deserialize, Clone)]
 pub(crate) struct ManagerConfig {
@@ -75,6 +186,76 @@ pub(crate) struct ManagerConfig {
     /// The default is `false`.
     #[serde(default)]
     pub debug: bool,
+
+    /// Host name under which pipelines started by this instance are
+    /// reachable from other hosts, e.g., by Prometheus.
+    ///
+    /// Defaults to "localhost".
+    #[serde(default = "default_advertised_host")]
+    pub advertised_host: String,
+
+    /// Pipeline runner backends by name.
+    ///
+    /// For example:
+    ///
+    /// ```yaml
+    /// runners:
+    ///   local:
+    ///     type: local
+    ///   cluster:
+    ///     type: remote
+    ///     url: http://agent:8080
+    ///     token: <secret>
+    /// ```
+    ///
+    /// Defaults to a single local runner named "local".
+    #[serde(default = "default_runners")]
+    pub runners: BTreeMap<String, RunnerConfig>,
+
+    /// Runner used to start pipelines unless the request specifies
+    /// a different one.
+    ///
+    /// Defaults to "local".
+    #[serde(default = "default_runner")]
+    pub default_runner: String,
+
+    /// Pipeline health monitoring and restart policy.
+    ///
+    /// For example:
+    ///
+    /// ```yaml
+    /// pipeline_monitor:
+    ///   auto_restart: true
+    ///   max_restarts: 5
+    /// ```
+    #[serde(default)]
+    pub pipeline_monitor: PipelineMonitorConfig,
+
+    /// Directory where compiled executables of all project versions are
+    /// stored.
+    ///
+    /// A runner agent only runs executables from this directory, which
+    /// must therefore contain the executables compiled by the manager, e.g.,
+    /// by mounting the manager's executables directory as a shared volume.
+    ///
+    /// Defaults to `executables` under `working_directory`.
+    pub executables_directory: Option<String>,
+
+    /// Number of most recent versions of each project whose compiled
+    /// executables are kept in `executables_directory`, defaults to 10.
+    ///
+    /// Executables of older versions are deleted after each successful
+    /// compilation, unless a pipeline that hasn't been shut down still uses
+    /// them.  Pipelines can't be created from the deleted versions until
+    /// they are compiled again.
+    #[serde(default = "default_executable_retention")]
+    pub executable_retention: u32,
+
+    /// Secret that clients must send in the `Authorization: Bearer` header
+    /// of every request to a runner agent.
+    ///
+    /// Required when running as a runner agent (`--agent`).
+    pub agent_token: Option<String>,
 }
 
 impl ManagerConfig {
@@ -84,26 +265,7 @@ impl ManagerConfig {
     /// `dbsp_override_path`, and `static_html` fields to absolute paths;
     /// fails if any of the paths doesn't exist or isn't readable.
     pub(crate) async fn canonicalize(self) -> AnyResult<Self> {
-        let mut result = self.clone();
-        create_dir_all(&result.working_directory)
-            .await
-            .map_err(|e| {
-                AnyError::msg(format!(
-                    "unable to create or open working directry '{}': {e}",
-                    result.working_directory
-                ))
-            })?;
-
-        result.working_directory = canonicalize(&result.working_directory)
-            .await
-            .map_err(|e| {
-                AnyError::msg(format!(
-                    "error canonicalizing working directory path '{}': {e}",
-                    result.working_directory
-                ))
-            })?
-            .to_string_lossy()
-            .into_owned();
+        let mut result = self.canonicalize_working_directory().await?;
         result.sql_compiler_home = canonicalize(&result.sql_compiler_home)
             .await
             .map_err(|e| {
@@ -138,6 +300,35 @@ impl ManagerConfig {
         Ok(result)
     }
 
+    /// Create the working directory if it doesn't exist and convert
+    /// `working_directory` to an absolute path.
+    ///
+    /// This is the only path used by a runner agent.
+    pub(crate) async fn canonicalize_working_directory(self) -> AnyResult<Self> {
+        let mut result = self;
+        create_dir_all(&result.working_directory)
+            .await
+            .map_err(|e| {
+                AnyError::msg(format!(
+                    "unable to create or open working directry '{}': {e}",
+                    result.working_directory
+                ))
+            })?;
+
+        result.working_directory = canonicalize(&result.working_directory)
+            .await
+            .map_err(|e| {
+                AnyError::msg(format!(
+                    "error canonicalizing working directory path '{}': {e}",
+                    result.working_directory
+                ))
+            })?
+            .to_string_lossy()
+            .into_owned();
+
+        Ok(result)
+    }
+
     /// Crate name for a project.
     ///
     /// Note: we rely on the project id and not name, so projects can
@@ -214,6 +405,32 @@ impl ManagerConfig {
             .join(Self::crate_name(project_id))
     }
 
+    /// Directory where the manager keeps compiled executables of all
+    /// projects.
+    pub(crate) fn executables_dir(&self) -> PathBuf {
+        match &self.executables_directory {
+            Some(dir) => PathBuf::from(dir),
+            None => Path::new(&self.working_directory).join("executables"),
+        }
+    }
+
+    /// Directory where the manager keeps compiled executables of all versions
+    /// of the project.
+    pub(crate) fn project_executables_dir(&self, project_id: ProjectId) -> PathBuf {
+        self.executables_dir().join(Self::crate_name(project_id))
+    }
+
+    /// Location of the compiled executable for the specified version of the
+    /// project.
+    ///
+    /// The compiler copies the executable here after successfully compiling
+    /// the project, so that the executable survives subsequent modifications
+    /// of the project.
+    pub(crate) fn versioned_executable(&self, project_id: ProjectId, version: Version) -> PathBuf {
+        self.project_executables_dir(project_id)
+            .join(format!("{}_v{version}", Self::crate_name(project_id)))
+    }
+
     /// Location to store pipeline files at runtime.
     pub(crate) fn pipeline_dir(&self, pipeline_id: PipelineId) -> PathBuf {
         Path::new(&self.working_directory)
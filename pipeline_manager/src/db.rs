@@ -1,19 +1,28 @@
-use crate::{ManagerConfig, ProjectStatus};
+use crate::{ManagerConfig, PipelineStatus, ProjectStatus};
 use anyhow::{anyhow, Error as AnyError, Result as AnyResult};
 use chrono::{DateTime, Utc};
 use log::error;
 use serde::{Deserialize, Serialize};
 use std::{error::Error as StdError, fmt, fmt::Display};
-use tokio_postgres::{Client, NoTls};
+use tokio_postgres::{Client, NoTls, Row};
 use utoipa::ToSchema;
 
 /// Project database API.
 ///
 /// The API assumes that the caller holds a database lock, and therefore
-/// doesn't use transactions (and hence doesn't need to deal with conflicts).
+/// doesn't need to deal with conflicts.  Modifications that touch several
+/// tables, e.g., a project and its version history, run in a transaction, so
+/// that a failure cannot leave the tables inconsistent.
 ///
 /// The database schema is defined in `create_db.sql`.
 ///
+/// # Version history
+///
+/// Every modification of project code or config creates a new version of the
+/// project or config.  The `project_history` and `project_config_history`
+/// tables store all versions, including the current one, so that pipelines
+/// can be created from, and compared against, any past version.
+///
 /// # Compilation queue
 ///
 /// We use the `status` and `status_since` columns to maintain the compilation
@@ -47,7 +56,7 @@ impl Display for ConfigId {
 }
 
 /// Unique pipeline id.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
 #[repr(transparent)]
 #[serde(transparent)]
 pub(crate) struct PipelineId(pub i64);
@@ -58,7 +67,7 @@ impl Display for PipelineId {
 }
 
 /// Version number.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
 #[repr(transparent)]
 #[serde(transparent)]
 pub(crate) struct Version(i64);
@@ -69,6 +78,15 @@ impl Display for Version {
 }
 
 impl Version {
+    pub(crate) fn new(version: i64) -> Self {
+        Self(version)
+    }
+
+    /// Version number as an integer.
+    pub(crate) fn number(&self) -> i64 {
+        self.0
+    }
+
     fn increment(&self) -> Self {
         Self(self.0 + 1)
     }
@@ -80,6 +98,8 @@ pub(crate) enum DBError {
     OutdatedProjectVersion(Version),
     UnknownConfig(ConfigId),
     UnknownPipeline(PipelineId),
+    UnknownProjectVersion(ProjectId, Version),
+    UnknownConfigVersion(ConfigId, Version),
 }
 
 impl Display for DBError {
@@ -95,6 +115,15 @@ impl Display for DBError {
             DBError::UnknownPipeline(pipeline_id) => {
                 write!(f, "Unknown pipeline id '{pipeline_id}'")
             }
+            DBError::UnknownProjectVersion(project_id, version) => {
+                write!(f, "Unknown version '{version}' of project '{project_id}'")
+            }
+            DBError::UnknownConfigVersion(config_id, version) => {
+                write!(
+                    f,
+                    "Unknown version '{version}' of project config '{config_id}'"
+                )
+            }
         }
     }
 }
@@ -131,6 +160,34 @@ impl ProjectStatus {
     }
 }
 
+/// The database encodes pipeline status using the `status` and `error`
+/// columns of the `pipeline` and `pipeline_history` tables.  `error` is only
+/// used if `status` is `"failed"`.
+impl PipelineStatus {
+    /// Decode `PipelineStatus` from the values of `error` and `status` columns.
+    fn from_columns(status_string: &str, error_string: Option<String>) -> AnyResult<Self> {
+        match status_string {
+            "starting" => Ok(Self::Starting),
+            "running" => Ok(Self::Running),
+            "paused" => Ok(Self::Paused),
+            "failed" => Ok(Self::Failed(error_string.unwrap_or_default())),
+            "shutdown" => Ok(Self::ShutDown),
+            status => Err(AnyError::msg(format!(
+                "invalid pipeline status string '{status}'"
+            ))),
+        }
+    }
+    fn to_columns(&self) -> (&'static str, Option<String>) {
+        match self {
+            PipelineStatus::Starting => ("starting", None),
+            PipelineStatus::Running => ("running", None),
+            PipelineStatus::Paused => ("paused", None),
+            PipelineStatus::Failed(error) => ("failed", Some(error.clone())),
+            PipelineStatus::ShutDown => ("shutdown", None),
+        }
+    }
+}
+
 /// Project descriptor.
 #[derive(Serialize, ToSchema)]
 pub(crate) struct ProjectDescr {
@@ -154,17 +211,87 @@ pub(crate) struct ConfigDescr {
     pub config: String,
 }
 
+/// Project version descriptor.
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ProjectVersionDescr {
+    pub version: Version,
+    /// Time when the version was created.
+    pub created: DateTime<Utc>,
+}
+
+/// Project config version descriptor.
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ConfigVersionDescr {
+    pub version: Version,
+    /// Config name at this version.
+    pub name: String,
+    /// Time when the version was created.
+    pub created: DateTime<Utc>,
+}
+
 /// Pipeline descriptor.
 #[derive(Serialize, ToSchema)]
 pub(crate) struct PipelineDescr {
     pub pipeline_id: PipelineId,
     pub project_id: ProjectId,
     pub project_version: Version,
+    /// Config that the pipeline was created from.
+    pub config_id: ConfigId,
+    pub config_version: Version,
+    /// Name of the runner backend that started the pipeline.
+    pub runner: String,
+    /// Host that runs the pipeline.
+    pub host: String,
+    /// TCP port that the pipeline process listens on.
     pub port: u16,
+    /// `true` if the pipeline has been shut down by the user.
     pub killed: bool,
+    /// Pipeline status tracked by the manager.
+    pub status: PipelineStatus,
+    /// Time of the last status change.
+    pub status_since: DateTime<Utc>,
+    /// Number of times the pipeline was restarted automatically after a
+    /// failure.
+    pub restarts: u32,
     pub created: DateTime<Utc>,
 }
 
+/// Columns of the `pipeline` table decoded by [`PipelineDescr::from_row`].
+const PIPELINE_COLUMNS: &str = "id, project_id, project_version, config_id, config_version, runner, host, port, killed, status, error, status_since, restarts, created";
+
+impl PipelineDescr {
+    /// Decode pipeline descriptor from a row that contains `PIPELINE_COLUMNS`.
+    fn from_row(row: &Row) -> AnyResult<Self> {
+        let status: &str = row.try_get(9)?;
+        let error: Option<String> = row.try_get(10)?;
+
+        Ok(Self {
+            pipeline_id: PipelineId(row.try_get(0)?),
+            project_id: ProjectId(row.try_get(1)?),
+            project_version: Version(row.try_get(2)?),
+            config_id: ConfigId(row.try_get(3)?),
+            config_version: Version(row.try_get(4)?),
+            runner: row.try_get(5)?,
+            host: row.try_get(6)?,
+            port: row.try_get::<_, i32>(7)? as u16,
+            killed: row.try_get(8)?,
+            status: PipelineStatus::from_columns(status, error)?,
+            status_since: row.try_get(11)?,
+            restarts: row.try_get::<_, i32>(12)? as u32,
+            created: row.try_get(13)?,
+        })
+    }
+}
+
+/// Pipeline status transition.
+#[derive(Serialize, ToSchema)]
+pub(crate) struct PipelineEvent {
+    /// New pipeline status.
+    pub status: PipelineStatus,
+    /// Time of the transition.
+    pub time: DateTime<Utc>,
+}
+
 impl ProjectDB {
     /// Connect to the project database.
     ///
@@ -237,7 +364,7 @@ impl ProjectDB {
 
     /// Create a new project.
     pub(crate) async fn new_project(
-        &self,
+        &mut self,
         project_name: &str,
         project_code: &str,
     ) -> AnyResult<(ProjectId, Version)> {
@@ -247,12 +374,18 @@ impl ProjectDB {
             .await?;
         let id: ProjectId = ProjectId(row.try_get(0)?);
 
-        self.dbclient
-            .execute(
-                "INSERT INTO project (id, version, name, code, status_since) VALUES($1, 1, $2, $3, now())",
-                &[&id.0, &project_name, &project_code],
-            )
-            .await?;
+        let txn = self.dbclient.transaction().await?;
+        txn.execute(
+            "INSERT INTO project (id, version, name, code, status_since) VALUES($1, 1, $2, $3, now())",
+            &[&id.0, &project_name, &project_code],
+        )
+        .await?;
+        txn.execute(
+            "INSERT INTO project_history (project_id, version, code, created) VALUES($1, 1, $2, now())",
+            &[&id.0, &project_code],
+        )
+        .await?;
+        txn.commit().await?;
 
         Ok((id, Version(1)))
     }
@@ -281,12 +414,18 @@ impl ProjectDB {
                 // Only increment `version` if new code actually differs from the
                 // current version.
                 version = version.increment();
-                self.dbclient
-                    .execute(
-                        "UPDATE project SET version = $1, name = $2, code = $3, status = NULL, error = NULL WHERE id = $4",
-                        &[&version.0, &project_name, code, &project_id.0],
-                    )
-                    .await?;
+                let txn = self.dbclient.transaction().await?;
+                txn.execute(
+                    "UPDATE project SET version = $1, name = $2, code = $3, status = NULL, error = NULL WHERE id = $4",
+                    &[&version.0, &project_name, code, &project_id.0],
+                )
+                .await?;
+                txn.execute(
+                    "INSERT INTO project_history (project_id, version, code, created) VALUES($1, $2, $3, now())",
+                    &[&project_id.0, &version.0, code],
+                )
+                .await?;
+                txn.commit().await?;
             }
             _ => {
                 self.dbclient
@@ -301,6 +440,58 @@ impl ProjectDB {
         Ok(version)
     }
 
+    /// Retrieve code of the specified version of a project.
+    pub(crate) async fn project_code_version(
+        &self,
+        project_id: ProjectId,
+        version: Version,
+    ) -> AnyResult<String> {
+        let row = self
+            .dbclient
+            .query_opt(
+                "SELECT code FROM project_history WHERE project_id = $1 AND version = $2",
+                &[&project_id.0, &version.0],
+            )
+            .await?;
+
+        match row {
+            None => {
+                // Distinguish unknown project from unknown version.
+                let _descr = self.get_project(project_id).await?;
+                Err(anyhow!(DBError::UnknownProjectVersion(project_id, version)))
+            }
+            Some(row) => Ok(row.try_get(0)?),
+        }
+    }
+
+    /// List all versions of a project, oldest first.
+    pub(crate) async fn list_project_versions(
+        &self,
+        project_id: ProjectId,
+    ) -> AnyResult<Vec<ProjectVersionDescr>> {
+        // Check that the project exists, so we return an error instead of an
+        // empty list of versions.
+        let _descr = self.get_project(project_id).await?;
+
+        let rows = self
+            .dbclient
+            .query(
+                "SELECT version, created FROM project_history WHERE project_id = $1 ORDER BY version",
+                &[&project_id.0],
+            )
+            .await?;
+        let mut result = Vec::with_capacity(rows.len());
+
+        for row in rows.into_iter() {
+            result.push(ProjectVersionDescr {
+                version: Version(row.try_get(0)?),
+                created: row.try_get(1)?,
+            });
+        }
+
+        Ok(result)
+    }
+
     /// Retrieve project descriptor.
     ///
     /// Returns `None` if `project_id` is not found in the database.
@@ -550,7 +741,7 @@ impl ProjectDB {
 
     /// Create a new project config.
     pub(crate) async fn new_config(
-        &self,
+        &mut self,
         project_id: ProjectId,
         config_name: &str,
         config: &str,
@@ -565,12 +756,18 @@ impl ProjectDB {
             .await?;
         let id: ConfigId = ConfigId(row.try_get(0)?);
 
-        self.dbclient
-            .execute(
-                "INSERT INTO project_config (id, project_id, version, name, config) VALUES($1, $2, 1, $3, $4)",
-                &[&id.0, &project_id.0, &config_name, &config],
-            )
-            .await?;
+        let txn = self.dbclient.transaction().await?;
+        txn.execute(
+            "INSERT INTO project_config (id, project_id, version, name, config) VALUES($1, $2, 1, $3, $4)",
+            &[&id.0, &project_id.0, &config_name, &config],
+        )
+        .await?;
+        txn.execute(
+            "INSERT INTO project_config_history (config_id, version, name, config, created) VALUES($1, 1, $2, $3, now())",
+            &[&id.0, &config_name, &config],
+        )
+        .await?;
+        txn.commit().await?;
 
         Ok((id, Version(1)))
     }
@@ -589,14 +786,76 @@ impl ProjectDB {
         let config = config.clone().unwrap_or(descr.config);
 
         let version = descr.version.increment();
-        self.dbclient
-            .execute(
-                "UPDATE project_config SET version = $1, name = $2, config = $3 WHERE id = $4",
-                &[&version.0, &config_name, &config, &config_id.0],
+        let txn = self.dbclient.transaction().await?;
+        txn.execute(
+            "UPDATE project_config SET version = $1, name = $2, config = $3 WHERE id = $4",
+            &[&version.0, &config_name, &config, &config_id.0],
+        )
+        .await?;
+        txn.execute(
+            "INSERT INTO project_config_history (config_id, version, name, config, created) VALUES($1, $2, $3, $4, now())",
+            &[&config_id.0, &version.0, &config_name, &config],
+        )
+        .await?;
+        txn.commit().await?;
+
+        Ok(version)
+    }
+
+    /// Retrieve the specified version of a project config.
+    pub(crate) async fn get_config_version(
+        &self,
+        config_id: ConfigId,
+        version: Version,
+    ) -> AnyResult<ConfigDescr> {
+        // Fails if the config doesn't exist.
+        let descr = self.get_config(config_id).await?;
+
+        let row = self
+            .dbclient
+            .query_opt(
+                "SELECT name, config FROM project_config_history WHERE config_id = $1 AND version = $2",
+                &[&config_id.0, &version.0],
+            )
+            .await?
+            .ok_or(DBError::UnknownConfigVersion(config_id, version))?;
+
+        Ok(ConfigDescr {
+            config_id,
+            project_id: descr.project_id,
+            version,
+            name: row.try_get(0)?,
+            config: row.try_get(1)?,
+        })
+    }
+
+    /// List all versions of a project config, oldest first.
+    pub(crate) async fn list_config_versions(
+        &self,
+        config_id: ConfigId,
+    ) -> AnyResult<Vec<ConfigVersionDescr>> {
+        // Check that the config exists, so we return an error instead of an
+        // empty list of versions.
+        let _descr = self.get_config(config_id).await?;
+
+        let rows = self
+            .dbclient
+            .query(
+                "SELECT version, name, created FROM project_config_history WHERE config_id = $1 ORDER BY version",
+                &[&config_id.0],
             )
             .await?;
+        let mut result = Vec::with_capacity(rows.len());
 
-        Ok(version)
+        for row in rows.into_iter() {
+            result.push(ConfigVersionDescr {
+                version: Version(row.try_get(0)?),
+                name: row.try_get(1)?,
+                created: row.try_get(2)?,
+            });
+        }
+
+        Ok(result)
     }
 
     /// Delete project config.
@@ -625,61 +884,233 @@ impl ProjectDB {
     }
 
     /// Insert a new record to the `pipeline` table.
+    ///
+    /// The new pipeline is in the [`PipelineStatus::Paused`] state.
+    #[allow(clippy::too_many_arguments)]
     pub(crate) async fn new_pipeline(
         &self,
         pipeline_id: PipelineId,
         project_id: ProjectId,
         project_version: Version,
+        config: &ConfigDescr,
+        runner: &str,
+        host: &str,
         port: u16,
     ) -> AnyResult<()> {
         // Convert port to a SQL-compatible type (see `trait ToSql`).
         let port = port as i32;
+        let (status, error) = PipelineStatus::Paused.to_columns();
 
         self.dbclient
             .execute(
-                "INSERT INTO pipeline (id, project_id, project_version, port, killed, created) VALUES($1, $2, $3, $4, false, now())",
-                &[&pipeline_id.0, &project_id.0, &project_version.0, &port],
+                "INSERT INTO pipeline (id, project_id, project_version, config_id, config_version, runner, host, port, config, killed, status, error, status_since, restarts, created) VALUES($1, $2, $3, $4, $5, $6, $7, $8, $9, false, $10, $11, now(), 0, now())",
+                &[&pipeline_id.0, &project_id.0, &project_version.0, &config.config_id.0, &config.version.0, &runner, &host, &port, &config.config, &status, &error],
             )
             .await?;
+        self.log_pipeline_status(pipeline_id, status, &error)
+            .await?;
 
         Ok(())
     }
 
-    /// Read pipeline status.
-    ///
-    /// Returns pipeline port number and `killed` flag.
-    pub(crate) async fn pipeline_status(&self, pipeline_id: PipelineId) -> AnyResult<(u16, bool)> {
+    /// Read pipeline descriptor.
+    pub(crate) async fn get_pipeline(&self, pipeline_id: PipelineId) -> AnyResult<PipelineDescr> {
         let row = self
             .dbclient
             .query_opt(
-                "SELECT port, killed FROM pipeline WHERE id = $1",
+                &format!("SELECT {PIPELINE_COLUMNS} FROM pipeline WHERE id = $1"),
                 &[&pipeline_id.0],
             )
             .await?;
 
         if let Some(row) = row {
-            let port: i32 = row.try_get(0)?;
-            let killed: bool = row.try_get(1)?;
-
-            Ok((port as u16, killed))
+            PipelineDescr::from_row(&row)
         } else {
             Err(anyhow!(DBError::UnknownPipeline(pipeline_id)))
         }
     }
 
-    /// Set `killed` flag to `true`.
+    /// Read the config that the pipeline was started with.
+    pub(crate) async fn pipeline_config(&self, pipeline_id: PipelineId) -> AnyResult<String> {
+        let row = self
+            .dbclient
+            .query_opt(
+                "SELECT config FROM pipeline WHERE id = $1",
+                &[&pipeline_id.0],
+            )
+            .await?
+            .ok_or(DBError::UnknownPipeline(pipeline_id))?;
+
+        Ok(row.try_get(0)?)
+    }
+
+    /// Set `killed` flag to `true` and change pipeline status to
+    /// [`PipelineStatus::ShutDown`].
     pub(crate) async fn set_pipeline_killed(&self, pipeline_id: PipelineId) -> AnyResult<bool> {
+        let (status, error) = PipelineStatus::ShutDown.to_columns();
+
         let num_updated = self
             .dbclient
             .execute(
-                "UPDATE pipeline SET killed=true WHERE id = $1",
-                &[&pipeline_id.0],
+                "UPDATE pipeline SET killed = true, status = $1, error = $2, status_since = now() WHERE id = $3",
+                &[&status, &error, &pipeline_id.0],
             )
             .await?;
+        if num_updated > 0 {
+            self.log_pipeline_status(pipeline_id, status, &error)
+                .await?;
+        }
 
         Ok(num_updated > 0)
     }
 
+    /// Update pipeline status.
+    ///
+    /// Note: doesn't check that the pipeline exists.
+    pub(crate) async fn set_pipeline_status(
+        &self,
+        pipeline_id: PipelineId,
+        status: PipelineStatus,
+    ) -> AnyResult<()> {
+        let (status, error) = status.to_columns();
+
+        let num_updated = self
+            .dbclient
+            .execute(
+                "UPDATE pipeline SET status = $1, error = $2, status_since = now() WHERE id = $3",
+                &[&status, &error, &pipeline_id.0],
+            )
+            .await?;
+        if num_updated > 0 {
+            self.log_pipeline_status(pipeline_id, status, &error)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Update pipeline status after a status check.
+    ///
+    /// Updates pipeline status to `status` if the pipeline hasn't been shut
+    /// down and its current status in the database matches
+    /// `expected_status` (ignoring the error message).  Returns `true` if
+    /// the status was updated.
+    pub(crate) async fn set_pipeline_status_guarded(
+        &self,
+        pipeline_id: PipelineId,
+        expected_status: &PipelineStatus,
+        status: PipelineStatus,
+    ) -> AnyResult<bool> {
+        let (expected_status, _) = expected_status.to_columns();
+        let (status, error) = status.to_columns();
+
+        let num_updated = self
+            .dbclient
+            .execute(
+                "UPDATE pipeline SET status = $1, error = $2, status_since = now() WHERE id = $3 AND status = $4 AND NOT killed",
+                &[&status, &error, &pipeline_id.0, &expected_status],
+            )
+            .await?;
+        if num_updated > 0 {
+            self.log_pipeline_status(pipeline_id, status, &error)
+                .await?;
+        }
+
+        Ok(num_updated > 0)
+    }
+
+    /// Record pipeline status transition in the `pipeline_history` table.
+    async fn log_pipeline_status(
+        &self,
+        pipeline_id: PipelineId,
+        status: &str,
+        error: &Option<String>,
+    ) -> AnyResult<()> {
+        self.dbclient
+            .execute(
+                "INSERT INTO pipeline_history (pipeline_id, status, error, time) VALUES($1, $2, $3, now())",
+                &[&pipeline_id.0, &status, error],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Retrieve status transitions of a pipeline, oldest first.
+    pub(crate) async fn pipeline_history(
+        &self,
+        pipeline_id: PipelineId,
+    ) -> AnyResult<Vec<PipelineEvent>> {
+        let rows = self
+            .dbclient
+            .query(
+                "SELECT status, error, time FROM pipeline_history WHERE pipeline_id = $1 ORDER BY time",
+                &[&pipeline_id.0],
+            )
+            .await?;
+        let mut result = Vec::with_capacity(rows.len());
+
+        for row in rows.into_iter() {
+            let status: &str = row.try_get(0)?;
+            let error: Option<String> = row.try_get(1)?;
+
+            result.push(PipelineEvent {
+                status: PipelineStatus::from_columns(status, error)?,
+                time: row.try_get(2)?,
+            });
+        }
+
+        Ok(result)
+    }
+
+    /// Record new host and port of a restarted pipeline.
+    pub(crate) async fn set_pipeline_location(
+        &self,
+        pipeline_id: PipelineId,
+        host: &str,
+        port: u16,
+    ) -> AnyResult<()> {
+        // Convert port to a SQL-compatible type (see `trait ToSql`).
+        let port = port as i32;
+
+        self.dbclient
+            .execute(
+                "UPDATE pipeline SET host = $1, port = $2 WHERE id = $3",
+                &[&host, &port, &pipeline_id.0],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Increment the number of automatic restarts of a pipeline.
+    pub(crate) async fn increment_pipeline_restarts(
+        &self,
+        pipeline_id: PipelineId,
+    ) -> AnyResult<()> {
+        self.dbclient
+            .execute(
+                "UPDATE pipeline SET restarts = restarts + 1 WHERE id = $1",
+                &[&pipeline_id.0],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// List pipelines that haven't been shut down by the user.
+    pub(crate) async fn list_live_pipelines(&self) -> AnyResult<Vec<PipelineDescr>> {
+        let rows = self
+            .dbclient
+            .query(
+                &format!("SELECT {PIPELINE_COLUMNS} FROM pipeline WHERE NOT killed"),
+                &[],
+            )
+            .await?;
+
+        rows.iter().map(PipelineDescr::from_row).collect()
+    }
+
     /// Delete `pipeline` from the DB.
     pub(crate) async fn delete_pipeline(&self, pipeline_id: PipelineId) -> AnyResult<bool> {
         let num_deleted = self
@@ -702,23 +1133,157 @@ impl ProjectDB {
         let rows = self
             .dbclient
             .query(
-                "SELECT id, project_version, port, killed, created FROM pipeline WHERE project_id = $1",
+                &format!("SELECT {PIPELINE_COLUMNS} FROM pipeline WHERE project_id = $1"),
                 &[&project_id.0],
             )
             .await?;
-        let mut result = Vec::with_capacity(rows.len());
 
-        for row in rows.into_iter() {
-            result.push(PipelineDescr {
-                pipeline_id: PipelineId(row.try_get(0)?),
-                project_id,
-                project_version: Version(row.try_get(1)?),
-                port: row.try_get::<_, i32>(2)? as u16,
-                killed: row.try_get(3)?,
-                created: row.try_get(4)?,
-            });
-        }
+        rows.iter().map(PipelineDescr::from_row).collect()
+    }
+}
 
-        Ok(result)
+#[cfg(test)]
+mod test {
+    use super::{DBError, ProjectDB, Version};
+    use crate::ManagerConfig;
+
+    /// Connect to the test database specified by the
+    /// `DBSP_TEST_PG_CONNECTION_STRING` environment variable.
+    ///
+    /// The database must be initialized with `create_db.sql`.  Returns `None`
+    /// if the variable isn't set, in which case the test is skipped.
+    async fn test_db() -> Option<ProjectDB> {
+        let connection_string = match std::env::var("DBSP_TEST_PG_CONNECTION_STRING") {
+            Ok(connection_string) => connection_string,
+            Err(_) => {
+                eprintln!("DBSP_TEST_PG_CONNECTION_STRING is not set, skipping database test");
+                return None;
+            }
+        };
+        let config: ManagerConfig = serde_yaml::from_str(&format!(
+            "pg_connection_string: '{connection_string}'\nsql_compiler_home: .\n"
+        ))
+        .unwrap();
+
+        Some(ProjectDB::connect(&config).await.unwrap())
+    }
+
+    #[actix_web::test]
+    async fn test_project_history() {
+        let mut db = match test_db().await {
+            Some(db) => db,
+            None => return,
+        };
+
+        let (project_id, version) = db.new_project("history", "code v1").await.unwrap();
+        assert_eq!(version, Version::new(1));
+
+        // Renaming the project or submitting identical code doesn't create a
+        // new version.
+        let version = db
+            .update_project(project_id, "history2", &Some("code v1".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(version, Version::new(1));
+
+        let version = db
+            .update_project(project_id, "history2", &Some("code v2".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(version, Version::new(2));
+
+        let versions = db.list_project_versions(project_id).await.unwrap();
+        assert_eq!(
+            versions.iter().map(|v| v.version).collect::<Vec<_>>(),
+            vec![Version::new(1), Version::new(2)]
+        );
+        assert_eq!(
+            db.project_code_version(project_id, Version::new(1))
+                .await
+                .unwrap(),
+            "code v1"
+        );
+        assert_eq!(
+            db.project_code_version(project_id, Version::new(2))
+                .await
+                .unwrap(),
+            "code v2"
+        );
+        assert!(matches!(
+            db.project_code_version(project_id, Version::new(3))
+                .await
+                .unwrap_err()
+                .downcast_ref::<DBError>(),
+            Some(DBError::UnknownProjectVersion(..))
+        ));
+
+        // History is deleted along with the project.
+        db.delete_project(project_id).await.unwrap();
+        assert!(matches!(
+            db.project_code_version(project_id, Version::new(1))
+                .await
+                .unwrap_err()
+                .downcast_ref::<DBError>(),
+            Some(DBError::UnknownProject(..))
+        ));
+    }
+
+    #[actix_web::test]
+    async fn test_config_history() {
+        let mut db = match test_db().await {
+            Some(db) => db,
+            None => return,
+        };
+
+        let (project_id, _) = db.new_project("config_history", "code").await.unwrap();
+        let (config_id, version) = db
+            .new_config(project_id, "config", "config v1")
+            .await
+            .unwrap();
+        assert_eq!(version, Version::new(1));
+
+        let version = db
+            .update_config(config_id, "config2", &Some("config v2".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(version, Version::new(2));
+
+        let version = db.update_config(config_id, "config3", &None).await.unwrap();
+        assert_eq!(version, Version::new(3));
+
+        let versions = db.list_config_versions(config_id).await.unwrap();
+        assert_eq!(
+            versions
+                .iter()
+                .map(|v| (v.version, v.name.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (Version::new(1), "config"),
+                (Version::new(2), "config2"),
+                (Version::new(3), "config3")
+            ]
+        );
+
+        let old = db
+            .get_config_version(config_id, Version::new(1))
+            .await
+            .unwrap();
+        assert_eq!(old.config, "config v1");
+        let current = db
+            .get_config_version(config_id, Version::new(3))
+            .await
+            .unwrap();
+        assert_eq!(current.config, "config v2");
+        assert_eq!(current.project_id, project_id);
+
+        assert!(matches!(
+            db.get_config_version(config_id, Version::new(4))
+                .await
+                .unwrap_err()
+                .downcast_ref::<DBError>(),
+            Some(DBError::UnknownConfigVersion(..))
+        ));
+
+        db.delete_project(project_id).await.unwrap();
     }
 }
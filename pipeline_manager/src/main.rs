@@ -21,12 +21,14 @@
 //!
 //! * Runner.  The runner component is responsible for starting and killing
 //!   compiled pipelines and for interacting with them at runtime.  It also
-//!   registers each pipeline with Prometheus.
+//!   registers each pipeline with Prometheus.  Pipelines run either as child
+//!   processes of the manager or on a remote runner agent, which is an
+//!   instance of the manager executable started with the `--agent` flag.
+//!   The runner tracks pipeline status in the database, health-checks live
+//!   pipelines, and optionally restarts failed pipelines.
 
 // TODOs:
 // * Tests.
-// * Support multi-node DBSP deployments (the current architecture assumes that
-//   pipelines and the Prometheus server run on the same host as this server).
 // * Proper UI.
 
 use actix_files as fs;
@@ -50,8 +52,12 @@ use anyhow::{Error as AnyError, Result as AnyResult};
 use clap::Parser;
 use env_logger::Env;
 use serde::{Deserialize, Serialize};
+use similar::TextDiff;
 use std::sync::Arc;
-use tokio::{fs::read, sync::Mutex};
+use tokio::{
+    fs::{read, remove_dir_all},
+    sync::Mutex,
+};
 use utoipa::{openapi::OpenApi as OpenApiDoc, OpenApi, ToSchema};
 use utoipa_swagger_ui::SwaggerUi;
 
@@ -62,7 +68,10 @@ mod runner;
 
 pub(crate) use compiler::{Compiler, ProjectStatus};
 pub(crate) use config::ManagerConfig;
-use db::{ConfigId, DBError, PipelineId, ProjectDB, ProjectId, Version};
+use db::{
+    ConfigId, DBError, PipelineDescr, PipelineEvent, PipelineId, ProjectDB, ProjectId, Version,
+};
+pub(crate) use runner::PipelineStatus;
 use runner::{Runner, RunnerError};
 
 #[derive(Parser, Debug)]
@@ -76,6 +85,11 @@ struct Args {
     /// Allows modifying JavaScript without restarting the server.
     #[arg(short, long)]
     static_html: Option<String>,
+
+    /// Run as a runner agent that starts pipelines on behalf of a remote
+    /// pipeline manager instead of running the pipeline manager.
+    #[arg(long)]
+    agent: bool,
 }
 
 #[derive(OpenApi)]
@@ -98,6 +112,11 @@ struct Args {
   one of the configs.  Clients can start multiple pipelines for a project with
   the same or different configs.
 
+* *Version history*.  Every modification of a project or config creates a new
+  version.  All versions are preserved: clients can list, retrieve, and
+  compare past versions, and start pipelines from any compiled project version
+  and any config version.
+
 # Concurrency
 
 The API prevents race conditions due to multiple users accessing the same
@@ -106,15 +125,20 @@ while user 2 is starting a pipeline for the same project.  The pipeline
 may end up running the old or the new version, potentially leading to
 unexpected behaviors.  The API prevents such situations by associating a
 monotonically increasing version number with each project and configuration.
-Every request to compile the project or start a pipeline must include project
-id _and_ version number. If the version number isn't equal to the current
-version in the database, this means that the last version of the project
-observed by the user is outdated, so the request is rejected."
+Every request to compile the project must include project id _and_ version
+number. If the version number isn't equal to the current version in the
+database, this means that the last version of the project observed by the user
+is outdated, so the request is rejected.  Requests to start a pipeline specify
+the project and config versions to run, so the pipeline always runs the exact
+versions observed by the user."
     ),
     paths(
         list_projects,
         project_code,
         project_status,
+        list_project_versions,
+        project_version_code,
+        project_diff,
         new_project,
         update_project,
         compile_project,
@@ -124,10 +148,14 @@ observed by the user is outdated, so the request is rejected."
         update_config,
         delete_config,
         list_project_configs,
+        list_config_versions,
+        config_version,
+        config_diff,
         new_pipeline,
         list_project_pipelines,
         pipeline_status,
         pipeline_metadata,
+        pipeline_metrics,
         pipeline_start,
         pipeline_pause,
         shutdown_pipeline,
@@ -136,15 +164,20 @@ observed by the user is outdated, so the request is rejected."
     components(schemas(
         db::ProjectDescr,
         db::ConfigDescr,
+        db::ProjectVersionDescr,
+        db::ConfigVersionDescr,
         db::PipelineDescr,
+        db::PipelineEvent,
         ProjectId,
         PipelineId,
         ConfigId,
         Version,
         ProjectStatus,
+        PipelineStatus,
         ErrorResponse,
         ProjectCodeResponse,
         ProjectStatusResponse,
+        DiffResponse,
         NewProjectRequest,
         NewProjectResponse,
         UpdateProjectRequest,
@@ -157,6 +190,7 @@ observed by the user is outdated, so the request is rejected."
         UpdateConfigResponse,
         NewPipelineRequest,
         NewPipelineResponse,
+        PipelineStatusResponse,
         ShutdownPipelineRequest,
     ),),
     tags(
@@ -184,6 +218,10 @@ async fn main() -> AnyResult<()> {
     let mut config: ManagerConfig = serde_yaml::from_str(&config_yaml)
         .map_err(|e| AnyError::msg(format!("error parsing config file '{config_file}': {e}")))?;
 
+    if args.agent {
+        return runner::run_agent(config.canonicalize_working_directory().await?).await;
+    }
+
     if let Some(static_html) = &args.static_html {
         config.static_html = Some(static_html.clone());
     }
@@ -272,6 +310,9 @@ where
         .service(list_projects)
         .service(project_code)
         .service(project_status)
+        .service(list_project_versions)
+        .service(project_version_code)
+        .service(project_diff)
         .service(new_project)
         .service(update_project)
         .service(compile_project)
@@ -280,10 +321,14 @@ where
         .service(update_config)
         .service(delete_config)
         .service(list_project_configs)
+        .service(list_config_versions)
+        .service(config_version)
+        .service(config_diff)
         .service(new_pipeline)
         .service(list_project_pipelines)
         .service(pipeline_status)
         .service(pipeline_metadata)
+        .service(pipeline_metrics)
         .service(pipeline_start)
         .service(pipeline_pause)
         .service(shutdown_pipeline)
@@ -334,12 +379,16 @@ fn http_resp_from_error(error: &AnyError) -> HttpResponse {
             DBError::OutdatedProjectVersion(_) => HttpResponse::Conflict(),
             DBError::UnknownConfig(_) => HttpResponse::NotFound(),
             DBError::UnknownPipeline(_) => HttpResponse::NotFound(),
+            DBError::UnknownProjectVersion(..) => HttpResponse::NotFound(),
+            DBError::UnknownConfigVersion(..) => HttpResponse::NotFound(),
         }
         .json(ErrorResponse::new(&message))
     } else if let Some(runner_error) = error.downcast_ref::<RunnerError>() {
         let message = runner_error.to_string();
         match runner_error {
             RunnerError::PipelineShutdown(_) => HttpResponse::Conflict(),
+            RunnerError::UnknownRunner(_) => HttpResponse::BadRequest(),
+            RunnerError::ProjectNotCompiled(..) => HttpResponse::Conflict(),
         }
         .json(ErrorResponse::new(&message))
     } else {
@@ -371,6 +420,18 @@ fn parse_config_id_param(req: &HttpRequest) -> Result<ConfigId, HttpResponse> {
     }
 }
 
+fn parse_version_param(req: &HttpRequest) -> Result<Version, HttpResponse> {
+    match req.match_info().get("version") {
+        None => Err(HttpResponse::BadRequest().body("missing version argument")),
+        Some(version) => match version.parse::<i64>() {
+            Err(e) => {
+                Err(HttpResponse::BadRequest().body(format!("invalid version '{version}': {e}")))
+            }
+            Ok(version) => Ok(Version::new(version)),
+        },
+    }
+}
+
 fn parse_pipeline_id_param(req: &HttpRequest) -> Result<PipelineId, HttpResponse> {
     match req.match_info().get("pipeline_id") {
         None => Err(HttpResponse::BadRequest().body("missing pipeline id argument")),
@@ -508,6 +569,163 @@ async fn project_status(state: WebData<ServerState>, req: HttpRequest) -> impl R
         .unwrap_or_else(|e| http_resp_from_error(&e))
 }
 
+/// List all versions of a project.
+#[utoipa::path(
+    responses(
+        (status = OK, description = "Project version list retrieved successfully.", body = [ProjectVersionDescr]),
+        (status = NOT_FOUND
+            , description = "Specified `project_id` does not exist in the database."
+            , body = ErrorResponse
+            , example = json!(ErrorResponse::new("Unknown project id '42'"))),
+    ),
+    params(
+        ("project_id" = i64, Path, description = "Unique project identifier")
+    ),
+    tag = "Project"
+)]
+#[get("/projects/{project_id}/versions")]
+async fn list_project_versions(state: WebData<ServerState>, req: HttpRequest) -> impl Responder {
+    let project_id = match parse_project_id_param(&req) {
+        Err(e) => {
+            return e;
+        }
+        Ok(project_id) => project_id,
+    };
+
+    state
+        .db
+        .lock()
+        .await
+        .list_project_versions(project_id)
+        .await
+        .map(|versions| {
+            HttpResponse::Ok()
+                .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+                .json(versions)
+        })
+        .unwrap_or_else(|e| http_resp_from_error(&e))
+}
+
+/// Returns the SQL source code of the specified version of the project.
+#[utoipa::path(
+    responses(
+        (status = OK, description = "Project code retrieved successfully.", body = ProjectCodeResponse),
+        (status = NOT_FOUND
+            , description = "Specified `project_id` or `version` does not exist in the database."
+            , body = ErrorResponse
+            , example = json!(ErrorResponse::new("Unknown version '3' of project '42'"))),
+    ),
+    params(
+        ("project_id" = i64, Path, description = "Unique project identifier"),
+        ("version" = i64, Path, description = "Project version")
+    ),
+    tag = "Project"
+)]
+#[get("/projects/{project_id}/versions/{version}")]
+async fn project_version_code(state: WebData<ServerState>, req: HttpRequest) -> impl Responder {
+    let project_id = match parse_project_id_param(&req) {
+        Err(e) => {
+            return e;
+        }
+        Ok(project_id) => project_id,
+    };
+    let version = match parse_version_param(&req) {
+        Err(e) => {
+            return e;
+        }
+        Ok(version) => version,
+    };
+
+    state
+        .db
+        .lock()
+        .await
+        .project_code_version(project_id, version)
+        .await
+        .map(|code| HttpResponse::Ok().json(&ProjectCodeResponse { version, code }))
+        .unwrap_or_else(|e| http_resp_from_error(&e))
+}
+
+/// Request to compare two versions of a project or config.
+#[derive(Deserialize)]
+struct DiffRequest {
+    /// Old version.
+    from: Version,
+    /// New version.
+    to: Version,
+}
+
+/// Response to a diff request.
+#[derive(Serialize, ToSchema)]
+struct DiffResponse {
+    /// Old version.
+    from: Version,
+    /// New version.
+    to: Version,
+    /// Unified diff between the two versions; empty if the versions are
+    /// identical.
+    diff: String,
+}
+
+/// Compute a unified diff between `old` and `new`.
+fn unified_diff(old: &str, new: &str, old_header: &str, new_header: &str) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .header(old_header, new_header)
+        .to_string()
+}
+
+/// Compare the SQL code of two versions of a project.
+#[utoipa::path(
+    responses(
+        (status = OK, description = "Diff computed successfully.", body = DiffResponse),
+        (status = NOT_FOUND
+            , description = "Specified `project_id` or one of the versions does not exist in the database."
+            , body = ErrorResponse
+            , example = json!(ErrorResponse::new("Unknown version '3' of project '42'"))),
+    ),
+    params(
+        ("project_id" = i64, Path, description = "Unique project identifier"),
+        ("from" = i64, Query, description = "Old project version"),
+        ("to" = i64, Query, description = "New project version")
+    ),
+    tag = "Project"
+)]
+#[get("/projects/{project_id}/diff")]
+async fn project_diff(
+    state: WebData<ServerState>,
+    req: HttpRequest,
+    query: web::Query<DiffRequest>,
+) -> impl Responder {
+    let project_id = match parse_project_id_param(&req) {
+        Err(e) => {
+            return e;
+        }
+        Ok(project_id) => project_id,
+    };
+
+    let db = state.db.lock().await;
+    let old_code = match db.project_code_version(project_id, query.from).await {
+        Err(e) => return http_resp_from_error(&e),
+        Ok(code) => code,
+    };
+    let new_code = match db.project_code_version(project_id, query.to).await {
+        Err(e) => return http_resp_from_error(&e),
+        Ok(code) => code,
+    };
+
+    HttpResponse::Ok().json(&DiffResponse {
+        from: query.from,
+        to: query.to,
+        diff: unified_diff(
+            &old_code,
+            &new_code,
+            &format!("project{project_id}_v{}.sql", query.from),
+            &format!("project{project_id}_v{}.sql", query.to),
+        ),
+    })
+}
+
 /// Request to create a new DBSP project.
 #[derive(Deserialize, ToSchema)]
 struct NewProjectRequest {
@@ -741,10 +959,14 @@ async fn delete_project(state: WebData<ServerState>, req: HttpRequest) -> impl R
         Err(e) => return http_resp_from_error(&e),
     }
 
-    db.delete_project(project_id)
-        .await
-        .map(|_| HttpResponse::Ok().finish())
-        .unwrap_or_else(|e| http_resp_from_error(&e))
+    if let Err(e) = db.delete_project(project_id).await {
+        return http_resp_from_error(&e);
+    }
+
+    // Delete compiled executables of all project versions.
+    let _ = remove_dir_all(state.config.project_executables_dir(project_id)).await;
+
+    HttpResponse::Ok().finish()
 }
 
 /// Request to create a new project configuration.
@@ -917,17 +1139,151 @@ async fn list_project_configs(state: WebData<ServerState>, req: HttpRequest) ->
         .unwrap_or_else(|e| http_resp_from_error(&e))
 }
 
+/// List all versions of a project configuration.
+#[utoipa::path(
+    responses(
+        (status = OK, description = "Config version list retrieved successfully.", body = [ConfigVersionDescr]),
+        (status = NOT_FOUND
+            , description = "Specified `config_id` does not exist in the database."
+            , body = ErrorResponse
+            , example = json!(ErrorResponse::new("Unknown project config id '5'"))),
+    ),
+    params(
+        ("config_id" = i64, Path, description = "Unique configuration identifier")
+    ),
+    tag = "Config"
+)]
+#[get("/configs/{config_id}/versions")]
+async fn list_config_versions(state: WebData<ServerState>, req: HttpRequest) -> impl Responder {
+    let config_id = match parse_config_id_param(&req) {
+        Err(e) => {
+            return e;
+        }
+        Ok(config_id) => config_id,
+    };
+
+    state
+        .db
+        .lock()
+        .await
+        .list_config_versions(config_id)
+        .await
+        .map(|versions| {
+            HttpResponse::Ok()
+                .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+                .json(versions)
+        })
+        .unwrap_or_else(|e| http_resp_from_error(&e))
+}
+
+/// Retrieve the specified version of a project configuration.
+#[utoipa::path(
+    responses(
+        (status = OK, description = "Config retrieved successfully.", body = ConfigDescr),
+        (status = NOT_FOUND
+            , description = "Specified `config_id` or `version` does not exist in the database."
+            , body = ErrorResponse
+            , example = json!(ErrorResponse::new("Unknown version '2' of project config '5'"))),
+    ),
+    params(
+        ("config_id" = i64, Path, description = "Unique configuration identifier"),
+        ("version" = i64, Path, description = "Config version")
+    ),
+    tag = "Config"
+)]
+#[get("/configs/{config_id}/versions/{version}")]
+async fn config_version(state: WebData<ServerState>, req: HttpRequest) -> impl Responder {
+    let config_id = match parse_config_id_param(&req) {
+        Err(e) => {
+            return e;
+        }
+        Ok(config_id) => config_id,
+    };
+    let version = match parse_version_param(&req) {
+        Err(e) => {
+            return e;
+        }
+        Ok(version) => version,
+    };
+
+    state
+        .db
+        .lock()
+        .await
+        .get_config_version(config_id, version)
+        .await
+        .map(|config| HttpResponse::Ok().json(config))
+        .unwrap_or_else(|e| http_resp_from_error(&e))
+}
+
+/// Compare two versions of a project configuration.
+#[utoipa::path(
+    responses(
+        (status = OK, description = "Diff computed successfully.", body = DiffResponse),
+        (status = NOT_FOUND
+            , description = "Specified `config_id` or one of the versions does not exist in the database."
+            , body = ErrorResponse
+            , example = json!(ErrorResponse::new("Unknown version '2' of project config '5'"))),
+    ),
+    params(
+        ("config_id" = i64, Path, description = "Unique configuration identifier"),
+        ("from" = i64, Query, description = "Old config version"),
+        ("to" = i64, Query, description = "New config version")
+    ),
+    tag = "Config"
+)]
+#[get("/configs/{config_id}/diff")]
+async fn config_diff(
+    state: WebData<ServerState>,
+    req: HttpRequest,
+    query: web::Query<DiffRequest>,
+) -> impl Responder {
+    let config_id = match parse_config_id_param(&req) {
+        Err(e) => {
+            return e;
+        }
+        Ok(config_id) => config_id,
+    };
+
+    let db = state.db.lock().await;
+    let old_config = match db.get_config_version(config_id, query.from).await {
+        Err(e) => return http_resp_from_error(&e),
+        Ok(config) => config,
+    };
+    let new_config = match db.get_config_version(config_id, query.to).await {
+        Err(e) => return http_resp_from_error(&e),
+        Ok(config) => config,
+    };
+
+    HttpResponse::Ok().json(&DiffResponse {
+        from: query.from,
+        to: query.to,
+        diff: unified_diff(
+            &old_config.config,
+            &new_config.config,
+            &format!("{}_v{}.yaml", old_config.name, query.from),
+            &format!("{}_v{}.yaml", new_config.name, query.to),
+        ),
+    })
+}
+
 /// Request to create a new pipeline.
 #[derive(Deserialize, ToSchema)]
 pub(self) struct NewPipelineRequest {
     /// Project id to create pipeline for.
     project_id: ProjectId,
-    /// Latest project version known to the client.
+    /// Project version to run.  Can be any compiled version of the
+    /// project, not only the latest one.
     project_version: Version,
     /// Project config to run the pipeline with.
     config_id: ConfigId,
-    /// Latest config version known to the client.
+    /// Config version to run the pipeline with.  Can be any version of the
+    /// config.
     config_version: Version,
+    /// Runner backend to start the pipeline with (see the `runners` section
+    /// of the manager configuration).  Uses the default runner if not
+    /// specified.
+    runner: Option<String>,
 }
 
 /// Response to a pipeline creation request.
@@ -935,6 +1291,8 @@ pub(self) struct NewPipelineRequest {
 struct NewPipelineResponse {
     /// Unique id assigned to the new pipeline.
     pipeline_id: PipelineId,
+    /// Host that runs the pipeline.
+    host: String,
     /// TCP port that the pipeline process listens on.
     port: u16,
 }
@@ -949,15 +1307,15 @@ struct NewPipelineResponse {
     responses(
         (status = OK, description = "Pipeline successfully created.", body = NewPipelineResponse),
         (status = NOT_FOUND
-            , description = "Specified `project_id` or `config_id` does not exist in the database."
+            , description = "Specified `project_id`, `config_id`, or one of the versions does not exist in the database."
             , body = ErrorResponse
             , example = json!(ErrorResponse::new("Unknown config id '5'"))),
         (status = CONFLICT
-            , description = "Project or config version in the request doesn't match the latest version in the database."
+            , description = "The specified project version hasn't been compiled."
             , body = ErrorResponse
-            , example = json!(ErrorResponse::new("Outdated project version '{3}'"))),
+            , example = json!(ErrorResponse::new("Version '3' of project '42' hasn't been compiled"))),
         (status = BAD_REQUEST
-            , description = "`config_id` refers to a config that does not belong to `project_id`."
+            , description = "`config_id` refers to a config that does not belong to `project_id` or `runner` is not a configured runner."
             , body = ErrorResponse
             , example = json!(ErrorResponse::new("Config '9' does not belong to project '15'"))),
         (status = INTERNAL_SERVER_ERROR
@@ -1016,13 +1374,29 @@ async fn list_project_pipelines(state: WebData<ServerState>, req: HttpRequest) -
         .unwrap_or_else(|e| http_resp_from_error(&e))
 }
 
+/// Response to a pipeline status request.
+#[derive(Serialize, ToSchema)]
+struct PipelineStatusResponse {
+    /// Pipeline descriptor, including the status tracked by the manager.
+    pipeline: PipelineDescr,
+    /// Pipeline status transitions, oldest first.
+    history: Vec<PipelineEvent>,
+    /// Status and performance counters reported by the pipeline controller;
+    /// `null` if the pipeline is not running or cannot be reached.
+    // TODO: implement `ToSchema` for `ControllerStatus`, which is the actual
+    // type of this field.
+    #[schema(value_type = Option<Object>)]
+    controller: Option<serde_json::Value>,
+}
+
 /// Retrieve pipeline status and performance counters.
+///
+/// Returns the pipeline status tracked by the manager, the history of
+/// status transitions, and, if the pipeline is live, the status and
+/// performance counters reported by the pipeline.
 #[utoipa::path(
     responses(
-        // TODO: figure out how to specify that response contains arbitrary JSON,
-        // or, better yet, implement `ToSchema` for `ControllerStatus`, which is the
-        // actual type returned by this endpoint.
-        (status = OK, description = "Pipeline status retrieved successfully.", body = String),
+        (status = OK, description = "Pipeline status retrieved successfully.", body = PipelineStatusResponse),
         (status = NOT_FOUND
             , description = "Specified `pipeline_id` does not exist in the database."
             , body = ErrorResponse
@@ -1044,7 +1418,7 @@ async fn pipeline_status(state: WebData<ServerState>, req: HttpRequest) -> impl
 
     state
         .runner
-        .forward_to_pipeline(pipeline_id, Method::GET, "status")
+        .pipeline_status(pipeline_id)
         .await
         .unwrap_or_else(|e| http_resp_from_error(&e))
 }
@@ -1079,6 +1453,36 @@ async fn pipeline_metadata(state: WebData<ServerState>, req: HttpRequest) -> imp
         .unwrap_or_else(|e| http_resp_from_error(&e))
 }
 
+/// Retrieve pipeline metrics in the Prometheus format.
+#[utoipa::path(
+    responses(
+        (status = OK, description = "Pipeline metrics retrieved successfully.", body = String),
+        (status = NOT_FOUND
+            , description = "Specified `pipeline_id` does not exist in the database."
+            , body = ErrorResponse
+            , example = json!(ErrorResponse::new("Unknown pipeline id '13'"))),
+    ),
+    params(
+        ("pipeline_id" = i64, Path, description = "Unique pipeline identifier")
+    ),
+    tag = "Pipeline"
+)]
+#[get("/pipelines/{pipeline_id}/metrics")]
+async fn pipeline_metrics(state: WebData<ServerState>, req: HttpRequest) -> impl Responder {
+    let pipeline_id = match parse_pipeline_id_param(&req) {
+        Err(e) => {
+            return e;
+        }
+        Ok(pipeline_id) => pipeline_id,
+    };
+
+    state
+        .runner
+        .forward_to_pipeline(pipeline_id, Method::GET, "metrics")
+        .await
+        .unwrap_or_else(|e| http_resp_from_error(&e))
+}
+
 /// Start pipeline.
 #[utoipa::path(
     responses(
@@ -1104,7 +1508,7 @@ async fn pipeline_start(state: WebData<ServerState>, req: HttpRequest) -> impl R
 
     state
         .runner
-        .forward_to_pipeline(pipeline_id, Method::GET, "start")
+        .start_pipeline(pipeline_id)
         .await
         .unwrap_or_else(|e| http_resp_from_error(&e))
 }
@@ -1134,7 +1538,7 @@ async fn pipeline_pause(state: WebData<ServerState>, req: HttpRequest) -> impl R
 
     state
         .runner
-        .forward_to_pipeline(pipeline_id, Method::GET, "pause")
+        .pause_pipeline(pipeline_id)
         .await
         .unwrap_or_else(|e| http_resp_from_error(&e))
 }
@@ -1153,7 +1557,8 @@ pub(self) struct ShutdownPipelineRequest {
 /// to terminate (which can take several seconds).
 ///
 /// The pipeline is not deleted from the database, but its
-/// `killed` flag is set to `true`.
+/// `killed` flag is set to `true` and its status changes to
+/// `ShutDown`.
 #[utoipa::path(
     request_body = ShutdownPipelineRequest,
     responses(
@@ -1224,3 +1629,19 @@ async fn delete_pipeline(state: WebData<ServerState>, req: HttpRequest) -> impl
         .await
         .unwrap_or_else(|e| http_resp_from_error(&e))
 }
+
+#[cfg(test)]
+mod test {
+    use super::unified_diff;
+
+    #[test]
+    fn test_unified_diff() {
+        // Identical versions produce an empty diff.
+        assert_eq!(unified_diff("a\nb\n", "a\nb\n", "v1", "v2"), "");
+
+        assert_eq!(
+            unified_diff("a\nb\nc\n", "a\nx\nc\n", "v1", "v2"),
+            "--- v1\n+++ v2\n@@ -1,3 +1,3 @@\n a\n-b\n+x\n c\n"
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! Runner agent: an HTTP service that runs pipelines on behalf of a remote
+//! pipeline manager (see [`RemoteRunner`](`super::RemoteRunner`)).
+//!
+//! The agent only runs executables from its own executables directory and
+//! rejects requests that don't carry the shared secret configured in
+//! [`ManagerConfig::agent_token`].
+
+use super::{LocalRunner, PipelineLocation, RunnerBackend, StartPipelineRequest};
+use crate::{parse_pipeline_id_param, ErrorResponse, ManagerConfig, PipelineId};
+use actix_web::{
+    delete,
+    dev::{ServiceFactory, ServiceRequest},
+    http::header::AUTHORIZATION,
+    middleware::Logger,
+    post, route, web,
+    web::Data as WebData,
+    App, Error as ActixError, HttpRequest, HttpResponse, HttpServer, Responder,
+};
+use anyhow::{Error as AnyError, Result as AnyResult};
+use log::info;
+use std::{collections::HashMap, sync::Mutex};
+
+pub(super) struct AgentState {
+    runner: LocalRunner,
+    /// Secret that clients must present with each request.
+    token: String,
+    /// Locations of pipelines started by this agent.
+    ///
+    /// This state is not persistent: after a restart, the agent cannot
+    /// reach pipelines started by its previous instance.
+    pipelines: Mutex<HashMap<PipelineId, PipelineLocation>>,
+}
+
+impl AgentState {
+    /// Fails if `config` doesn't specify `agent_token`.
+    pub(super) fn new(config: &ManagerConfig) -> AnyResult<Self> {
+        let token = match &config.agent_token {
+            Some(token) if !token.is_empty() => token.clone(),
+            _ => {
+                return Err(AnyError::msg(
+                    "runner agent requires a non-empty 'agent_token' in the config file",
+                ))
+            }
+        };
+
+        Ok(Self {
+            runner: LocalRunner::new(config),
+            token,
+            pipelines: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Check that `req` carries the shared secret in the `Authorization:
+    /// Bearer` header.
+    fn authorize(&self, req: &HttpRequest) -> Result<(), HttpResponse> {
+        let token = req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|header| header.to_str().ok())
+            .and_then(|header| header.strip_prefix("Bearer "));
+
+        match token {
+            Some(token) if constant_time_eq(token.as_bytes(), self.token.as_bytes()) => Ok(()),
+            _ => Err(HttpResponse::Unauthorized()
+                .json(ErrorResponse::new("missing or invalid runner agent token"))),
+        }
+    }
+}
+
+/// Compare secrets in time that only depends on their lengths.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Run the runner agent.
+///
+/// The agent listens on `config.bind_address` and `config.port` and stores
+/// pipeline files in `config.working_directory`.  It runs executables from
+/// `config.executables_directory` and reports `config.advertised_host` as the
+/// host of the pipelines it starts.
+pub(crate) async fn run_agent(config: ManagerConfig) -> AnyResult<()> {
+    let state = WebData::new(AgentState::new(&config)?);
+
+    info!(
+        "Starting runner agent on {}:{}",
+        config.bind_address, config.port
+    );
+    HttpServer::new(move || build_agent_app(App::new().wrap(Logger::default()), state.clone()))
+        .bind((config.bind_address.clone(), config.port))?
+        .run()
+        .await?;
+
+    Ok(())
+}
+
+pub(super) fn build_agent_app<T>(app: App<T>, state: WebData<AgentState>) -> App<T>
+where
+    T: ServiceFactory<ServiceRequest, Config = (), Error = ActixError, InitError = ()>,
+{
+    app.app_data(state)
+        .service(start_pipeline)
+        .service(stop_pipeline)
+        .service(delete_pipeline)
+        .service(forward_to_pipeline)
+}
+
+#[post("/pipelines")]
+async fn start_pipeline(
+    state: WebData<AgentState>,
+    req: HttpRequest,
+    request: web::Json<StartPipelineRequest>,
+) -> impl Responder {
+    if let Err(e) = state.authorize(&req) {
+        return e;
+    }
+
+    match state.runner.start_pipeline(&request).await {
+        Ok(location) => {
+            state
+                .pipelines
+                .lock()
+                .unwrap()
+                .insert(request.pipeline_id, location.clone());
+            HttpResponse::Ok().json(location)
+        }
+        Err(e) => HttpResponse::InternalServerError().json(ErrorResponse::new(&e.to_string())),
+    }
+}
+
+#[delete("/pipelines/{pipeline_id}/process")]
+async fn stop_pipeline(state: WebData<AgentState>, req: HttpRequest) -> impl Responder {
+    if let Err(e) = state.authorize(&req) {
+        return e;
+    }
+
+    let pipeline_id = match parse_pipeline_id_param(&req) {
+        Err(e) => {
+            return e;
+        }
+        Ok(pipeline_id) => pipeline_id,
+    };
+
+    match state.runner.stop_pipeline(pipeline_id).await {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(e) => HttpResponse::InternalServerError().json(ErrorResponse::new(&e.to_string())),
+    }
+}
+
+#[delete("/pipelines/{pipeline_id}")]
+async fn delete_pipeline(state: WebData<AgentState>, req: HttpRequest) -> impl Responder {
+    if let Err(e) = state.authorize(&req) {
+        return e;
+    }
+
+    let pipeline_id = match parse_pipeline_id_param(&req) {
+        Err(e) => {
+            return e;
+        }
+        Ok(pipeline_id) => pipeline_id,
+    };
+
+    state.pipelines.lock().unwrap().remove(&pipeline_id);
+
+    match state.runner.delete_pipeline(pipeline_id).await {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(e) => HttpResponse::InternalServerError().json(ErrorResponse::new(&e.to_string())),
+    }
+}
+
+#[route("/pipelines/{pipeline_id}/{endpoint}", method = "GET", method = "POST")]
+async fn forward_to_pipeline(state: WebData<AgentState>, req: HttpRequest) -> impl Responder {
+    if let Err(e) = state.authorize(&req) {
+        return e;
+    }
+
+    let pipeline_id = match parse_pipeline_id_param(&req) {
+        Err(e) => {
+            return e;
+        }
+        Ok(pipeline_id) => pipeline_id,
+    };
+    let endpoint = req.match_info().get("endpoint").unwrap_or_default();
+
+    let location = match state.pipelines.lock().unwrap().get(&pipeline_id) {
+        None => {
+            return HttpResponse::BadGateway().json(ErrorResponse::new(&format!(
+                "Unknown pipeline id '{pipeline_id}'"
+            )));
+        }
+        Some(location) => location.clone(),
+    };
+
+    state
+        .runner
+        .forward(pipeline_id, &location, req.method().clone(), endpoint)
+        .await
+        .unwrap_or_else(|e| {
+            HttpResponse::BadGateway().json(ErrorResponse::new(&format!(
+                "Failed to connect to pipeline: {e}"
+            )))
+        })
+}
@@ -0,0 +1,249 @@
+use super::{forward_request, PipelineLocation, RunnerBackend, StartPipelineRequest};
+use crate::{ManagerConfig, PipelineId};
+use actix_web::{http::Method, HttpResponse};
+use anyhow::{Error as AnyError, Result as AnyResult};
+use async_trait::async_trait;
+use regex::Regex;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    pin::Pin,
+    process::Stdio,
+    sync::Mutex,
+};
+use tokio::{
+    fs,
+    fs::{canonicalize, create_dir_all, remove_dir_all, File},
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncSeek, BufReader, SeekFrom},
+    process::{Child, Command},
+    time::{sleep, Duration, Instant},
+};
+
+const STARTUP_TIMEOUT: Duration = Duration::from_millis(10_000);
+const LOG_SUFFIX_LEN: i64 = 10_000;
+
+/// Runner backend that runs pipelines as child processes of the current
+/// process.
+///
+/// Each pipeline gets a directory under the working directory of the
+/// manager (or the runner agent), which stores the pipeline's config,
+/// metadata, and log files.
+///
+/// The runner keeps handles of the processes it started, so that it can
+/// confirm that a pipeline process has exited before starting a new process
+/// for the same pipeline.  These handles are not persistent: processes
+/// started by a previous instance of the runner cannot be stopped.
+pub(crate) struct LocalRunner {
+    config: ManagerConfig,
+    processes: Mutex<HashMap<PipelineId, Child>>,
+}
+
+impl LocalRunner {
+    pub(crate) fn new(config: &ManagerConfig) -> Self {
+        Self {
+            config: config.clone(),
+            processes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Locate the executable for the requested project version in the
+    /// executables directory.
+    ///
+    /// Fails if the executable does not exist or resolves to a file outside
+    /// the executables directory, e.g., via a symlink.
+    async fn executable(&self, request: &StartPipelineRequest) -> AnyResult<PathBuf> {
+        let executables_dir = canonicalize(self.config.executables_dir())
+            .await
+            .map_err(|e| {
+                AnyError::msg(format!(
+                    "failed to access executables directory '{}': {e}",
+                    self.config.executables_dir().display()
+                ))
+            })?;
+        let executable = self
+            .config
+            .versioned_executable(request.project_id, request.version);
+        let resolved = canonicalize(&executable).await.map_err(|e| {
+            AnyError::msg(format!(
+                "executable for version '{}' of project '{}' is not available: {e}",
+                request.version, request.project_id
+            ))
+        })?;
+
+        if !resolved.starts_with(&executables_dir) {
+            return Err(AnyError::msg(format!(
+                "executable '{}' is outside of the executables directory '{}'",
+                resolved.display(),
+                executables_dir.display()
+            )));
+        }
+
+        Ok(resolved)
+    }
+
+    async fn start(&self, request: &StartPipelineRequest) -> AnyResult<Child> {
+        let pipeline_id = request.pipeline_id;
+
+        // Never run two processes in the same pipeline directory.
+        if let Some(process) = self.processes.lock().unwrap().get_mut(&pipeline_id) {
+            if process.try_wait()?.is_none() {
+                return Err(AnyError::msg(format!(
+                    "a process for pipeline '{pipeline_id}' is already running"
+                )));
+            }
+        }
+
+        let executable = self.executable(request).await?;
+
+        // Create pipeline directory (delete old directory if exists); write metadata
+        // and config files to it.
+        let pipeline_dir = self.config.pipeline_dir(pipeline_id);
+        create_dir_all(&pipeline_dir).await?;
+
+        let config_file_path = self.config.config_file_path(pipeline_id);
+        fs::write(&config_file_path, &request.config).await?;
+
+        let metadata_file_path = self.config.metadata_file_path(pipeline_id);
+        fs::write(&metadata_file_path, &request.metadata).await?;
+
+        let log_file_path = self.config.log_file_path(pipeline_id);
+        let log_file = File::create(&log_file_path).await?;
+        let out_file_path = self.config.out_file_path(pipeline_id);
+        let out_file = File::create(&out_file_path).await?;
+
+        // Run executable, set current directory to pipeline directory, pass metadata
+        // file and config as arguments.
+        let pipeline_process = Command::new(&executable)
+            .current_dir(&pipeline_dir)
+            .arg("--config-file")
+            .arg(&config_file_path)
+            .arg("--metadata-file")
+            .arg(&metadata_file_path)
+            .stdin(Stdio::null())
+            .stdout(out_file.into_std().await)
+            .stderr(log_file.into_std().await)
+            .spawn()
+            .map_err(|e| AnyError::msg(format!("failed to run '{}': {e}", executable.display())))?;
+
+        Ok(pipeline_process)
+    }
+
+    /// Monitor pipeline log until either port number or error shows up or
+    /// the child process exits.
+    async fn wait_for_startup(log_file_path: &Path) -> AnyResult<u16> {
+        let mut log_file_lines = BufReader::new(File::open(log_file_path).await?).lines();
+
+        let start = Instant::now();
+
+        let portnum_regex = Regex::new(r"Started HTTP server on port (\w+)\b").unwrap();
+        let error_regex = Regex::new(r"Failed to create server.*").unwrap();
+
+        loop {
+            if let Some(line) = log_file_lines.next_line().await? {
+                if let Some(captures) = portnum_regex.captures(&line) {
+                    if let Some(portnum_match) = captures.get(1) {
+                        if let Ok(port) = portnum_match.as_str().parse::<u16>() {
+                            return Ok(port);
+                        } else {
+                            return Err(AnyError::msg("invalid port number in log: '{line}'"));
+                        }
+                    } else {
+                        return Err(AnyError::msg(
+                            "couldn't parse server port number from log: '{line}'",
+                        ));
+                    }
+                };
+                if let Some(mtch) = error_regex.find(&line) {
+                    return Err(AnyError::msg(mtch.as_str().to_string()));
+                };
+            }
+
+            if start.elapsed() > STARTUP_TIMEOUT {
+                let log = Self::log_suffix(log_file_path).await;
+                return Err(AnyError::msg(format!("waiting for pipeline initialization status timed out after {STARTUP_TIMEOUT:?}\n{log}")));
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    async fn log_suffix_inner(log_file_path: &Path) -> AnyResult<String> {
+        let mut buf = Vec::with_capacity(LOG_SUFFIX_LEN as usize);
+
+        let mut file = File::open(log_file_path).await?;
+
+        Pin::new(&mut file).start_seek(SeekFrom::End(-LOG_SUFFIX_LEN))?;
+        file.read_to_end(&mut buf).await?;
+
+        let suffix = String::from_utf8_lossy(&buf);
+        Ok(format!("log file tail:\n{suffix}"))
+    }
+
+    /// Read up to `LOG_SUFFIX_LEN` bytes from the end of the pipeline log in
+    /// order to include the suffix of the log in a diagnostic message.
+    async fn log_suffix(log_file_path: &Path) -> String {
+        Self::log_suffix_inner(log_file_path)
+            .await
+            .unwrap_or_else(|e| format!("[unable to read log file: {e}]"))
+    }
+}
+
+#[async_trait(?Send)]
+impl RunnerBackend for LocalRunner {
+    async fn start_pipeline(&self, request: &StartPipelineRequest) -> AnyResult<PipelineLocation> {
+        let mut pipeline_process = self.start(request).await?;
+
+        match Self::wait_for_startup(&self.config.log_file_path(request.pipeline_id)).await {
+            Ok(port) => {
+                self.processes
+                    .lock()
+                    .unwrap()
+                    .insert(request.pipeline_id, pipeline_process);
+                Ok(PipelineLocation {
+                    host: self.config.advertised_host.clone(),
+                    port,
+                })
+            }
+            Err(e) => {
+                let _ = pipeline_process.kill().await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn forward(
+        &self,
+        _pipeline_id: PipelineId,
+        location: &PipelineLocation,
+        method: Method,
+        endpoint: &str,
+    ) -> AnyResult<HttpResponse> {
+        // The pipeline runs on the same host as the runner.
+        forward_request(
+            method,
+            &format!("http://localhost:{}/{endpoint}", location.port),
+            None,
+        )
+        .await
+    }
+
+    async fn stop_pipeline(&self, pipeline_id: PipelineId) -> AnyResult<()> {
+        let process = self.processes.lock().unwrap().remove(&pipeline_id);
+        match process {
+            None => Err(AnyError::msg(format!(
+                "no process for pipeline '{pipeline_id}' was started by this runner"
+            ))),
+            Some(mut process) => {
+                if process.try_wait()?.is_none() {
+                    process.kill().await?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    async fn delete_pipeline(&self, pipeline_id: PipelineId) -> AnyResult<()> {
+        self.processes.lock().unwrap().remove(&pipeline_id);
+        remove_dir_all(self.config.pipeline_dir(pipeline_id)).await?;
+        Ok(())
+    }
+}
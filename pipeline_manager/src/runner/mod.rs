@@ -0,0 +1,781 @@
+use crate::{
+    config::RunnerConfig, db::PipelineDescr, ErrorResponse, ManagerConfig, NewPipelineRequest,
+    NewPipelineResponse, PipelineId, PipelineStatusResponse, ProjectDB, ProjectId, ProjectStatus,
+    Version,
+};
+use actix_web::{body::to_bytes, http::Method, rt::spawn, HttpResponse};
+use anyhow::{Error as AnyError, Result as AnyResult};
+use async_trait::async_trait;
+use awc::Client;
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap, error::Error as StdError, fmt, fmt::Display, process::Stdio, sync::Arc,
+};
+use tokio::{
+    fs,
+    fs::{create_dir_all, remove_file},
+    process::{Child, Command},
+    sync::Mutex,
+    task::JoinHandle,
+};
+use utoipa::ToSchema;
+
+mod agent;
+mod local;
+mod monitor;
+mod remote;
+
+pub(crate) use agent::run_agent;
+pub(crate) use local::LocalRunner;
+pub(crate) use remote::RemoteRunner;
+
+#[derive(Debug)]
+pub(crate) enum RunnerError {
+    PipelineShutdown(PipelineId),
+    UnknownRunner(String),
+    ProjectNotCompiled(ProjectId, Version),
+}
+
+impl Display for RunnerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunnerError::PipelineShutdown(pipeline_id) => {
+                write!(f, "Pipeline '{pipeline_id}' has been shut down")
+            }
+            RunnerError::UnknownRunner(runner) => {
+                write!(f, "Unknown runner '{runner}'")
+            }
+            RunnerError::ProjectNotCompiled(project_id, version) => {
+                write!(
+                    f,
+                    "Version '{version}' of project '{project_id}' hasn't been compiled"
+                )
+            }
+        }
+    }
+}
+
+impl StdError for RunnerError {}
+
+/// Pipeline status tracked by the manager.
+#[derive(Clone, Debug, Serialize, Eq, PartialEq, ToSchema)]
+pub(crate) enum PipelineStatus {
+    /// The pipeline process is being restarted after a failure.
+    Starting,
+    /// The pipeline is processing inputs.
+    Running,
+    /// The pipeline process is running, but doesn't process inputs.  Newly
+    /// created pipelines are in this state until the user starts them.
+    Paused,
+    /// The pipeline crashed, became unreachable, or failed to restart.
+    Failed(String),
+    /// The pipeline has been shut down by the user.
+    ShutDown,
+}
+
+/// Location of a running pipeline.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct PipelineLocation {
+    /// Host name that the pipeline's HTTP server is reachable at, e.g., by
+    /// Prometheus.
+    pub host: String,
+    /// TCP port that the pipeline's HTTP server listens on.
+    pub port: u16,
+}
+
+/// Request to a runner backend to start a pipeline.
+///
+/// This request is sent to the remote runner agent as JSON (see
+/// [`RemoteRunner`]).  The request identifies the executable to run by
+/// project id and version; each backend looks the executable up in its own
+/// executables directory (see [`ManagerConfig::versioned_executable`]).
+#[derive(Serialize, Deserialize)]
+pub(crate) struct StartPipelineRequest {
+    /// Id of the new pipeline.
+    pub pipeline_id: PipelineId,
+    /// Project to run.
+    pub project_id: ProjectId,
+    /// Version of the project to run.
+    pub version: Version,
+    /// Pipeline configuration (YAML).
+    pub config: String,
+    /// Pipeline metadata (JSON, see [`PipelineMetadata`]).
+    pub metadata: String,
+}
+
+impl StartPipelineRequest {
+    /// Build a request to start pipeline `pipeline_id` running version
+    /// `version` of project `project_id` with config `config`.
+    ///
+    /// Fails with [`RunnerError::ProjectNotCompiled`] if the executable for
+    /// this version of the project is not available.
+    async fn new(
+        manager_config: &ManagerConfig,
+        db: &ProjectDB,
+        pipeline_id: PipelineId,
+        project_id: ProjectId,
+        version: Version,
+        config: String,
+    ) -> AnyResult<Self> {
+        let code = db.project_code_version(project_id, version).await?;
+
+        // The current version of the project may have been compiled by a
+        // previous instance of the manager, whose file system state we don't
+        // trust (see `ProjectDB::reset_project_status`).  For older versions,
+        // the executable is the only evidence of successful compilation.
+        let project_descr = db.get_project(project_id).await?;
+        let executable = manager_config.versioned_executable(project_id, version);
+        if (project_descr.version == version && project_descr.status != ProjectStatus::Success)
+            || fs::metadata(&executable).await.is_err()
+        {
+            return Err(AnyError::from(RunnerError::ProjectNotCompiled(
+                project_id, version,
+            )));
+        }
+
+        let metadata = PipelineMetadata {
+            project_id,
+            version,
+            code,
+        };
+
+        Ok(Self {
+            pipeline_id,
+            project_id,
+            version,
+            config,
+            metadata: serde_json::to_string(&metadata).unwrap(),
+        })
+    }
+}
+
+/// Backend that runs pipeline processes on behalf of the [`Runner`].
+///
+/// The manager records the name of the backend that started each pipeline in
+/// the database and sends all subsequent requests to the pipeline through the
+/// same backend.
+#[async_trait(?Send)]
+pub(crate) trait RunnerBackend: Send + Sync {
+    /// Start a pipeline and wait for it to initialize.
+    async fn start_pipeline(&self, request: &StartPipelineRequest) -> AnyResult<PipelineLocation>;
+
+    /// Send an HTTP request to `endpoint` of a running pipeline and return
+    /// the pipeline's response.
+    ///
+    /// Returns an error if the pipeline cannot be reached.
+    async fn forward(
+        &self,
+        pipeline_id: PipelineId,
+        location: &PipelineLocation,
+        method: Method,
+        endpoint: &str,
+    ) -> AnyResult<HttpResponse>;
+
+    /// Kill the pipeline process if it is still running and wait for it to
+    /// exit.
+    ///
+    /// Fails if the backend cannot confirm that the process has exited, e.g.,
+    /// because the process was started by a previous instance of the backend.
+    async fn stop_pipeline(&self, pipeline_id: PipelineId) -> AnyResult<()>;
+
+    /// Delete the file system state of a pipeline that is no longer running.
+    async fn delete_pipeline(&self, pipeline_id: PipelineId) -> AnyResult<()>;
+}
+
+/// Runner backends by name.
+type Backends = BTreeMap<String, Box<dyn RunnerBackend>>;
+
+/// Lookup runner backend by name.
+fn backend<'a>(backends: &'a Backends, runner: &str) -> AnyResult<&'a dyn RunnerBackend> {
+    backends
+        .get(runner)
+        .map(|backend| backend.as_ref())
+        .ok_or_else(|| AnyError::from(RunnerError::UnknownRunner(runner.to_string())))
+}
+
+impl PipelineDescr {
+    fn location(&self) -> PipelineLocation {
+        PipelineLocation {
+            host: self.host.clone(),
+            port: self.port,
+        }
+    }
+}
+
+impl RunnerConfig {
+    /// Instantiate the runner backend described by `self`.
+    fn backend(&self, config: &ManagerConfig) -> Box<dyn RunnerBackend> {
+        match self {
+            RunnerConfig::Local => Box::new(LocalRunner::new(config)),
+            RunnerConfig::Remote { url, token } => Box::new(RemoteRunner::new(url, token)),
+        }
+    }
+}
+
+/// The Runner component responsible for running and interacting with
+/// pipelines at runtime.
+///
+/// # Runner backends
+///
+/// Pipelines are started by one of the runner backends configured in
+/// [`ManagerConfig::runners`]:
+///
+/// * [`LocalRunner`] runs pipelines as child processes of the manager.
+///
+/// * [`RemoteRunner`] delegates to a runner agent, a small HTTP service that
+///   runs pipelines as its own child processes, e.g., on a different host or
+///   in a container (see [`run_agent`]).
+///
+/// There is no dedicated container backend: to run pipelines in containers,
+/// run a runner agent inside a container that mounts the manager's
+/// executables directory.
+///
+/// The name of the backend that started the pipeline is stored in the
+/// database along with the pipeline's host and port.  Requests to the
+/// pipeline, e.g., `start`, `pause`, `status`, and `metrics`, are proxied
+/// through the same backend.
+///
+/// # Starting a pipeline
+///
+/// Starting a pipeline amounts to running the compiled executable with
+/// selected config, and monitoring the pipeline log file for either
+/// "Started HTTP server on port XXXXX" or "Failed to create server
+/// [detailed error message]".  In the former case, the host and port number
+/// are recorded in the database.  In the latter case, the error message is
+/// returned to the client.
+///
+/// # Killing a pipeline
+///
+/// To stop the pipeline, the runner sends a `/kill` HTTP request to the
+/// pipeline.  This request is asynchronous: the pipeline may continue running
+/// for a few seconds after the request succeeds.
+///
+/// # Pipeline status
+///
+/// The runner records the status of each pipeline ([`PipelineStatus`]) in
+/// the database along with the history of status transitions.  User
+/// requests to start, pause, and shut down the pipeline update its status.
+/// A background [`PipelineMonitor`](`monitor::PipelineMonitor`) detects
+/// failed pipelines and optionally restarts them (see
+/// [`ManagerConfig::pipeline_monitor`]).
+///
+/// # Prometheus
+///
+/// The runner registers pipelines with Prometheus using the
+/// `file_sd_config` mechanism.  When creating a pipeline, the runner
+/// creates a Prometheus target config file in a directory, which Prometheus
+/// monitors.
+pub struct Runner {
+    db: Arc<Mutex<ProjectDB>>,
+    config: ManagerConfig,
+    backends: Arc<Backends>,
+    // TODO: The Prometheus server should be isntantiated and managed by k8s.
+    prometheus_server: Option<Child>,
+    monitor_task: JoinHandle<()>,
+}
+
+/// Pipeline metadata.
+///
+/// A pipeline is initialized with a static metadata string (supplied via
+/// file read on startup).  This metadata is accessed via pipeline's
+/// `/metadata` endpoint.  The pipeline doesn't enforce any particular
+/// format, schema, or semantics of the metadata string, but various tools
+/// and UIs may.
+#[derive(Serialize)]
+struct PipelineMetadata {
+    /// Project id.
+    project_id: ProjectId,
+    /// Project version.
+    version: Version,
+    /// Project code.
+    code: String,
+}
+
+impl Drop for Runner {
+    fn drop(&mut self) {
+        self.monitor_task.abort();
+        if let Some(mut prometheus) = self.prometheus_server.take() {
+            let _ = prometheus.start_kill();
+        }
+    }
+}
+
+impl Runner {
+    pub(crate) async fn new(db: Arc<Mutex<ProjectDB>>, config: &ManagerConfig) -> AnyResult<Self> {
+        if !config.runners.contains_key(&config.default_runner) {
+            return Err(AnyError::msg(format!(
+                "default runner '{}' is not defined in the 'runners' section of the config",
+                config.default_runner
+            )));
+        }
+
+        let backends: Arc<Backends> = Arc::new(
+            config
+                .runners
+                .iter()
+                .map(|(name, runner_config)| (name.clone(), runner_config.backend(config)))
+                .collect(),
+        );
+
+        // Initialize Prometheus.
+        let prometheus_server = Self::start_prometheus(config).await?;
+
+        // Runner backends use `awc`, whose futures are not `Send`, so we
+        // spawn the monitor on the current thread.
+        let monitor_task =
+            spawn(monitor::PipelineMonitor::new(db.clone(), config, backends.clone()).run());
+
+        Ok(Self {
+            db,
+            config: config.clone(),
+            backends,
+            prometheus_server,
+            monitor_task,
+        })
+    }
+
+    /// Lookup runner backend by name.
+    fn backend(&self, runner: &str) -> AnyResult<&dyn RunnerBackend> {
+        backend(&self.backends, runner)
+    }
+
+    async fn start_prometheus(config: &ManagerConfig) -> AnyResult<Option<Child>> {
+        // Create `prometheus` dir before starting any pipelines so that the
+        // Prometheus server can locate the directory to scan.
+        let prometheus_dir = config.prometheus_dir();
+        create_dir_all(&prometheus_dir).await.map_err(|e| {
+            AnyError::msg(format!(
+                "error creating Prometheus configs directory '{}': {e}",
+                prometheus_dir.display()
+            ))
+        })?;
+
+        // FIXME: This should be handled by the deployment infrastructure.
+        if config.with_prometheus {
+            // Prometheus server configuration.
+            let prometheus_config = format!(
+                r#"
+global:
+  scrape_interval: 5s
+
+scrape_configs:
+  - job_name: dbsp
+    file_sd_configs:
+    - files:
+      - '{}/pipeline*.yaml'
+"#,
+                prometheus_dir.display()
+            );
+            let prometheus_config_file = config.prometheus_server_config_file();
+            fs::write(&prometheus_config_file, prometheus_config)
+                .await
+                .map_err(|e| {
+                    AnyError::msg(format!(
+                        "error writing Prometheus config file '{}': {e}",
+                        prometheus_config_file.display()
+                    ))
+                })?;
+
+            // Start the Prometheus server, which will
+            // inherit stdout, stderr from us.
+            let prometheus_process = Command::new("prometheus")
+                .arg("--config.file")
+                .arg(&prometheus_config_file)
+                .stdin(Stdio::null())
+                .spawn()
+                .map_err(|e| AnyError::msg(format!("failed to start Prometheus server, {e}")))?;
+
+            Ok(Some(prometheus_process))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Start a new pipeline.
+    ///
+    /// Starts the pipeline using the runner backend specified in the request
+    /// or the default backend and waits for the pipeline to initialize,
+    /// returning pipeline id, host, and port number.
+    ///
+    /// The request may specify any past version of the project and config,
+    /// as long as the executable for that project version is available.
+    pub(crate) async fn run_pipeline(
+        &self,
+        request: &NewPipelineRequest,
+    ) -> AnyResult<HttpResponse> {
+        let runner = request
+            .runner
+            .clone()
+            .unwrap_or_else(|| self.config.default_runner.clone());
+        let backend = self.backend(&runner)?;
+
+        let db = self.db.lock().await;
+
+        let project_descr = db.get_project(request.project_id).await?;
+
+        // Read and validate the requested version of the project config.
+        let config_descr = db
+            .get_config_version(request.config_id, request.config_version)
+            .await?;
+
+        if config_descr.project_id != request.project_id {
+            return Ok(HttpResponse::BadRequest().body(format!(
+                "Config '{}' does not belong to project '{}'",
+                request.config_id, request.project_id
+            )));
+        }
+
+        let pipeline_id = db.alloc_pipeline_id().await?;
+
+        // let config_yaml = self.create_topics(config_yaml).await?;
+
+        // Fails if the requested project version hasn't been compiled.
+        let start_request = StartPipelineRequest::new(
+            &self.config,
+            &db,
+            pipeline_id,
+            request.project_id,
+            request.project_version,
+            config_descr.config.clone(),
+        )
+        .await?;
+
+        // Unlock db -- the next part can be slow.
+        drop(db);
+
+        let location = backend.start_pipeline(&start_request).await?;
+
+        // Store pipeline in the database.
+        if let Err(e) = self
+            .db
+            .lock()
+            .await
+            .new_pipeline(
+                pipeline_id,
+                request.project_id,
+                request.project_version,
+                &config_descr,
+                &runner,
+                &location.host,
+                location.port,
+            )
+            .await
+        {
+            let _ = backend
+                .forward(pipeline_id, &location, Method::GET, "kill")
+                .await;
+            return Err(e);
+        };
+        let json_string = serde_json::to_string(&NewPipelineResponse {
+            pipeline_id,
+            host: location.host.clone(),
+            port: location.port,
+        })
+        .unwrap();
+
+        // Create Prometheus config file for the pipeline.
+        // The Prometheus server should pick up this file automatically.
+        create_prometheus_config(
+            &self.config,
+            &project_descr.name,
+            request.project_id,
+            pipeline_id,
+            &location,
+        )
+        .await
+        .unwrap_or_else(|e| {
+            // Don't abandon pipeline, just log the error.
+            error!("Failed to create Prometheus config file for pipeline '{pipeline_id}': {e}");
+        });
+
+        Ok(HttpResponse::Ok()
+            .content_type(mime::APPLICATION_JSON)
+            .body(json_string))
+    }
+
+    /*
+    async fn create_topics(&self, config_yaml: &str) -> AnyResult<(KafkaResources, String)> {
+        let mut config: ControllerConfig = serde_yaml::from_str(config_yaml)
+            .map_err(|e| AnyError::msg(format!("error parsing pipeline configuration: {e}")))?;
+
+        for (_, input_config) in config.inputs.iter_mut() {
+            if input_config.transport.name == "kafka" {
+                if let Some(topics) = input_config.transport.config.get("topics") {
+                    let topics_string = serde_yaml::to_string(topics);
+                    let topics: Vec<String> = serde_yaml::from_value(topics)
+                        .map_err(|e| AnyError::msg(format!("error parsing Kafka topic list '{topics_string}': {e}")))?;
+                    for topic in topics.iter_mut() {
+                        if topic == "?" {
+                            *topic = self.generate_topic_name("input_");
+                        }
+                    }
+                    input_config.transport.config.set("topics", serde_yaml::to_value(topics))
+                }
+            }
+        }
+
+        for (_, output_config) in config.outputs.iter_mut() {
+            if input_config.transport.name == "kafka" {
+                if let Some(YamlValue::String(topic) = input_config.transport.config.get_mut("topic") {
+                    if topic == "?" {
+                        topic = Self::generate_topic_name("output_");
+                    }
+                }
+            }
+        }
+
+        let new_config = serde_yaml::to_string(config);
+
+        Ok((kafka_resources, new_config))
+
+    }
+    */
+
+    /// Send a `/kill` request to the pipeline process, but keep the pipeline
+    /// state in the database and file system.
+    ///
+    /// After calling this method, the user can still do post-mortem analysis
+    /// of the pipeline, e.g., access its logs or study its performance
+    /// metrics in Prometheus.
+    ///
+    /// Use the [`delete_pipeline`](`Self::delete_pipeline`) method to remove
+    /// all traces of the pipeline from the manager.
+    pub(crate) async fn shutdown_pipeline(
+        &self,
+        pipeline_id: PipelineId,
+    ) -> AnyResult<HttpResponse> {
+        let db = self.db.lock().await;
+
+        self.do_shutdown_pipeline(&db, pipeline_id).await
+    }
+
+    async fn do_shutdown_pipeline(
+        &self,
+        db: &ProjectDB,
+        pipeline_id: PipelineId,
+    ) -> AnyResult<HttpResponse> {
+        let pipeline = db.get_pipeline(pipeline_id).await?;
+
+        if pipeline.killed {
+            return Ok(HttpResponse::Ok()
+                .body(serde_json::to_string("Pipeline already shut down.").unwrap()));
+        };
+
+        let backend = self.backend(&pipeline.runner)?;
+        let response = match backend
+            .forward(pipeline_id, &pipeline.location(), Method::GET, "kill")
+            .await
+        {
+            Ok(response) => response,
+            Err(_) => {
+                db.set_pipeline_killed(pipeline_id).await?;
+                // We failed to reach the pipeline, which likely means
+                // that it crashed or was killed manually by the user.
+                return Ok(HttpResponse::Ok().body(
+                    serde_json::to_string(&format!(
+                        "Pipeline at '{}:{}' already shut down.",
+                        pipeline.host, pipeline.port
+                    ))
+                    .unwrap(),
+                ));
+            }
+        };
+
+        if response.status().is_success() {
+            db.set_pipeline_killed(pipeline_id).await?;
+            Ok(HttpResponse::Ok()
+                .body(serde_json::to_string("Pipeline successfully terminated.").unwrap()))
+        } else {
+            Ok(HttpResponse::InternalServerError().body(
+                serde_json::to_string(&ErrorResponse::new(&format!(
+                    "Failed to shut down the pipeline; response from pipeline controller: {}",
+                    response.status()
+                )))
+                .unwrap(),
+            ))
+        }
+    }
+
+    /// Kill the pipeline if it is still running, delete its file system state
+    /// and remove the pipeline from the database.
+    pub(crate) async fn delete_pipeline(&self, pipeline_id: PipelineId) -> AnyResult<HttpResponse> {
+        let db = self.db.lock().await;
+
+        // Kill pipeline.
+        let response = self.do_shutdown_pipeline(&db, pipeline_id).await?;
+        if !response.status().is_success() {
+            return Ok(response);
+        }
+
+        // TODO: Delete temporary topics.
+
+        // Delete Prometheus config.
+        let _ = remove_file(self.config.prometheus_pipeline_config_file(pipeline_id)).await;
+
+        // Delete pipeline directory.
+        let pipeline = db.get_pipeline(pipeline_id).await?;
+        self.backend(&pipeline.runner)?
+            .delete_pipeline(pipeline_id)
+            .await?;
+        db.delete_pipeline(pipeline_id).await?;
+
+        Ok(HttpResponse::Ok()
+            .body(serde_json::to_string("Pipeline successfully deleted.").unwrap()))
+    }
+
+    /// Forward an HTTP request to `endpoint` of a running pipeline through
+    /// the runner backend that started the pipeline.
+    pub(crate) async fn forward_to_pipeline(
+        &self,
+        pipeline_id: PipelineId,
+        method: Method,
+        endpoint: &str,
+    ) -> AnyResult<HttpResponse> {
+        let pipeline = self.db.lock().await.get_pipeline(pipeline_id).await?;
+
+        if pipeline.killed {
+            return Err(AnyError::from(RunnerError::PipelineShutdown(pipeline_id)));
+        }
+
+        self.backend(&pipeline.runner)?
+            .forward(pipeline_id, &pipeline.location(), method, endpoint)
+            .await
+            .map_err(|e| AnyError::msg(format!("Failed to connect to pipeline: {e}")))
+    }
+
+    /// Send a `/start` request to the pipeline and mark the pipeline as
+    /// running if the request succeeds.
+    pub(crate) async fn start_pipeline(&self, pipeline_id: PipelineId) -> AnyResult<HttpResponse> {
+        self.forward_and_set_status(pipeline_id, "start", PipelineStatus::Running)
+            .await
+    }
+
+    /// Send a `/pause` request to the pipeline and mark the pipeline as
+    /// paused if the request succeeds.
+    pub(crate) async fn pause_pipeline(&self, pipeline_id: PipelineId) -> AnyResult<HttpResponse> {
+        self.forward_and_set_status(pipeline_id, "pause", PipelineStatus::Paused)
+            .await
+    }
+
+    async fn forward_and_set_status(
+        &self,
+        pipeline_id: PipelineId,
+        endpoint: &str,
+        status: PipelineStatus,
+    ) -> AnyResult<HttpResponse> {
+        let response = self
+            .forward_to_pipeline(pipeline_id, Method::GET, endpoint)
+            .await?;
+
+        if response.status().is_success() {
+            self.db
+                .lock()
+                .await
+                .set_pipeline_status(pipeline_id, status)
+                .await?;
+        }
+
+        Ok(response)
+    }
+
+    /// Retrieve pipeline status tracked by the manager along with the status
+    /// reported by the pipeline itself.
+    pub(crate) async fn pipeline_status(&self, pipeline_id: PipelineId) -> AnyResult<HttpResponse> {
+        let db = self.db.lock().await;
+        let pipeline = db.get_pipeline(pipeline_id).await?;
+        let history = db.pipeline_history(pipeline_id).await?;
+        drop(db);
+
+        let controller = match pipeline.status {
+            PipelineStatus::Running | PipelineStatus::Paused if !pipeline.killed => {
+                match self
+                    .backend(&pipeline.runner)?
+                    .forward(pipeline_id, &pipeline.location(), Method::GET, "status")
+                    .await
+                {
+                    Ok(response) if response.status().is_success() => {
+                        to_bytes(response.into_body())
+                            .await
+                            .ok()
+                            .and_then(|body| serde_json::from_slice(&body).ok())
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+
+        let json_string = serde_json::to_string(&PipelineStatusResponse {
+            pipeline,
+            history,
+            controller,
+        })
+        .unwrap();
+
+        Ok(HttpResponse::Ok()
+            .content_type(mime::APPLICATION_JSON)
+            .body(json_string))
+    }
+}
+
+/// Create Prometheus config file for a pipeline.
+async fn create_prometheus_config(
+    config: &ManagerConfig,
+    project_name: &str,
+    project_id: ProjectId,
+    pipeline_id: PipelineId,
+    location: &PipelineLocation,
+) -> AnyResult<()> {
+    let PipelineLocation { host, port } = location;
+    let prometheus_config = format!(
+        r#"- targets: [ "{host}:{port}" ]
+  labels:
+    project_name: "{project_name}"
+    pipeline_id: {pipeline_id}
+    project_id: {project_id}"#
+    );
+    fs::write(
+        config.prometheus_pipeline_config_file(pipeline_id),
+        prometheus_config,
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Send an HTTP request to `url` and convert the response into an
+/// `HttpResponse` that can be returned to the client.
+///
+/// Sends `token`, if any, in the `Authorization: Bearer` header.
+async fn forward_request(
+    method: Method,
+    url: &str,
+    token: Option<&str>,
+) -> AnyResult<HttpResponse> {
+    let client = Client::default();
+    let mut request = client.request(method, url);
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+
+    let mut response = request
+        .send()
+        .await
+        .map_err(|e| AnyError::msg(format!("{e}")))?;
+
+    let response_body = response.body().await?;
+
+    let mut response_builder = HttpResponse::build(response.status());
+    // Remove `Connection` as per
+    // https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Connection#Directives
+    for (header_name, header_value) in response
+        .headers()
+        .iter()
+        .filter(|(h, _)| *h != "connection")
+    {
+        response_builder.insert_header((header_name.clone(), header_value.clone()));
+    }
+
+    Ok(response_builder.body(response_body))
+}
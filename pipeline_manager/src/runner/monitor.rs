@@ -0,0 +1,475 @@
+use super::{backend, create_prometheus_config, Backends, StartPipelineRequest};
+use crate::{db::PipelineDescr, ManagerConfig, PipelineId, PipelineStatus, ProjectDB};
+use actix_web::http::Method;
+use anyhow::Result as AnyResult;
+use chrono::Utc;
+use log::{error, info, warn};
+use std::{collections::HashMap, sync::Arc};
+use tokio::{sync::Mutex, time::sleep};
+
+/// Background task that monitors the health of live pipelines and restarts
+/// failed pipelines.
+///
+/// Every `health_check_interval` the monitor sends a `/status` request to
+/// each pipeline in the [`Running`](`PipelineStatus::Running`) or
+/// [`Paused`](`PipelineStatus::Paused`) state.  A pipeline that cannot be
+/// reached in `failure_threshold` consecutive checks is marked as
+/// [`Failed`](`PipelineStatus::Failed`).
+///
+/// When `auto_restart` is enabled, the monitor restarts failed pipelines
+/// with the same project version and config, as long as the executable for
+/// the project version is available.  The n'th restart of a pipeline happens
+/// `restart_backoff * 2^(n-1)` after the failure; the monitor gives up
+/// after `max_restarts` attempts.  A restarted pipeline gets the state
+/// (running or paused) it was in before the failure.
+///
+/// An unreachable pipeline may still be running, so before restarting a
+/// pipeline the monitor asks the runner backend to kill the old process and
+/// confirm that it has exited (see
+/// [`RunnerBackend::stop_pipeline`](`super::RunnerBackend::stop_pipeline`)).
+/// If the backend cannot confirm this, e.g., because the process was started
+/// by a previous instance of the manager, the pipeline stays failed.
+///
+/// All status changes are performed conditionally on the pipeline still
+/// being in the state observed by the monitor, so the monitor never
+/// overrides concurrent status changes requested by the user, e.g., pipeline
+/// shutdown.
+pub(super) struct PipelineMonitor {
+    db: Arc<Mutex<ProjectDB>>,
+    config: ManagerConfig,
+    backends: Arc<Backends>,
+    failures: FailureCounter,
+}
+
+/// Counts consecutive failed health checks of each pipeline.
+#[derive(Default)]
+struct FailureCounter {
+    failures: HashMap<PipelineId, u32>,
+}
+
+impl FailureCounter {
+    /// Record the outcome of a health check of a pipeline and return the
+    /// number of consecutive failed checks of the pipeline.
+    fn record(&mut self, pipeline_id: PipelineId, healthy: bool) -> u32 {
+        if healthy {
+            self.failures.remove(&pipeline_id);
+            0
+        } else {
+            let failures = self.failures.entry(pipeline_id).or_insert(0);
+            *failures += 1;
+            *failures
+        }
+    }
+
+    /// Forget pipelines that are not being health-checked anymore.
+    fn retain(&mut self, pipelines: &[PipelineDescr]) {
+        self.failures.retain(|pipeline_id, _| {
+            pipelines.iter().any(|pipeline| {
+                pipeline.pipeline_id == *pipeline_id
+                    && matches!(
+                        pipeline.status,
+                        PipelineStatus::Running | PipelineStatus::Paused
+                    )
+            })
+        });
+    }
+}
+
+impl PipelineMonitor {
+    pub(super) fn new(
+        db: Arc<Mutex<ProjectDB>>,
+        config: &ManagerConfig,
+        backends: Arc<Backends>,
+    ) -> Self {
+        Self {
+            db,
+            config: config.clone(),
+            backends,
+            failures: FailureCounter::default(),
+        }
+    }
+
+    pub(super) async fn run(mut self) {
+        loop {
+            sleep(self.config.pipeline_monitor.health_check_interval()).await;
+
+            if let Err(e) = self.check_pipelines().await {
+                error!("pipeline monitor failed to check pipelines: '{e}'");
+            }
+        }
+    }
+
+    async fn check_pipelines(&mut self) -> AnyResult<()> {
+        let pipelines = self.db.lock().await.list_live_pipelines().await?;
+        self.failures.retain(&pipelines);
+
+        for pipeline in pipelines.iter() {
+            let result = match &pipeline.status {
+                PipelineStatus::Running | PipelineStatus::Paused => {
+                    self.check_health(pipeline).await
+                }
+                PipelineStatus::Failed(_) => {
+                    let failed_for = (Utc::now() - pipeline.status_since)
+                        .to_std()
+                        .unwrap_or_default();
+                    if self
+                        .config
+                        .pipeline_monitor
+                        .restart_due(pipeline.restarts, failed_for)
+                    {
+                        self.restart_pipeline(pipeline).await
+                    } else {
+                        Ok(())
+                    }
+                }
+                _ => Ok(()),
+            };
+
+            if let Err(e) = result {
+                error!(
+                    "pipeline monitor failed to process pipeline '{}': '{e}'",
+                    pipeline.pipeline_id
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Mark the pipeline as failed if it cannot be reached in
+    /// `failure_threshold` consecutive checks.
+    async fn check_health(&mut self, pipeline: &PipelineDescr) -> AnyResult<()> {
+        let pipeline_id = pipeline.pipeline_id;
+        let threshold = self.config.pipeline_monitor.failure_threshold;
+
+        let e = match backend(&self.backends, &pipeline.runner)?
+            .forward(pipeline_id, &pipeline.location(), Method::GET, "status")
+            .await
+        {
+            Ok(_) => {
+                self.failures.record(pipeline_id, true);
+                return Ok(());
+            }
+            Err(e) => e,
+        };
+
+        let failures = self.failures.record(pipeline_id, false);
+        if failures < threshold {
+            warn!("Health check {failures} of {threshold} of pipeline '{pipeline_id}' failed: {e}");
+            return Ok(());
+        }
+
+        let error = format!(
+            "pipeline at '{}:{}' is unreachable after {failures} consecutive health checks: {e}",
+            pipeline.host, pipeline.port
+        );
+        if self
+            .db
+            .lock()
+            .await
+            .set_pipeline_status_guarded(
+                pipeline_id,
+                &pipeline.status,
+                PipelineStatus::Failed(error.clone()),
+            )
+            .await?
+        {
+            warn!("Pipeline '{pipeline_id}' failed: {error}");
+        }
+
+        Ok(())
+    }
+
+    async fn restart_pipeline(&self, pipeline: &PipelineDescr) -> AnyResult<()> {
+        let pipeline_id = pipeline.pipeline_id;
+        let backend = backend(&self.backends, &pipeline.runner)?;
+
+        let db = self.db.lock().await;
+        if !db
+            .set_pipeline_status_guarded(pipeline_id, &pipeline.status, PipelineStatus::Starting)
+            .await?
+        {
+            return Ok(());
+        }
+        db.increment_pipeline_restarts(pipeline_id).await?;
+
+        info!(
+            "Restarting pipeline '{pipeline_id}' (attempt {} of {})",
+            pipeline.restarts + 1,
+            self.config.pipeline_monitor.max_restarts
+        );
+
+        // Resume the pipeline if it was running when it failed.
+        let resume = db
+            .pipeline_history(pipeline_id)
+            .await?
+            .iter()
+            .rev()
+            .find_map(|event| match event.status {
+                PipelineStatus::Running => Some(true),
+                PipelineStatus::Paused => Some(false),
+                _ => None,
+            })
+            .unwrap_or(false);
+
+        let (request, project_name) = match self.restart_request(&db, pipeline).await {
+            Ok(request) => request,
+            Err(e) => {
+                db.set_pipeline_status(
+                    pipeline_id,
+                    PipelineStatus::Failed(format!("failed to restart pipeline: {e}")),
+                )
+                .await?;
+                return Ok(());
+            }
+        };
+
+        // Unlock db -- the next part can be slow.
+        drop(db);
+
+        // The old process may still be running if it was merely unreachable;
+        // make sure it has exited before starting a new process that uses the
+        // same pipeline directory and outputs.
+        if let Err(e) = backend.stop_pipeline(pipeline_id).await {
+            self.db
+                .lock()
+                .await
+                .set_pipeline_status_guarded(
+                    pipeline_id,
+                    &PipelineStatus::Starting,
+                    PipelineStatus::Failed(format!(
+                        "failed to restart pipeline: cannot confirm that the previous pipeline process has exited: {e}"
+                    )),
+                )
+                .await?;
+            return Ok(());
+        }
+
+        let location = match backend.start_pipeline(&request).await {
+            Ok(location) => location,
+            Err(e) => {
+                self.db
+                    .lock()
+                    .await
+                    .set_pipeline_status_guarded(
+                        pipeline_id,
+                        &PipelineStatus::Starting,
+                        PipelineStatus::Failed(format!("failed to restart pipeline: {e}")),
+                    )
+                    .await?;
+                return Ok(());
+            }
+        };
+
+        let db = self.db.lock().await;
+        db.set_pipeline_location(pipeline_id, &location.host, location.port)
+            .await?;
+        if !db
+            .set_pipeline_status_guarded(
+                pipeline_id,
+                &PipelineStatus::Starting,
+                PipelineStatus::Paused,
+            )
+            .await?
+        {
+            // The user shut down the pipeline while we were restarting it.
+            drop(db);
+            let _ = backend
+                .forward(pipeline_id, &location, Method::GET, "kill")
+                .await;
+            return Ok(());
+        }
+        drop(db);
+
+        create_prometheus_config(
+            &self.config,
+            &project_name,
+            pipeline.project_id,
+            pipeline_id,
+            &location,
+        )
+        .await
+        .unwrap_or_else(|e| {
+            error!("Failed to update Prometheus config file for pipeline '{pipeline_id}': {e}");
+        });
+
+        if resume {
+            let started = backend
+                .forward(pipeline_id, &location, Method::GET, "start")
+                .await
+                .map(|response| response.status().is_success())
+                .unwrap_or(false);
+            if started {
+                self.db
+                    .lock()
+                    .await
+                    .set_pipeline_status_guarded(
+                        pipeline_id,
+                        &PipelineStatus::Paused,
+                        PipelineStatus::Running,
+                    )
+                    .await?;
+            }
+        }
+
+        info!(
+            "Restarted pipeline '{pipeline_id}' at '{}:{}'",
+            location.host, location.port
+        );
+        Ok(())
+    }
+
+    /// Build a request to restart the pipeline with the same project version
+    /// and config; returns the request along with the project name.
+    async fn restart_request(
+        &self,
+        db: &ProjectDB,
+        pipeline: &PipelineDescr,
+    ) -> AnyResult<(StartPipelineRequest, String)> {
+        let project = db.get_project(pipeline.project_id).await?;
+        let config = db.pipeline_config(pipeline.pipeline_id).await?;
+        let request = StartPipelineRequest::new(
+            &self.config,
+            db,
+            pipeline.pipeline_id,
+            pipeline.project_id,
+            pipeline.project_version,
+            config,
+        )
+        .await?;
+
+        Ok((request, project.name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::FailureCounter;
+    use crate::{
+        config::PipelineMonitorConfig,
+        db::PipelineDescr,
+        runner::{LocalRunner, RunnerBackend, StartPipelineRequest},
+        ConfigId, ManagerConfig, PipelineId, PipelineStatus, ProjectId, Version,
+    };
+    use chrono::Utc;
+    use std::{fs, os::unix::fs::PermissionsExt, path::Path, time::Duration};
+
+    fn pipeline(pipeline_id: PipelineId, status: PipelineStatus) -> PipelineDescr {
+        PipelineDescr {
+            pipeline_id,
+            project_id: ProjectId(1),
+            project_version: Version::new(1),
+            config_id: ConfigId(1),
+            config_version: Version::new(1),
+            runner: "local".to_string(),
+            host: "localhost".to_string(),
+            port: 0,
+            killed: false,
+            status,
+            status_since: Utc::now(),
+            restarts: 0,
+            created: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_failure_counter() {
+        let mut failures = FailureCounter::default();
+
+        // A successful check resets the count.
+        assert_eq!(failures.record(PipelineId(1), false), 1);
+        assert_eq!(failures.record(PipelineId(1), false), 2);
+        assert_eq!(failures.record(PipelineId(1), true), 0);
+        assert_eq!(failures.record(PipelineId(1), false), 1);
+
+        // Pipelines are counted separately.
+        assert_eq!(failures.record(PipelineId(2), false), 1);
+        assert_eq!(failures.record(PipelineId(1), false), 2);
+
+        // Forget pipelines that failed or have been shut down.
+        failures.retain(&[
+            pipeline(PipelineId(1), PipelineStatus::Failed("error".to_string())),
+            pipeline(PipelineId(2), PipelineStatus::Running),
+        ]);
+        assert_eq!(failures.record(PipelineId(1), false), 1);
+        assert_eq!(failures.record(PipelineId(2), false), 2);
+    }
+
+    #[test]
+    fn test_restart_due() {
+        let policy = PipelineMonitorConfig {
+            auto_restart: true,
+            max_restarts: 3,
+            restart_backoff_ms: 1_000,
+            max_restart_backoff_ms: 3_000,
+            ..Default::default()
+        };
+
+        assert!(!policy.restart_due(0, Duration::from_millis(999)));
+        assert!(policy.restart_due(0, Duration::from_millis(1_000)));
+        assert!(!policy.restart_due(1, Duration::from_millis(1_999)));
+        assert!(policy.restart_due(1, Duration::from_millis(2_000)));
+
+        // The backoff is capped at `max_restart_backoff_ms`.
+        assert!(policy.restart_due(2, Duration::from_millis(3_000)));
+
+        // Give up after `max_restarts` attempts.
+        assert!(!policy.restart_due(3, Duration::from_secs(3600)));
+
+        let disabled = PipelineMonitorConfig {
+            auto_restart: false,
+            ..policy
+        };
+        assert!(!disabled.restart_due(0, Duration::from_secs(3600)));
+    }
+
+    #[actix_web::test]
+    async fn test_stop_pipeline() {
+        let dir = tempfile::tempdir().unwrap();
+        let config: ManagerConfig = serde_yaml::from_str(&format!(
+            "working_directory: {}\nsql_compiler_home: {}\n",
+            dir.path().display(),
+            dir.path().display()
+        ))
+        .unwrap();
+
+        // A fake pipeline that keeps running after initialization and
+        // records its process id.
+        let executable = config.versioned_executable(ProjectId(1), Version::new(1));
+        fs::create_dir_all(config.project_executables_dir(ProjectId(1))).unwrap();
+        fs::write(
+            &executable,
+            "#!/bin/sh\necho $$ > pid\necho 'Started HTTP server on port 1234' >&2\nexec sleep 600\n",
+        )
+        .unwrap();
+        fs::set_permissions(&executable, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let runner = LocalRunner::new(&config);
+        let pipeline_id = PipelineId(1);
+        let request = StartPipelineRequest {
+            pipeline_id,
+            project_id: ProjectId(1),
+            version: Version::new(1),
+            config: String::new(),
+            metadata: String::new(),
+        };
+
+        runner.start_pipeline(&request).await.unwrap();
+        let pid = fs::read_to_string(config.pipeline_dir(pipeline_id).join("pid")).unwrap();
+        let proc_dir = Path::new("/proc").join(pid.trim());
+        assert!(proc_dir.exists());
+
+        // A second process cannot be started while the first one is running.
+        assert!(runner.start_pipeline(&request).await.is_err());
+
+        // Stopping the pipeline waits for the process to exit.
+        runner.stop_pipeline(pipeline_id).await.unwrap();
+        assert!(!proc_dir.exists());
+
+        // The process is gone, so we cannot stop it again, but can restart
+        // the pipeline.
+        assert!(runner.stop_pipeline(pipeline_id).await.is_err());
+        runner.start_pipeline(&request).await.unwrap();
+        runner.stop_pipeline(pipeline_id).await.unwrap();
+    }
+}
@@ -0,0 +1,306 @@
+use super::{forward_request, PipelineLocation, RunnerBackend, StartPipelineRequest};
+use crate::PipelineId;
+use actix_web::{
+    http::{Method, StatusCode},
+    HttpResponse,
+};
+use anyhow::{Error as AnyError, Result as AnyResult};
+use async_trait::async_trait;
+use awc::Client;
+use std::time::Duration;
+
+/// Timeout for the agent to start a pipeline.  Must exceed the time the
+/// agent waits for the pipeline to initialize.
+const START_TIMEOUT: Duration = Duration::from_millis(20_000);
+
+/// Runner backend that delegates to a runner agent.
+///
+/// A runner agent is a pipeline manager instance started with the `--agent`
+/// flag (see [`run_agent`](`super::run_agent`)).  It runs pipelines as its
+/// own child processes using a [`LocalRunner`](`super::LocalRunner`), e.g.,
+/// on a different host or inside a container, and proxies requests to them.
+/// The agent looks up compiled project executables by project id and version
+/// in its own executables directory (see
+/// [`ManagerConfig::executables_directory`](`crate::ManagerConfig::executables_directory`)),
+/// which must be populated with the executables compiled by the manager,
+/// e.g., via a shared volume.
+///
+/// Every request to the agent carries the shared secret configured for the
+/// runner in the `Authorization: Bearer` header.  The agent rejects requests
+/// without a valid secret with `401 Unauthorized`.
+///
+/// The agent exposes the following endpoints:
+///
+/// * `POST /pipelines` - start a pipeline described by a JSON-encoded
+///   [`StartPipelineRequest`]; returns the location of the pipeline.
+///
+/// * `GET|POST /pipelines/{pipeline_id}/{endpoint}` - forward the request to
+///   `endpoint` of the pipeline.  Returns `502 Bad Gateway` if the pipeline
+///   cannot be reached.
+///
+/// * `DELETE /pipelines/{pipeline_id}/process` - kill the pipeline process
+///   and wait for it to exit.
+///
+/// * `DELETE /pipelines/{pipeline_id}` - delete the pipeline's file system
+///   state.
+pub(crate) struct RemoteRunner {
+    /// Agent URL, e.g., `http://agent:8080`.
+    url: String,
+    /// Secret shared with the agent.
+    token: String,
+}
+
+impl RemoteRunner {
+    pub(crate) fn new(url: &str, token: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+        }
+    }
+
+    fn connection_error<E>(&self, error: E) -> AnyError
+    where
+        E: std::fmt::Display,
+    {
+        AnyError::msg(format!(
+            "failed to connect to runner agent at '{}': {error}",
+            self.url
+        ))
+    }
+}
+
+#[async_trait(?Send)]
+impl RunnerBackend for RemoteRunner {
+    async fn start_pipeline(&self, request: &StartPipelineRequest) -> AnyResult<PipelineLocation> {
+        let mut response = Client::default()
+            .post(format!("{}/pipelines", self.url))
+            .bearer_auth(&self.token)
+            .timeout(START_TIMEOUT)
+            .send_json(request)
+            .await
+            .map_err(|e| self.connection_error(e))?;
+
+        if !response.status().is_success() {
+            let body = response.body().await?;
+            return Err(AnyError::msg(format!(
+                "runner agent at '{}' failed to start the pipeline: {}",
+                self.url,
+                String::from_utf8_lossy(&body)
+            )));
+        }
+
+        response
+            .json::<PipelineLocation>()
+            .await
+            .map_err(|e| AnyError::msg(format!("invalid response from runner agent: {e}")))
+    }
+
+    async fn forward(
+        &self,
+        pipeline_id: PipelineId,
+        _location: &PipelineLocation,
+        method: Method,
+        endpoint: &str,
+    ) -> AnyResult<HttpResponse> {
+        let response = forward_request(
+            method,
+            &format!("{}/pipelines/{pipeline_id}/{endpoint}", self.url),
+            Some(&self.token),
+        )
+        .await
+        .map_err(|e| self.connection_error(e))?;
+
+        if response.status() == StatusCode::BAD_GATEWAY {
+            Err(AnyError::msg(format!(
+                "runner agent at '{}' cannot reach pipeline '{pipeline_id}'",
+                self.url
+            )))
+        } else if response.status() == StatusCode::UNAUTHORIZED {
+            Err(AnyError::msg(format!(
+                "runner agent at '{}' rejected the runner token",
+                self.url
+            )))
+        } else {
+            Ok(response)
+        }
+    }
+
+    async fn stop_pipeline(&self, pipeline_id: PipelineId) -> AnyResult<()> {
+        let mut response = Client::default()
+            .delete(format!("{}/pipelines/{pipeline_id}/process", self.url))
+            .bearer_auth(&self.token)
+            .send()
+            .await
+            .map_err(|e| self.connection_error(e))?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            let body = response.body().await?;
+            Err(AnyError::msg(format!(
+                "runner agent at '{}' failed to stop pipeline '{pipeline_id}': {}",
+                self.url,
+                String::from_utf8_lossy(&body)
+            )))
+        }
+    }
+
+    async fn delete_pipeline(&self, pipeline_id: PipelineId) -> AnyResult<()> {
+        let mut response = Client::default()
+            .delete(format!("{}/pipelines/{pipeline_id}", self.url))
+            .bearer_auth(&self.token)
+            .send()
+            .await
+            .map_err(|e| self.connection_error(e))?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            let body = response.body().await?;
+            Err(AnyError::msg(format!(
+                "runner agent at '{}' failed to delete pipeline '{pipeline_id}': {}",
+                self.url,
+                String::from_utf8_lossy(&body)
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RemoteRunner;
+    use crate::{
+        runner::{agent::build_agent_app, agent::AgentState, RunnerBackend, StartPipelineRequest},
+        ManagerConfig, PipelineId, ProjectId, Version,
+    };
+    use actix_web::{body::to_bytes, http::Method, rt::spawn, web, App, HttpResponse, HttpServer};
+    use std::{
+        fs,
+        os::unix::fs::{symlink, PermissionsExt},
+    };
+
+    fn start_request(pipeline_id: PipelineId, version: Version) -> StartPipelineRequest {
+        StartPipelineRequest {
+            pipeline_id,
+            project_id: ProjectId(1),
+            version,
+            config: String::new(),
+            metadata: String::new(),
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_remote_runner() {
+        // A fake pipeline server.
+        let pipeline_server = HttpServer::new(|| {
+            App::new().route(
+                "/status",
+                web::get().to(|| async { HttpResponse::Ok().body("running") }),
+            )
+        })
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let pipeline_port = pipeline_server.addrs()[0].port();
+        spawn(pipeline_server.run());
+
+        let dir = tempfile::tempdir().unwrap();
+        let config: ManagerConfig = serde_yaml::from_str(&format!(
+            "working_directory: {}\nsql_compiler_home: {}\nadvertised_host: agent-host\nagent_token: secret\n",
+            dir.path().display(),
+            dir.path().display()
+        ))
+        .unwrap();
+
+        // A fake pipeline executable that reports the port of the fake server.
+        let executable = config.versioned_executable(ProjectId(1), Version::new(1));
+        fs::create_dir_all(config.project_executables_dir(ProjectId(1))).unwrap();
+        fs::write(
+            &executable,
+            format!("#!/bin/sh\necho 'Started HTTP server on port {pipeline_port}' >&2\n"),
+        )
+        .unwrap();
+        fs::set_permissions(&executable, fs::Permissions::from_mode(0o755)).unwrap();
+
+        // An executable outside of the executables directory.
+        let outside = dir.path().join("outside.sh");
+        fs::copy(&executable, &outside).unwrap();
+        symlink(
+            &outside,
+            config.versioned_executable(ProjectId(1), Version::new(2)),
+        )
+        .unwrap();
+
+        // Start the agent.
+        let state = web::Data::new(AgentState::new(&config).unwrap());
+        let agent_server = HttpServer::new(move || build_agent_app(App::new(), state.clone()))
+            .bind(("127.0.0.1", 0))
+            .unwrap();
+        let agent_port = agent_server.addrs()[0].port();
+        spawn(agent_server.run());
+
+        let agent_url = format!("http://127.0.0.1:{agent_port}/");
+        let runner = RemoteRunner::new(&agent_url, "secret");
+        let pipeline_id = PipelineId(1);
+
+        // Requests without the shared secret are rejected.
+        let intruder = RemoteRunner::new(&agent_url, "guess");
+        assert!(intruder
+            .start_pipeline(&start_request(pipeline_id, Version::new(1)))
+            .await
+            .is_err());
+
+        // Executables outside of the executables directory or that don't
+        // exist are rejected.
+        assert!(runner
+            .start_pipeline(&start_request(pipeline_id, Version::new(2)))
+            .await
+            .is_err());
+        assert!(runner
+            .start_pipeline(&start_request(pipeline_id, Version::new(3)))
+            .await
+            .is_err());
+
+        let location = runner
+            .start_pipeline(&start_request(pipeline_id, Version::new(1)))
+            .await
+            .unwrap();
+        assert_eq!(location.host, "agent-host");
+        assert_eq!(location.port, pipeline_port);
+
+        let response = runner
+            .forward(pipeline_id, &location, Method::GET, "status")
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+        assert_eq!(to_bytes(response.into_body()).await.unwrap(), "running");
+
+        assert!(intruder
+            .forward(pipeline_id, &location, Method::GET, "status")
+            .await
+            .is_err());
+
+        // Unknown pipeline.
+        assert!(runner
+            .forward(PipelineId(2), &location, Method::GET, "status")
+            .await
+            .is_err());
+
+        // The fake pipeline exits right after initialization.
+        assert!(intruder.stop_pipeline(pipeline_id).await.is_err());
+        runner.stop_pipeline(pipeline_id).await.unwrap();
+        assert!(runner.stop_pipeline(pipeline_id).await.is_err());
+
+        assert!(intruder.delete_pipeline(pipeline_id).await.is_err());
+        assert!(config.pipeline_dir(pipeline_id).exists());
+
+        runner.delete_pipeline(pipeline_id).await.unwrap();
+        assert!(!config.pipeline_dir(pipeline_id).exists());
+    }
+
+    #[test]
+    fn test_agent_requires_token() {
+        let config: ManagerConfig =
+            serde_yaml::from_str("working_directory: .\nsql_compiler_home: .\n").unwrap();
+        assert!(AgentState::new(&config).is_err());
+    }
+}
@@ -29,10 +29,12 @@
 use crate::{
     circuit::{
         cache::{CircuitCache, CircuitStoreMarker},
+        description::CircuitDescription,
         metadata::OperatorMeta,
         operator_traits::{
-            BinaryOperator, Data, ImportOperator, NaryOperator, QuaternaryOperator, SinkOperator,
-            SourceOperator, StrictUnaryOperator, TernaryOperator, UnaryOperator,
+            BinaryOperator, Data, ImportOperator, NaryOperator, OperatorState, QuaternaryOperator,
+            SinkOperator, SourceOperator, StatePartitioner, StrictUnaryOperator, TernaryOperator,
+            UnaryOperator,
         },
         schedule::{
             DynamicScheduler, Error as SchedulerError, Executor, IterativeExecutor, OnceExecutor,
@@ -41,8 +43,9 @@ use crate::{
         trace::{CircuitEvent, SchedulerEvent},
     },
     circuit_cache_key,
+    monitor::TraceMonitor,
     operator::communication::Exchange,
-    Runtime,
+    Error, Runtime,
 };
 use std::{
     borrow::Cow,
@@ -327,6 +330,44 @@ pub trait Node {
     fn fixedpoint(&self, scope: Scope) -> bool;
 
     fn map_nodes_recursive(&self, _f: &mut dyn FnMut(&dyn Node)) {}
+
+    /// See [`Operator::is_stateful()`](super::operator_traits::Operator::is_stateful).
+    fn is_stateful(&self) -> bool {
+        false
+    }
+
+    /// See
+    /// [`Operator::preserves_sharding()`](super::operator_traits::Operator::preserves_sharding).
+    fn preserves_sharding(&self) -> bool {
+        false
+    }
+
+    /// See
+    /// [`Operator::state_transferable()`](super::operator_traits::Operator::state_transferable).
+    fn state_transferable(&self) -> bool {
+        true
+    }
+
+    /// See
+    /// [`Operator::state_partitioner()`](super::operator_traits::Operator::state_partitioner).
+    fn state_partitioner(&self) -> Option<StatePartitioner> {
+        None
+    }
+
+    /// See [`Operator::take_state()`](super::operator_traits::Operator::take_state).
+    fn take_state(&mut self) -> Option<OperatorState> {
+        None
+    }
+
+    /// See
+    /// [`Operator::restore_state()`](super::operator_traits::Operator::restore_state).
+    fn restore_state(&mut self, _states: Vec<OperatorState>) {}
+
+    /// `true` if the node encapsulates a sink operator, which consumes a
+    /// stream without producing an output stream.
+    fn is_sink(&self) -> bool {
+        false
+    }
 }
 
 /// Id of an operator, guaranteed to be unique within a circuit.
@@ -710,6 +751,55 @@ impl Circuit<()> {
         Self::build_with_scheduler::<F, T, DynamicScheduler>(constructor)
     }
 
+    /// Create a circuit and prepare it for execution, returning a
+    /// description of the circuit along with the circuit handle.
+    ///
+    /// Like [`build`](`Self::build`), but additionally records the topology of
+    /// the circuit during construction and returns it as a
+    /// [`CircuitDescription`].  Use [`CircuitDescription::validate`] to check
+    /// the circuit for common mistakes.
+    ///
+    /// Fails if the circuit cannot be scheduled or if the events emitted while
+    /// constructing the circuit do not describe a valid circuit.
+    pub fn build_with_description<F, T>(
+        constructor: F,
+    ) -> Result<(CircuitHandle, T, CircuitDescription), Error>
+    where
+        F: FnOnce(&mut Circuit<()>) -> T,
+    {
+        // Remember the first invalid event instead of panicking.
+        let trace_error: Rc<RefCell<Option<String>>> = Rc::new(RefCell::new(None));
+        let circuit_trace_error = trace_error.clone();
+        let scheduler_trace_error = trace_error.clone();
+
+        let (handle, (res, description)) = Self::build(|circuit| {
+            let monitor = TraceMonitor::new(
+                move |event, err| {
+                    circuit_trace_error
+                        .borrow_mut()
+                        .get_or_insert_with(|| format!("invalid circuit event {event}: {err}"));
+                },
+                move |event, err| {
+                    scheduler_trace_error
+                        .borrow_mut()
+                        .get_or_insert_with(|| format!("invalid scheduler event {event}: {err}"));
+                },
+            );
+            monitor.attach_circuit_events(circuit, "description");
+            let res = constructor(circuit);
+            circuit.unregister_circuit_event_handler("description");
+            (res, monitor.description(circuit))
+        })?;
+
+        if let Some(error) = trace_error.take() {
+            return Err(Error::Custom(format!(
+                "failed to describe the circuit: {error}"
+            )));
+        }
+
+        Ok((handle, res, description))
+    }
+
     /// Create a circuit and prepare it for execution.
     ///
     /// Similar to [`build`](`Self::build`), but with a user-specified
@@ -967,6 +1057,57 @@ impl<P> Circuit<P> {
         }
     }
 
+    /// Returns the name of the first operator in the circuit whose state
+    /// cannot be transferred to a runtime with a different number of
+    /// workers, if any.
+    pub(crate) fn untransferable_operator(&self) -> Option<Cow<'static, str>> {
+        self.inner()
+            .nodes
+            .iter()
+            .find(|node| !node.state_transferable())
+            .map(|node| node.name())
+    }
+
+    /// Returns names and state partitioners of all operators in the circuit
+    /// that maintain state across clock cycles, in the order in which they
+    /// were added to the circuit.
+    pub(crate) fn state_partitioners(&self) -> Vec<(Cow<'static, str>, StatePartitioner)> {
+        self.inner()
+            .nodes
+            .iter()
+            .filter_map(|node| {
+                node.state_partitioner()
+                    .map(|partitioner| (node.name(), partitioner))
+            })
+            .collect()
+    }
+
+    /// Extract the state of all operators returned by
+    /// [`Self::state_partitioners`], in the same order.
+    pub(crate) fn take_state(&self) -> Vec<Option<OperatorState>> {
+        self.inner_mut()
+            .nodes
+            .iter_mut()
+            .filter(|node| node.state_partitioner().is_some())
+            .map(|node| node.take_state())
+            .collect()
+    }
+
+    /// Restore the state of all operators returned by
+    /// [`Self::state_partitioners`].  `states[i]` contains the parts of the
+    /// state of the `i`th operator assigned to this worker.
+    pub(crate) fn restore_state(&self, states: Vec<Vec<OperatorState>>) {
+        let mut inner = self.inner_mut();
+        let nodes = inner
+            .nodes
+            .iter_mut()
+            .filter(|node| node.state_partitioner().is_some());
+
+        for (node, states) in nodes.zip(states) {
+            node.restore_state(states);
+        }
+    }
+
     /// Deliver `clock_start` notification to all nodes in the circuit.
     pub(super) fn clock_start(&self, scope: Scope) {
         for node in self.inner_mut().nodes.iter_mut() {
@@ -987,7 +1128,7 @@ impl<P> Circuit<P> {
 
     /// Send the specified `CircuitEvent` to all handlers attached to the
     /// circuit.
-    fn log_circuit_event(&self, event: &CircuitEvent) {
+    pub(crate) fn log_circuit_event(&self, event: &CircuitEvent) {
         self.inner().log_circuit_event(event);
     }
 
@@ -2124,6 +2265,30 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         self.operator.fixedpoint(scope)
     }
+
+    fn is_stateful(&self) -> bool {
+        self.operator.is_stateful()
+    }
+
+    fn preserves_sharding(&self) -> bool {
+        self.operator.preserves_sharding()
+    }
+
+    fn state_transferable(&self) -> bool {
+        self.operator.state_transferable()
+    }
+
+    fn state_partitioner(&self) -> Option<StatePartitioner> {
+        self.operator.state_partitioner()
+    }
+
+    fn take_state(&mut self) -> Option<OperatorState> {
+        self.operator.take_state()
+    }
+
+    fn restore_state(&mut self, states: Vec<OperatorState>) {
+        self.operator.restore_state(states)
+    }
 }
 
 struct UnaryNode<C, I, O, Op> {
@@ -2210,6 +2375,30 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         self.operator.fixedpoint(scope)
     }
+
+    fn is_stateful(&self) -> bool {
+        self.operator.is_stateful()
+    }
+
+    fn preserves_sharding(&self) -> bool {
+        self.operator.preserves_sharding()
+    }
+
+    fn state_transferable(&self) -> bool {
+        self.operator.state_transferable()
+    }
+
+    fn state_partitioner(&self) -> Option<StatePartitioner> {
+        self.operator.state_partitioner()
+    }
+
+    fn take_state(&mut self) -> Option<OperatorState> {
+        self.operator.take_state()
+    }
+
+    fn restore_state(&mut self, states: Vec<OperatorState>) {
+        self.operator.restore_state(states)
+    }
 }
 
 struct SinkNode<C, I, Op> {
@@ -2288,6 +2477,34 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         self.operator.fixedpoint(scope)
     }
+
+    fn is_stateful(&self) -> bool {
+        self.operator.is_stateful()
+    }
+
+    fn preserves_sharding(&self) -> bool {
+        self.operator.preserves_sharding()
+    }
+
+    fn state_transferable(&self) -> bool {
+        self.operator.state_transferable()
+    }
+
+    fn state_partitioner(&self) -> Option<StatePartitioner> {
+        self.operator.state_partitioner()
+    }
+
+    fn take_state(&mut self) -> Option<OperatorState> {
+        self.operator.take_state()
+    }
+
+    fn restore_state(&mut self, states: Vec<OperatorState>) {
+        self.operator.restore_state(states)
+    }
+
+    fn is_sink(&self) -> bool {
+        true
+    }
 }
 
 struct BinaryNode<C, I1, I2, O, Op> {
@@ -2402,6 +2619,30 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         self.operator.fixedpoint(scope)
     }
+
+    fn is_stateful(&self) -> bool {
+        self.operator.is_stateful()
+    }
+
+    fn preserves_sharding(&self) -> bool {
+        self.operator.preserves_sharding()
+    }
+
+    fn state_transferable(&self) -> bool {
+        self.operator.state_transferable()
+    }
+
+    fn state_partitioner(&self) -> Option<StatePartitioner> {
+        self.operator.state_partitioner()
+    }
+
+    fn take_state(&mut self) -> Option<OperatorState> {
+        self.operator.take_state()
+    }
+
+    fn restore_state(&mut self, states: Vec<OperatorState>) {
+        self.operator.restore_state(states)
+    }
 }
 
 struct TernaryNode<C, I1, I2, I3, O, Op> {
@@ -2520,6 +2761,30 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         self.operator.fixedpoint(scope)
     }
+
+    fn is_stateful(&self) -> bool {
+        self.operator.is_stateful()
+    }
+
+    fn preserves_sharding(&self) -> bool {
+        self.operator.preserves_sharding()
+    }
+
+    fn state_transferable(&self) -> bool {
+        self.operator.state_transferable()
+    }
+
+    fn state_partitioner(&self) -> Option<StatePartitioner> {
+        self.operator.state_partitioner()
+    }
+
+    fn take_state(&mut self) -> Option<OperatorState> {
+        self.operator.take_state()
+    }
+
+    fn restore_state(&mut self, states: Vec<OperatorState>) {
+        self.operator.restore_state(states)
+    }
 }
 
 struct QuaternaryNode<C, I1, I2, I3, I4, O, Op> {
@@ -2656,6 +2921,30 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         self.operator.fixedpoint(scope)
     }
+
+    fn is_stateful(&self) -> bool {
+        self.operator.is_stateful()
+    }
+
+    fn preserves_sharding(&self) -> bool {
+        self.operator.preserves_sharding()
+    }
+
+    fn state_transferable(&self) -> bool {
+        self.operator.state_transferable()
+    }
+
+    fn state_partitioner(&self) -> Option<StatePartitioner> {
+        self.operator.state_partitioner()
+    }
+
+    fn take_state(&mut self) -> Option<OperatorState> {
+        self.operator.take_state()
+    }
+
+    fn restore_state(&mut self, states: Vec<OperatorState>) {
+        self.operator.restore_state(states)
+    }
 }
 
 struct NaryNode<C, I, O, Op>
@@ -2777,6 +3066,30 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         self.operator.fixedpoint(scope)
     }
+
+    fn is_stateful(&self) -> bool {
+        self.operator.is_stateful()
+    }
+
+    fn preserves_sharding(&self) -> bool {
+        self.operator.preserves_sharding()
+    }
+
+    fn state_transferable(&self) -> bool {
+        self.operator.state_transferable()
+    }
+
+    fn state_partitioner(&self) -> Option<StatePartitioner> {
+        self.operator.state_partitioner()
+    }
+
+    fn take_state(&mut self) -> Option<OperatorState> {
+        self.operator.take_state()
+    }
+
+    fn restore_state(&mut self, states: Vec<OperatorState>) {
+        self.operator.restore_state(states)
+    }
 }
 
 // The output half of a feedback node.  We implement a feedback node using a
@@ -2870,6 +3183,30 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         unsafe { (*self.operator.get()).fixedpoint(scope) }
     }
+
+    fn is_stateful(&self) -> bool {
+        unsafe { (*self.operator.get()).is_stateful() }
+    }
+
+    fn preserves_sharding(&self) -> bool {
+        unsafe { (*self.operator.get()).preserves_sharding() }
+    }
+
+    fn state_transferable(&self) -> bool {
+        unsafe { (*self.operator.get()).state_transferable() }
+    }
+
+    fn state_partitioner(&self) -> Option<StatePartitioner> {
+        unsafe { (*self.operator.get()).state_partitioner() }
+    }
+
+    fn take_state(&mut self) -> Option<OperatorState> {
+        unsafe { (*self.operator.get()).take_state() }
+    }
+
+    fn restore_state(&mut self, states: Vec<OperatorState>) {
+        unsafe { (*self.operator.get()).restore_state(states) }
+    }
 }
 
 /// The input half of a feedback node
@@ -3076,6 +3413,13 @@ where
         self.circuit.inner().fixedpoint(scope + 1)
     }
 
+    fn state_transferable(&self) -> bool {
+        // Operators in nested circuits can maintain state across clock cycles
+        // of the parent circuit (e.g., traces in incremental recursive
+        // computations), which is not transferred on rescaling.
+        false
+    }
+
     fn map_nodes_recursive(&self, f: &mut dyn FnMut(&dyn Node)) {
         self.circuit.map_nodes_recursive(f);
     }
@@ -3120,6 +3464,26 @@ impl CircuitHandle {
         self.executor.run(&self.circuit)
     }
 
+    /// See [`Circuit::untransferable_operator`].
+    pub(crate) fn untransferable_operator(&self) -> Option<Cow<'static, str>> {
+        self.circuit.untransferable_operator()
+    }
+
+    /// See [`Circuit::state_partitioners`].
+    pub(crate) fn state_partitioners(&self) -> Vec<(Cow<'static, str>, StatePartitioner)> {
+        self.circuit.state_partitioners()
+    }
+
+    /// See [`Circuit::take_state`].
+    pub(crate) fn take_state(&self) -> Vec<Option<OperatorState>> {
+        self.circuit.take_state()
+    }
+
+    /// See [`Circuit::restore_state`].
+    pub(crate) fn restore_state(&self, states: Vec<Vec<OperatorState>>) {
+        self.circuit.restore_state(states)
+    }
+
     /// Attach a scheduler event handler to the circuit.
     ///
     /// This method is identical to
@@ -3149,7 +3513,7 @@ impl CircuitHandle {
 #[cfg(test)]
 mod tests {
     use crate::{
-        circuit::schedule::{DynamicScheduler, Scheduler, StaticScheduler},
+        circuit::schedule::{AdaptiveScheduler, DynamicScheduler, Scheduler, StaticScheduler},
         monitor::TraceMonitor,
         operator::{Generator, Z1},
         Circuit,
@@ -3167,6 +3531,11 @@ mod tests {
         sum_circuit::<DynamicScheduler>();
     }
 
+    #[test]
+    fn sum_circuit_adaptive() {
+        sum_circuit::<AdaptiveScheduler>();
+    }
+
     // Compute the sum of numbers from 0 to 99.
     fn sum_circuit<S>()
     where
@@ -3213,6 +3582,11 @@ mod tests {
         recursive_sum_circuit::<DynamicScheduler>()
     }
 
+    #[test]
+    fn recursive_sum_circuit_adaptive() {
+        recursive_sum_circuit::<AdaptiveScheduler>()
+    }
+
     fn recursive_sum_circuit<S>()
     where
         S: Scheduler + 'static,
@@ -3261,6 +3635,11 @@ mod tests {
         factorial::<DynamicScheduler>();
     }
 
+    #[test]
+    fn factorial_adaptive() {
+        factorial::<AdaptiveScheduler>();
+    }
+
     // Nested circuit.  The circuit contains a source node that counts up from
     // 1.  For each `n` output by the source node, the nested circuit computes
     // factorial(n) using a `NestedSource` operator that counts from n down to
@@ -1,16 +1,58 @@
 use crate::{
-    circuit::runtime::RuntimeHandle, profile::Profiler, Circuit, Error as DBSPError, Runtime,
-    RuntimeError, SchedulerError,
+    circuit::{
+        description::{CircuitDescription, CircuitWarning},
+        operator_traits::{OperatorState, StatePartitioner},
+        runtime::RuntimeHandle,
+        schedule::{DynamicScheduler, Scheduler},
+        GlobalNodeId,
+    },
+    default_hash,
+    monitor::GraphNode,
+    operator::{
+        provenance::{lineage, LineageEdge},
+        Lineage,
+    },
+    profile::{ChromeTrace, Profiler, TraceEvent, WorkerProfile},
+    Circuit, Error as DBSPError, Runtime, RuntimeError, SchedulerError,
 };
 use crossbeam::channel::{bounded, Receiver, Sender, TryRecvError};
 use std::{
+    borrow::Cow,
+    fmt::{self, Debug, Formatter},
     fs,
     fs::create_dir_all,
+    hash::Hash,
+    mem::take,
     path::{Path, PathBuf},
+    sync::{Arc, Mutex},
     thread::Result as ThreadResult,
     time::Instant,
 };
 
+/// Configuration of a circuit instantiated by `Runtime::init_circuit_inner`.
+#[derive(Default)]
+struct CircuitConfig {
+    /// Number of worker threads.
+    nworkers: usize,
+    /// Number of threads in the thread pool shared by workers (see
+    /// [`Runtime::run_with_thread_pool`]); 0 means no thread pool.
+    pool_threads: usize,
+    /// Runtime whose input and output handles are inherited by the new
+    /// runtime.
+    predecessor: Option<Runtime>,
+    /// Enable provenance tracing in the circuit.
+    provenance: bool,
+}
+
+impl CircuitConfig {
+    fn with_workers(nworkers: usize) -> Self {
+        Self {
+            nworkers,
+            ..Default::default()
+        }
+    }
+}
+
 impl Runtime {
     /// Instantiate a circuit in a multithreaded runtime.
     ///
@@ -38,6 +80,131 @@ impl Runtime {
         F: FnOnce(&mut Circuit<()>) -> T + Clone + Send + 'static,
         T: Clone + Send + 'static,
     {
+        Self::init_circuit_with_scheduler::<F, T, DynamicScheduler>(nworkers, constructor)
+    }
+
+    /// Like [`Self::init_circuit`], but evaluates the circuit in each worker
+    /// using scheduler `S`.
+    pub fn init_circuit_with_scheduler<F, T, S>(
+        nworkers: usize,
+        constructor: F,
+    ) -> Result<(DBSPHandle, T), DBSPError>
+    where
+        F: FnOnce(&mut Circuit<()>) -> T + Clone + Send + 'static,
+        T: Clone + Send + 'static,
+        S: Scheduler + 'static,
+    {
+        Self::init_circuit_inner::<F, T, S>(CircuitConfig::with_workers(nworkers), constructor)
+    }
+
+    /// Like [`Self::init_circuit`], but additionally creates a thread pool
+    /// with `pool_threads` threads that workers use to parallelize expensive
+    /// operators (see [`Runtime::run_with_thread_pool`]).
+    pub fn init_circuit_with_thread_pool<F, T>(
+        nworkers: usize,
+        pool_threads: usize,
+        constructor: F,
+    ) -> Result<(DBSPHandle, T), DBSPError>
+    where
+        F: FnOnce(&mut Circuit<()>) -> T + Clone + Send + 'static,
+        T: Clone + Send + 'static,
+    {
+        Self::init_circuit_inner::<F, T, DynamicScheduler>(
+            CircuitConfig {
+                pool_threads,
+                ..CircuitConfig::with_workers(nworkers)
+            },
+            constructor,
+        )
+    }
+
+    /// Like [`Self::init_circuit`], but creates a circuit whose number of
+    /// workers can be changed at runtime using [`DBSPHandle::rescale`].
+    ///
+    /// Rescaling transfers the state of operators, e.g., traces maintained
+    /// by joins and aggregates, to the new set of workers, and does not
+    /// require any additional memory while the circuit is running.  In
+    /// addition to the requirements listed in [`Self::init_circuit`], all
+    /// operators in the top-level circuit that maintain state across clock
+    /// cycles must support state transfer (see
+    /// [`Operator::state_transferable`](`crate::circuit::operator_traits::Operator::state_transferable`)).
+    /// In particular, the top-level circuit must not contain
+    /// [`Generator`](`crate::operator::Generator`)s,
+    /// [`Z1`](`crate::operator::Z1`) operators, including those created by
+    /// [`Stream::integrate`](`crate::Stream::integrate`) and
+    /// [`Stream::delay`](`crate::Stream::delay`), or nested circuits.
+    /// [`DBSPHandle::rescale`] checks these requirements and fails without
+    /// changing the circuit if they are not met.
+    pub fn init_rescalable_circuit<F, T>(
+        nworkers: usize,
+        constructor: F,
+    ) -> Result<(DBSPHandle, T), DBSPError>
+    where
+        F: FnOnce(&mut Circuit<()>) -> T + Clone + Send + 'static,
+        T: Clone + Send + 'static,
+    {
+        let (mut dbsp, res) = Self::init_circuit_inner::<F, T, DynamicScheduler>(
+            CircuitConfig::with_workers(nworkers),
+            constructor.clone(),
+        )?;
+
+        dbsp.restart = Some(Restart(Box::new(move |nworkers, predecessor| {
+            Self::init_circuit_inner::<F, T, DynamicScheduler>(
+                CircuitConfig {
+                    predecessor: Some(predecessor),
+                    ..CircuitConfig::with_workers(nworkers)
+                },
+                constructor.clone(),
+            )
+            .map(|(dbsp, _)| dbsp)
+        })));
+
+        Ok((dbsp, res))
+    }
+
+    /// Like [`Self::init_circuit`], but enables provenance tracing in the
+    /// circuit.
+    ///
+    /// In this mode, the `map`, `filter`, `index`, `join`, `aggregate`, and
+    /// `distinct` operators of the top-level circuit record the input records that each
+    /// output record was derived from, which can be queried using
+    /// [`DBSPHandle::provenance`].  Provenance tracing is slow and consumes
+    /// memory proportional to the total number of records processed by the
+    /// circuit, so it should only be used in tests and debugging runs.
+    pub fn init_circuit_with_provenance<F, T>(
+        nworkers: usize,
+        constructor: F,
+    ) -> Result<(DBSPHandle, T), DBSPError>
+    where
+        F: FnOnce(&mut Circuit<()>) -> T + Clone + Send + 'static,
+        T: Clone + Send + 'static,
+    {
+        Self::init_circuit_inner::<F, T, DynamicScheduler>(
+            CircuitConfig {
+                provenance: true,
+                ..CircuitConfig::with_workers(nworkers)
+            },
+            constructor,
+        )
+    }
+
+    /// Instantiate a circuit in a new runtime configured by `config`.
+    fn init_circuit_inner<F, T, S>(
+        config: CircuitConfig,
+        constructor: F,
+    ) -> Result<(DBSPHandle, T), DBSPError>
+    where
+        F: FnOnce(&mut Circuit<()>) -> T + Clone + Send + 'static,
+        T: Clone + Send + 'static,
+        S: Scheduler + 'static,
+    {
+        let CircuitConfig {
+            nworkers,
+            pool_threads,
+            predecessor,
+            provenance,
+        } = config;
+
         // When a worker finishes building the circuit, it sends completion status back
         // to us via this channel.  The function returns after receiving a
         // notification from each worker.
@@ -52,7 +219,7 @@ impl Runtime {
         let (status_senders, status_receivers): (Vec<_>, Vec<_>) =
             (0..nworkers).map(|_| bounded(1)).unzip();
 
-        let runtime = Self::run(nworkers, move || {
+        let runtime = Self::run_with_predecessor(nworkers, pool_threads, predecessor, move || {
             let worker_index = Runtime::worker_index();
 
             // Drop all but one channels.  This makes sure that if one of the worker panics
@@ -61,22 +228,26 @@ impl Runtime {
             let status_sender = status_senders.into_iter().nth(worker_index).unwrap();
             let command_receiver = command_receivers.into_iter().nth(worker_index).unwrap();
 
-            let (circuit, profiler) = match Circuit::build(|circuit| {
-                let profiler = Profiler::new(circuit);
-                let res = constructor(circuit);
-                (res, profiler)
-            }) {
-                Ok((circuit, (res, profiler))) => {
-                    if init_sender.send(Ok(res)).is_err() {
+            let (circuit, profiler, provenance, inputs) =
+                match Circuit::build_with_scheduler::<_, _, S>(|circuit| {
+                    // Provenance must be enabled before any operators are created.
+                    let provenance = provenance.then(|| circuit.enable_provenance());
+                    let profiler = Profiler::new(circuit);
+                    let res = constructor(circuit);
+                    let inputs = circuit.input_registry();
+                    (res, profiler, provenance, inputs)
+                }) {
+                    Ok((circuit, (res, profiler, provenance, inputs))) => {
+                        if init_sender.send(Ok(res)).is_err() {
+                            return;
+                        }
+                        (circuit, profiler, provenance, inputs)
+                    }
+                    Err(e) => {
+                        let _ = init_sender.send(Err(e));
                         return;
                     }
-                    (circuit, profiler)
-                }
-                Err(e) => {
-                    let _ = init_sender.send(Err(e));
-                    return;
-                }
-            };
+                };
 
             // TODO: uncomment this when we have support for background compaction.
             // let mut moregc = true;
@@ -107,6 +278,103 @@ impl Runtime {
                             return;
                         }
                     }
+                    Ok(Command::CircuitGraph) => {
+                        let (nodes, edges) = profiler.circuit_graph();
+                        if status_sender
+                            .send(Ok(Response::CircuitGraph(nodes, edges)))
+                            .is_err()
+                        {
+                            return;
+                        }
+                    }
+                    Ok(Command::Describe) => {
+                        if status_sender
+                            .send(Ok(Response::Description(profiler.description())))
+                            .is_err()
+                        {
+                            return;
+                        }
+                    }
+                    Ok(Command::StructuredProfile) => {
+                        if status_sender
+                            .send(Ok(Response::StructuredProfile(profiler.profile())))
+                            .is_err()
+                        {
+                            return;
+                        }
+                    }
+                    Ok(Command::TraceEvents(epoch)) => {
+                        if status_sender
+                            .send(Ok(Response::TraceEvents(profiler.trace_events(epoch))))
+                            .is_err()
+                        {
+                            return;
+                        }
+                    }
+                    Ok(Command::CheckpointInputs) => {
+                        inputs.checkpoint();
+                        if status_sender.send(Ok(Response::Unit)).is_err() {
+                            return;
+                        }
+                    }
+                    Ok(Command::RollbackInputs) => {
+                        inputs.rollback();
+                        if status_sender.send(Ok(Response::Unit)).is_err() {
+                            return;
+                        }
+                    }
+                    Ok(Command::StageInputs) => {
+                        inputs.stage();
+                        if status_sender.send(Ok(Response::Unit)).is_err() {
+                            return;
+                        }
+                    }
+                    Ok(Command::CheckRescalable) => {
+                        let operator = circuit.untransferable_operator();
+                        if status_sender
+                            .send(Ok(Response::Operator(operator)))
+                            .is_err()
+                        {
+                            return;
+                        }
+                    }
+                    Ok(Command::StatePartitioners) => {
+                        if status_sender
+                            .send(Ok(Response::StatePartitioners(
+                                circuit.state_partitioners(),
+                            )))
+                            .is_err()
+                        {
+                            return;
+                        }
+                    }
+                    Ok(Command::TakeState(partitioners, nworkers)) => {
+                        let states = circuit
+                            .take_state()
+                            .into_iter()
+                            .zip(partitioners.iter())
+                            .map(|(state, partitioner)| {
+                                state
+                                    .map(|state| partitioner(worker_index, state, nworkers))
+                                    .unwrap_or_default()
+                            })
+                            .collect();
+                        if status_sender.send(Ok(Response::State(states))).is_err() {
+                            return;
+                        }
+                    }
+                    Ok(Command::RestoreState(states)) => {
+                        circuit.restore_state(take(&mut states.lock().unwrap()[worker_index]));
+                        if status_sender.send(Ok(Response::Unit)).is_err() {
+                            return;
+                        }
+                    }
+                    Ok(Command::Lineage) => {
+                        let edges = provenance.as_ref().map(|provenance| provenance.edges());
+                        if status_sender.send(Ok(Response::Lineage(edges))).is_err() {
+                            return;
+                        }
+                    }
                     // Nothing to do: do some housekeeping and relinquish the CPU if there's none
                     // left.
                     Err(TryRecvError::Empty) => {
@@ -150,6 +418,9 @@ impl Runtime {
             return Err(error);
         }
 
+        // All workers have created their input and output handles.
+        runtime.runtime().release_predecessor();
+
         let dbsp = DBSPHandle::new(runtime, command_senders, status_receivers);
 
         // `constructor` should return identical results in all workers.  Use
@@ -163,11 +434,58 @@ enum Command {
     Step,
     EnableProfiler,
     DumpProfile,
+    CircuitGraph,
+    Describe,
+    StructuredProfile,
+    /// Collect CPU profiler timeline with timestamps relative to the given
+    /// instant.
+    TraceEvents(Instant),
+    /// Record the contents of input handles.
+    CheckpointInputs,
+    /// Discard data buffered in input handles since the last checkpoint.
+    RollbackInputs,
+    /// Move data buffered in input handles aside for the next clock cycle.
+    StageInputs,
+    /// Find an operator whose state cannot be transferred on rescaling.
+    CheckRescalable,
+    /// Collect state partitioners of stateful operators.
+    StatePartitioners,
+    /// Extract the state of all stateful operators and split it across the
+    /// given number of workers using the partitioners of the new runtime.
+    TakeState(Arc<Vec<StatePartitioner>>, usize),
+    /// Restore operator state extracted by `TakeState`.  Worker `i` takes the
+    /// `i`th element of the vector, which contains the parts of the state
+    /// of each stateful operator assigned to this worker.
+    RestoreState(Arc<Mutex<Vec<Vec<Vec<OperatorState>>>>>),
+    /// Collect lineage edges recorded by provenance tracing.
+    Lineage,
 }
 
 enum Response {
     Unit,
     Profile(String),
+    CircuitGraph(Vec<GraphNode>, Vec<(GlobalNodeId, GlobalNodeId)>),
+    Description(CircuitDescription),
+    StructuredProfile(WorkerProfile),
+    TraceEvents(Vec<TraceEvent>),
+    /// Name of an operator that does not support state transfer, if any.
+    Operator(Option<Cow<'static, str>>),
+    StatePartitioners(Vec<(Cow<'static, str>, StatePartitioner)>),
+    /// Operator state split across the workers of a new runtime, indexed by
+    /// operator and then by worker.
+    State(Vec<Vec<OperatorState>>),
+    /// Lineage edges or `None` if provenance tracing is disabled.
+    Lineage(Option<Vec<LineageEdge>>),
+}
+
+/// Instantiates the circuit with the specified number of workers in a new
+/// runtime that inherits handles from the given runtime.
+struct Restart(Box<dyn Fn(usize, Runtime) -> Result<DBSPHandle, DBSPError> + Send>);
+
+impl Debug for Restart {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("Restart")
+    }
 }
 
 /// A handle to control the execution of a circuit in a multithreaded runtime.
@@ -181,6 +499,11 @@ pub struct DBSPHandle {
     // Channels used to receive command completion status from
     // workers.
     status_receivers: Vec<Receiver<Result<Response, SchedulerError>>>,
+    // Used to re-instantiate the circuit when rescaling; `None` if the
+    // circuit was not created with `Runtime::init_rescalable_circuit`.
+    restart: Option<Restart>,
+    // `true` while a transaction is in progress.
+    transaction: bool,
 }
 
 impl DBSPHandle {
@@ -194,6 +517,8 @@ impl DBSPHandle {
             runtime: Some(runtime),
             command_senders,
             status_receivers,
+            restart: None,
+            transaction: false,
         }
     }
 
@@ -242,11 +567,100 @@ impl DBSPHandle {
         self.status_receivers.len()
     }
 
+    /// Fix the set of inputs consumed by the next clock cycle.
+    ///
+    /// Normally, each input operator consumes data pushed to its input handle
+    /// up to the point when the operator is evaluated, so updates pushed
+    /// concurrently with [`Self::step`] may or may not be consumed by that
+    /// step.  This method moves all data buffered in input handles aside, so
+    /// that the next clock cycle consumes exactly this data, while data
+    /// pushed after this call is consumed by the following clock cycle.  This
+    /// allows the client to determine precisely which updates each clock
+    /// cycle processes without blocking input handles while the circuit is
+    /// running.
+    ///
+    /// Staged inputs are not affected by [`Self::abort_transaction`].  They
+    /// must be consumed by [`Self::step`] before calling [`Self::rescale`],
+    /// which discards them.
+    pub fn stage_inputs(&mut self) -> Result<(), DBSPError> {
+        self.broadcast_command(Command::StageInputs, |_| {})
+    }
+
     /// Evaluate the circuit for one clock cycle.
+    ///
+    /// Inside a transaction (see [`Self::start_transaction`]), this method
+    /// does not evaluate the circuit.  Inputs remain buffered in input handles
+    /// until the transaction commits.
     pub fn step(&mut self) -> Result<(), DBSPError> {
+        if self.transaction {
+            if self.runtime.is_none() {
+                return Err(DBSPError::Runtime(RuntimeError::Killed));
+            }
+            return Ok(());
+        }
+
         self.broadcast_command(Command::Step, |_| {})
     }
 
+    /// Start a transaction.
+    ///
+    /// A transaction groups multiple clock cycles into one atomic update.
+    /// Inputs pushed to the circuit during the transaction, including inputs
+    /// buffered before the start of the transaction that have not yet been
+    /// consumed by [`Self::step`], are accumulated in input handles instead of
+    /// being processed at each step.  [`Self::commit_transaction`] processes
+    /// all accumulated inputs in a single clock cycle, so that output
+    /// handles only observe the net change produced by the transaction as a
+    /// whole, and never an intermediate state.  [`Self::abort_transaction`]
+    /// discards inputs pushed after the start of the transaction, leaving the
+    /// circuit in its pre-transaction state.  Inputs buffered before the
+    /// start of the transaction are preserved and consumed by the next call
+    /// to [`Self::step`].
+    ///
+    /// Note that inputs created with [`Circuit::add_input_stream`] hold a
+    /// single value per worker, which is overwritten by subsequent
+    /// [`InputHandle::set_for_worker`](`crate::InputHandle::set_for_worker`)
+    /// calls during the transaction; aborting the transaction restores the
+    /// value set before the transaction started.
+    pub fn start_transaction(&mut self) -> Result<(), DBSPError> {
+        if self.transaction {
+            return Err(DBSPError::Runtime(RuntimeError::TransactionInProgress));
+        }
+
+        self.broadcast_command(Command::CheckpointInputs, |_| {})?;
+        self.transaction = true;
+        Ok(())
+    }
+
+    /// Commit the current transaction.
+    ///
+    /// Evaluates the circuit for one clock cycle, processing all inputs
+    /// accumulated during the transaction.
+    pub fn commit_transaction(&mut self) -> Result<(), DBSPError> {
+        if !self.transaction {
+            return Err(DBSPError::Runtime(RuntimeError::NoTransaction));
+        }
+
+        self.transaction = false;
+        self.step()
+    }
+
+    /// Abort the current transaction, discarding all inputs pushed to the
+    /// circuit since the start of the transaction.
+    pub fn abort_transaction(&mut self) -> Result<(), DBSPError> {
+        if !self.transaction {
+            return Err(DBSPError::Runtime(RuntimeError::NoTransaction));
+        }
+
+        self.transaction = false;
+        self.broadcast_command(Command::RollbackInputs, |_| {})
+    }
+
+    /// `true` if a transaction is in progress.
+    pub fn in_transaction(&self) -> bool {
+        self.transaction
+    }
+
     /// Enable CPU profiler.
     ///
     /// Enable recording of CPU usage info.  When CPU profiling is enabled,
@@ -285,6 +699,285 @@ impl DBSPHandle {
         Ok(dir_path)
     }
 
+    /// Returns all operators and subcircuits of the circuit along with stream
+    /// edges between them.
+    ///
+    /// All workers instantiate identical circuits, so this method returns
+    /// the graph of the circuit in worker 0.
+    pub fn circuit_graph(
+        &mut self,
+    ) -> Result<(Vec<GraphNode>, Vec<(GlobalNodeId, GlobalNodeId)>), DBSPError> {
+        let mut graph = None;
+
+        self.broadcast_command(Command::CircuitGraph, |resp| {
+            if let Response::CircuitGraph(nodes, edges) = resp {
+                graph.get_or_insert((nodes, edges));
+            }
+        })?;
+
+        Ok(graph.unwrap_or_default())
+    }
+
+    /// Returns a serializable description of the circuit, including
+    /// operators, streams, ownership preferences and nesting.
+    ///
+    /// All workers instantiate identical circuits, so this method returns
+    /// the description of the circuit in worker 0.
+    pub fn circuit_description(&mut self) -> Result<CircuitDescription, DBSPError> {
+        let mut description = None;
+
+        self.broadcast_command(Command::Describe, |resp| {
+            if let Response::Description(worker_description) = resp {
+                description.get_or_insert(worker_description);
+            }
+        })?;
+
+        Ok(description.unwrap_or_default())
+    }
+
+    /// Check the circuit for common mistakes, such as unsharded inputs of
+    /// stateful operators in a multi-worker circuit (see
+    /// [`CircuitDescription::validate`]).
+    pub fn validate(&mut self) -> Result<Vec<CircuitWarning>, DBSPError> {
+        Ok(self.circuit_description()?.validate())
+    }
+
+    /// Collect structured profiles of all workers.
+    ///
+    /// Returns one [`WorkerProfile`] per worker thread, containing circuit
+    /// topology, typed operator metadata and, if CPU profiling was enabled
+    /// (see [`Self::enable_cpu_profiler`]), CPU usage info.
+    pub fn profile(&mut self) -> Result<Vec<WorkerProfile>, DBSPError> {
+        let mut profiles = Vec::with_capacity(self.num_workers());
+
+        self.broadcast_command(Command::StructuredProfile, |resp| {
+            if let Response::StructuredProfile(prof) = resp {
+                profiles.push(prof);
+            }
+        })?;
+
+        Ok(profiles)
+    }
+
+    /// Collect operator evaluation timelines of all workers in the Chrome
+    /// trace event format.
+    ///
+    /// Timestamps are in microseconds since the handle was created.  Only
+    /// evaluations performed while the CPU profiler was enabled (see
+    /// [`Self::enable_cpu_profiler`]) are reported.
+    pub fn chrome_trace(&mut self) -> Result<ChromeTrace, DBSPError> {
+        let mut trace = ChromeTrace::default();
+
+        self.broadcast_command(Command::TraceEvents(self.start_time), |resp| {
+            if let Response::TraceEvents(events) = resp {
+                trace.trace_events.extend(events);
+            }
+        })?;
+
+        Ok(trace)
+    }
+
+    /// Dump profiling information in JSON format to the specified directory.
+    ///
+    /// Creates `dir_path/<timestamp>/profile.json` containing an array of
+    /// per-worker profiles (see [`Self::profile`]) and
+    /// `dir_path/<timestamp>/trace.json` containing the operator evaluation
+    /// timeline (see [`Self::chrome_trace`]), which can be opened in
+    /// `chrome://tracing` or Perfetto.
+    #[cfg(feature = "with-serde")]
+    pub fn dump_profile_json<P: AsRef<Path>>(&mut self, dir_path: P) -> Result<PathBuf, DBSPError> {
+        let elapsed = self.start_time.elapsed().as_micros();
+
+        let dir_path = dir_path.as_ref().join(elapsed.to_string());
+        create_dir_all(&dir_path)?;
+
+        let profiles = self.profile()?;
+        let trace = self.chrome_trace()?;
+
+        fs::write(
+            dir_path.join("profile.json"),
+            serde_json::to_vec_pretty(&profiles).map_err(std::io::Error::from)?,
+        )?;
+        fs::write(
+            dir_path.join("trace.json"),
+            serde_json::to_vec(&trace).map_err(std::io::Error::from)?,
+        )?;
+
+        Ok(dir_path)
+    }
+
+    /// Returns the lineage of an output record.
+    ///
+    /// Finds all streams where `record` occurs last, i.e., is not passed
+    /// unmodified to another traced operator, and follows lineage edges
+    /// recorded by provenance tracing backward to records that contributed
+    /// to it.  Returns one [`Lineage`] per contributing record and path
+    /// through the circuit.  Records are matched by value: records of Z-sets
+    /// are specified by their key and records of indexed Z-sets as
+    /// `(key, value)` tuples of the same types as in the stream.  Lineage is tracked through `map`, `filter`,
+    /// `index`, `join`, `aggregate`, and `distinct` operators; records
+    /// produced by any other operator, including input operators, are
+    /// reported as sources.
+    ///
+    /// Only circuits created with [`Runtime::init_circuit_with_provenance`]
+    /// support this method; otherwise it returns
+    /// [`RuntimeError::NoProvenance`].
+    pub fn provenance<K>(&mut self, record: &K) -> Result<Vec<Lineage>, DBSPError>
+    where
+        K: Hash,
+    {
+        let mut edges = Some(Vec::new());
+
+        self.broadcast_command(Command::Lineage, |resp| {
+            if let Response::Lineage(worker_edges) = resp {
+                match (&mut edges, worker_edges) {
+                    (Some(edges), Some(worker_edges)) => edges.extend(worker_edges),
+                    _ => edges = None,
+                }
+            }
+        })?;
+
+        match edges {
+            Some(edges) => Ok(lineage(edges, default_hash(record))),
+            None => Err(DBSPError::Runtime(RuntimeError::NoProvenance)),
+        }
+    }
+
+    /// Change the number of worker threads.
+    ///
+    /// Instantiates the circuit with `nworkers` workers in a new runtime and
+    /// moves the state of all stateful operators, e.g., traces maintained by
+    /// joins and aggregates, to the new workers, repartitioning it the way
+    /// the new circuit shards its data.  Subsequent clock cycles produce the
+    /// same outputs as the original circuit would have.
+    ///
+    /// The current runtime keeps running until the new runtime has started
+    /// and the state of its operators has been extracted, so if any of these
+    /// steps fails, this method returns an error and leaves the current
+    /// circuit unchanged and still rescalable.  An error while restoring the
+    /// state in the new runtime is returned after the new circuit has
+    /// replaced the current one.
+    ///
+    /// Input and output handles returned by the circuit constructor remain
+    /// valid.  Updates pushed to input handles but not yet consumed by the
+    /// circuit are redistributed across the new workers and will be consumed
+    /// during the next call to [`Self::step`].  Outputs produced by the last
+    /// clock cycle must be read before calling this method, as they are
+    /// discarded.
+    ///
+    /// The circuit cannot be rescaled while a transaction is in progress
+    /// ([`RuntimeError::TransactionInProgress`]).
+    ///
+    /// Returns [`RuntimeError::NoWorkers`] if `nworkers` is 0.  Only circuits
+    /// created with [`Runtime::init_rescalable_circuit`] can be rescaled;
+    /// otherwise this method returns [`RuntimeError::NotRescalable`].  Returns
+    /// [`RuntimeError::OperatorNotRescalable`] if the circuit contains an
+    /// operator whose state cannot be transferred.
+    pub fn rescale(&mut self, nworkers: usize) -> Result<(), DBSPError> {
+        if self.runtime.is_none() {
+            return Err(DBSPError::Runtime(RuntimeError::Killed));
+        }
+        if nworkers == 0 {
+            return Err(DBSPError::Runtime(RuntimeError::NoWorkers));
+        }
+        if self.transaction {
+            return Err(DBSPError::Runtime(RuntimeError::TransactionInProgress));
+        }
+        if self.restart.is_none() {
+            return Err(DBSPError::Runtime(RuntimeError::NotRescalable));
+        }
+
+        let mut operator = None;
+        self.broadcast_command(Command::CheckRescalable, |resp| {
+            if let Response::Operator(Some(name)) = resp {
+                operator.get_or_insert(name);
+            }
+        })?;
+        if let Some(operator) = operator {
+            return Err(DBSPError::Runtime(RuntimeError::OperatorNotRescalable(
+                operator.into_owned(),
+            )));
+        }
+
+        // Start the new runtime while the current one is still running.
+        // Inherited handles keep feeding the current runtime until
+        // `commit_handles` is called below.  `self.restart` stays in place
+        // until the new circuit takes over, so that an error leaves this
+        // handle rescalable.
+        let predecessor = self.runtime.as_ref().unwrap().runtime().clone();
+        let mut new = (self.restart.as_ref().unwrap().0)(nworkers, predecessor)?;
+
+        let layouts = self
+            .state_partitioners()
+            .and_then(|old_layout| Ok((old_layout, new.state_partitioners()?)));
+        let (old_layout, new_layout) = match layouts {
+            Ok(layouts) => layouts,
+            Err(e) => {
+                let _ = new.kill_inner();
+                return Err(e);
+            }
+        };
+
+        // Both circuits are built by the same constructor, but the sets of
+        // operators can differ, e.g., sharding is a no-op with one worker.
+        // Stateful operators must match one-to-one.
+        if old_layout.len() != new_layout.len()
+            || old_layout
+                .iter()
+                .zip(new_layout.iter())
+                .any(|((old, _), (new, _))| old != new)
+        {
+            let _ = new.kill_inner();
+            return Err(DBSPError::Runtime(RuntimeError::NotRescalable));
+        }
+
+        let partitioners = Arc::new(
+            new_layout
+                .into_iter()
+                .map(|(_, partitioner)| partitioner)
+                .collect::<Vec<_>>(),
+        );
+        let mut restore: Vec<Vec<Vec<OperatorState>>> = (0..nworkers)
+            .map(|_| (0..partitioners.len()).map(|_| Vec::new()).collect())
+            .collect();
+        let state = self.broadcast_command(Command::TakeState(partitioners, nworkers), |resp| {
+            if let Response::State(operators) = resp {
+                for (operator, parts) in operators.into_iter().enumerate() {
+                    for (worker, part) in parts.into_iter().enumerate() {
+                        restore[worker][operator].push(part);
+                    }
+                }
+            }
+        });
+        if let Err(e) = state {
+            let _ = new.kill_inner();
+            return Err(e);
+        }
+
+        new.runtime.as_ref().unwrap().runtime().commit_handles();
+        let _ = self.kill_inner();
+
+        new.start_time = self.start_time;
+        new.restart = self.restart.take();
+        *self = new;
+
+        self.broadcast_command(Command::RestoreState(Arc::new(Mutex::new(restore))), |_| {})
+    }
+
+    /// Names and state partitioners of stateful operators, as reported by
+    /// worker 0.
+    fn state_partitioners(
+        &mut self,
+    ) -> Result<Vec<(Cow<'static, str>, StatePartitioner)>, DBSPError> {
+        let mut layout = None;
+        self.broadcast_command(Command::StatePartitioners, |resp| {
+            if let Response::StatePartitioners(partitioners) = resp {
+                layout.get_or_insert(partitioners);
+            }
+        })?;
+        Ok(layout.unwrap_or_default())
+    }
+
     /// Terminate the execution of the circuit, exiting all worker threads.
     ///
     /// If one or more of the worker threads panics, returns the argument the
@@ -312,7 +1005,13 @@ impl Drop for DBSPHandle {
 
 #[cfg(test)]
 mod tests {
-    use crate::{operator::Generator, Error as DBSPError, Runtime, RuntimeError};
+    use crate::{
+        circuit::CircuitWarning,
+        operator::{FilterMap, Generator, Max},
+        trace::BatchReader,
+        zset, Circuit, CollectionHandle, Error as DBSPError, OrdZSet, OutputHandle, Runtime,
+        RuntimeError, UpsertHandle,
+    };
 
     // Panic during initialization in worker thread.
     #[test]
@@ -400,6 +1099,77 @@ mod tests {
         handle.kill().unwrap();
     }
 
+    #[test]
+    #[cfg(feature = "with-serde")]
+    fn test_profile_json() {
+        let (mut handle, _) = Runtime::init_circuit(2, |circuit| {
+            circuit
+                .add_source(Generator::new(|| 5usize))
+                .inspect(|_| {});
+        })
+        .unwrap();
+
+        handle.enable_cpu_profiler().unwrap();
+        handle.step().unwrap();
+        handle.step().unwrap();
+
+        let profiles = handle.profile().unwrap();
+        assert_eq!(profiles.len(), 2);
+        for (worker, profile) in profiles.iter().enumerate() {
+            assert_eq!(profile.worker, worker);
+            assert_eq!(profile.nodes.len(), 2);
+            assert_eq!(profile.edges.len(), 1);
+            assert_eq!(profile.nodes[0].cpu.as_ref().unwrap().invocations(), 2);
+        }
+
+        // Two evaluations of two operators in each worker plus a metadata event
+        // per worker.
+        let trace = handle.chrome_trace().unwrap();
+        assert_eq!(trace.trace_events.len(), 10);
+
+        let dir = handle
+            .dump_profile_json(std::env::temp_dir().join("test_profile_json"))
+            .unwrap();
+        let profile: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(dir.join("profile.json")).unwrap()).unwrap();
+        assert_eq!(profile.as_array().unwrap().len(), 2);
+        assert_eq!(profile[0]["nodes"][0]["id"], serde_json::json!([0]));
+
+        let trace: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(dir.join("trace.json")).unwrap()).unwrap();
+        let events = trace["traceEvents"].as_array().unwrap();
+        assert!(events
+            .iter()
+            .any(|event| event["ph"] == "X" && event["tid"] == 1));
+
+        handle.kill().unwrap();
+    }
+
+    #[test]
+    fn test_validate() {
+        let (mut handle, _) = Runtime::init_circuit(2, |circuit| {
+            let input = circuit.add_source(Generator::new(|| zset! { 1u64 => 1, 2 => 1 }));
+            input.distinct().output();
+            input.inspect(|_| {});
+        })
+        .unwrap();
+
+        let description = handle.circuit_description().unwrap();
+        assert_eq!(description.workers, 2);
+        assert!(description.nodes.iter().any(|node| node.sharded));
+
+        let warnings = handle.validate().unwrap();
+        assert!(warnings.iter().any(|warning| matches!(
+            warning,
+            CircuitWarning::UnconsumedStream { operator, .. } if operator == "Inspect"
+        )));
+        assert!(!warnings
+            .iter()
+            .any(|warning| matches!(warning, CircuitWarning::UnshardedInput { .. })));
+
+        handle.kill().unwrap();
+    }
+
     // Drop the runtime.
     #[test]
     fn test_drop1() {
@@ -419,4 +1189,315 @@ mod tests {
 
         handle.step().unwrap();
     }
+
+    // Run a circuit that consolidates and merges large batches with a thread
+    // pool of `pool_threads` threads and return its outputs after each step.
+    fn thread_pool_outputs(pool_threads: usize) -> Vec<OrdZSet<u64, isize>> {
+        let (mut handle, (mut input, output)) =
+            Runtime::init_circuit_with_thread_pool(2, pool_threads, |circuit| {
+                let (stream, input) = circuit.add_input_zset::<u64, isize>();
+                let output = stream.integrate_trace().consolidate().output();
+                (input, output)
+            })
+            .unwrap();
+
+        let mut outputs = Vec::new();
+        for step in 0..4 {
+            // Overlapping keys with duplicates and retractions, so that both
+            // consolidation and merging have to combine weights.
+            input.append(
+                &mut (0..200_000)
+                    .map(|x| ((x * 3 + step) % 300_000, if x % 5 == 0 { -1 } else { 1 }))
+                    .collect(),
+            );
+            handle.step().unwrap();
+            outputs.push(output.consolidate());
+        }
+
+        handle.kill().unwrap();
+        outputs
+    }
+
+    // Large batches consolidated and merged using a thread pool produce the
+    // same result as sequential evaluation.
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_thread_pool() {
+        let sequential = thread_pool_outputs(0);
+        let parallel = thread_pool_outputs(4);
+
+        assert!(sequential.iter().all(|batch| !batch.is_empty()));
+        assert_eq!(parallel, sequential);
+    }
+
+    type RescaleHandles = (
+        CollectionHandle<u64, isize>,
+        UpsertHandle<u64, Option<u64>>,
+        OutputHandle<OrdZSet<(u64, u64), isize>>,
+    );
+
+    fn rescale_test_circuit(circuit: &mut Circuit<()>) -> RescaleHandles {
+        let (zset, zset_handle) = circuit.add_input_zset::<u64, isize>();
+        let (map, map_handle) = circuit.add_input_map::<u64, u64, isize>();
+
+        let output = zset
+            .index_with(|x| (x % 100, *x))
+            .join::<(), _, _, _>(&map, |_k, x, v| (*x, *v))
+            .distinct()
+            .output();
+
+        (zset_handle, map_handle, output)
+    }
+
+    // A rescaled circuit produces the same outputs as a circuit with a fixed
+    // number of workers.
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_rescale() {
+        let (mut expected_handle, (mut expected_zset, mut expected_map, expected_output)) =
+            Runtime::init_circuit(2, rescale_test_circuit).unwrap();
+        let (mut handle, (mut zset, mut map, output)) =
+            Runtime::init_rescalable_circuit(2, rescale_test_circuit).unwrap();
+
+        for (step, nworkers) in [2, 4, 4, 1, 3].into_iter().enumerate() {
+            let step = step as u64;
+
+            let zset_updates: Vec<_> = (0..1000).map(|x| (x + step * 500, 1)).collect();
+            let map_updates: Vec<_> = (0..100)
+                .map(|k| (k, ((k + step) % 5 != 0).then_some(k + step)))
+                .collect();
+
+            expected_zset.append(&mut zset_updates.clone());
+            expected_map.append(&mut map_updates.clone());
+            expected_handle.step().unwrap();
+
+            // Rescale with pending inputs.
+            zset.append(&mut zset_updates.clone());
+            map.append(&mut map_updates.clone());
+            if handle.num_workers() != nworkers {
+                handle.rescale(nworkers).unwrap();
+                assert_eq!(handle.num_workers(), nworkers);
+            }
+            handle.step().unwrap();
+
+            assert_eq!(output.consolidate(), expected_output.consolidate());
+        }
+
+        expected_handle.kill().unwrap();
+        handle.kill().unwrap();
+    }
+
+    #[test]
+    fn test_provenance() {
+        let (mut handle, (mut orders, mut prices)) =
+            Runtime::init_circuit_with_provenance(2, |circuit| {
+                // (customer, item)
+                let (orders, orders_handle) = circuit.add_input_zset::<(u64, u64), isize>();
+                // (item, price)
+                let (prices, prices_handle) = circuit.add_input_zset::<(u64, u64), isize>();
+
+                // Most expensive item ordered by each customer.
+                orders
+                    .filter(|(customer, _)| *customer != 0)
+                    .map(|&(customer, item)| (item, customer))
+                    .index::<u64, u64>()
+                    .join::<(), _, _, _>(&prices.index(), |_item, customer, price| {
+                        (*customer, *price)
+                    })
+                    .index::<u64, u64>()
+                    .aggregate::<(), _>(Max);
+
+                (orders_handle, prices_handle)
+            })
+            .unwrap();
+
+        orders.append(&mut vec![((1, 10), 1), ((1, 11), 1), ((0, 12), 1)]);
+        prices.append(&mut vec![((10, 5), 1), ((11, 7), 1), ((12, 100), 1)]);
+        handle.step().unwrap();
+
+        let mut lineage = handle.provenance(&(1u64, 7u64)).unwrap();
+        lineage.sort_by(|l1, l2| l1.record.cmp(&l2.record));
+
+        let records: Vec<_> = lineage
+            .iter()
+            .map(|lineage| lineage.record.as_str())
+            .collect();
+        assert_eq!(records, vec!["(1, 10)", "(1, 11)", "(10, 5)", "(11, 7)"]);
+
+        let operators: Vec<_> = lineage[0]
+            .path
+            .iter()
+            .map(|(_, operator)| *operator)
+            .collect();
+        assert_eq!(
+            operators,
+            vec!["Filter", "Map", "Index", "Join", "Index", "Aggregate"]
+        );
+
+        let operators: Vec<_> = lineage[2]
+            .path
+            .iter()
+            .map(|(_, operator)| *operator)
+            .collect();
+        assert_eq!(operators, vec!["Index", "Join", "Index", "Aggregate"]);
+
+        // Filtered out.
+        assert!(handle.provenance(&(0u64, 100u64)).unwrap().is_empty());
+
+        handle.kill().unwrap();
+
+        let (mut handle, _) = Runtime::init_circuit(2, |circuit| {
+            circuit.add_input_zset::<u64, isize>();
+        })
+        .unwrap();
+
+        if let DBSPError::Runtime(err) = handle.provenance(&1u64).unwrap_err() {
+            assert_eq!(err, RuntimeError::NoProvenance);
+        } else {
+            panic!();
+        }
+
+        handle.kill().unwrap();
+    }
+
+    #[test]
+    fn test_rescale_not_rescalable() {
+        let (mut handle, _) = Runtime::init_circuit(2, |circuit| {
+            circuit.add_input_zset::<u64, isize>();
+        })
+        .unwrap();
+
+        if let DBSPError::Runtime(err) = handle.rescale(4).unwrap_err() {
+            assert_eq!(err, RuntimeError::NotRescalable);
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn test_rescale_no_workers() {
+        let (mut handle, _) = Runtime::init_rescalable_circuit(2, |circuit| {
+            circuit.add_input_zset::<u64, isize>();
+        })
+        .unwrap();
+
+        if let DBSPError::Runtime(err) = handle.rescale(0).unwrap_err() {
+            assert_eq!(err, RuntimeError::NoWorkers);
+        } else {
+            panic!();
+        }
+        assert_eq!(handle.num_workers(), 2);
+        handle.step().unwrap();
+    }
+
+    // Rescaling a circuit with an operator whose state cannot be transferred
+    // fails and leaves the circuit running with the original workers.
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_rescale_untransferable_operator() {
+        let (mut handle, (mut input, output)) = Runtime::init_rescalable_circuit(2, |circuit| {
+            let (stream, input) = circuit.add_input_zset::<u64, isize>();
+            let output = stream.integrate().output();
+            (input, output)
+        })
+        .unwrap();
+
+        input.append(&mut vec![(1, 1)]);
+        handle.step().unwrap();
+
+        match handle.rescale(4).unwrap_err() {
+            DBSPError::Runtime(RuntimeError::OperatorNotRescalable(_)) => {}
+            err => panic!("unexpected error {err:?}"),
+        }
+        assert_eq!(handle.num_workers(), 2);
+
+        input.append(&mut vec![(2, 1)]);
+        handle.step().unwrap();
+        assert_eq!(output.consolidate(), zset! { 1 => 1, 2 => 1 });
+
+        handle.kill().unwrap();
+    }
+
+    #[test]
+    fn test_stage_inputs() {
+        let (mut handle, (mut input, output)) = Runtime::init_circuit(4, |circuit| {
+            let (stream, input) = circuit.add_input_zset::<u64, isize>();
+            let output = stream.output();
+            (input, output)
+        })
+        .unwrap();
+
+        input.append(&mut vec![(1, 1), (2, 1)]);
+        handle.stage_inputs().unwrap();
+        input.append(&mut vec![(3, 1)]);
+
+        // Only staged inputs are consumed by the next step.
+        handle.step().unwrap();
+        assert_eq!(output.consolidate(), zset! { 1 => 1, 2 => 1 });
+
+        handle.step().unwrap();
+        assert_eq!(output.consolidate(), zset! { 3 => 1 });
+
+        handle.kill().unwrap();
+    }
+
+    #[test]
+    fn test_transaction() {
+        let (mut handle, (mut input, output)) = Runtime::init_circuit(4, |circuit| {
+            let (stream, input) = circuit.add_input_zset::<u64, isize>();
+            let output = stream.integrate().output();
+            (input, output)
+        })
+        .unwrap();
+
+        input.append(&mut vec![(1, 1)]);
+        handle.step().unwrap();
+        assert_eq!(output.consolidate(), zset! { 1 => 1 });
+
+        // Outputs are not updated until the transaction commits.
+        handle.start_transaction().unwrap();
+        for x in 2..5 {
+            input.append(&mut vec![(x, 1), (1, -1)]);
+            handle.step().unwrap();
+            assert_eq!(output.consolidate(), zset! {});
+        }
+        input.append(&mut vec![(1, 3)]);
+        handle.commit_transaction().unwrap();
+        assert_eq!(
+            output.consolidate(),
+            zset! { 1 => 1, 2 => 1, 3 => 1, 4 => 1 }
+        );
+
+        // Aborted transaction has no effect.
+        handle.start_transaction().unwrap();
+        input.append(&mut vec![(1, -1), (5, 1)]);
+        handle.step().unwrap();
+        handle.abort_transaction().unwrap();
+
+        handle.step().unwrap();
+        assert_eq!(
+            output.consolidate(),
+            zset! { 1 => 1, 2 => 1, 3 => 1, 4 => 1 }
+        );
+
+        // Aborting a transaction preserves inputs buffered before it started.
+        input.append(&mut vec![(6, 1), (7, 1)]);
+        handle.start_transaction().unwrap();
+        input.append(&mut vec![(8, 1)]);
+        handle.abort_transaction().unwrap();
+
+        handle.step().unwrap();
+        assert_eq!(
+            output.consolidate(),
+            zset! { 1 => 1, 2 => 1, 3 => 1, 4 => 1, 6 => 1, 7 => 1 }
+        );
+
+        if let DBSPError::Runtime(err) = handle.commit_transaction().unwrap_err() {
+            assert_eq!(err, RuntimeError::NoTransaction);
+        } else {
+            panic!();
+        }
+
+        handle.kill().unwrap();
+    }
 }
@@ -0,0 +1,497 @@
+//! Serializable description of a circuit and static checks on it.
+//!
+//! A [`CircuitDescription`] lists all operators and subcircuits of a circuit
+//! along with streams connecting them.  It is produced by
+//! [`Circuit::build_with_description`](`crate::Circuit::build_with_description`)
+//! or [`DBSPHandle::circuit_description`](`crate::DBSPHandle::circuit_description`)
+//! and can be checked for common mistakes using
+//! [`CircuitDescription::validate`].
+
+use petgraph::{algo::tarjan_scc, graphmap::DiGraphMap};
+#[cfg(feature = "with-serde")]
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    fmt::Display,
+};
+
+/// Description of a circuit instantiated in one worker.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(Serialize))]
+pub struct CircuitDescription {
+    /// Number of workers in the runtime that runs the circuit.
+    pub workers: usize,
+    /// Operators and subcircuits sorted by id.
+    pub nodes: Vec<NodeDescription>,
+    /// Streams and dependencies connecting nodes.
+    pub edges: Vec<EdgeDescription>,
+}
+
+/// An operator or subcircuit.
+///
+/// The two halves of a strict operator are described as a single node
+/// identified by the id of the output half.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(Serialize))]
+pub struct NodeDescription {
+    /// Global node id, i.e., the path from the root circuit to the node.
+    /// Nodes of a subcircuit are prefixed with the id of the subcircuit.
+    pub id: Vec<usize>,
+    pub name: String,
+    /// Source location where the operator was created, if known.
+    pub location: Option<String>,
+    pub kind: NodeDescriptionKind,
+    /// `true` if the output stream of the node is known to be sharded across
+    /// workers (see [`Stream::shard`](`crate::Stream::shard`)).
+    pub sharded: bool,
+    /// `true` if the operator requires inputs sharded by key in a
+    /// multi-worker circuit (see
+    /// [`Operator::is_stateful`](`crate::circuit::operator_traits::Operator::is_stateful`)).
+    pub stateful: bool,
+    /// `true` if the output of the operator is sharded whenever all its
+    /// inputs are sharded (see
+    /// [`Operator::preserves_sharding`](`crate::circuit::operator_traits::Operator::preserves_sharding`)).
+    pub preserves_sharding: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(Serialize))]
+#[cfg_attr(feature = "with-serde", serde(tag = "type"))]
+pub enum NodeDescriptionKind {
+    /// Operator with an output stream.
+    Operator,
+    /// Strict operator, e.g., `Z^-1`, whose output only depends on inputs
+    /// from previous clock cycles.
+    StrictOperator,
+    /// Operator without an output stream.
+    Sink,
+    Circuit {
+        iterative: bool,
+    },
+}
+
+/// A stream or dependency from node `from` to node `to`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "with-serde", derive(Serialize))]
+pub struct EdgeDescription {
+    pub from: Vec<usize>,
+    pub to: Vec<usize>,
+    /// Ownership preference of the consumer of a stream (see
+    /// [`OwnershipPreference`](`crate::circuit::OwnershipPreference`)), or
+    /// `None` for a dependency edge, which only requires `from` to be
+    /// evaluated before `to`.
+    pub ownership_preference: Option<usize>,
+}
+
+impl EdgeDescription {
+    /// `true` if the edge represents a stream.
+    pub fn is_stream(&self) -> bool {
+        self.ownership_preference.is_some()
+    }
+}
+
+/// A likely mistake in a circuit reported by [`CircuitDescription::validate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(Serialize))]
+pub enum CircuitWarning {
+    /// A stateful operator in a multi-worker circuit reads a stream that is
+    /// not sharded by key, so each worker only sees part of the state.
+    UnshardedInput {
+        node: Vec<usize>,
+        operator: String,
+        input: Vec<usize>,
+    },
+    /// The output stream of an operator is not consumed by any other
+    /// operator.
+    UnconsumedStream { node: Vec<usize>, operator: String },
+    /// A cycle of streams that does not contain a strict operator such as
+    /// `Z^-1`.
+    FeedbackWithoutDelay { nodes: Vec<Vec<usize>> },
+}
+
+impl Display for CircuitWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnshardedInput {
+                node,
+                operator,
+                input,
+            } => write!(
+                f,
+                "stateful operator '{operator}' {node:?} reads unsharded stream {input:?} in a \
+                 multi-worker circuit"
+            ),
+            Self::UnconsumedStream { node, operator } => {
+                write!(
+                    f,
+                    "output stream of operator '{operator}' {node:?} is not consumed"
+                )
+            }
+            Self::FeedbackWithoutDelay { nodes } => {
+                write!(f, "feedback loop without a delay through nodes {nodes:?}")
+            }
+        }
+    }
+}
+
+impl CircuitDescription {
+    /// Check the circuit for common mistakes:
+    ///
+    /// * In a multi-worker circuit, a stateful operator (e.g., `distinct`,
+    ///   `join`, or `aggregate`) whose input stream is not sharded by key.
+    ///
+    /// * An operator whose output stream is not consumed by any other
+    ///   operator.
+    ///
+    /// * A feedback loop that does not contain a strict operator such as
+    ///   `Z^-1`.
+    pub fn validate(&self) -> Vec<CircuitWarning> {
+        let mut warnings = Vec::new();
+
+        if self.workers > 1 {
+            self.check_sharding(&mut warnings);
+        }
+        self.check_unconsumed(&mut warnings);
+        self.check_feedback(&mut warnings);
+
+        warnings
+    }
+
+    fn stream_edges(&self) -> impl Iterator<Item = &EdgeDescription> {
+        self.edges.iter().filter(|edge| edge.is_stream())
+    }
+
+    fn check_sharding(&self, warnings: &mut Vec<CircuitWarning>) {
+        let mut inputs: HashMap<&[usize], Vec<&[usize]>> = HashMap::new();
+        for edge in self.stream_edges() {
+            inputs
+                .entry(edge.to.as_slice())
+                .or_default()
+                .push(edge.from.as_slice());
+        }
+
+        // Start from the assumption that all sharding-preserving operators
+        // produce sharded outputs and refine it until a fixpoint is reached.
+        // This correctly handles cycles through delay operators.
+        let mut sharded: HashSet<&[usize]> = self
+            .nodes
+            .iter()
+            .filter(|node| node.sharded || node.preserves_sharding)
+            .map(|node| node.id.as_slice())
+            .collect();
+
+        loop {
+            let unsharded = self
+                .nodes
+                .iter()
+                .filter(|node| {
+                    !node.sharded
+                        && sharded.contains(node.id.as_slice())
+                        && inputs.get(node.id.as_slice()).map_or(false, |from| {
+                            from.iter().any(|input| !sharded.contains(input))
+                        })
+                })
+                .map(|node| node.id.as_slice())
+                .collect::<Vec<_>>();
+
+            if unsharded.is_empty() {
+                break;
+            }
+            for id in unsharded {
+                sharded.remove(id);
+            }
+        }
+
+        for node in self.nodes.iter() {
+            if !node.stateful {
+                continue;
+            }
+            for input in inputs.get(node.id.as_slice()).into_iter().flatten() {
+                if !sharded.contains(input) {
+                    warnings.push(CircuitWarning::UnshardedInput {
+                        node: node.id.clone(),
+                        operator: node.name.clone(),
+                        input: input.to_vec(),
+                    });
+                }
+            }
+        }
+    }
+
+    fn check_unconsumed(&self, warnings: &mut Vec<CircuitWarning>) {
+        let consumed: HashSet<&[usize]> = self
+            .stream_edges()
+            .map(|edge| edge.from.as_slice())
+            .collect();
+
+        for node in self.nodes.iter() {
+            if matches!(
+                node.kind,
+                NodeDescriptionKind::Operator | NodeDescriptionKind::StrictOperator
+            ) && !consumed.contains(node.id.as_slice())
+            {
+                warnings.push(CircuitWarning::UnconsumedStream {
+                    node: node.id.clone(),
+                    operator: node.name.clone(),
+                });
+            }
+        }
+    }
+
+    fn check_feedback(&self, warnings: &mut Vec<CircuitWarning>) {
+        // Feedback loops are formed by nodes of the same circuit.  Streams
+        // that cross circuit boundaries are attributed to the outermost
+        // subcircuits that contain their endpoints.
+        let mut graph = DiGraphMap::<usize, ()>::new();
+        for edge in self.stream_edges() {
+            let common = edge
+                .from
+                .iter()
+                .zip(edge.to.iter())
+                .take_while(|(from, to)| from == to)
+                .count();
+            if common >= edge.from.len() || common >= edge.to.len() {
+                continue;
+            }
+
+            let from = self
+                .nodes
+                .binary_search_by(|node| node.id.as_slice().cmp(&edge.from[..=common]));
+            let to = self
+                .nodes
+                .binary_search_by(|node| node.id.as_slice().cmp(&edge.to[..=common]));
+            if let (Ok(from), Ok(to)) = (from, to) {
+                graph.add_edge(from, to, ());
+            }
+        }
+
+        for scc in tarjan_scc(&graph) {
+            if scc.len() == 1 && !graph.contains_edge(scc[0], scc[0]) {
+                continue;
+            }
+            if scc
+                .iter()
+                .any(|index| self.nodes[*index].kind == NodeDescriptionKind::StrictOperator)
+            {
+                continue;
+            }
+
+            let mut nodes = scc
+                .into_iter()
+                .map(|index| self.nodes[index].id.clone())
+                .collect::<Vec<_>>();
+            nodes.sort();
+            warnings.push(CircuitWarning::FeedbackWithoutDelay { nodes });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        CircuitDescription, CircuitWarning, EdgeDescription, NodeDescription, NodeDescriptionKind,
+    };
+    use crate::{
+        operator::{communication::SkewConfig, FilterMap, Generator},
+        trace::Batch,
+        Circuit, OrdZSet, Runtime, Stream,
+    };
+
+    fn node(id: &[usize], name: &str, kind: NodeDescriptionKind) -> NodeDescription {
+        NodeDescription {
+            id: id.to_vec(),
+            name: name.to_string(),
+            location: None,
+            kind,
+            sharded: false,
+            stateful: false,
+            preserves_sharding: false,
+        }
+    }
+
+    fn stream(from: &[usize], to: &[usize]) -> EdgeDescription {
+        EdgeDescription {
+            from: from.to_vec(),
+            to: to.to_vec(),
+            ownership_preference: Some(0),
+        }
+    }
+
+    fn generator<P>(circuit: &Circuit<P>) -> Stream<Circuit<P>, OrdZSet<u64, isize>>
+    where
+        P: Clone + 'static,
+    {
+        circuit.add_source(Generator::new(|| {
+            OrdZSet::from_keys((), vec![(1, 1), (2, 1)])
+        }))
+    }
+
+    #[test]
+    fn test_description() {
+        let (_handle, _, description) = Circuit::build_with_description(|circuit| {
+            let input = generator(circuit);
+            input.distinct().inspect(|_| {});
+        })
+        .unwrap();
+
+        assert_eq!(description.workers, 1);
+        assert!(description.nodes.iter().any(|node| node.name == "Distinct"
+            && node.kind == NodeDescriptionKind::Operator
+            && node.stateful));
+        assert!(description.nodes.iter().any(|node| node.name == "Inspect"
+            && node.location.as_ref().unwrap().contains("description.rs")));
+        assert!(description.edges.iter().any(|edge| edge.is_stream()));
+
+        // The output of `inspect` is not consumed.
+        assert_eq!(
+            description.validate(),
+            vec![CircuitWarning::UnconsumedStream {
+                node: description
+                    .nodes
+                    .iter()
+                    .find(|node| node.name == "Inspect")
+                    .unwrap()
+                    .id
+                    .clone(),
+                operator: "Inspect".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_nested_description() {
+        let (_handle, _, description) = Circuit::build_with_description(|circuit| {
+            let input = generator(circuit);
+            circuit
+                .recursive(|child, x: Stream<_, OrdZSet<u64, isize>>| {
+                    let input = input.delta0(child);
+                    Ok(input.plus(&x).distinct())
+                })
+                .unwrap()
+                .output();
+        })
+        .unwrap();
+
+        assert!(description
+            .nodes
+            .iter()
+            .any(|node| node.kind == NodeDescriptionKind::Circuit { iterative: true }));
+        assert!(description
+            .nodes
+            .iter()
+            .any(|node| node.id.len() == 2 && node.kind == NodeDescriptionKind::StrictOperator));
+        assert!(description
+            .nodes
+            .iter()
+            .any(|node| node.kind == NodeDescriptionKind::Sink));
+
+        // The feedback loop created by `recursive` contains `Z^-1`.
+        assert!(!description
+            .validate()
+            .iter()
+            .any(|warning| matches!(warning, CircuitWarning::FeedbackWithoutDelay { .. })));
+    }
+
+    #[test]
+    fn test_sharded_description() {
+        let hruntime = Runtime::run(4, || {
+            let (_handle, _, description) = Circuit::build_with_description(|circuit| {
+                // Library operators shard their inputs.
+                generator(circuit).distinct().output();
+            })
+            .unwrap();
+
+            assert_eq!(description.workers, 4);
+            assert!(description.nodes.iter().any(|node| node.sharded));
+            assert!(!description
+                .validate()
+                .iter()
+                .any(|warning| matches!(warning, CircuitWarning::UnshardedInput { .. })));
+        });
+
+        hruntime.join().unwrap();
+    }
+
+    #[test]
+    fn test_skewed_description() {
+        let hruntime = Runtime::run(4, || {
+            let (_handle, _, description) = Circuit::build_with_description(|circuit| {
+                let input = generator(circuit).map_index(|&k| (k, k));
+                input
+                    .stream_join_skewed(&input, |k, _, _| *k, &SkewConfig::new(100))
+                    .output();
+            })
+            .unwrap();
+
+            // The skewed join reads streams partitioned by `shard_skewed`,
+            // which are not sharded by key by design.
+            assert!(description
+                .nodes
+                .iter()
+                .any(|node| node.name == "Join" && !node.stateful));
+            assert!(!description
+                .validate()
+                .iter()
+                .any(|warning| matches!(warning, CircuitWarning::UnshardedInput { .. })));
+        });
+
+        hruntime.join().unwrap();
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut description = CircuitDescription {
+            workers: 2,
+            nodes: vec![
+                node(&[0], "Generator", NodeDescriptionKind::Operator),
+                node(&[1], "Distinct", NodeDescriptionKind::Operator),
+                node(&[2], "Z^-1", NodeDescriptionKind::StrictOperator),
+                node(&[3], "Plus", NodeDescriptionKind::Operator),
+                node(&[4], "Map", NodeDescriptionKind::Operator),
+                node(&[5], "Output", NodeDescriptionKind::Sink),
+            ],
+            edges: vec![
+                stream(&[0], &[1]),
+                stream(&[1], &[3]),
+                stream(&[3], &[4]),
+                stream(&[4], &[3]),
+                stream(&[1], &[2]),
+                stream(&[2], &[5]),
+                stream(&[3], &[5]),
+            ],
+        };
+        description.nodes[1].stateful = true;
+        description.nodes[2].preserves_sharding = true;
+
+        assert_eq!(
+            description.validate(),
+            vec![
+                CircuitWarning::UnshardedInput {
+                    node: vec![1],
+                    operator: "Distinct".to_string(),
+                    input: vec![0],
+                },
+                CircuitWarning::FeedbackWithoutDelay {
+                    nodes: vec![vec![3], vec![4]],
+                },
+            ]
+        );
+
+        // Sharding the input of `Distinct` fixes the first warning.
+        let mut sharded = description.clone();
+        sharded.nodes[0].sharded = true;
+        assert_eq!(
+            sharded.validate(),
+            vec![CircuitWarning::FeedbackWithoutDelay {
+                nodes: vec![vec![3], vec![4]],
+            }]
+        );
+
+        // Single-worker circuits don't require sharding.
+        let mut single_worker = description;
+        single_worker.workers = 1;
+        single_worker.edges[3] = stream(&[4], &[5]);
+        assert_eq!(single_worker.validate(), vec![]);
+    }
+}
@@ -1,3 +1,5 @@
+#[cfg(feature = "with-serde")]
+use serde::{ser::SerializeMap, Serialize, Serializer};
 use size_of::{HumanBytes, TotalSize};
 use std::{
     borrow::Cow,
@@ -183,3 +185,49 @@ impl From<TotalSize> for MetaItem {
         )
     }
 }
+
+/// Serializes metadata as a map from labels to items.
+#[cfg(feature = "with-serde")]
+impl Serialize for OperatorMeta {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.entries.len()))?;
+        for (label, item) in self.entries.iter() {
+            map.serialize_entry(label.as_ref(), item)?;
+        }
+        map.end()
+    }
+}
+
+/// Serializes each item as a single-entry map from the item type to its
+/// value, e.g., `{"Int": 5}`, so that consumers don't need to parse
+/// human-readable strings.  Byte counts are serialized as integers.
+#[cfg(feature = "with-serde")]
+impl Serialize for MetaItem {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Self::Int(int) => serializer.serialize_newtype_variant("MetaItem", 0, "Int", int),
+            Self::Percent(percent) => {
+                serializer.serialize_newtype_variant("MetaItem", 1, "Percent", percent)
+            }
+            Self::String(string) => {
+                serializer.serialize_newtype_variant("MetaItem", 2, "String", string)
+            }
+            Self::Array(array) => {
+                serializer.serialize_newtype_variant("MetaItem", 3, "Array", array)
+            }
+            Self::Map(map) => serializer.serialize_newtype_variant("MetaItem", 4, "Map", map),
+            Self::Bytes(bytes) => {
+                serializer.serialize_newtype_variant("MetaItem", 5, "Bytes", &bytes.bytes)
+            }
+            Self::Duration(duration) => {
+                serializer.serialize_newtype_variant("MetaItem", 6, "Duration", duration)
+            }
+        }
+    }
+}
@@ -14,6 +14,7 @@ pub(crate) mod runtime;
 pub mod metadata;
 pub mod cache;
 pub mod circuit_builder;
+pub mod description;
 pub mod operator_traits;
 pub mod schedule;
 pub mod trace;
@@ -24,6 +25,9 @@ pub use circuit_builder::{
     OwnershipPreference, Scope, Stream,
 };
 pub use dbsp_handle::DBSPHandle;
+pub use description::{
+    CircuitDescription, CircuitWarning, EdgeDescription, NodeDescription, NodeDescriptionKind,
+};
 pub use runtime::{Error as RuntimeError, LocalStore, LocalStoreMarker, Runtime, RuntimeHandle};
 
 pub use schedule::Error as SchedulerError;
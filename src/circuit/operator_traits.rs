@@ -7,7 +7,19 @@ use crate::circuit::{
     metadata::{OperatorLocation, OperatorMeta},
     OwnershipPreference, Scope,
 };
-use std::borrow::Cow;
+use std::{any::Any, borrow::Cow, sync::Arc};
+
+/// State of an operator extracted by [`Operator::take_state`].
+pub type OperatorState = Box<dyn Any + Send>;
+
+/// Splits operator state across the workers of a runtime (see
+/// [`Operator::state_partitioner`]).
+///
+/// `partitioner(worker, state, num_workers)` splits `state`, extracted from
+/// the operator in worker `worker` of the old runtime, into `num_workers`
+/// parts, one for each worker of the new runtime.
+pub type StatePartitioner =
+    Arc<dyn Fn(usize, OperatorState, usize) -> Vec<OperatorState> + Send + Sync>;
 
 /// Minimal requirements for values exchanged by operators.
 pub trait Data: Clone + 'static {}
@@ -194,6 +206,58 @@ pub trait Operator: 'static {
     /// of the fixed point computation, but not as part of an integrator circuit
     /// ([`Stream::integrate`](`crate::circuit::Stream::integrate`)).
     fn fixedpoint(&self, scope: Scope) -> bool;
+
+    /// Returns `true` if the operator combines tuples with the same key, e.g.,
+    /// to maintain per-key state or to join or aggregate them, and therefore
+    /// computes incorrect results in a multi-worker circuit unless its inputs
+    /// are sharded by key.
+    ///
+    /// Used by [`CircuitDescription::validate`](`crate::circuit::CircuitDescription::validate`)
+    /// to detect stateful operators with unsharded inputs.
+    fn is_stateful(&self) -> bool {
+        false
+    }
+
+    /// Returns `true` if the output of the operator is sharded whenever all
+    /// its inputs are sharded, e.g., because it only delays or accumulates
+    /// its inputs.
+    fn preserves_sharding(&self) -> bool {
+        false
+    }
+
+    /// Returns `false` if the operator maintains state across clock cycles
+    /// that cannot be transferred to a runtime with a different number of
+    /// workers.  A circuit that contains such an operator in its top-level
+    /// scope cannot be rescaled (see
+    /// [`DBSPHandle::rescale`](`crate::DBSPHandle::rescale`)).
+    fn state_transferable(&self) -> bool {
+        true
+    }
+
+    /// Returns the function used to split the state of this operator
+    /// across workers of the current runtime when the circuit is rescaled,
+    /// or `None` if the operator does not keep any state across clock cycles.
+    ///
+    /// The function is obtained from the operator in the new runtime and
+    /// applied to states extracted by [`Self::take_state`] from the
+    /// corresponding operators in the old runtime, so that the state is
+    /// partitioned according to the sharding scheme of the new runtime.
+    fn state_partitioner(&self) -> Option<StatePartitioner> {
+        None
+    }
+
+    /// Extract the state of the operator, leaving the operator empty.
+    ///
+    /// Only invoked between clock cycles for operators that return a
+    /// partitioner from [`Self::state_partitioner`].
+    fn take_state(&mut self) -> Option<OperatorState> {
+        None
+    }
+
+    /// Restore the state of the operator from parts produced by the state
+    /// partitioner for the current worker, one for each worker of the old
+    /// runtime.
+    fn restore_state(&mut self, _states: Vec<OperatorState>) {}
 }
 
 /// A source operator that injects data from the outside world or from the
@@ -3,13 +3,18 @@
 
 use crossbeam::channel::bounded;
 use crossbeam_utils::sync::{Parker, Unparker};
+use rayon::{
+    iter::{IntoParallelIterator, ParallelIterator},
+    ThreadPool, ThreadPoolBuilder,
+};
 use std::{
     cell::{Cell, RefCell},
     fmt,
     fmt::{Debug, Display, Error as FmtError, Formatter},
+    mem::take,
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc,
+        Arc, Mutex,
     },
     thread::{Builder, JoinHandle, LocalKey, Result as ThreadResult},
 };
@@ -19,6 +24,23 @@ use typedmap::{TypedDashMap, TypedMapKey};
 pub enum Error {
     WorkerPanic(usize),
     Killed,
+    /// The circuit was not created with support for rescaling (see
+    /// [`Runtime::init_rescalable_circuit`]).
+    NotRescalable,
+    /// The circuit contains an operator whose state cannot be transferred to
+    /// a runtime with a different number of workers.
+    OperatorNotRescalable(String),
+    /// The requested number of workers is zero.
+    NoWorkers,
+    /// [`DBSPHandle::start_transaction`](`crate::DBSPHandle::start_transaction`)
+    /// was called while a transaction is in progress.
+    TransactionInProgress,
+    /// The operation requires a transaction, but no transaction is in
+    /// progress.
+    NoTransaction,
+    /// The circuit was not created with provenance tracing enabled (see
+    /// [`Runtime::init_circuit_with_provenance`]).
+    NoProvenance,
 }
 
 impl Display for Error {
@@ -28,6 +50,14 @@ impl Display for Error {
                 write!(f, "worker thread '{worker}' panicked")
             }
             Self::Killed => f.write_str("circuit killed by the user"),
+            Self::NotRescalable => f.write_str("circuit does not support rescaling"),
+            Self::OperatorNotRescalable(operator) => {
+                write!(f, "operator '{operator}' does not support rescaling")
+            }
+            Self::NoWorkers => f.write_str("the number of workers must be greater than 0"),
+            Self::TransactionInProgress => f.write_str("transaction already in progress"),
+            Self::NoTransaction => f.write_str("no transaction in progress"),
+            Self::NoProvenance => f.write_str("provenance tracing is not enabled"),
         }
     }
 }
@@ -64,21 +94,46 @@ pub type LocalStore = TypedDashMap<LocalStoreMarker>;
 struct RuntimeInner {
     nworkers: usize,
     store: LocalStore,
+    thread_pool: Option<ThreadPool>,
+    // The runtime this runtime replaces after rescaling.  Input and output
+    // handles created by the predecessor are inherited by the new runtime.
+    predecessor: Mutex<Option<Runtime>>,
+    // Completes the transfer of handles inherited from the predecessor (see
+    // `Runtime::commit_handles`).
+    handle_commits: Mutex<Vec<Box<dyn FnOnce() + Send>>>,
 }
 
 impl Debug for RuntimeInner {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.debug_struct("RuntimeInner")
             .field("nworkers", &self.nworkers)
+            .field(
+                "thread_pool_size",
+                &self
+                    .thread_pool
+                    .as_ref()
+                    .map_or(0, ThreadPool::current_num_threads),
+            )
             .finish()
     }
 }
 
 impl RuntimeInner {
-    fn new(nworkers: usize) -> Self {
+    fn new(nworkers: usize, pool_threads: usize, predecessor: Option<Runtime>) -> Self {
+        let thread_pool = (pool_threads > 0).then(|| {
+            ThreadPoolBuilder::new()
+                .num_threads(pool_threads)
+                .thread_name(|index| format!("dbsp-pool-{index}"))
+                .build()
+                .unwrap_or_else(|error| panic!("failed to create thread pool: {error}"))
+        });
+
         Self {
             nworkers,
             store: TypedDashMap::new(),
+            thread_pool,
+            predecessor: Mutex::new(predecessor),
+            handle_commits: Mutex::new(Vec::new()),
         }
     }
 }
@@ -138,7 +193,53 @@ impl Runtime {
     where
         F: FnOnce() + Clone + Send + 'static,
     {
-        let runtime = Self(Arc::new(RuntimeInner::new(workers)));
+        Self::run_with_thread_pool(workers, 0, circuit)
+    }
+
+    /// Like [`Self::run`], but additionally creates a work-stealing pool of
+    /// `pool_threads` threads shared by all workers in the runtime.
+    ///
+    /// Workers evaluate their circuits sequentially, so an expensive operator
+    /// in one worker can keep other workers waiting for it at the next
+    /// exchange.  The thread pool allows such operators to split their work
+    /// into tasks that are executed in parallel by idle pool threads (see
+    /// [`Runtime::join`] and [`Runtime::map_parallel`]).  Currently the pool
+    /// is used to merge large batches in traces and to sort large batches of
+    /// input tuples when building batches.
+    ///
+    /// Join operators do not use the pool: their cursor loops call
+    /// user-supplied closures, which are not required to be `Send` or
+    /// `Sync`, so they always run on the worker thread.  Use
+    /// [`Stream::shard_skewed`](`crate::Stream::shard_skewed`) to spread the
+    /// work of joins with skewed keys across workers instead.
+    ///
+    /// When `pool_threads` is `0`, no pool is created and all work is
+    /// performed by the worker threads, which is equivalent to
+    /// [`Self::run`].
+    pub fn run_with_thread_pool<F>(workers: usize, pool_threads: usize, circuit: F) -> RuntimeHandle
+    where
+        F: FnOnce() + Clone + Send + 'static,
+    {
+        Self::run_with_predecessor(workers, pool_threads, None, circuit)
+    }
+
+    /// Like [`Self::run_with_thread_pool`], but creates a runtime that
+    /// inherits input and output handles from `predecessor` (see
+    /// [`Self::predecessor`]).
+    pub(crate) fn run_with_predecessor<F>(
+        workers: usize,
+        pool_threads: usize,
+        predecessor: Option<Runtime>,
+        circuit: F,
+    ) -> RuntimeHandle
+    where
+        F: FnOnce() + Clone + Send + 'static,
+    {
+        let runtime = Self(Arc::new(RuntimeInner::new(
+            workers,
+            pool_threads,
+            predecessor,
+        )));
 
         let mut handles = Vec::with_capacity(workers);
         handles.extend((0..workers).map(|worker_index| {
@@ -224,6 +325,93 @@ impl Runtime {
         &self.inner().store
     }
 
+    /// Returns the number of threads in the thread pool shared by workers in
+    /// the current runtime, or `0` if the runtime was created without a
+    /// thread pool or if the current thread does not run in a runtime.
+    pub fn thread_pool_size() -> usize {
+        RUNTIME.with(|rt| {
+            rt.borrow()
+                .as_ref()
+                .and_then(|runtime| runtime.inner().thread_pool.as_ref())
+                .map_or(0, ThreadPool::current_num_threads)
+        })
+    }
+
+    /// Evaluate closures `a` and `b`, potentially in parallel.
+    ///
+    /// If the current runtime has a thread pool (see
+    /// [`Self::run_with_thread_pool`]), the closures are submitted to the
+    /// pool and the calling worker blocks until both complete.  Otherwise,
+    /// they are evaluated sequentially by the calling thread.
+    pub fn join<A, B, RA, RB>(a: A, b: B) -> (RA, RB)
+    where
+        A: FnOnce() -> RA + Send,
+        B: FnOnce() -> RB + Send,
+        RA: Send,
+        RB: Send,
+    {
+        // Already running in the pool (e.g., in a nested parallel section).
+        if rayon::current_thread_index().is_some() {
+            return rayon::join(a, b);
+        }
+
+        match Self::runtime() {
+            Some(runtime) if runtime.inner().thread_pool.is_some() => runtime
+                .inner()
+                .thread_pool
+                .as_ref()
+                .unwrap()
+                .install(|| rayon::join(a, b)),
+            _ => (a(), b()),
+        }
+    }
+
+    /// Evaluate `f` in the thread pool of the current runtime, if any, so that
+    /// parallel iterators used by `f` execute in the pool.
+    ///
+    /// Without a thread pool `f` is evaluated by the calling thread.
+    pub fn install<F, R>(f: F) -> R
+    where
+        F: FnOnce() -> R + Send,
+        R: Send,
+    {
+        if rayon::current_thread_index().is_some() {
+            return f();
+        }
+
+        match Self::runtime() {
+            Some(runtime) if runtime.inner().thread_pool.is_some() => {
+                runtime.inner().thread_pool.as_ref().unwrap().install(f)
+            }
+            _ => f(),
+        }
+    }
+
+    /// Apply `f` to all elements of `tasks`, potentially in parallel,
+    /// returning results in the same order as `tasks`.
+    ///
+    /// See [`Self::join`] for details about where the work is executed.
+    pub fn map_parallel<T, R, F>(tasks: Vec<T>, f: F) -> Vec<R>
+    where
+        T: Send,
+        R: Send,
+        F: Fn(T) -> R + Send + Sync,
+    {
+        if rayon::current_thread_index().is_some() {
+            return tasks.into_par_iter().map(f).collect();
+        }
+
+        match Self::runtime() {
+            Some(runtime) if runtime.inner().thread_pool.is_some() => runtime
+                .inner()
+                .thread_pool
+                .as_ref()
+                .unwrap()
+                .install(|| tasks.into_par_iter().map(f).collect()),
+            _ => tasks.into_iter().map(f).collect(),
+        }
+    }
+
     /// A per-worker sequential counter.
     ///
     /// This method can be used to generate unique identifiers that will be the
@@ -240,6 +428,59 @@ impl Runtime {
         result
     }
 
+    /// A per-worker sequential counter used to assign identifiers to input and
+    /// output handles.
+    ///
+    /// Unlike [`Self::sequence_next`], which is also used by operators that
+    /// are only instantiated in multithreaded runtimes, e.g., exchange
+    /// operators, this counter only counts handles.  Handle identifiers are
+    /// therefore the same in runtimes with different numbers of workers,
+    /// which allows a runtime to find handles created by its predecessor.
+    pub(crate) fn handle_sequence_next(&self, worker_index: usize) -> usize {
+        debug_assert!(worker_index < self.inner().nworkers);
+        let mut entry = self
+            .local_store()
+            .entry(HandleId(worker_index))
+            .or_insert(0);
+        let result = *entry;
+        *entry += 1;
+        result
+    }
+
+    /// Returns the runtime replaced by this runtime after rescaling (see
+    /// [`DBSPHandle::rescale`](`crate::DBSPHandle::rescale`)), if any.
+    ///
+    /// Input and output handles look up their counterparts in the
+    /// predecessor's local store, so that handles held by the client remain
+    /// valid after rescaling.
+    pub(crate) fn predecessor(&self) -> Option<Runtime> {
+        self.inner().predecessor.lock().unwrap().clone()
+    }
+
+    /// Drop the reference to the predecessor runtime once all circuits in
+    /// this runtime have been constructed.
+    pub(crate) fn release_predecessor(&self) {
+        self.inner().predecessor.lock().unwrap().take();
+    }
+
+    /// Register a closure that switches a handle inherited from the
+    /// predecessor over to this runtime.
+    ///
+    /// Inherited handles keep serving the predecessor until
+    /// [`Self::commit_handles`] is called, so that the predecessor can keep
+    /// running if this runtime fails to start.
+    pub(crate) fn on_handles_commit(&self, commit: Box<dyn FnOnce() + Send>) {
+        self.inner().handle_commits.lock().unwrap().push(commit);
+    }
+
+    /// Switch all handles inherited from the predecessor over to this
+    /// runtime.
+    pub(crate) fn commit_handles(&self) {
+        for commit in take(&mut *self.inner().handle_commits.lock().unwrap()) {
+            commit();
+        }
+    }
+
     /// Returns current worker's parker to be used by schedulers.
     ///
     /// Whenever a circuit scheduler needs to block waiting for
@@ -345,11 +586,18 @@ impl TypedMapKey<LocalStoreMarker> for WorkerId {
     type Value = usize;
 }
 
+#[derive(Hash, PartialEq, Eq)]
+struct HandleId(usize);
+
+impl TypedMapKey<LocalStoreMarker> for HandleId {
+    type Value = usize;
+}
+
 #[cfg(test)]
 mod tests {
     use super::Runtime;
     use crate::{
-        circuit::schedule::{DynamicScheduler, Scheduler, StaticScheduler},
+        circuit::schedule::{AdaptiveScheduler, DynamicScheduler, Scheduler, StaticScheduler},
         operator::Generator,
         Circuit,
     };
@@ -367,6 +615,12 @@ mod tests {
         test_runtime::<DynamicScheduler>();
     }
 
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_runtime_adaptive() {
+        test_runtime::<AdaptiveScheduler>();
+    }
+
     fn test_runtime<S>()
     where
         S: Scheduler + 'static,
@@ -408,6 +662,12 @@ mod tests {
         test_kill::<DynamicScheduler>();
     }
 
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_kill_adaptive() {
+        test_kill::<AdaptiveScheduler>();
+    }
+
     // Test `RuntimeHandle::kill`.
     fn test_kill<S>()
     where
@@ -0,0 +1,554 @@
+//! Adaptive scheduler.
+//!
+//! The adaptive scheduler is a cost-aware variant of
+//! [`DynamicScheduler`](`crate::circuit::schedule::DynamicScheduler`).
+//! It evaluates nodes subject to the same two constraints (a node is
+//! evaluated after its predecessors; an async node is only evaluated in a
+//! ready state), but instead of a fixed structural priority it learns the
+//! cost of each node from previous clock cycles and uses it to order
+//! runnable nodes.
+//!
+//! # Design
+//!
+//! ## Cost model
+//!
+//! The scheduler measures the wall-clock time of every node evaluation (the
+//! same quantity reported per operator by
+//! [`CPUProfiler`](`crate::profile::CPUProfiler`)) and maintains an
+//! exponential moving average of this time for each node.  Since the cost of
+//! most operators is proportional to the size of the batches they process,
+//! the moving average tracks changes in input batch sizes across steps
+//! without having to inspect stream contents.
+//!
+//! ## Priorities
+//!
+//! At the end of each step the scheduler recomputes node priorities as the
+//! length of the most expensive path from the node to any sink of the
+//! circuit (the "bottom level" of the node in list scheduling terminology).
+//! Picking the runnable node with the highest bottom level first starts
+//! long, expensive chains of operators as early as possible.  In a
+//! multi-worker runtime this means that data gets sent to exchange
+//! operators early, so that peers can make progress on their share of
+//! the work while this worker evaluates independent parts of the circuit.
+//!
+//! Before costs have been measured (i.e., during the first step), every node
+//! is assumed to have unit cost, so the priority is the length of the longest
+//! path to a sink.
+//!
+//! ## Scope
+//!
+//! This scheduler only changes the *order* in which a worker evaluates
+//! runnable nodes.  In particular:
+//!
+//! * Costs are measured by the scheduler itself rather than read from a
+//!   [`CPUProfiler`](`crate::profile::CPUProfiler`) attached to the circuit,
+//!   and batch sizes are not inspected: operators don't expose the size of
+//!   their inputs in a uniform way, so changes in batch size are only
+//!   visible through their effect on evaluation time.
+//! * Each worker evaluates one node at a time.  Operators are not `Send`, so
+//!   independent subgraphs of the same worker cannot run concurrently; the
+//!   only overlap comes from other workers processing data received through
+//!   exchange operators.
+//! * Operators cannot yield in the middle of an evaluation.  An async
+//!   operator that is not ready is skipped until it notifies the scheduler
+//!   (see below).
+//!
+//! Use the `--scheduler` option of the NEXMark benchmark to compare this
+//! scheduler against [`StaticScheduler`](`crate::circuit::schedule::StaticScheduler`)
+//! and [`DynamicScheduler`](`crate::circuit::schedule::DynamicScheduler`).
+//!
+//! ## Async operators
+//!
+//! Async operators that are not ready do not block the worker: the scheduler
+//! keeps evaluating other runnable nodes and polls for ready notifications
+//! after every evaluation while there are async nodes waiting to run, so
+//! that an async operator resumes as soon as it becomes ready rather than
+//! after the run queue drains.  The worker only parks when there is no
+//! runnable work left.
+
+use std::{
+    cell::{RefCell, RefMut},
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use crate::circuit::{
+    runtime::Runtime,
+    schedule::{
+        util::{circuit_graph, ownership_constraints},
+        Error, Scheduler,
+    },
+    trace::SchedulerEvent,
+    Circuit, GlobalNodeId, NodeId,
+};
+use crossbeam_utils::sync::Unparker;
+use petgraph::algo::toposort;
+use priority_queue::PriorityQueue;
+
+/// Cost assumed for nodes that haven't been evaluated yet, in nanoseconds.
+const DEFAULT_COST: u64 = 1;
+
+/// Weight of the most recent measurement in the moving average of the node
+/// evaluation cost, expressed as `1/COST_SMOOTHING`.
+const COST_SMOOTHING: u64 = 4;
+
+/// A task is a unit of work scheduled by the adaptive scheduler.
+/// It contains a reference to a node in the circuit and associated metadata.
+struct Task {
+    // Immutable fields (initialized once when preparing the scheduler).
+    /// Circuit node to be scheduled.
+    node_id: NodeId,
+
+    /// The number of predecessors of the node in the circuit graph.
+    num_predecessors: usize,
+
+    /// Successors of the node in the circuit graph.
+    successors: Vec<NodeId>,
+
+    /// `true` if this is an async node.
+    is_async: bool,
+
+    // Mutable fields.
+    /// Moving average of the time it takes to evaluate the node, in
+    /// nanoseconds, or `None` if the node hasn't been evaluated yet.
+    cost: Option<u64>,
+
+    /// Scheduling priority: the estimated cost of the most expensive path
+    /// from this node to a sink, including the node itself.  Recomputed at
+    /// the end of every step.
+    priority: u64,
+
+    /// Number of predecessors not yet evaluated.  Set to `num_predecessors`
+    /// at the start of each step.
+    unsatisfied_dependencies: usize,
+
+    /// `true` if the async node is known to be in a ready state.  Always
+    /// `true` for non-async nodes.
+    is_ready: bool,
+
+    /// Task has been scheduled (put on the run queue) in the current clock
+    /// cycle.
+    scheduled: bool,
+}
+
+impl Task {
+    /// Fold a new measurement into the moving average of the node's cost.
+    fn update_cost(&mut self, elapsed: u64) {
+        let cost = match self.cost {
+            None => elapsed,
+            Some(cost) => {
+                (cost.saturating_mul(COST_SMOOTHING - 1)).saturating_add(elapsed) / COST_SMOOTHING
+            }
+        };
+        self.cost = Some(cost.max(1));
+    }
+
+    /// Current cost estimate.
+    fn cost(&self) -> u64 {
+        self.cost.unwrap_or(DEFAULT_COST)
+    }
+}
+
+/// The set of async nodes for which the scheduler has received ready
+/// notifications.
+#[derive(Clone)]
+struct Notifications {
+    /// Nodes that received notifications.
+    nodes: Arc<Mutex<HashSet<NodeId>>>,
+
+    /// Handle to wake up the scheduler thread when a notification arrives.
+    unparker: Unparker,
+}
+
+impl Notifications {
+    fn new(size: usize, unparker: Unparker) -> Self {
+        Self {
+            nodes: Arc::new(Mutex::new(HashSet::with_capacity(size))),
+            unparker,
+        }
+    }
+
+    /// Add a new notification.
+    fn notify(&self, node_id: NodeId) {
+        self.nodes.lock().unwrap().insert(node_id);
+        self.unparker.unpark();
+    }
+}
+
+/// Adaptive scheduler internals.
+struct Inner {
+    // Immutable fields (initialized once when preparing the scheduler).
+    /// List of tasks that must be evaluated at each clock cycle.
+    /// Task index is equal to the node id.
+    tasks: Vec<Task>,
+
+    /// Nodes in reverse topological order (including ownership constraints),
+    /// used to recompute priorities.
+    reverse_topo_order: Vec<NodeId>,
+
+    // Mutable fields.
+    /// Ready notifications received while the scheduler was busy or sleeping.
+    notifications: Notifications,
+
+    /// Tasks that are ready to be executed, sorted by priority.
+    runnable: PriorityQueue<NodeId, u64>,
+
+    /// Number of async tasks whose predecessors have been evaluated in the
+    /// current step, but that are not ready yet.
+    waiting_async: usize,
+}
+
+impl Inner {
+    /// Add task `id` to the run queue.
+    fn push_runnable(&mut self, id: NodeId) {
+        let task = &mut self.tasks[id.id()];
+        debug_assert!(task.unsatisfied_dependencies == 0);
+        debug_assert!(task.is_ready);
+        debug_assert!(!task.scheduled);
+
+        self.runnable.push(task.node_id, task.priority);
+        task.scheduled = true;
+    }
+
+    /// Dequeue a highest-priority task from the runnable queue.
+    /// Update all successors of the task, reducing their unsatisfied
+    /// dependencies by 1.  Move successors to the runnable queue
+    /// when possible.
+    fn dequeue_next_task(&mut self) -> Option<NodeId> {
+        let (node_id, _) = self.runnable.pop()?;
+        let id = node_id.id();
+
+        for i in 0..self.tasks[id].successors.len() {
+            let succ_id = self.tasks[id].successors[i];
+            let successor = &mut self.tasks[succ_id.id()];
+            debug_assert!(successor.unsatisfied_dependencies != 0);
+            successor.unsatisfied_dependencies -= 1;
+            if successor.unsatisfied_dependencies == 0 {
+                if successor.is_ready {
+                    self.push_runnable(succ_id);
+                } else {
+                    self.waiting_async += 1;
+                }
+            }
+        }
+        Some(node_id)
+    }
+
+    /// Process and dequeue new notifications.
+    fn process_notifications<P>(&mut self, circuit: &Circuit<P>)
+    where
+        P: Clone + 'static,
+    {
+        let nodes: Vec<NodeId> = self.notifications.nodes.lock().unwrap().drain().collect();
+
+        for id in nodes.into_iter() {
+            let task = &mut self.tasks[id.id()];
+            debug_assert!(task.is_async);
+
+            // Ignore duplicate and spurious notifications.
+            if task.is_ready || !circuit.ready(id) {
+                continue;
+            }
+
+            task.is_ready = true;
+
+            // A notification for an already scheduled task takes effect at
+            // the next clock cycle.
+            if task.unsatisfied_dependencies == 0 && !task.scheduled {
+                self.waiting_async -= 1;
+                self.push_runnable(id);
+            }
+        }
+    }
+
+    /// Recompute task priorities from the current cost estimates.
+    fn update_priorities(&mut self) {
+        for i in 0..self.reverse_topo_order.len() {
+            let id = self.reverse_topo_order[i].id();
+            let downstream = self.tasks[id]
+                .successors
+                .iter()
+                .map(|succ| self.tasks[succ.id()].priority)
+                .max()
+                .unwrap_or(0);
+            let task = &mut self.tasks[id];
+            task.priority = task.cost().saturating_add(downstream);
+        }
+    }
+
+    fn prepare<P>(circuit: &Circuit<P>) -> Result<Self, Error>
+    where
+        P: Clone + 'static,
+    {
+        let mut g = circuit_graph(circuit);
+
+        let extra_constraints = ownership_constraints(circuit)?;
+
+        for (from, to) in extra_constraints.iter() {
+            g.add_edge(*from, *to, ());
+        }
+
+        // `toposort` fails if the graph contains cycles.
+        let mut reverse_topo_order = toposort(&g, None).map_err(|e| Error::CyclicCircuit {
+            node_id: GlobalNodeId::child_of(circuit, e.node_id()),
+        })?;
+        reverse_topo_order.reverse();
+
+        let num_nodes = circuit.num_nodes();
+        let mut successors: HashMap<NodeId, Vec<NodeId>> = HashMap::with_capacity(num_nodes);
+        let mut num_predecessors: HashMap<NodeId, usize> = HashMap::with_capacity(num_nodes);
+
+        let edges = circuit
+            .edges()
+            .iter()
+            .map(|edge| (edge.from, edge.to))
+            .collect::<Vec<_>>();
+
+        for (from, to) in edges.into_iter().chain(extra_constraints.into_iter()) {
+            successors.entry(from).or_insert_with(Vec::new).push(to);
+            *num_predecessors.entry(to).or_default() += 1;
+        }
+
+        let mut tasks = Vec::with_capacity(num_nodes);
+        let mut num_async_nodes = 0;
+
+        for (i, node_id) in circuit.node_ids().into_iter().enumerate() {
+            // We rely on node id to be equal to its index.
+            assert!(i == node_id.id());
+
+            let num_predecessors = num_predecessors.get(&node_id).cloned().unwrap_or(0);
+            let is_async = circuit.is_async_node(node_id);
+            if is_async {
+                num_async_nodes += 1;
+            }
+
+            tasks.push(Task {
+                node_id,
+                num_predecessors,
+                successors: successors.remove(&node_id).unwrap_or_default(),
+                is_async,
+                cost: None,
+                priority: 0,
+                unsatisfied_dependencies: num_predecessors,
+                is_ready: !is_async,
+                scheduled: false,
+            });
+        }
+
+        let unparker = Runtime::parker().with(|parker| parker.unparker().clone());
+        let mut scheduler = Self {
+            tasks,
+            reverse_topo_order,
+            notifications: Notifications::new(num_async_nodes, unparker),
+            runnable: PriorityQueue::with_capacity(num_nodes),
+            waiting_async: 0,
+        };
+        scheduler.update_priorities();
+
+        // Setup scheduler callbacks.
+        for node_id in circuit.node_ids().into_iter() {
+            if circuit.is_async_node(node_id) {
+                let notifications = scheduler.notifications.clone();
+                circuit.register_ready_callback(
+                    node_id,
+                    Box::new(move || notifications.notify(node_id)),
+                );
+
+                // Since we missed any earlier notifications, generate one for
+                // each ready node.
+                if circuit.ready(node_id) {
+                    scheduler.notifications.notify(node_id);
+                }
+            }
+        }
+
+        Ok(scheduler)
+    }
+
+    fn step<P>(&mut self, circuit: &Circuit<P>) -> Result<(), Error>
+    where
+        P: Clone + 'static,
+    {
+        circuit.log_scheduler_event(&SchedulerEvent::step_start());
+
+        let mut completed_tasks = 0;
+        self.waiting_async = 0;
+
+        // Reset unsatisfied dependencies, initialize runnable queue.
+        for i in 0..self.tasks.len() {
+            let task = &mut self.tasks[i];
+            task.unsatisfied_dependencies = task.num_predecessors;
+            task.scheduled = false;
+
+            if task.unsatisfied_dependencies == 0 {
+                if task.is_ready {
+                    let node_id = task.node_id;
+                    self.push_runnable(node_id);
+                } else {
+                    self.waiting_async += 1;
+                }
+            }
+        }
+
+        while completed_tasks < self.tasks.len() {
+            if Runtime::kill_in_progress() {
+                return Err(Error::Killed);
+            }
+
+            // Let async operators that became ready since the last evaluation
+            // compete with other runnable tasks.
+            if self.waiting_async > 0 {
+                self.process_notifications(circuit);
+            }
+
+            match self.dequeue_next_task() {
+                None => {
+                    self.process_notifications(circuit);
+
+                    // Nothing to do -- sleep waiting for a notification to
+                    // unpark us.
+                    if self.runnable.is_empty() {
+                        Runtime::parker().with(|parker| parker.park());
+                    }
+                }
+
+                Some(node_id) => {
+                    let start = Instant::now();
+                    circuit.eval_node(node_id)?;
+                    let elapsed = start.elapsed().as_nanos() as u64;
+
+                    let task = &mut self.tasks[node_id.id()];
+                    task.update_cost(elapsed);
+                    if task.is_async {
+                        task.is_ready = false;
+                    }
+
+                    completed_tasks += 1;
+                }
+            }
+        }
+
+        self.update_priorities();
+
+        circuit.log_scheduler_event(&SchedulerEvent::step_end());
+        Ok(())
+    }
+}
+
+/// Scheduler that orders nodes based on their measured evaluation cost.
+///
+/// See [module-level documentation](`self`) for details.
+pub struct AdaptiveScheduler(RefCell<Inner>);
+
+impl AdaptiveScheduler {
+    fn inner_mut(&self) -> RefMut<'_, Inner> {
+        self.0.borrow_mut()
+    }
+}
+
+impl Scheduler for AdaptiveScheduler {
+    fn prepare<P>(circuit: &Circuit<P>) -> Result<Self, Error>
+    where
+        P: Clone + 'static,
+    {
+        Ok(Self(RefCell::new(Inner::prepare(circuit)?)))
+    }
+
+    fn step<P>(&self, circuit: &Circuit<P>) -> Result<(), Error>
+    where
+        P: Clone + 'static,
+    {
+        self.inner_mut().step(circuit)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AdaptiveScheduler, Task, COST_SMOOTHING};
+    use crate::{
+        circuit::{trace::SchedulerEvent, GlobalNodeId, NodeId},
+        operator::Generator,
+        Circuit,
+    };
+    use std::{cell::RefCell, rc::Rc, thread::sleep, time::Duration};
+
+    #[test]
+    fn test_update_cost() {
+        let mut task = Task {
+            node_id: NodeId::new(0),
+            num_predecessors: 0,
+            successors: Vec::new(),
+            is_async: false,
+            cost: None,
+            priority: 0,
+            unsatisfied_dependencies: 0,
+            is_ready: true,
+            scheduled: false,
+        };
+
+        task.update_cost(1);
+        assert_eq!(task.cost, Some(1));
+
+        // A measured cost of 1 must be averaged, not treated as unmeasured.
+        task.update_cost(1 + COST_SMOOTHING * 100);
+        assert_eq!(task.cost, Some(101));
+    }
+
+    // Two independent chains: a short one with an expensive operator and a
+    // long one with cheap operators.  Before costs are known, the longer
+    // chain goes first; once the scheduler has measured the expensive
+    // operator, the short chain goes first.
+    #[test]
+    fn test_order_follows_cost() {
+        let evaluated: Rc<RefCell<Vec<GlobalNodeId>>> = Rc::new(RefCell::new(Vec::new()));
+        let evaluated_clone = evaluated.clone();
+
+        let (circuit, (short_source, long_source)) =
+            Circuit::build_with_scheduler::<_, _, AdaptiveScheduler>(move |circuit| {
+                circuit.register_scheduler_event_handler("order", move |event| {
+                    if let SchedulerEvent::EvalStart { node } = event {
+                        evaluated_clone.borrow_mut().push(node.global_id().clone());
+                    }
+                });
+
+                let short = circuit.add_source(Generator::new(|| 0usize));
+                short
+                    .apply(|x| {
+                        sleep(Duration::from_millis(20));
+                        *x
+                    })
+                    .inspect(|_| {});
+
+                let long = circuit.add_source(Generator::new(|| 0usize));
+                long.apply(|x| *x)
+                    .apply(|x| *x)
+                    .apply(|x| *x)
+                    .apply(|x| *x)
+                    .inspect(|_| {});
+
+                (
+                    short.origin_node_id().clone(),
+                    long.origin_node_id().clone(),
+                )
+            })
+            .unwrap();
+
+        let first_source = || {
+            evaluated
+                .borrow()
+                .iter()
+                .find(|node| **node == short_source || **node == long_source)
+                .cloned()
+                .unwrap()
+        };
+
+        evaluated.borrow_mut().clear();
+        circuit.step().unwrap();
+        assert_eq!(first_source(), long_source);
+
+        evaluated.borrow_mut().clear();
+        circuit.step().unwrap();
+        assert_eq!(first_source(), short_source);
+    }
+}
@@ -13,6 +13,9 @@ pub use static_scheduler::StaticScheduler;
 mod dynamic_scheduler;
 pub use dynamic_scheduler::DynamicScheduler;
 
+mod adaptive_scheduler;
+pub use adaptive_scheduler::AdaptiveScheduler;
+
 /// Scheduler errors.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Error {
@@ -115,6 +115,14 @@ pub enum CircuitEvent {
         /// stream.
         to: GlobalNodeId,
     },
+
+    /// The output stream of node `node_id` is sharded across workers by key
+    /// (see [`Stream::shard`](`crate::Stream::shard`)).  This event can be
+    /// emitted multiple times for the same stream.
+    ShardedStream {
+        /// Global id of the node that produces the stream.
+        node_id: GlobalNodeId,
+    },
 }
 
 impl CircuitEvent {
@@ -205,6 +213,11 @@ impl CircuitEvent {
         }
     }
 
+    /// Create a [`CircuitEvent::ShardedStream`] event instance.
+    pub fn sharded_stream(node_id: GlobalNodeId) -> Self {
+        Self::ShardedStream { node_id }
+    }
+
     /// `true` if `self` is a [`CircuitEvent::StrictOperatorInput`]
     pub fn is_strict_input_event(&self) -> bool {
         matches!(self, Self::StrictOperatorInput { .. })
@@ -431,6 +444,10 @@ impl Display for CircuitEvent {
             } => {
                 write!(f, "Dependency({from} -> {to})")
             }
+
+            Self::ShardedStream { node_id } => {
+                write!(f, "ShardedStream({node_id})")
+            }
         }
     }
 }
@@ -19,6 +19,9 @@ pub mod trace;
 #[cfg(feature = "with-nexmark")]
 pub mod nexmark;
 
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
 pub use crate::error::Error;
 pub use crate::hash::default_hash;
 pub use crate::num_entries::NumEntries;
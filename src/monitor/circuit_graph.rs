@@ -1,12 +1,20 @@
 use crate::{
-    circuit::{metadata::OperatorLocation, trace::EdgeKind, GlobalNodeId, NodeId},
-    monitor::visual_graph::{
-        ClusterNode, Edge as VisEdge, Graph as VisGraph, Node as VisNode, SimpleNode,
+    circuit::{
+        description::{EdgeDescription, NodeDescription, NodeDescriptionKind},
+        metadata::OperatorLocation,
+        trace::EdgeKind,
+        GlobalNodeId, NodeId,
+    },
+    monitor::{
+        visual_graph::{
+            ClusterNode, Edge as VisEdge, Graph as VisGraph, Node as VisNode, SimpleNode,
+        },
+        GraphNode, GraphNodeKind,
     },
 };
 use std::{
     borrow::Cow,
-    collections::{hash_map::Entry, HashMap},
+    collections::{hash_map::Entry, HashMap, HashSet},
     slice,
 };
 
@@ -168,6 +176,18 @@ pub(super) enum NodeKind {
     StrictOutput,
 }
 
+/// Properties of circuit nodes that are not observable through circuit
+/// events and are instead obtained from the nodes themselves.
+#[derive(Default)]
+pub(super) struct NodeProperties {
+    /// Nodes that encapsulate sink operators.
+    pub sinks: HashSet<GlobalNodeId>,
+    /// Nodes whose operators require inputs sharded by key.
+    pub stateful: HashSet<GlobalNodeId>,
+    /// Nodes whose operators preserve sharding of their inputs.
+    pub preserves_sharding: HashSet<GlobalNodeId>,
+}
+
 /// A node in a circuit graph represents an operator or a circuit.
 pub(super) struct Node {
     id: GlobalNodeId,
@@ -291,6 +311,108 @@ impl Node {
         node_ident
     }
 
+    /// Append `self` and all its descendants to `nodes`.
+    ///
+    /// The two halves of a strict operator are reported as a single node
+    /// identified by the id of the output half, like in a visual graph.
+    fn export(&self, nodes: &mut Vec<GraphNode>) {
+        match &self.kind {
+            NodeKind::Operator => nodes.push(GraphNode {
+                id: self.id.clone(),
+                name: self.name.clone(),
+                location: self.location,
+                kind: GraphNodeKind::Operator,
+            }),
+            NodeKind::StrictInput { output } => nodes.push(GraphNode {
+                id: self.id.parent_id().unwrap().child(*output),
+                name: self.name.clone(),
+                location: self.location,
+                kind: GraphNodeKind::Operator,
+            }),
+            NodeKind::StrictOutput => {}
+            NodeKind::Circuit {
+                iterative,
+                children,
+                ..
+            } => {
+                // Don't report the root circuit.
+                if !self.id.path().is_empty() {
+                    nodes.push(GraphNode {
+                        id: self.id.clone(),
+                        name: self.name.clone(),
+                        location: self.location,
+                        kind: GraphNodeKind::Circuit {
+                            iterative: *iterative,
+                        },
+                    });
+                }
+                for child in children.values() {
+                    child.export(nodes);
+                }
+            }
+        }
+    }
+
+    /// Append descriptions of `self` and all its descendants to `nodes`.
+    ///
+    /// Like [`Self::export`], describes the two halves of a strict operator as
+    /// a single node.
+    fn describe(
+        &self,
+        properties: &NodeProperties,
+        sharded: &HashSet<GlobalNodeId>,
+        nodes: &mut Vec<NodeDescription>,
+    ) {
+        let (id, kind) = match &self.kind {
+            NodeKind::Operator if properties.sinks.contains(&self.id) => {
+                (self.id.clone(), NodeDescriptionKind::Sink)
+            }
+            NodeKind::Operator => (self.id.clone(), NodeDescriptionKind::Operator),
+            NodeKind::StrictInput { output } => (
+                self.id.parent_id().unwrap().child(*output),
+                NodeDescriptionKind::StrictOperator,
+            ),
+            NodeKind::StrictOutput => return,
+            NodeKind::Circuit {
+                iterative,
+                children,
+                ..
+            } => {
+                for child in children.values() {
+                    child.describe(properties, sharded, nodes);
+                }
+
+                // Don't report the root circuit.
+                if self.id.path().is_empty() {
+                    return;
+                }
+                (
+                    self.id.clone(),
+                    NodeDescriptionKind::Circuit {
+                        iterative: *iterative,
+                    },
+                )
+            }
+        };
+
+        nodes.push(NodeDescription {
+            id: node_path(&id),
+            name: self.name.to_string(),
+            location: self.location.map(|location| {
+                format!(
+                    "{}:{}:{}",
+                    location.file(),
+                    location.line(),
+                    location.column()
+                )
+            }),
+            kind,
+            sharded: sharded.contains(&id),
+            stateful: properties.stateful.contains(&id),
+            preserves_sharding: properties.preserves_sharding.contains(&id),
+        });
+    }
+
     /// Output circuit node as a node in a visual graph.
     fn visualize(&self, annotate: &dyn Fn(&GlobalNodeId) -> String) -> Option<VisNode> {
         match &self.kind {
@@ -335,6 +457,8 @@ pub(super) struct CircuitGraph {
     /// stream or have a dependency on it.
     /// A node can occur in this vector multiple times.
     edges: HashMap<GlobalNodeId, Vec<(GlobalNodeId, EdgeKind)>>,
+    /// Nodes whose output streams are sharded across workers.
+    sharded: HashSet<GlobalNodeId>,
 }
 
 impl CircuitGraph {
@@ -352,6 +476,7 @@ impl CircuitGraph {
                 },
             ),
             edges: HashMap::new(),
+            sharded: HashSet::new(),
         }
     }
 
@@ -376,6 +501,79 @@ impl CircuitGraph {
         }
     }
 
+    pub(super) fn add_sharded(&mut self, id: &GlobalNodeId) {
+        self.sharded.insert(id.clone());
+    }
+
+    /// Returns descriptions of all nodes of the graph and of all edges between
+    /// them, including dependency edges, sorted by node id.
+    ///
+    /// `sinks` identifies operators without output streams, which cannot be
+    /// distinguished from other operators based on circuit events alone.
+    pub(super) fn describe(
+        &self,
+        properties: &NodeProperties,
+    ) -> (Vec<NodeDescription>, Vec<EdgeDescription>) {
+        let mut nodes = Vec::new();
+        self.nodes.describe(properties, &self.sharded, &mut nodes);
+        nodes.sort_by(|n1, n2| n1.id.cmp(&n2.id));
+
+        let mut edges = Vec::new();
+        for (from_id, to) in self.edges.iter() {
+            for (to_id, kind) in to.iter() {
+                let to_node = self.node_ref(to_id).unwrap();
+                let to_id = match to_node.kind {
+                    NodeKind::StrictInput { output } => to_id.parent_id().unwrap().child(output),
+                    _ => to_id.clone(),
+                };
+
+                // Skip the dependency between the two halves of a strict
+                // operator.
+                if &to_id == from_id {
+                    continue;
+                }
+
+                edges.push(EdgeDescription {
+                    from: node_path(from_id),
+                    to: node_path(&to_id),
+                    ownership_preference: match kind {
+                        EdgeKind::Stream(preference) => Some(preference.raw()),
+                        EdgeKind::Dependency => None,
+                    },
+                });
+            }
+        }
+        edges.sort();
+
+        (nodes, edges)
+    }
+
+    /// Returns all nodes of the graph and stream edges between them, sorted by
+    /// node id.
+    pub(super) fn export(&self) -> (Vec<GraphNode>, Vec<(GlobalNodeId, GlobalNodeId)>) {
+        let mut nodes = Vec::new();
+        self.nodes.export(&mut nodes);
+        nodes.sort_by(|n1, n2| n1.id.cmp(&n2.id));
+
+        let mut edges = Vec::new();
+        for (from_id, to) in self.edges.iter() {
+            for (to_id, kind) in to.iter() {
+                let to_node = self.node_ref(to_id).unwrap();
+                let to_id = match to_node.kind {
+                    NodeKind::StrictInput { output } => to_id.parent_id().unwrap().child(output),
+                    _ => to_id.clone(),
+                };
+
+                if kind.is_stream() {
+                    edges.push((from_id.clone(), to_id));
+                }
+            }
+        }
+        edges.sort();
+
+        (nodes, edges)
+    }
+
     /// Output circuit graph as visual graph.
     pub(super) fn visualize(&self, annotate: &dyn Fn(&GlobalNodeId) -> String) -> VisGraph {
         let cluster = self
@@ -410,6 +608,10 @@ impl CircuitGraph {
     }
 }
 
+fn node_path(id: &GlobalNodeId) -> Vec<usize> {
+    id.path().iter().map(|node_id| node_id.id()).collect()
+}
+
 fn label(name: &str, location: OperatorLocation) -> String {
     if let Some(location) = location {
         let file = if location.file().starts_with(env!("CARGO_MANIFEST_DIR")) {
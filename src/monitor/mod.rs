@@ -6,11 +6,12 @@ mod circuit_graph;
 pub mod visual_graph;
 
 use crate::circuit::{
+    description::CircuitDescription,
     metadata::OperatorLocation,
     trace::{CircuitEvent, SchedulerEvent},
-    Circuit, GlobalNodeId, NodeId,
+    Circuit, GlobalNodeId, NodeId, Runtime,
 };
-use circuit_graph::{CircuitGraph, Node, NodeKind, Region, RegionId};
+use circuit_graph::{CircuitGraph, Node, NodeKind, NodeProperties, Region, RegionId};
 use std::{
     borrow::Cow,
     collections::{HashMap, HashSet},
@@ -174,6 +175,54 @@ impl TraceMonitor {
     {
         self.0.lock().unwrap().circuit.visualize(&annotate)
     }
+
+    /// Returns all operators and subcircuits of the circuit along with stream
+    /// edges between them.
+    pub fn graph(&self) -> (Vec<GraphNode>, Vec<(GlobalNodeId, GlobalNodeId)>) {
+        self.0.lock().unwrap().circuit.export()
+    }
+
+    /// Returns a description of `circuit`, which must be the circuit the
+    /// monitor is attached to.
+    pub fn description(&self, circuit: &Circuit<()>) -> CircuitDescription {
+        let mut properties = NodeProperties::default();
+        circuit.map_nodes_recursive(&mut |node| {
+            if node.is_sink() {
+                properties.sinks.insert(node.global_id().clone());
+            }
+            if node.is_stateful() {
+                properties.stateful.insert(node.global_id().clone());
+            }
+            if node.preserves_sharding() {
+                properties
+                    .preserves_sharding
+                    .insert(node.global_id().clone());
+            }
+        });
+
+        let (nodes, edges) = self.0.lock().unwrap().circuit.describe(&properties);
+
+        CircuitDescription {
+            workers: Runtime::runtime().map_or(1, |runtime| runtime.num_workers()),
+            nodes,
+            edges,
+        }
+    }
+}
+
+/// An operator or subcircuit in a circuit graph (see [`TraceMonitor::graph`]).
+#[derive(Clone, Debug)]
+pub struct GraphNode {
+    pub id: GlobalNodeId,
+    pub name: Cow<'static, str>,
+    pub location: OperatorLocation,
+    pub kind: GraphNodeKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphNodeKind {
+    Operator,
+    Circuit { iterative: bool },
 }
 
 pub struct TraceMonitorInternal {
@@ -389,6 +438,13 @@ impl TraceMonitorInternal {
                 }
 
                 CircuitEvent::PopRegion => self.pop_region(),
+                CircuitEvent::ShardedStream { node_id } => {
+                    self.circuit
+                        .node_ref(node_id)
+                        .ok_or_else(|| TraceError::UnknownNode(node_id.clone()))?;
+                    self.circuit.add_sharded(node_id);
+                    Ok(())
+                }
                 _ => panic!("unknown event"),
             }
         }
@@ -111,6 +111,21 @@ pub struct Config {
     /// Store results in a csv file in addition to printing on the command-line.
     #[clap(long = "csv", env = "DBSP_RESULTS_AS_CSV")]
     pub output_csv: Option<String>,
+
+    /// Scheduler used to evaluate the circuit in each worker.
+    #[clap(long, default_value = "dynamic", env = "DBSP_SCHEDULER", value_enum)]
+    pub scheduler: Scheduler,
+}
+
+/// Circuit schedulers that can be selected for the benchmark.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Scheduler {
+    /// [`StaticScheduler`](`crate::circuit::schedule::StaticScheduler`).
+    Static,
+    /// [`DynamicScheduler`](`crate::circuit::schedule::DynamicScheduler`).
+    Dynamic,
+    /// [`AdaptiveScheduler`](`crate::circuit::schedule::AdaptiveScheduler`).
+    Adaptive,
 }
 
 /// Implementation of config methods based on the Java implementation at
@@ -146,6 +161,7 @@ impl Default for Config {
             source_buffer_size: 10_000,
             input_batch_size: 40_000,
             output_csv: None,
+            scheduler: Scheduler::Dynamic,
         }
     }
 }
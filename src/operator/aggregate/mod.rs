@@ -10,13 +10,14 @@ use std::{
 
 use crate::{
     algebra::{
-        DefaultSemigroup, GroupValue, HasOne, HasZero, IndexedZSet, Lattice, MulByRef,
+        DefaultSemigroup, GroupValue, HasOne, HasZero, IndexedZSet, Lattice, MonoidValue, MulByRef,
         PartialOrder, Semigroup, ZRingValue,
     },
     circuit::{
         operator_traits::{BinaryOperator, Operator, UnaryOperator},
-        Circuit, Scope, Stream,
+        Circuit, Runtime, Scope, Stream,
     },
+    operator::communication::{HotKeyPlacement, HotKeys, SkewConfig},
     time::Timestamp,
     trace::{
         cursor::{Cursor, CursorGroup},
@@ -193,6 +194,61 @@ where
             .mark_sharded()
     }
 
+    /// Skew-aware version of [`Self::stream_aggregate`].
+    ///
+    /// Tuples with hot keys (see
+    /// [`hot_key_counts`](`Stream::hot_key_counts`)) are spread across all
+    /// workers.  Each worker computes partial aggregates over its share of
+    /// the input, which are then sharded by key and combined using the
+    /// aggregator's [`Semigroup`](`Aggregator::Semigroup`).
+    #[allow(clippy::type_complexity)]
+    pub fn stream_aggregate_skewed<A>(
+        &self,
+        aggregator: A,
+        config: &SkewConfig,
+    ) -> Stream<Circuit<P>, OrdIndexedZSet<Z::Key, A::Output, Z::R>>
+    where
+        Z: IndexedZSet + Send,
+        A: Aggregator<Z::Val, (), Z::R>,
+        Z::R: ZRingValue,
+    {
+        self.stream_aggregate_skewed_generic(aggregator, config)
+    }
+
+    /// Like [`Self::stream_aggregate_skewed`], but can return any batch type.
+    pub fn stream_aggregate_skewed_generic<A, O>(
+        &self,
+        aggregator: A,
+        config: &SkewConfig,
+    ) -> Stream<Circuit<P>, O>
+    where
+        Z: IndexedZSet + Send,
+        A: Aggregator<Z::Val, (), Z::R>,
+        O: IndexedZSet<Key = Z::Key, Val = A::Output>,
+        O::R: ZRingValue,
+    {
+        if Runtime::runtime().map_or(1, |runtime| runtime.num_workers()) == 1 {
+            return self.stream_aggregate_generic(aggregator);
+        }
+
+        let hot_keys = self
+            .hot_key_counts(config)
+            .apply(|counts: &BTreeMap<Z::Key, usize>| {
+                counts
+                    .keys()
+                    .map(|key| (key.clone(), HotKeyPlacement::Spread))
+                    .collect::<HotKeys<_>>()
+            });
+
+        let partial: Stream<_, OrdIndexedZSet<Z::Key, (usize, A::Accumulator), O::R>> =
+            self.circuit().add_unary_operator(
+                Aggregate::new_skewed(PartialAggregator::new(aggregator.clone())),
+                &self.shard_skewed(&hot_keys),
+            );
+
+        partial.stream_aggregate_generic(CombinePartials::new(aggregator))
+    }
+
     /// Incremental aggregation operator.
     ///
     /// This operator is an incremental version of [`Self::stream_aggregate`].
@@ -222,9 +278,31 @@ where
         O: Batch<Key = Z::Key, Val = A::Output, Time = ()>,
         O::R: ZRingValue,
     {
-        let circuit = self.circuit();
         let stream = self.shard();
+        let output = stream
+            .aggregate_partitioned::<TS, A, O>(aggregator, true)
+            .mark_sharded();
+        stream.record_aggregate_lineage(self.origin_node_id(), &output);
+        output
+    }
 
+    // Incrementally aggregates `self` (see `aggregate_generic`), which must be
+    // partitioned across workers so that all tuples with the same key are
+    // processed by the same worker, unless `sharded_input` is `false`, in
+    // which case the input is partitioned by `shard_skewed_incremental` and
+    // the output contains partial aggregates.
+    fn aggregate_partitioned<TS, A, O>(
+        &self,
+        aggregator: A,
+        sharded_input: bool,
+    ) -> Stream<Circuit<P>, O>
+    where
+        TS: DBTimestamp,
+        Z: IndexedZSet + Send,
+        A: Aggregator<Z::Val, TS, Z::R>,
+        O: Batch<Key = Z::Key, Val = A::Output, Time = ()>,
+        O::R: ZRingValue,
+    {
         // We construct the following circuit.  See `AggregateIncremental` documentation
         // for details.
         //
@@ -237,14 +315,16 @@ where
         //                └─────┘                  └────────────────────┘      └──────┘
         // ```
 
-        circuit
+        self.circuit()
             .add_binary_operator(
-                AggregateIncremental::new(aggregator),
-                &stream,
-                &stream.trace::<Spine<TS::OrdValBatch<Z::Key, Z::Val, Z::R>>>(),
+                AggregateIncremental {
+                    sharded_input,
+                    ..AggregateIncremental::new(aggregator)
+                },
+                self,
+                &self.trace::<Spine<TS::OrdValBatch<Z::Key, Z::Val, Z::R>>>(),
             )
             .upsert::<TS, O>()
-            .mark_sharded()
     }
 
     /// A version of [`Self::aggregate`] optimized for linear
@@ -332,9 +412,174 @@ where
     }
 }
 
+impl<Z> Stream<Circuit<()>, Z>
+where
+    Z: IndexedZSet + Send,
+    Z::R: ZRingValue,
+{
+    /// Skew-aware version of [`Self::aggregate`].
+    ///
+    /// Like [`Self::stream_aggregate_skewed`], spreads tuples with hot keys
+    /// across all workers, incrementally computes partial aggregates in each
+    /// worker, and combines them using the aggregator's
+    /// [`Semigroup`](`Aggregator::Semigroup`).  The input is partitioned by
+    /// [`shard_skewed_incremental`](`Stream::shard_skewed_incremental`), so a
+    /// key stays hot once it has been detected.
+    ///
+    /// This operator is only available in the root scope.
+    #[allow(clippy::type_complexity)]
+    pub fn aggregate_skewed<A>(
+        &self,
+        aggregator: A,
+        config: &SkewConfig,
+    ) -> Stream<Circuit<()>, OrdIndexedZSet<Z::Key, A::Output, Z::R>>
+    where
+        A: Aggregator<Z::Val, (), Z::R>,
+    {
+        self.aggregate_skewed_generic(aggregator, config)
+    }
+
+    /// Like [`Self::aggregate_skewed`], but can return any batch type.
+    pub fn aggregate_skewed_generic<A, O>(
+        &self,
+        aggregator: A,
+        config: &SkewConfig,
+    ) -> Stream<Circuit<()>, O>
+    where
+        A: Aggregator<Z::Val, (), Z::R>,
+        O: Batch<Key = Z::Key, Val = A::Output, Time = ()>,
+        O::R: ZRingValue,
+    {
+        if Runtime::runtime().map_or(1, |runtime| runtime.num_workers()) == 1 {
+            return self.aggregate_generic::<(), A, O>(aggregator);
+        }
+
+        let hot_keys = self
+            .hot_key_counts(config)
+            .apply(|counts: &BTreeMap<Z::Key, usize>| {
+                counts
+                    .keys()
+                    .map(|key| (key.clone(), HotKeyPlacement::Spread))
+                    .collect::<HotKeys<_>>()
+            });
+
+        let partial: Stream<_, OrdIndexedZSet<Z::Key, (usize, A::Accumulator), Z::R>> = self
+            .shard_skewed_incremental(&hot_keys)
+            .aggregate_partitioned::<(), _, _>(PartialAggregator::new(aggregator.clone()), false);
+
+        partial.aggregate_generic::<(), _, O>(CombinePartials::<_, Z::Val, Z::R>::new(aggregator))
+    }
+}
+
+/// Aggregator used internally by [`Stream::stream_aggregate_skewed`] and
+/// [`Stream::aggregate_skewed`].
+/// Computes the accumulator of the wrapped aggregator over the part of the
+/// input processed by the current worker and tags it with the worker index,
+/// so that partial aggregates computed by different workers remain distinct.
+#[derive(Clone)]
+struct PartialAggregator<A> {
+    aggregator: A,
+}
+
+impl<A> PartialAggregator<A> {
+    fn new(aggregator: A) -> Self {
+        Self { aggregator }
+    }
+}
+
+impl<K, T, R, A> Aggregator<K, T, R> for PartialAggregator<A>
+where
+    A: Aggregator<K, T, R>,
+{
+    type Accumulator = A::Accumulator;
+    type Output = (usize, A::Accumulator);
+    type Semigroup = A::Semigroup;
+
+    fn aggregate<'s, C>(&self, cursor: &mut C) -> Option<Self::Accumulator>
+    where
+        C: Cursor<'s, K, (), T, R>,
+    {
+        self.aggregator.aggregate(cursor)
+    }
+
+    fn finalize(&self, accumulator: Self::Accumulator) -> Self::Output {
+        (Runtime::worker_index(), accumulator)
+    }
+}
+
+/// Aggregator used internally by [`Stream::stream_aggregate_skewed`] and
+/// [`Stream::aggregate_skewed`].
+/// Combines partial aggregates computed by [`PartialAggregator`] using the
+/// semigroup of the wrapped aggregator.
+///
+/// # Type arguments
+///
+/// * `A` - wrapped aggregator.
+/// * `K` - type of values aggregated by `A`.
+/// * `PR` - weight type of values aggregated by `A`.
+#[derive(Clone)]
+struct CombinePartials<A, K, PR> {
+    aggregator: A,
+    _type: PhantomData<(K, PR)>,
+}
+
+impl<A, K, PR> CombinePartials<A, K, PR> {
+    fn new(aggregator: A) -> Self {
+        Self {
+            aggregator,
+            _type: PhantomData,
+        }
+    }
+}
+
+impl<K, T, R, PR, A> Aggregator<(usize, A::Accumulator), T, R> for CombinePartials<A, K, PR>
+where
+    K: Clone + 'static,
+    PR: Clone + 'static,
+    T: Timestamp,
+    R: MonoidValue,
+    A: Aggregator<K, (), PR>,
+{
+    type Accumulator = A::Accumulator;
+    type Output = A::Output;
+    type Semigroup = A::Semigroup;
+
+    fn aggregate<'s, C>(&self, cursor: &mut C) -> Option<Self::Accumulator>
+    where
+        C: Cursor<'s, (usize, A::Accumulator), (), T, R>,
+    {
+        let mut result = None;
+
+        while cursor.key_valid() {
+            let weight = cursor.fold_times(R::zero(), |mut acc, _, weight| {
+                acc.add_assign_by_ref(weight);
+                acc
+            });
+            if !weight.is_zero() {
+                let partial = &cursor.key().1;
+                result = Some(match result {
+                    None => partial.clone(),
+                    Some(result) => A::Semigroup::combine(&result, partial),
+                });
+            }
+
+            cursor.step_key();
+        }
+
+        result
+    }
+
+    fn finalize(&self, accumulator: Self::Accumulator) -> Self::Output {
+        self.aggregator.finalize(accumulator)
+    }
+}
+
 /// Non-incremental aggregation operator.
 struct Aggregate<Z, A, O> {
     aggregator: A,
+    // `false` if the input is partitioned by `shard_skewed` rather than
+    // sharded by key.
+    sharded_input: bool,
     _type: PhantomData<(Z, O)>,
 }
 
@@ -342,6 +587,19 @@ impl<Z, A, O> Aggregate<Z, A, O> {
     pub fn new(aggregator: A) -> Self {
         Self {
             aggregator,
+            sharded_input: true,
+            _type: PhantomData,
+        }
+    }
+
+    /// Aggregate an input stream partitioned by
+    /// [`Stream::shard_skewed`](`crate::Stream::shard_skewed`), where tuples
+    /// with hot keys are spread across workers, producing partial results to
+    /// be combined by a downstream aggregate.
+    fn new_skewed(aggregator: A) -> Self {
+        Self {
+            aggregator,
+            sharded_input: false,
             _type: PhantomData,
         }
     }
@@ -359,6 +617,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn is_stateful(&self) -> bool {
+        self.sharded_input
+    }
 }
 
 impl<Z, A, O> UnaryOperator<Z, O> for Aggregate<Z, A, O>
@@ -420,6 +682,9 @@ where
     IT: BatchReader,
 {
     aggregator: A,
+    // `false` if the input is partitioned by `shard_skewed_incremental`
+    // rather than sharded by key.
+    sharded_input: bool,
     // Current time.
     // TODO: not needed once timekeeping is handled by the circuit.
     time: IT::Time,
@@ -443,6 +708,7 @@ where
     pub fn new(aggregator: A) -> Self {
         Self {
             aggregator,
+            sharded_input: true,
             time: <IT::Time as Timestamp>::clock_start(),
             empty_input: false,
             empty_output: false,
@@ -594,6 +860,10 @@ where
                 .keys()
                 .all(|ts| !ts.less_equal(&epoch_end))
     }
+
+    fn is_stateful(&self) -> bool {
+        self.sharded_input
+    }
 }
 
 impl<Z, IT, A> BinaryOperator<Z, IT, Vec<(Z::Key, Option<A::Output>)>>
@@ -683,7 +953,7 @@ mod test {
         algebra::DefaultSemigroup,
         indexed_zset,
         operator::GeneratorNested,
-        operator::{Fold, Min},
+        operator::{communication::SkewConfig, Fold, Min},
         time::NestedTimestamp32,
         trace::{cursor::Cursor, Batch, BatchReader},
         zset, Circuit, OrdIndexedZSet, OrdZSet, Runtime, Stream,
@@ -997,4 +1267,91 @@ mod test {
     fn count_test4() {
         count_test(4);
     }
+
+    #[test]
+    fn stream_aggregate_skewed_test() {
+        let (mut dbsp, mut input_handle) = Runtime::init_circuit(4, move |circuit| {
+            let (input_stream, input_handle) =
+                circuit.add_input_indexed_zset::<usize, usize, isize>();
+            let config = SkewConfig::new(100);
+
+            let sum = <Fold<_, DefaultSemigroup<_>, _, _>>::new(
+                0,
+                |sum: &mut isize, v: &usize, w: isize| *sum += *v as isize * w,
+            );
+
+            let expected = input_stream.stream_aggregate(sum.clone()).gather(0);
+            let skewed = input_stream.stream_aggregate_skewed(sum, &config).gather(0);
+            expected.apply2(&skewed, |expected, skewed| {
+                if Runtime::worker_index() == 0 {
+                    assert_eq!(expected.len(), 50);
+                }
+                assert_eq!(expected, skewed);
+            });
+
+            let expected = input_stream.stream_aggregate(Min).gather(0);
+            let skewed = input_stream.stream_aggregate_skewed(Min, &config).gather(0);
+            expected.apply2(&skewed, |expected, skewed| assert_eq!(expected, skewed));
+
+            input_handle
+        })
+        .unwrap();
+
+        // Key 0 is hot.
+        for step in 0..3 {
+            input_handle.append(
+                &mut (0..1000)
+                    .map(|v| (0, (v + step, 1)))
+                    .chain((1..50).map(|k| (k, (k + step, 1))))
+                    .collect(),
+            );
+            dbsp.step().unwrap();
+        }
+
+        dbsp.kill().unwrap();
+    }
+
+    #[test]
+    fn aggregate_skewed_test() {
+        let (mut dbsp, mut input_handle) = Runtime::init_circuit(4, move |circuit| {
+            let (input_stream, input_handle) =
+                circuit.add_input_indexed_zset::<usize, usize, isize>();
+            let config = SkewConfig::new(100);
+
+            let sum = <Fold<_, DefaultSemigroup<_>, _, _>>::new(
+                0,
+                |sum: &mut isize, v: &usize, w: isize| *sum += *v as isize * w,
+            );
+
+            let expected = input_stream.aggregate::<(), _>(sum.clone()).gather(0);
+            let skewed = input_stream.aggregate_skewed(sum, &config).gather(0);
+            expected.apply2(&skewed, |expected, skewed| assert_eq!(expected, skewed));
+
+            let expected = input_stream.aggregate::<(), _>(Min).gather(0);
+            let skewed = input_stream.aggregate_skewed(Min, &config).gather(0);
+            expected.apply2(&skewed, |expected, skewed| assert_eq!(expected, skewed));
+
+            input_handle
+        })
+        .unwrap();
+
+        // Key 0 is cold in the first step.
+        input_handle.append(
+            &mut (0..10)
+                .map(|v| (0, (v, 1)))
+                .chain((1..50).map(|k| (k, (k, 1))))
+                .collect(),
+        );
+        dbsp.step().unwrap();
+
+        // Key 0 becomes hot.
+        input_handle.append(&mut (10..1000).map(|v| (0, (v, 1))).collect());
+        dbsp.step().unwrap();
+
+        // Delete the minimum and other tuples inserted before key 0 became hot.
+        input_handle.append(&mut (0..10).map(|v| (0, (v, -1))).collect());
+        dbsp.step().unwrap();
+
+        dbsp.kill().unwrap();
+    }
 }
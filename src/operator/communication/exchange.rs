@@ -613,7 +613,7 @@ mod tests {
     use super::Exchange;
     use crate::{
         circuit::{
-            schedule::{DynamicScheduler, Scheduler, StaticScheduler},
+            schedule::{AdaptiveScheduler, DynamicScheduler, Scheduler, StaticScheduler},
             Runtime,
         },
         operator::Generator,
@@ -677,6 +677,12 @@ mod tests {
         test_exchange_operators::<DynamicScheduler>();
     }
 
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_exchange_operators_adaptive() {
+        test_exchange_operators::<AdaptiveScheduler>();
+    }
+
     // Create a circuit with `WORKERS` concurrent workers with the following
     // structure: `Generator - ExchangeSender -> ExchangeReceiver -> Inspect`.
     // `Generator` - yields sequential numbers 0, 1, 2, ...
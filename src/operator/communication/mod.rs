@@ -1,6 +1,8 @@
 mod exchange;
 mod gather;
 mod shard;
+mod skew;
 
 pub(crate) use exchange::Exchange;
 pub use exchange::{ExchangeReceiver, ExchangeSender};
+pub use skew::{HotKeyPlacement, HotKeys, SkewConfig};
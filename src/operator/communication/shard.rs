@@ -5,7 +5,7 @@
 // - different sharding modes.
 
 use crate::{
-    circuit::GlobalNodeId,
+    circuit::{trace::CircuitEvent, GlobalNodeId},
     circuit_cache_key, default_hash,
     trace::{cursor::Cursor, Batch, BatchReader, Builder, Spine, Trace},
     Circuit, Runtime, Stream,
@@ -149,6 +149,10 @@ where
                                 )),
                                 output.clone(),
                             );
+                            self.circuit()
+                                .log_circuit_event(&CircuitEvent::sharded_stream(
+                                    output.origin_node_id().clone(),
+                                ));
 
                             output
                         },
@@ -217,6 +221,8 @@ where
             )),
             self.clone(),
         );
+        self.circuit()
+            .log_circuit_event(&CircuitEvent::sharded_stream(self.origin_node_id().clone()));
         self.clone()
     }
 
@@ -0,0 +1,538 @@
+//! Skew-aware sharding.
+//!
+//! [`Stream::shard`] assigns all tuples with the same key to the same worker.
+//! When the distribution of keys is heavy-tailed, a handful of hot keys can
+//! overload the workers they are assigned to, while other workers sit idle.
+//! The operators in this module detect hot keys at runtime from per-worker
+//! statistics and distribute their tuples across all workers.  Operators built
+//! on top of them, e.g.,
+//! [`stream_join_skewed`](`Stream::stream_join_skewed`),
+//! [`stream_aggregate_skewed`](`Stream::stream_aggregate_skewed`) and their
+//! incremental counterparts [`join_skewed`](`Stream::join_skewed`) and
+//! [`aggregate_skewed`](`Stream::aggregate_skewed`), combine partial results
+//! computed by individual workers.
+
+use crate::{
+    algebra::{HasZero, ZRingValue},
+    circuit::{
+        operator_traits::{Operator, UnaryOperator},
+        Runtime, Scope,
+    },
+    default_hash,
+    trace::{cursor::Cursor, Batch, BatchReader, Builder, Spine, Trace},
+    Circuit, Stream,
+};
+use std::{borrow::Cow, cmp::max, collections::BTreeMap, ops::Neg, panic::Location};
+
+/// Configuration of skew-aware operators.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SkewConfig {
+    /// Minimal number of tuples with the same key across all workers in one
+    /// clock cycle for the key to be considered hot.
+    pub hot_key_threshold: usize,
+}
+
+impl SkewConfig {
+    pub fn new(hot_key_threshold: usize) -> Self {
+        Self { hot_key_threshold }
+    }
+}
+
+impl Default for SkewConfig {
+    fn default() -> Self {
+        Self::new(10_000)
+    }
+}
+
+/// Distribution of tuples with a hot key across workers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum HotKeyPlacement {
+    /// Shard tuples based on the hash of the `(key, value)` pair, spreading
+    /// them evenly across all workers.
+    Spread,
+    /// Send all tuples to every worker.
+    Broadcast,
+}
+
+/// Hot keys detected in the current clock cycle along with the placement of
+/// their tuples.
+pub type HotKeys<K> = BTreeMap<K, HotKeyPlacement>;
+
+impl<P, IB> Stream<Circuit<P>, IB>
+where
+    P: Clone + 'static,
+    IB: BatchReader<Time = ()> + Clone + 'static,
+{
+    /// Detect hot keys in the stream.
+    ///
+    /// Returns a stream of maps from hot keys to the total number of tuples
+    /// with this key across all workers.  A key is hot if this number is at
+    /// least `config.hot_key_threshold`.
+    ///
+    /// Each worker counts values per key in its local input batch and reports
+    /// keys with at least `hot_key_threshold / num_workers` values to all
+    /// peers.  A key whose total count reaches the threshold is reported by at
+    /// least one worker, but workers that did not report the key do not
+    /// contribute to its count, so the output is an under-estimate.  All
+    /// workers receive the same reports and therefore output identical maps.
+    #[track_caller]
+    pub fn hot_key_counts(
+        &self,
+        config: &SkewConfig,
+    ) -> Stream<Circuit<P>, BTreeMap<IB::Key, usize>> {
+        let location = Location::caller();
+        let threshold = config.hot_key_threshold;
+
+        let counts = match Runtime::runtime() {
+            Some(runtime) if runtime.num_workers() > 1 => {
+                let num_workers = runtime.num_workers();
+                let local_threshold = max(threshold / num_workers, 1);
+
+                let candidates = self.apply_named("HotKeyCandidates", move |batch: &IB| {
+                    count_keys(batch, local_threshold)
+                });
+
+                let (sender, receiver) = self.circuit().new_exchange_operators(
+                    &runtime,
+                    Runtime::worker_index(),
+                    Some(location),
+                    move |candidates: Vec<(IB::Key, usize)>,
+                          outputs: &mut Vec<Vec<(IB::Key, usize)>>| {
+                        for _ in 1..num_workers {
+                            outputs.push(candidates.clone());
+                        }
+                        outputs.push(candidates);
+                    },
+                    |counts: &mut BTreeMap<IB::Key, usize>, candidates| {
+                        for (key, count) in candidates {
+                            *counts.entry(key).or_default() += count;
+                        }
+                    },
+                );
+
+                self.circuit().add_exchange(sender, receiver, &candidates)
+            }
+            _ => self.apply_named("HotKeyCandidates", move |batch: &IB| {
+                count_keys(batch, threshold)
+                    .into_iter()
+                    .collect::<BTreeMap<_, _>>()
+            }),
+        };
+
+        counts.apply_named("HotKeys", move |counts: &BTreeMap<IB::Key, usize>| {
+            counts
+                .iter()
+                .filter(|(_, count)| **count >= threshold)
+                .map(|(key, count)| (key.clone(), *count))
+                .collect()
+        })
+    }
+
+    /// Shard batches across workers, distributing tuples with hot keys
+    /// according to `hot_keys`.
+    ///
+    /// Tuples whose key does not occur in `hot_keys` are sharded based on the
+    /// hash of the key, like in [`Self::shard`].  Tuples with a hot key are
+    /// sharded based on the hash of the `(key, value)` pair or sent to every
+    /// worker, depending on the [`HotKeyPlacement`] of the key.  `hot_keys`
+    /// must be identical across all workers.
+    ///
+    /// Unlike `shard`, this operator does not guarantee that all tuples with
+    /// the same key end up at the same worker.  Its output is therefore not
+    /// marked as sharded, and consumers must combine partial results computed
+    /// by individual workers.  Since the placement of hot keys can change
+    /// from one clock cycle to the next, this operator is only suitable for
+    /// non-incremental operators that process each input batch in isolation.
+    /// Use [`Self::shard_skewed_incremental`] for incremental operators.
+    #[track_caller]
+    pub fn shard_skewed(
+        &self,
+        hot_keys: &Stream<Circuit<P>, HotKeys<IB::Key>>,
+    ) -> Stream<Circuit<P>, IB>
+    where
+        IB: Batch + Send,
+    {
+        let location = Location::caller();
+
+        match Runtime::runtime() {
+            Some(runtime) if runtime.num_workers() > 1 => {
+                let num_workers = runtime.num_workers();
+
+                let partitioned = self.apply2(hot_keys, move |batch: &IB, hot_keys| {
+                    Self::shard_batch_skewed(batch, hot_keys, num_workers)
+                });
+
+                self.exchange_partitions(&runtime, &partitioned, location)
+            }
+            _ => self.clone(),
+        }
+    }
+
+    // Sends the `i`th batch in each vector in `partitioned` to worker `i`.
+    fn exchange_partitions(
+        &self,
+        runtime: &Runtime,
+        partitioned: &Stream<Circuit<P>, Vec<IB>>,
+        location: &'static Location<'static>,
+    ) -> Stream<Circuit<P>, IB>
+    where
+        IB: Batch + Send,
+    {
+        let (sender, receiver) = self.circuit().new_exchange_operators(
+            runtime,
+            Runtime::worker_index(),
+            Some(location),
+            |batches: Vec<IB>, outputs: &mut Vec<IB>| outputs.extend(batches),
+            |trace: &mut Spine<IB>, batch: IB| trace.insert(batch),
+        );
+
+        self.circuit()
+            .add_exchange(sender, receiver, partitioned)
+            .consolidate()
+    }
+
+    // Partitions the batch into `shards` partitions, placing tuples with hot
+    // keys according to `hot_keys`.
+    fn shard_batch_skewed(batch: &IB, hot_keys: &HotKeys<IB::Key>, shards: usize) -> Vec<IB>
+    where
+        IB: Batch,
+    {
+        let mut builders: Vec<_> = (0..shards)
+            .map(|_| IB::Builder::with_capacity((), batch.len() / shards))
+            .collect();
+
+        let mut cursor = batch.cursor();
+
+        while cursor.key_valid() {
+            match hot_keys.get(cursor.key()) {
+                None => {
+                    let batch_index = default_hash(cursor.key()) as usize % shards;
+                    while cursor.val_valid() {
+                        builders[batch_index].push((
+                            IB::item_from(cursor.key().clone(), cursor.val().clone()),
+                            cursor.weight(),
+                        ));
+                        cursor.step_val();
+                    }
+                }
+                Some(HotKeyPlacement::Spread) => {
+                    while cursor.val_valid() {
+                        let batch_index =
+                            default_hash(&(cursor.key(), cursor.val())) as usize % shards;
+                        builders[batch_index].push((
+                            IB::item_from(cursor.key().clone(), cursor.val().clone()),
+                            cursor.weight(),
+                        ));
+                        cursor.step_val();
+                    }
+                }
+                Some(HotKeyPlacement::Broadcast) => {
+                    while cursor.val_valid() {
+                        for builder in builders.iter_mut() {
+                            builder.push((
+                                IB::item_from(cursor.key().clone(), cursor.val().clone()),
+                                cursor.weight(),
+                            ));
+                        }
+                        cursor.step_val();
+                    }
+                }
+            }
+            cursor.step_key();
+        }
+
+        builders.into_iter().map(Builder::done).collect()
+    }
+}
+
+impl<IB> Stream<Circuit<()>, IB>
+where
+    IB: Batch<Time = ()> + Send,
+    IB::R: ZRingValue,
+{
+    /// Like [`Self::shard_skewed`], but keeps the placement of each key
+    /// stable over time, so that the output can be consumed by incremental
+    /// operators.
+    ///
+    /// Once a key occurs in `hot_keys`, it stays hot in all future clock
+    /// cycles with the placement it was first assigned; later changes to its
+    /// placement in `hot_keys` are ignored.  In the clock cycle when a key
+    /// becomes hot, tuples with this key received in earlier clock cycles are
+    /// moved to their new location: spread tuples are retracted from the
+    /// worker that the key is sharded to and inserted at the worker chosen by
+    /// the hash of the `(key, value)` pair, and broadcast tuples are inserted
+    /// at every other worker.  As a result, the integral of the output of each
+    /// worker is the same as if the key had been hot from the start.
+    ///
+    /// To perform these migrations, the operator maintains a trace of its
+    /// input.  The placement of hot keys depends on the number of workers,
+    /// so a circuit that uses this operator cannot be rescaled.  This
+    /// operator is only available in the root scope.
+    #[track_caller]
+    pub fn shard_skewed_incremental(
+        &self,
+        hot_keys: &Stream<Circuit<()>, HotKeys<IB::Key>>,
+    ) -> Stream<Circuit<()>, IB> {
+        let location = Location::caller();
+
+        match Runtime::runtime() {
+            Some(runtime) if runtime.num_workers() > 1 => {
+                let num_workers = runtime.num_workers();
+
+                let hot_keys = self
+                    .circuit()
+                    .add_unary_operator(StickyHotKeys::new(), hot_keys);
+
+                let migrated = self.integrate_trace().delay_trace().apply2(
+                    &hot_keys,
+                    move |history: &Spine<IB>, (_, new_hot_keys)| {
+                        migrate_hot_keys::<_, IB>(history, new_hot_keys, num_workers)
+                    },
+                );
+
+                let partitioned = self
+                    .apply2(&hot_keys, move |batch: &IB, (hot_keys, _)| {
+                        Self::shard_batch_skewed(batch, hot_keys, num_workers)
+                    })
+                    .apply2(&migrated, |partitions: &Vec<IB>, migrated: &Vec<IB>| {
+                        partitions
+                            .iter()
+                            .zip(migrated.iter())
+                            .map(|(partition, migrated)| partition.merge(migrated))
+                            .collect::<Vec<_>>()
+                    });
+
+                self.exchange_partitions(&runtime, &partitioned, location)
+            }
+            _ => self.clone(),
+        }
+    }
+}
+
+/// Accumulates hot keys across clock cycles for
+/// [`Stream::shard_skewed_incremental`].
+///
+/// Outputs all keys that have been hot so far along with the placement they
+/// were first assigned, and the subset of those keys that became hot in the
+/// current clock cycle.
+struct StickyHotKeys<K> {
+    hot_keys: HotKeys<K>,
+}
+
+impl<K> StickyHotKeys<K> {
+    fn new() -> Self {
+        Self {
+            hot_keys: HotKeys::new(),
+        }
+    }
+}
+
+impl<K> Operator for StickyHotKeys<K>
+where
+    K: 'static,
+{
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("StickyHotKeys")
+    }
+
+    fn fixedpoint(&self, _scope: Scope) -> bool {
+        true
+    }
+
+    fn state_transferable(&self) -> bool {
+        false
+    }
+}
+
+impl<K> UnaryOperator<HotKeys<K>, (HotKeys<K>, HotKeys<K>)> for StickyHotKeys<K>
+where
+    K: Ord + Clone + 'static,
+{
+    fn eval(&mut self, hot_keys: &HotKeys<K>) -> (HotKeys<K>, HotKeys<K>) {
+        let mut new_hot_keys = HotKeys::new();
+
+        for (key, placement) in hot_keys.iter() {
+            if !self.hot_keys.contains_key(key) {
+                self.hot_keys.insert(key.clone(), *placement);
+                new_hot_keys.insert(key.clone(), *placement);
+            }
+        }
+
+        (self.hot_keys.clone(), new_hot_keys)
+    }
+}
+
+// Partitions the tuples in `history` whose keys became hot in the current
+// clock cycle into `shards` batches that move them from the worker their key
+// is sharded to to their new location (see `shard_skewed_incremental`).
+fn migrate_hot_keys<H, IB>(history: &H, new_hot_keys: &HotKeys<IB::Key>, shards: usize) -> Vec<IB>
+where
+    H: BatchReader<Key = IB::Key, Val = IB::Val, R = IB::R, Time = ()>,
+    IB: Batch<Time = ()>,
+    IB::R: ZRingValue,
+{
+    let mut builders: Vec<_> = (0..shards)
+        .map(|_| IB::Builder::with_capacity((), 0))
+        .collect();
+
+    let mut cursor = history.cursor();
+
+    for (key, placement) in new_hot_keys.iter() {
+        cursor.seek_key(key);
+        if !cursor.key_valid() || cursor.key() != key {
+            continue;
+        }
+
+        let owner = default_hash(key) as usize % shards;
+
+        while cursor.val_valid() {
+            let weight = cursor.weight();
+            if !weight.is_zero() {
+                match placement {
+                    HotKeyPlacement::Spread => {
+                        let batch_index = default_hash(&(key, cursor.val())) as usize % shards;
+                        if batch_index != owner {
+                            builders[owner].push((
+                                IB::item_from(key.clone(), cursor.val().clone()),
+                                weight.clone().neg(),
+                            ));
+                            builders[batch_index]
+                                .push((IB::item_from(key.clone(), cursor.val().clone()), weight));
+                        }
+                    }
+                    HotKeyPlacement::Broadcast => {
+                        for (batch_index, builder) in builders.iter_mut().enumerate() {
+                            if batch_index != owner {
+                                builder.push((
+                                    IB::item_from(key.clone(), cursor.val().clone()),
+                                    weight.clone(),
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+            cursor.step_val();
+        }
+    }
+
+    builders.into_iter().map(Builder::done).collect()
+}
+
+// Returns keys with at least `min_count` values in `batch` along with their
+// value counts.
+fn count_keys<B>(batch: &B, min_count: usize) -> Vec<(B::Key, usize)>
+where
+    B: BatchReader<Time = ()>,
+{
+    let mut counts = Vec::new();
+    let mut cursor = batch.cursor();
+
+    while cursor.key_valid() {
+        let mut count = 0;
+        while cursor.val_valid() {
+            count += 1;
+            cursor.step_val();
+        }
+        if count >= min_count {
+            counts.push((cursor.key().clone(), count));
+        }
+        cursor.step_key();
+    }
+
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HotKeyPlacement, HotKeys, SkewConfig};
+    use crate::{
+        operator::Generator,
+        trace::{cursor::Cursor, BatchReader},
+        Circuit, OrdIndexedZSet, Runtime,
+    };
+    use std::collections::BTreeMap;
+
+    fn count_values(batch: &OrdIndexedZSet<usize, usize, isize>, key: &usize) -> usize {
+        let mut cursor = batch.cursor();
+        let mut count = 0;
+
+        cursor.seek_key(key);
+        if cursor.key_valid() && cursor.key() == key {
+            while cursor.val_valid() {
+                count += 1;
+                cursor.step_val();
+            }
+        }
+        count
+    }
+
+    // Key 0 is hot; all other keys have a single value per worker.
+    fn test_data(worker_index: usize) -> OrdIndexedZSet<usize, usize, isize> {
+        let tuples: Vec<_> = (0..100)
+            .map(|n| ((0, worker_index * 100 + n), 1))
+            .chain((1..100).map(|n| ((n, worker_index), 1)))
+            .collect();
+        <OrdIndexedZSet<usize, usize, isize>>::from_tuples((), tuples)
+    }
+
+    #[test]
+    fn test_hot_key_counts() {
+        let hruntime = Runtime::run(4, || {
+            let circuit = Circuit::build(move |circuit| {
+                let input =
+                    circuit.add_source(Generator::new(|| test_data(Runtime::worker_index())));
+                input.hot_key_counts(&SkewConfig::new(200)).inspect(
+                    |counts: &BTreeMap<usize, usize>| {
+                        assert_eq!(counts, &BTreeMap::from([(0, 400)]));
+                    },
+                );
+            })
+            .unwrap()
+            .0;
+
+            for _ in 0..3 {
+                circuit.step().unwrap();
+            }
+        });
+
+        hruntime.join().unwrap();
+    }
+
+    #[test]
+    fn test_shard_skewed() {
+        do_test_shard_skewed(HotKeyPlacement::Spread);
+        do_test_shard_skewed(HotKeyPlacement::Broadcast);
+    }
+
+    fn do_test_shard_skewed(placement: HotKeyPlacement) {
+        const WORKERS: usize = 4;
+
+        let hruntime = Runtime::run(WORKERS, move || {
+            let circuit = Circuit::build(move |circuit| {
+                let input =
+                    circuit.add_source(Generator::new(|| test_data(Runtime::worker_index())));
+                let hot_keys =
+                    circuit.add_source(Generator::new(move || HotKeys::from([(0, placement)])));
+                input.shard_skewed(&hot_keys).inspect(
+                    move |batch: &OrdIndexedZSet<usize, usize, isize>| {
+                        let hot = count_values(batch, &0);
+                        match placement {
+                            // 400 values must be spread across 4 workers.
+                            HotKeyPlacement::Spread => assert!(hot > 0 && hot < 400),
+                            HotKeyPlacement::Broadcast => assert_eq!(hot, 400),
+                        }
+                    },
+                );
+            })
+            .unwrap()
+            .0;
+
+            for _ in 0..3 {
+                circuit.step().unwrap();
+            }
+        });
+
+        hruntime.join().unwrap();
+    }
+}
@@ -131,7 +131,7 @@ impl<C> Condition<C> {
 #[cfg(test)]
 mod test {
     use crate::{
-        circuit::schedule::{DynamicScheduler, Scheduler, StaticScheduler},
+        circuit::schedule::{AdaptiveScheduler, DynamicScheduler, Scheduler, StaticScheduler},
         monitor::TraceMonitor,
         operator::{DelayedFeedback, FilterMap, Generator},
         trace::ord::{OrdIndexedZSet, OrdZSet},
@@ -148,6 +148,11 @@ mod test {
         iterate_with_conditions::<DynamicScheduler>();
     }
 
+    #[test]
+    fn iterate_with_conditions_adaptive() {
+        iterate_with_conditions::<AdaptiveScheduler>();
+    }
+
     fn iterate_with_conditions<S>()
     where
         S: Scheduler + 'static,
@@ -67,6 +67,9 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         self.time >= 2
     }
+    fn state_transferable(&self) -> bool {
+        false
+    }
 }
 
 impl<R, T, W, C> SourceOperator<C> for CsvSource<R, T, W, C>
@@ -36,9 +36,12 @@ where
     {
         self.circuit()
             .cache_get_or_insert_with(DistinctId::new(self.origin_node_id().clone()), || {
-                self.circuit()
+                let distinct = self
+                    .circuit()
                     .add_unary_operator(Distinct::new(), &self.shard())
-                    .mark_sharded()
+                    .mark_sharded();
+                distinct.record_identity_lineage("Distinct", self.origin_node_id());
+                distinct
             })
             .clone()
     }
@@ -52,7 +55,9 @@ where
         Z: IndexedZSet + Send,
         Z::R: ZRingValue,
     {
-        self.shard().distinct_incremental_inner().mark_sharded()
+        let distinct = self.shard().distinct_incremental_inner().mark_sharded();
+        distinct.record_identity_lineage("Distinct", self.origin_node_id());
+        distinct
     }
 
     fn distinct_incremental_inner(&self) -> Stream<Circuit<P>, Z>
@@ -153,6 +158,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn is_stateful(&self) -> bool {
+        true
+    }
 }
 
 impl<Z> UnaryOperator<Z, Z> for Distinct<Z>
@@ -203,6 +212,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn is_stateful(&self) -> bool {
+        true
+    }
 }
 
 impl<Z, I> BinaryOperator<Z, I, Z> for DistinctIncremental<Z, I>
@@ -482,6 +495,10 @@ where
             && self.empty_output
             && self.future_updates.values().all(|vals| vals.is_empty())
     }
+
+    fn is_stateful(&self) -> bool {
+        true
+    }
 }
 
 impl<Z, T> BinaryOperator<Z, T, Z> for DistinctTrace<Z, T>
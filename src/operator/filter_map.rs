@@ -5,6 +5,7 @@ use crate::{
         operator_traits::{Operator, UnaryOperator},
         Circuit, OwnershipPreference, Scope, Stream,
     },
+    operator::provenance::record_id,
     trace::{Batch, BatchReader, Builder, Consumer, Cursor, ValueConsumer},
     DBData, DBWeight, OrdIndexedZSet, OrdZSet,
 };
@@ -176,6 +177,7 @@ where
             .circuit()
             .add_unary_operator(FilterKeys::new(filter_func), &self.try_sharded_version());
         filtered.mark_sharded_if(self);
+        filtered.record_identity_lineage("Filter", self.origin_node_id());
         filtered
     }
 
@@ -184,10 +186,15 @@ where
         F: Fn(Self::ItemRef<'_>) -> T + Clone + 'static,
         O: Batch<Key = T, Val = (), Time = (), R = Self::R>,
     {
-        self.circuit().add_unary_operator(
+        let derive = map_func.clone();
+        let mapped = self.circuit().add_unary_operator(
             MapKeys::new(map_func.clone(), move |x| (map_func)(&x)),
             self,
-        )
+        );
+        self.record_lineage("Map", mapped.origin_node_id(), move |key, _| {
+            vec![record_id::<O>(&derive(key), &())]
+        });
+        mapped
     }
 
     fn map_index_generic<F, KT, VT, O>(&self, map_func: F) -> Stream<Circuit<P>, O>
@@ -247,6 +254,7 @@ where
             .circuit()
             .add_unary_operator(FilterVals::new(filter_func), &self.try_sharded_version());
         filtered.mark_sharded_if(self);
+        filtered.record_identity_lineage("Filter", self.origin_node_id());
         filtered
     }
 
@@ -255,10 +263,15 @@ where
         F: Fn(Self::ItemRef<'_>) -> T + Clone + 'static,
         O: Batch<Key = T, Val = (), Time = (), R = Self::R>,
     {
-        self.circuit().add_unary_operator(
+        let derive = map_func.clone();
+        let mapped = self.circuit().add_unary_operator(
             Map::new(move |kv: Self::ItemRef<'_>| (map_func(kv), ())),
             self,
-        )
+        );
+        self.record_lineage("Map", mapped.origin_node_id(), move |key, val| {
+            vec![record_id::<O>(&derive((key, val)), &())]
+        });
+        mapped
     }
 
     fn map_index_generic<F, KT, VT, O>(&self, map_func: F) -> Stream<Circuit<P>, O>
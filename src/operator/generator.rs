@@ -38,6 +38,9 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         false
     }
+    fn state_transferable(&self) -> bool {
+        false
+    }
 }
 
 impl<T, F> SourceOperator<T> for Generator<T, F>
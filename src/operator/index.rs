@@ -6,6 +6,7 @@ use crate::{
         Circuit, GlobalNodeId, OwnershipPreference, Scope, Stream,
     },
     circuit_cache_key,
+    operator::provenance::record_id,
     trace::{
         cursor::Cursor, ord::OrdIndexedZSet, Batch, BatchReader, Builder, Consumer, ValueConsumer,
     },
@@ -44,7 +45,10 @@ where
     {
         self.circuit()
             .cache_get_or_insert_with(IndexId::new(self.origin_node_id().clone()), || {
-                self.circuit().add_unary_operator(Index::new(), self)
+                let indexed = self.circuit().add_unary_operator(Index::new(), self);
+                // `(key, value)` tuples and indexed records are formatted identically.
+                indexed.record_identity_lineage("Index", self.origin_node_id());
+                indexed
             })
             .clone()
     }
@@ -79,8 +83,15 @@ where
         CO: Batch<Time = (), R = CI::R>,
         F: Fn(&CI::Key) -> (CO::Key, CO::Val) + Clone + 'static,
     {
-        self.circuit()
-            .add_unary_operator(IndexWith::new(index_func), self)
+        let derive = index_func.clone();
+        let indexed = self
+            .circuit()
+            .add_unary_operator(IndexWith::new(index_func), self);
+        self.record_lineage("Index", indexed.origin_node_id(), move |key, _| {
+            let (k, v) = derive(key);
+            vec![record_id::<CO>(&k, &v)]
+        });
+        indexed
     }
 }
 
@@ -4,18 +4,20 @@ use crate::{
         operator_traits::{Operator, SourceOperator},
         LocalStoreMarker, Scope,
     },
-    default_hash,
+    circuit_cache_key, default_hash,
     trace::Batch,
     Circuit, DBData, DBWeight, OrdIndexedZSet, OrdZSet, Runtime, Stream,
 };
 use std::{
     borrow::Cow,
+    cell::RefCell,
     hash::{Hash, Hasher},
     marker::PhantomData,
     mem::{swap, take},
+    rc::Rc,
     sync::{
         atomic::{AtomicUsize, Ordering},
-        Arc, Mutex,
+        Arc, Mutex, RwLock,
     },
 };
 use typedmap::TypedMapKey;
@@ -50,7 +52,7 @@ impl Circuit<()> {
     where
         T: Default + Clone + Send + 'static,
     {
-        let (input, input_handle) = Input::new(|x| x);
+        let (input, input_handle) = Input::new(self, |x| x, keep_partitions, clone_checkpoint);
         let stream = self.add_source(input);
         (stream, input_handle)
     }
@@ -77,7 +79,12 @@ impl Circuit<()> {
         K: DBData,
         R: DBWeight,
     {
-        let (input, input_handle) = Input::new(|tuples| OrdZSet::from_keys((), tuples));
+        let (input, input_handle) = Input::new(
+            self,
+            |tuples| OrdZSet::from_keys((), tuples),
+            round_robin,
+            truncate_checkpoint,
+        );
         let stream = self.add_source(input);
 
         let zset_handle = <CollectionHandle<K, R>>::new(input_handle);
@@ -112,12 +119,17 @@ impl Circuit<()> {
         V: DBData,
         R: DBWeight,
     {
-        let (input, input_handle) = Input::new(|tuples: Vec<(K, (V, R))>| {
-            OrdIndexedZSet::from_tuples(
-                (),
-                tuples.into_iter().map(|(k, (v, w))| ((k, v), w)).collect(),
-            )
-        });
+        let (input, input_handle) = Input::new(
+            self,
+            |tuples: Vec<(K, (V, R))>| {
+                OrdIndexedZSet::from_tuples(
+                    (),
+                    tuples.into_iter().map(|(k, (v, w))| ((k, v), w)).collect(),
+                )
+            },
+            round_robin,
+            truncate_checkpoint,
+        );
         let stream = self.add_source(input);
 
         let zset_handle = <CollectionHandle<K, (V, R)>>::new(input_handle);
@@ -233,13 +245,19 @@ impl Circuit<()> {
         R: DBData + ZRingValue,
     {
         self.region("input_set", || {
-            let (input, input_handle) = Input::new(|tuples: Vec<(K, bool)>| tuples);
+            let (input, input_handle) = Input::new(
+                self,
+                |tuples: Vec<(K, bool)>| tuples,
+                partition_by_key,
+                truncate_checkpoint,
+            );
             let input_stream = self.add_source(input);
-            let upsert_handle = <UpsertHandle<K, bool>>::new(input_handle);
 
             let upsert =
                 self.add_upsert(input_stream, |insert| if insert { Some(()) } else { None });
 
+            let upsert_handle = <UpsertHandle<K, bool>>::new(input_handle);
+
             (upsert, upsert_handle)
         })
     }
@@ -317,12 +335,18 @@ impl Circuit<()> {
         R: DBData + ZRingValue,
     {
         self.region("input_map", || {
-            let (input, input_handle) = Input::new(|tuples: Vec<(K, Option<V>)>| tuples);
+            let (input, input_handle) = Input::new(
+                self,
+                |tuples: Vec<(K, Option<V>)>| tuples,
+                partition_by_key,
+                truncate_checkpoint,
+            );
             let input_stream = self.add_source(input);
-            let zset_handle = <UpsertHandle<K, Option<V>>>::new(input_handle);
 
             let upsert = self.add_upsert(input_stream, |val| val);
 
+            let zset_handle = <UpsertHandle<K, Option<V>>>::new(input_handle);
+
             (upsert, zset_handle)
         })
     }
@@ -458,53 +482,182 @@ where
     }
 }
 
+/// Records the current contents of a mailbox and returns a closure that
+/// restores them.
+type Checkpoint<T> = fn(&Mailbox<T>) -> Box<dyn FnOnce()>;
+
+// Checkpoint of a mailbox that accumulates updates in a vector: updates are
+// only ever appended to the vector, so restoring the checkpoint truncates it
+// to its current length.
+fn truncate_checkpoint<T>(mailbox: &Mailbox<Vec<T>>) -> Box<dyn FnOnce()>
+where
+    T: Send + 'static,
+{
+    let len = mailbox.value.lock().unwrap().len();
+    let mailbox = mailbox.clone();
+    Box::new(move || mailbox.update(|v| v.truncate(len)))
+}
+
+// Checkpoint of a mailbox that holds a single value, which can be overwritten.
+fn clone_checkpoint<T>(mailbox: &Mailbox<T>) -> Box<dyn FnOnce()>
+where
+    T: Default + Clone + Send + 'static,
+{
+    let value = mailbox.value.lock().unwrap().clone();
+    let mailbox = mailbox.clone();
+    Box::new(move || mailbox.set(value))
+}
+
+// Mailboxes of an input handle, one per worker.
+struct Mailboxes<T> {
+    // Data pushed by the client.
+    inputs: Vec<Mailbox<T>>,
+    // Mailboxes of the runtime being created to replace the current runtime
+    // during rescaling.  They replace `inputs` once the new runtime has
+    // started (see `InputHandleInternal::commit_resize`).
+    next: Option<Vec<Mailbox<T>>>,
+}
+
+fn new_mailboxes<T>(num_workers: usize) -> Vec<Mailbox<T>>
+where
+    T: Default,
+{
+    (0..num_workers).map(|_| Mailbox::new()).collect()
+}
+
 struct InputHandleInternal<T> {
-    mailbox: Vec<Mailbox<T>>,
+    mailboxes: RwLock<Mailboxes<T>>,
+    // Redistributes per-worker values across a different number of workers
+    // when the circuit is rescaled.
+    repartition: fn(Vec<T>, usize) -> Vec<T>,
 }
 
 impl<T> InputHandleInternal<T>
 where
     T: Default + Clone,
 {
-    fn new(num_workers: usize) -> Self {
+    fn new(num_workers: usize, repartition: fn(Vec<T>, usize) -> Vec<T>) -> Self {
         assert_ne!(num_workers, 0);
 
-        let mut mailbox = Vec::with_capacity(num_workers);
-        for _ in 0..num_workers {
-            mailbox.push(Mailbox::new());
+        Self {
+            mailboxes: RwLock::new(Mailboxes {
+                inputs: new_mailboxes(num_workers),
+                next: None,
+            }),
+            repartition,
         }
+    }
 
-        Self { mailbox }
+    fn num_workers(&self) -> usize {
+        self.mailboxes.read().unwrap().inputs.len()
     }
 
     fn set_for_worker(&self, worker: usize, v: T) {
-        self.mailbox[worker].set(v);
+        self.mailboxes.read().unwrap().inputs[worker].set(v);
     }
 
     fn update_for_worker<F>(&self, worker: usize, f: F)
     where
         F: FnOnce(&mut T),
     {
-        self.mailbox[worker].update(f);
+        self.mailboxes.read().unwrap().inputs[worker].update(f);
+    }
+
+    /// Invoke `f` with the mailboxes of all workers.  The number of workers
+    /// does not change while `f` is running.
+    fn with_mailboxes<F>(&self, f: F)
+    where
+        F: FnOnce(&[Mailbox<T>]),
+    {
+        f(&self.mailboxes.read().unwrap().inputs);
     }
 
     /// Send the same value to all workers.
     fn set_for_all(&self, v: T) {
-        for i in 0..self.mailbox.len() - 1 {
-            self.mailbox[i].set(v.clone());
+        let mailboxes = self.mailboxes.read().unwrap();
+        let num_workers = mailboxes.inputs.len();
+
+        for i in 0..num_workers - 1 {
+            mailboxes.inputs[i].set(v.clone());
         }
-        self.mailbox[self.mailbox.len() - 1].set(v);
+        mailboxes.inputs[num_workers - 1].set(v);
     }
 
     fn clear_for_all(&self) {
-        for mailbox in self.mailbox.iter() {
+        for mailbox in self.mailboxes.read().unwrap().inputs.iter() {
             mailbox.set(Default::default());
         }
     }
 
-    fn mailbox(&self, worker: usize) -> &Mailbox<T> {
-        &self.mailbox[worker]
+    /// Returns the mailbox that the input operator in `worker` reads from.
+    ///
+    /// While the handle is being resized, returns the mailbox of the new
+    /// runtime.
+    fn mailbox(&self, worker: usize) -> Mailbox<T> {
+        let mailboxes = self.mailboxes.read().unwrap();
+        mailboxes.next.as_ref().unwrap_or(&mailboxes.inputs)[worker].clone()
+    }
+
+    /// Allocate mailboxes for a new runtime with `num_workers` workers.
+    ///
+    /// The client keeps writing to the current mailboxes until
+    /// [`Self::commit_resize`] is called, so that no data is lost if the new
+    /// runtime fails to start.
+    fn prepare_resize(&self, num_workers: usize) {
+        self.mailboxes.write().unwrap().next = Some(new_mailboxes(num_workers));
+    }
+
+    /// Switch to the mailboxes allocated by [`Self::prepare_resize`],
+    /// redistributing values buffered since the start of the last clock
+    /// cycle across the new set of mailboxes.
+    fn commit_resize(&self) {
+        let mut mailboxes = self.mailboxes.write().unwrap();
+        if let Some(next) = mailboxes.next.take() {
+            let pending = mailboxes.inputs.iter().map(Mailbox::take).collect();
+
+            for (mailbox, v) in next.iter().zip((self.repartition)(pending, next.len())) {
+                mailbox.set(v);
+            }
+            mailboxes.inputs = next;
+        }
+    }
+}
+
+/// Repartitioning function for inputs that cannot be redistributed, e.g.,
+/// streams created by [`Circuit::add_input_stream`]: keeps the values of
+/// the first `num_workers` workers.
+fn keep_partitions<T>(mut partitions: Vec<T>, num_workers: usize) -> Vec<T>
+where
+    T: Default,
+{
+    partitions.resize_with(num_workers, Default::default);
+    partitions
+}
+
+/// Redistribute tuples across `num_workers` partitions in round robin.
+fn round_robin<K, V>(partitions: Vec<Vec<(K, V)>>, num_workers: usize) -> Vec<Vec<(K, V)>> {
+    let mut result: Vec<Vec<(K, V)>> = (0..num_workers).map(|_| Vec::new()).collect();
+
+    for (i, tuple) in partitions.into_iter().flatten().enumerate() {
+        result[i % num_workers].push(tuple);
+    }
+    result
+}
+
+/// Redistribute tuples across `num_workers` partitions based on the hash of
+/// the key, like [`UpsertHandle`] does.  All tuples with the same key are
+/// stored in the same partition in the old and the new partitioning, so the
+/// relative order of updates to each key is preserved.
+fn partition_by_key<K, V>(partitions: Vec<Vec<(K, V)>>, num_workers: usize) -> Vec<Vec<(K, V)>>
+where
+    K: Hash,
+{
+    let mut result: Vec<Vec<(K, V)>> = (0..num_workers).map(|_| Vec::new()).collect();
+
+    for (k, v) in partitions.into_iter().flatten() {
+        result[(default_hash(&k) as u32 as usize) % num_workers].push((k, v));
     }
+    result
 }
 
 /// A handle used to write data to an input stream created by
@@ -525,17 +678,39 @@ impl<T> InputHandle<T>
 where
     T: Default + Send + Clone + 'static,
 {
-    fn new() -> Self {
+    fn new(repartition: fn(Vec<T>, usize) -> Vec<T>) -> Self {
         match Runtime::runtime() {
-            None => Self(Arc::new(InputHandleInternal::new(1))),
+            None => Self(Arc::new(InputHandleInternal::new(1, repartition))),
             Some(runtime) => {
-                let input_id = runtime.sequence_next(Runtime::worker_index());
+                let input_id = runtime.handle_sequence_next(Runtime::worker_index());
 
                 runtime
                     .local_store()
                     .entry(InputId::new(input_id))
                     .or_insert_with(|| {
-                        Self(Arc::new(InputHandleInternal::new(runtime.num_workers())))
+                        // After rescaling, reuse the handle created by the
+                        // previous runtime, so that clients can keep using it.
+                        let inherited = runtime.predecessor().and_then(|predecessor| {
+                            predecessor
+                                .local_store()
+                                .get(&InputId::new(input_id))
+                                .map(|handle| handle.value().clone())
+                        });
+
+                        match inherited {
+                            Some(handle) => {
+                                handle.0.prepare_resize(runtime.num_workers());
+                                let handle_clone = handle.clone();
+                                runtime.on_handles_commit(Box::new(move || {
+                                    handle_clone.0.commit_resize()
+                                }));
+                                handle
+                            }
+                            None => Self(Arc::new(InputHandleInternal::new(
+                                runtime.num_workers(),
+                                repartition,
+                            ))),
+                        }
                     })
                     .value()
                     .clone()
@@ -543,7 +718,7 @@ where
         }
     }
 
-    fn mailbox(&self, worker: usize) -> &Mailbox<T> {
+    fn mailbox(&self, worker: usize) -> Mailbox<T> {
         self.0.mailbox(worker)
     }
 
@@ -616,29 +791,24 @@ where
 {
     fn new(input_handle: InputHandle<Vec<(K, V)>>) -> Self {
         Self {
-            buffers: vec![Vec::new(); input_handle.0.mailbox.len()],
+            buffers: Vec::new(),
             input_handle,
             next_worker: AtomicUsize::new(0),
         }
     }
 
-    #[inline]
-    fn num_partitions(&self) -> usize {
-        self.buffers.len()
-    }
-
     /// Push a single `(key,value)` pair to the input stream.
     pub fn push(&self, k: K, v: V) {
-        let num_partitions = self.num_partitions();
-
-        if num_partitions > 1 {
-            let next_worker = self.next_worker.fetch_add(1, Ordering::AcqRel);
-            self.input_handle
-                .update_for_worker(next_worker % num_partitions, |tuples| tuples.push((k, v)));
-        } else {
-            self.input_handle
-                .update_for_worker(0, |tuples| tuples.push((k, v)));
-        }
+        self.input_handle.0.with_mailboxes(|mailboxes| {
+            let num_partitions = mailboxes.len();
+
+            if num_partitions > 1 {
+                let next_worker = self.next_worker.fetch_add(1, Ordering::AcqRel);
+                mailboxes[next_worker % num_partitions].update(|tuples| tuples.push((k, v)));
+            } else {
+                mailboxes[0].update(|tuples| tuples.push((k, v)));
+            }
+        });
     }
 
     /// Push multiple `(key,value)` pairs to the input stream.
@@ -662,44 +832,53 @@ where
     /// `append` operation.  The remaining updates will appear
     /// during subsequent logical clock cycles.
     pub fn append(&mut self, vals: &mut Vec<(K, V)>) {
-        let num_partitions = self.num_partitions();
+        let Self {
+            buffers,
+            input_handle,
+            next_worker,
+        } = self;
 
-        if num_partitions > 1 {
-            let mut next_worker = self.next_worker.load(Ordering::Acquire);
-            let partition_size = vals.len() / num_partitions;
+        input_handle.0.with_mailboxes(|mailboxes| {
+            let num_partitions = mailboxes.len();
 
-            for worker in 0..num_partitions {
-                if worker == num_partitions - 1 {
-                    self.buffers[next_worker % num_partitions].append(vals);
-                } else {
-                    let len = vals.len();
-                    // Draining from the end should be more efficient as it doesn't
-                    // require memcpy'ing the tail of the vector to the front.
-                    self.buffers[next_worker % num_partitions]
-                        .extend(vals.drain(len - partition_size..));
-                }
-                next_worker += 1;
-            }
-            self.next_worker.store(next_worker, Ordering::Release);
+            if num_partitions > 1 {
+                buffers.resize_with(num_partitions, Vec::new);
+
+                let mut worker = next_worker.load(Ordering::Acquire);
+                let partition_size = vals.len() / num_partitions;
 
-            for worker in 0..num_partitions {
-                self.input_handle.update_for_worker(worker, |tuples| {
+                for i in 0..num_partitions {
+                    if i == num_partitions - 1 {
+                        buffers[worker % num_partitions].append(vals);
+                    } else {
+                        let len = vals.len();
+                        // Draining from the end should be more efficient as it doesn't
+                        // require memcpy'ing the tail of the vector to the front.
+                        buffers[worker % num_partitions].extend(vals.drain(len - partition_size..));
+                    }
+                    worker += 1;
+                }
+                next_worker.store(worker, Ordering::Release);
+
+                for (mailbox, buffer) in mailboxes.iter().zip(buffers.iter_mut()) {
+                    mailbox.update(|tuples| {
+                        if tuples.is_empty() {
+                            *tuples = take(buffer);
+                        } else {
+                            tuples.append(buffer);
+                        }
+                    })
+                }
+            } else {
+                mailboxes[0].update(|tuples| {
                     if tuples.is_empty() {
-                        *tuples = take(&mut self.buffers[worker]);
+                        *tuples = take(vals);
                     } else {
-                        tuples.append(&mut self.buffers[worker]);
+                        tuples.append(vals);
                     }
-                })
+                });
             }
-        } else {
-            self.input_handle.update_for_worker(0, |tuples| {
-                if tuples.is_empty() {
-                    *tuples = take(vals);
-                } else {
-                    tuples.append(vals);
-                }
-            });
-        }
+        });
     }
 
     /// Clear all inputs buffered since the start of the last clock cycle.
@@ -787,30 +966,24 @@ where
         hash_func: Arc<dyn HashFunc<K>>,
     ) -> Self {
         Self {
-            buffers: vec![Vec::new(); input_handle.0.mailbox.len()],
+            buffers: Vec::new(),
             input_handle,
             hash_func,
         }
     }
 
-    #[inline]
-    fn num_partitions(&self) -> usize {
-        self.buffers.len()
-    }
-
     /// Push a single `(key,value)` pair to the input stream.
     pub fn push(&self, k: K, v: V) {
-        let num_partitions = self.num_partitions();
-
-        if num_partitions > 1 {
-            self.input_handle
-                .update_for_worker(((self.hash_func)(&k) as usize) % num_partitions, |tuples| {
-                    tuples.push((k, v))
-                });
-        } else {
-            self.input_handle
-                .update_for_worker(0, |tuples| tuples.push((k, v)));
-        }
+        self.input_handle.0.with_mailboxes(|mailboxes| {
+            let num_partitions = mailboxes.len();
+
+            if num_partitions > 1 {
+                mailboxes[((self.hash_func)(&k) as usize) % num_partitions]
+                    .update(|tuples| tuples.push((k, v)));
+            } else {
+                mailboxes[0].update(|tuples| tuples.push((k, v)));
+            }
+        });
     }
 
     /// Push multiple `(key,value)` pairs to the input stream.
@@ -834,30 +1007,40 @@ where
     /// `append` operation.  The remaining updates will appear
     /// during subsequent logical clock cycles.
     pub fn append(&mut self, vals: &mut Vec<(K, V)>) {
-        let num_partitions = self.num_partitions();
+        let Self {
+            buffers,
+            input_handle,
+            hash_func,
+        } = self;
 
-        if num_partitions > 1 {
-            for (k, v) in vals.drain(..) {
-                self.buffers[((self.hash_func)(&k) as usize) % num_partitions].push((k, v));
-            }
-            for worker in 0..num_partitions {
-                self.input_handle.update_for_worker(worker, |tuples| {
+        input_handle.0.with_mailboxes(|mailboxes| {
+            let num_partitions = mailboxes.len();
+
+            if num_partitions > 1 {
+                buffers.resize_with(num_partitions, Vec::new);
+
+                for (k, v) in vals.drain(..) {
+                    buffers[(hash_func(&k) as usize) % num_partitions].push((k, v));
+                }
+                for (mailbox, buffer) in mailboxes.iter().zip(buffers.iter_mut()) {
+                    mailbox.update(|tuples| {
+                        if tuples.is_empty() {
+                            *tuples = take(buffer);
+                        } else {
+                            tuples.append(buffer);
+                        }
+                    })
+                }
+            } else {
+                mailboxes[0].update(|tuples| {
                     if tuples.is_empty() {
-                        *tuples = take(&mut self.buffers[worker]);
+                        *tuples = take(vals);
                     } else {
-                        tuples.append(&mut self.buffers[worker]);
+                        tuples.append(vals);
                     }
-                })
+                });
             }
-        } else {
-            self.input_handle.update_for_worker(0, |tuples| {
-                if tuples.is_empty() {
-                    *tuples = take(vals);
-                } else {
-                    tuples.append(vals);
-                }
-            });
-        }
+        });
     }
 
     /// Clear all inputs buffered since the start of the last clock cycle.
@@ -881,6 +1064,66 @@ where
     }
 }
 
+circuit_cache_key!(InputRegistryId(() => Rc<InputRegistry>));
+
+/// Input operators of a circuit instantiated in one worker.
+#[derive(Default)]
+pub(crate) struct InputRegistry {
+    // Closures that checkpoint the mailbox of each input for the current
+    // worker.
+    checkpoint: RefCell<Vec<Box<dyn Fn() -> Box<dyn FnOnce()>>>>,
+    // Closures that restore the last checkpoint of each mailbox.
+    rollback: RefCell<Vec<Box<dyn FnOnce()>>>,
+    // Closures that move the contents of each mailbox aside for the next
+    // clock cycle.
+    stage: RefCell<Vec<Box<dyn Fn()>>>,
+}
+
+impl InputRegistry {
+    fn register(&self, checkpoint: Box<dyn Fn() -> Box<dyn FnOnce()>>, stage: Box<dyn Fn()>) {
+        self.checkpoint.borrow_mut().push(checkpoint);
+        self.stage.borrow_mut().push(stage);
+    }
+
+    /// Move data buffered in the current worker's input mailboxes aside, so
+    /// that the next clock cycle consumes exactly this data.  Data pushed to
+    /// the mailboxes after this call is consumed by the following clock
+    /// cycle.  Has no effect on inputs that have already been staged since
+    /// the last clock cycle.
+    pub(crate) fn stage(&self) {
+        for stage in self.stage.borrow().iter() {
+            stage();
+        }
+    }
+
+    /// Record the contents of the current worker's input mailboxes, replacing
+    /// the previous checkpoint.
+    pub(crate) fn checkpoint(&self) {
+        *self.rollback.borrow_mut() = self
+            .checkpoint
+            .borrow()
+            .iter()
+            .map(|checkpoint| checkpoint())
+            .collect();
+    }
+
+    /// Discard data buffered in the current worker's input mailboxes since
+    /// the last call to [`Self::checkpoint`].
+    pub(crate) fn rollback(&self) {
+        for rollback in take(&mut *self.rollback.borrow_mut()) {
+            rollback();
+        }
+    }
+}
+
+impl Circuit<()> {
+    /// Returns the registry of input operators of the circuit.
+    pub(crate) fn input_registry(&self) -> Rc<InputRegistry> {
+        self.cache_get_or_insert_with(InputRegistryId::new(()), Default::default)
+            .clone()
+    }
+}
+
 /// Source operator that injects data received via `InputHandle` to the circuit.
 ///
 /// ```text
@@ -895,6 +1138,9 @@ where
 /// ```
 struct Input<IT, OT, F> {
     mailbox: Mailbox<IT>,
+    // Contents of the mailbox moved aside by `InputRegistry::stage`, to be
+    // consumed by the next clock cycle instead of the mailbox.
+    staged: Rc<RefCell<Option<IT>>>,
     input_func: F,
     phantom: PhantomData<OT>,
 }
@@ -903,12 +1149,33 @@ impl<IT, OT, F> Input<IT, OT, F>
 where
     IT: Default + Clone + Send + 'static,
 {
-    fn new(input_func: F) -> (Self, InputHandle<IT>) {
-        let handle = InputHandle::new();
-        let mailbox = handle.mailbox(Runtime::worker_index()).clone();
+    fn new(
+        circuit: &Circuit<()>,
+        input_func: F,
+        repartition: fn(Vec<IT>, usize) -> Vec<IT>,
+        checkpoint: Checkpoint<IT>,
+    ) -> (Self, InputHandle<IT>) {
+        let handle = InputHandle::new(repartition);
+        let mailbox = handle.mailbox(Runtime::worker_index());
+
+        let staged = Rc::new(RefCell::new(None));
+
+        let mailbox_clone = mailbox.clone();
+        let stage_mailbox = mailbox.clone();
+        let stage_into = staged.clone();
+        circuit.input_registry().register(
+            Box::new(move || checkpoint(&mailbox_clone)),
+            Box::new(move || {
+                let mut staged = stage_into.borrow_mut();
+                if staged.is_none() {
+                    *staged = Some(stage_mailbox.take());
+                }
+            }),
+        );
 
         let input = Self {
             mailbox,
+            staged,
             input_func,
             phantom: PhantomData,
         };
@@ -939,7 +1206,11 @@ where
     F: Fn(IT) -> OT + 'static,
 {
     fn eval(&mut self) -> OT {
-        let v = self.mailbox.take();
+        let v = self
+            .staged
+            .borrow_mut()
+            .take()
+            .unwrap_or_else(|| self.mailbox.take());
         (self.input_func)(v)
     }
 }
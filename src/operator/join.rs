@@ -5,9 +5,10 @@ use crate::{
     circuit::{
         metadata::{MetaItem, OperatorLocation, OperatorMeta},
         operator_traits::{BinaryOperator, Operator},
-        Circuit, GlobalNodeId, Scope, Stream,
+        Circuit, GlobalNodeId, Runtime, Scope, Stream,
     },
     circuit_cache_key,
+    operator::communication::{HotKeyPlacement, HotKeys, SkewConfig},
     time::Timestamp,
     trace::{cursor::Cursor as TraceCursor, Batch, BatchReader, Batcher, Builder, Spine, Trace},
     DBData, DBTimestamp, OrdIndexedZSet, OrdZSet,
@@ -16,7 +17,7 @@ use size_of::{Context, SizeOf};
 use std::{
     borrow::Cow,
     cmp::{min, Ordering},
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     iter::once,
     marker::PhantomData,
     mem::{needs_drop, MaybeUninit},
@@ -106,6 +107,69 @@ where
         )
     }
 
+    /// Skew-aware version of [`Self::stream_join`].
+    ///
+    /// Detects hot keys in both input streams (see
+    /// [`hot_key_counts`](`Self::hot_key_counts`)).  For each hot key, tuples
+    /// from the input with more tuples under this key are spread across
+    /// all workers, while tuples with this key from the other input are
+    /// broadcast to every worker, so that each pair of tuples is still joined
+    /// by exactly one worker.  Tuples with other keys are sharded by key as
+    /// usual.  The output of the operator is the union of outputs computed by
+    /// individual workers.
+    #[track_caller]
+    #[allow(clippy::type_complexity)]
+    pub fn stream_join_skewed<F, I2, V>(
+        &self,
+        other: &Stream<Circuit<P>, I2>,
+        join: F,
+        config: &SkewConfig,
+    ) -> Stream<Circuit<P>, OrdZSet<V, <I1::R as MulByRef<I2::R>>::Output>>
+    where
+        I1: Batch<Time = ()> + Send,
+        I2: Batch<Key = I1::Key, Time = ()> + Send,
+        I1::R: MulByRef<I2::R>,
+        <I1::R as MulByRef<I2::R>>::Output: DBData + ZRingValue,
+        F: Fn(&I1::Key, &I1::Val, &I2::Val) -> V + 'static,
+        V: DBData,
+    {
+        self.stream_join_skewed_generic(other, join, config)
+    }
+
+    /// Like [`Self::stream_join_skewed`], but can return any batch type.
+    #[track_caller]
+    pub fn stream_join_skewed_generic<F, I2, Z>(
+        &self,
+        other: &Stream<Circuit<P>, I2>,
+        join: F,
+        config: &SkewConfig,
+    ) -> Stream<Circuit<P>, Z>
+    where
+        I1: Batch<Time = ()> + Send,
+        I2: Batch<Key = I1::Key, Time = ()> + Send,
+        Z: ZSet,
+        I1::R: MulByRef<I2::R, Output = Z::R>,
+        F: Fn(&I1::Key, &I1::Val, &I2::Val) -> Z::Key + 'static,
+    {
+        let location = Location::caller();
+
+        if Runtime::runtime().map_or(1, |runtime| runtime.num_workers()) == 1 {
+            return self.stream_join_generic(other, join);
+        }
+
+        let hot_keys = self
+            .hot_key_counts(config)
+            .apply2(&other.hot_key_counts(config), hot_join_keys);
+        let left_hot_keys = hot_keys.apply(|(left, _)| left.clone());
+        let right_hot_keys = hot_keys.apply(|(_, right)| right.clone());
+
+        self.circuit().add_binary_operator(
+            Join::new_skewed(join, location),
+            &self.shard_skewed(&left_hot_keys),
+            &other.shard_skewed(&right_hot_keys),
+        )
+    }
+
     fn stream_join_inner<F, I2, Z>(
         &self,
         other: &Stream<Circuit<P>, I2>,
@@ -124,6 +188,35 @@ where
     }
 }
 
+// Chooses placement of hot keys for `stream_join_skewed`: tuples from the
+// input with more tuples under each hot key are spread across workers, tuples
+// from the other input are broadcast.
+fn hot_join_keys<K>(
+    left: &BTreeMap<K, usize>,
+    right: &BTreeMap<K, usize>,
+) -> (HotKeys<K>, HotKeys<K>)
+where
+    K: Ord + Clone,
+{
+    let mut left_hot_keys = HotKeys::new();
+    let mut right_hot_keys = HotKeys::new();
+
+    for key in left.keys().chain(right.keys()) {
+        let left_count = left.get(key).copied().unwrap_or_default();
+        let right_count = right.get(key).copied().unwrap_or_default();
+
+        let (left_placement, right_placement) = if left_count >= right_count {
+            (HotKeyPlacement::Spread, HotKeyPlacement::Broadcast)
+        } else {
+            (HotKeyPlacement::Broadcast, HotKeyPlacement::Spread)
+        };
+        left_hot_keys.insert(key.clone(), left_placement);
+        right_hot_keys.insert(key.clone(), right_placement);
+    }
+
+    (left_hot_keys, right_hot_keys)
+}
+
 impl<I1> Stream<Circuit<()>, I1> {
     /// Incremental join of two streams of batches.
     ///
@@ -229,6 +322,47 @@ where
         other: &Stream<Circuit<P>, I2>,
         join_func: F,
     ) -> Stream<Circuit<P>, Z>
+    where
+        TS: DBTimestamp,
+        I2: IndexedZSet<Key = I1::Key, R = I1::R> + Send,
+        Z: IndexedZSet<R = I1::R>,
+        Z::R: MulByRef<Output = Z::R>,
+        F: Fn(&I1::Key, &I1::Val, &I2::Val) -> It + Clone + 'static,
+        It: IntoIterator<Item = (Z::Key, Z::Val)> + 'static,
+    {
+        let left_input = self.shard();
+        let right_input = other.shard();
+
+        let lineage_func = join_func.clone();
+        let output = left_input.join_partitioned::<TS, _, _, _, _>(
+            &right_input,
+            join_func,
+            true,
+            Location::caller(),
+        );
+
+        left_input.record_join_lineage::<I2, Z, _, _>(
+            &right_input,
+            self.origin_node_id(),
+            other.origin_node_id(),
+            output.origin_node_id(),
+            lineage_func,
+        );
+        output
+    }
+
+    // Incrementally joins `self` and `other` (see `join_generic`), which must
+    // be partitioned across workers so that every pair of tuples with the
+    // same key meets at exactly one worker.  `sharded_inputs` is `false` if
+    // the inputs are partitioned by `shard_skewed_incremental` rather than
+    // sharded by key.
+    fn join_partitioned<TS, I2, F, Z, It>(
+        &self,
+        other: &Stream<Circuit<P>, I2>,
+        join_func: F,
+        sharded_inputs: bool,
+        location: &'static Location<'static>,
+    ) -> Stream<Circuit<P>, Z>
     where
         TS: DBTimestamp,
         I2: IndexedZSet<Key = I1::Key, R = I1::R> + Send,
@@ -277,24 +411,27 @@ where
         // The advantage of this representation is that each term can be computed
         // as a join of one of the input streams with the trace of the other stream,
         // implemented by the `JoinTrace` operator.
-        let left = self.shard();
-        let right = other.shard();
-
-        let left_trace = left.trace::<Spine<TS::OrdValBatch<I1::Key, I1::Val, I1::R>>>();
-        let right_trace = right.trace::<Spine<TS::OrdValBatch<I1::Key, I2::Val, I1::R>>>();
+        let left_trace = self.trace::<Spine<TS::OrdValBatch<I1::Key, I1::Val, I1::R>>>();
+        let right_trace = other.trace::<Spine<TS::OrdValBatch<I1::Key, I2::Val, I1::R>>>();
 
         let left = self.circuit().add_binary_operator(
-            JoinTrace::new(join_func.clone(), Location::caller()),
-            &left,
+            JoinTrace {
+                sharded_inputs,
+                ..JoinTrace::new(join_func.clone(), location)
+            },
+            self,
             &right_trace,
         );
 
         let right = self.circuit().add_binary_operator(
-            JoinTrace::new(
-                move |k: &I1::Key, v2: &I2::Val, v1: &I1::Val| join_func(k, v1, v2),
-                Location::caller(),
-            ),
-            &right,
+            JoinTrace {
+                sharded_inputs,
+                ..JoinTrace::new(
+                    move |k: &I1::Key, v2: &I2::Val, v1: &I1::Val| join_func(k, v1, v2),
+                    location,
+                )
+            },
+            other,
             &left_trace.delay_trace(),
         );
 
@@ -333,12 +470,88 @@ where
     }
 }
 
+impl<I1> Stream<Circuit<()>, I1>
+where
+    I1: IndexedZSet + Send,
+    I1::R: ZRingValue,
+{
+    /// Skew-aware version of [`Self::join`].
+    ///
+    /// Like [`Self::stream_join_skewed`], detects hot keys in both inputs,
+    /// spreads tuples with each hot key from the input with more tuples under
+    /// this key across all workers, and broadcasts tuples with this key from
+    /// the other input.  Inputs are partitioned by
+    /// [`shard_skewed_incremental`](`Self::shard_skewed_incremental`), so a
+    /// key stays hot, with the same placement, once it has been detected.
+    /// The output of the operator is the union of outputs computed by
+    /// individual workers.
+    ///
+    /// This operator is only available in the root scope.
+    #[track_caller]
+    pub fn join_skewed<I2, F, V>(
+        &self,
+        other: &Stream<Circuit<()>, I2>,
+        join_func: F,
+        config: &SkewConfig,
+    ) -> Stream<Circuit<()>, OrdZSet<V, I1::R>>
+    where
+        I2: IndexedZSet<Key = I1::Key, R = I1::R> + Send,
+        F: Fn(&I1::Key, &I1::Val, &I2::Val) -> V + Clone + 'static,
+        V: DBData,
+    {
+        self.join_skewed_generic(
+            other,
+            move |k, v1, v2| once((join_func(k, v1, v2), ())),
+            config,
+        )
+    }
+
+    /// Like [`Self::join_skewed`], but can return any indexed Z-set type.
+    #[track_caller]
+    pub fn join_skewed_generic<I2, F, Z, It>(
+        &self,
+        other: &Stream<Circuit<()>, I2>,
+        join_func: F,
+        config: &SkewConfig,
+    ) -> Stream<Circuit<()>, Z>
+    where
+        I2: IndexedZSet<Key = I1::Key, R = I1::R> + Send,
+        Z: IndexedZSet<R = I1::R>,
+        Z::R: MulByRef<Output = Z::R>,
+        F: Fn(&I1::Key, &I1::Val, &I2::Val) -> It + Clone + 'static,
+        It: IntoIterator<Item = (Z::Key, Z::Val)> + 'static,
+    {
+        let location = Location::caller();
+
+        if Runtime::runtime().map_or(1, |runtime| runtime.num_workers()) == 1 {
+            return self.join_generic::<(), _, _, _, _>(other, join_func);
+        }
+
+        let hot_keys = self
+            .hot_key_counts(config)
+            .apply2(&other.hot_key_counts(config), hot_join_keys);
+        let left_hot_keys = hot_keys.apply(|(left, _)| left.clone());
+        let right_hot_keys = hot_keys.apply(|(_, right)| right.clone());
+
+        self.shard_skewed_incremental(&left_hot_keys)
+            .join_partitioned::<(), _, _, _, _>(
+                &other.shard_skewed_incremental(&right_hot_keys),
+                join_func,
+                false,
+                location,
+            )
+    }
+}
+
 /// Join two streams of batches.
 ///
 /// See [`Stream::join`](`crate::circuit::Stream::join`).
 pub struct Join<F, I1, I2, Z> {
     join_func: F,
     location: &'static Location<'static>,
+    // `false` if the inputs are partitioned by `shard_skewed` rather than
+    // sharded by key.
+    sharded_inputs: bool,
     _types: PhantomData<(I1, I2, Z)>,
 }
 
@@ -347,6 +560,19 @@ impl<F, I1, I2, Z> Join<F, I1, I2, Z> {
         Self {
             join_func,
             location,
+            sharded_inputs: true,
+            _types: PhantomData,
+        }
+    }
+
+    /// Join input streams partitioned by
+    /// [`Stream::shard_skewed`](`crate::Stream::shard_skewed`), which spreads
+    /// or broadcasts tuples with hot keys across workers.
+    pub fn new_skewed(join_func: F, location: &'static Location<'static>) -> Self {
+        Self {
+            join_func,
+            location,
+            sharded_inputs: false,
             _types: PhantomData,
         }
     }
@@ -370,6 +596,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn is_stateful(&self) -> bool {
+        self.sharded_inputs
+    }
 }
 
 impl<F, I1, I2, Z> BinaryOperator<I1, I2, Z> for Join<F, I1, I2, Z>
@@ -456,6 +686,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn is_stateful(&self) -> bool {
+        true
+    }
 }
 
 impl<F, I1, I2, Z> BinaryOperator<I1, I2, Z> for MonotonicJoin<F, I1, I2, Z>
@@ -532,6 +766,9 @@ where
 {
     join_func: F,
     location: &'static Location<'static>,
+    // `false` if the inputs are partitioned by `shard_skewed_incremental`
+    // rather than sharded by key.
+    sharded_inputs: bool,
     // TODO: not needed once timekeeping is handled by the circuit.
     time: T::Time,
     // Future update batches computed ahead of time, indexed by time
@@ -554,6 +791,7 @@ where
         Self {
             join_func,
             location,
+            sharded_inputs: true,
             time: <T::Time as Timestamp>::clock_start(),
             output_batchers: HashMap::new(),
             empty_input: false,
@@ -665,6 +903,10 @@ where
                 .keys()
                 .all(|time| !time.less_equal(&epoch_end))
     }
+
+    fn is_stateful(&self) -> bool {
+        self.sharded_inputs
+    }
 }
 
 impl<F, I, T, Z, It> BinaryOperator<I, T, Z> for JoinTrace<F, I, T, Z, It>
@@ -813,7 +1055,7 @@ where
 mod test {
     use crate::{
         indexed_zset,
-        operator::{DelayedFeedback, FilterMap, Generator},
+        operator::{communication::SkewConfig, DelayedFeedback, FilterMap, Generator},
         time::{NestedTimestamp32, Product},
         trace::{
             ord::{OrdIndexedZSet, OrdZSet},
@@ -1336,4 +1578,102 @@ mod test {
 
         circuit.kill().unwrap();
     }
+
+    #[test]
+    fn stream_join_skewed_test() {
+        let (mut circuit, (mut input1, mut input2)) = Runtime::init_circuit(4, move |circuit| {
+            let (input1, input_handle1) = circuit.add_input_indexed_zset::<usize, usize, isize>();
+            let (input2, input_handle2) = circuit.add_input_indexed_zset::<usize, usize, isize>();
+
+            let join_func = |k: &usize, v1: &usize, v2: &usize| (*k, *v1, *v2);
+            let expected = input1.stream_join(&input2, join_func).gather(0);
+            let skewed = input1
+                .stream_join_skewed(&input2, join_func, &SkewConfig::new(100))
+                .gather(0);
+
+            expected.apply2(&skewed, |expected, skewed| {
+                if Runtime::worker_index() == 0 {
+                    assert_eq!(expected.len(), 11048);
+                }
+                assert_eq!(expected, skewed);
+            });
+
+            (input_handle1, input_handle2)
+        })
+        .unwrap();
+
+        // Key 0 is hot in the first input, key 1 is hot in the second input.
+        input1.append(
+            &mut (0..1000)
+                .map(|v| (0, (v, 1)))
+                .chain((1..50).map(|k| (k, (k, 1))))
+                .collect(),
+        );
+        input2.append(
+            &mut (0..10)
+                .map(|v| (0, (v, 1)))
+                .chain((0..1000).map(|v| (1, (v, 1))))
+                .chain((2..50).map(|k| (k, (k, 1))))
+                .collect(),
+        );
+        circuit.step().unwrap();
+
+        circuit.kill().unwrap();
+    }
+
+    #[test]
+    fn join_skewed_test() {
+        let (mut circuit, (mut input1, mut input2)) = Runtime::init_circuit(4, move |circuit| {
+            let (input1, input_handle1) = circuit.add_input_indexed_zset::<usize, usize, isize>();
+            let (input2, input_handle2) = circuit.add_input_indexed_zset::<usize, usize, isize>();
+
+            let join_func = |k: &usize, v1: &usize, v2: &usize| (*k, *v1, *v2);
+            let expected = input1.join::<(), _, _, _>(&input2, join_func).gather(0);
+            let skewed = input1
+                .join_skewed(&input2, join_func, &SkewConfig::new(100))
+                .gather(0);
+
+            expected.apply2(&skewed, |expected, skewed| assert_eq!(expected, skewed));
+
+            (input_handle1, input_handle2)
+        })
+        .unwrap();
+
+        // Keys 0 and 1 are cold in the first step.
+        input1.append(
+            &mut (0..10)
+                .map(|v| (0, (v, 1)))
+                .chain((1..50).map(|k| (k, (k, 1))))
+                .collect(),
+        );
+        input2.append(
+            &mut (0..10)
+                .map(|v| (0, (v, 1)))
+                .chain((0..10).map(|v| (1, (v, 1))))
+                .collect(),
+        );
+        circuit.step().unwrap();
+
+        // Key 0 becomes hot in the first input, key 1 in the second input.
+        input1.append(&mut (10..1000).map(|v| (0, (v, 1))).collect());
+        input2.append(&mut (10..1000).map(|v| (1, (v, 1))).collect());
+        circuit.step().unwrap();
+
+        // Delete tuples inserted before the keys became hot.
+        input1.append(
+            &mut (0..10)
+                .map(|v| (0, (v, -1)))
+                .chain((1000..1010).map(|v| (0, (v, 1))))
+                .collect(),
+        );
+        input2.append(
+            &mut (0..5)
+                .map(|v| (0, (v, -1)))
+                .chain((0..10).map(|v| (1, (v, -1))))
+                .collect(),
+        );
+        circuit.step().unwrap();
+
+        circuit.kill().unwrap();
+    }
 }
@@ -6,6 +6,7 @@ pub mod recursive;
 
 pub(crate) mod apply;
 pub(crate) mod inspect;
+pub(crate) mod provenance;
 pub(crate) mod upsert;
 
 mod aggregate;
@@ -51,5 +52,6 @@ pub use join_range::StreamJoinRange;
 pub use neg::UnaryMinus;
 pub use output::OutputHandle;
 pub use plus::{Minus, Plus};
+pub use provenance::Lineage;
 pub use sum::Sum;
 pub use z1::{DelayedFeedback, DelayedNestedFeedback, Z1Nested, Z1};
@@ -11,7 +11,10 @@ use std::{
     borrow::Cow,
     hash::{Hash, Hasher},
     marker::PhantomData,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
 };
 use typedmap::TypedMapKey;
 
@@ -27,7 +30,21 @@ where
     /// of the stream is buffered inside the handle and can be read using
     /// the [`OutputHandle`] API.
     pub fn output(&self) -> OutputHandle<T> {
-        let (output, output_handle) = Output::new();
+        let (output, output_handle) = Output::new(false);
+        self.circuit().add_sink(output, self);
+        output_handle
+    }
+
+    /// Create a tap: an output handle that is disabled until the client
+    /// enables it.
+    ///
+    /// A tap allows inspecting an internal stream of a running circuit
+    /// on demand.  While disabled (see [`OutputHandle::set_enabled`]), the
+    /// tap does not copy the contents of the stream to the handle and
+    /// doesn't claim ownership of stream values, so its overhead is
+    /// negligible.
+    pub fn tap(&self) -> OutputHandle<T> {
+        let (output, output_handle) = Output::new(true);
         self.circuit().add_sink(output, self);
         output_handle
     }
@@ -78,27 +95,67 @@ where
 }
 
 struct OutputHandleInternal<T> {
-    mailbox: Vec<Mailbox<Option<T>>>,
+    mailbox: RwLock<Vec<Mailbox<Option<T>>>>,
+    // Mailboxes of the runtime being created to replace the current runtime
+    // during rescaling.  They replace `mailbox` once the new runtime has
+    // started.
+    next: RwLock<Option<Vec<Mailbox<Option<T>>>>>,
+    // Output operators don't write to disabled handles.
+    enabled: AtomicBool,
 }
 
 impl<T> OutputHandleInternal<T> {
-    fn new(num_workers: usize) -> Self {
+    fn new(num_workers: usize, enabled: bool) -> Self {
         assert_ne!(num_workers, 0);
 
-        let mut mailbox = Vec::with_capacity(num_workers);
-        for _ in 0..num_workers {
-            mailbox.push(Mailbox::new());
+        Self {
+            mailbox: RwLock::new(Self::new_mailboxes(num_workers)),
+            next: RwLock::new(None),
+            enabled: AtomicBool::new(enabled),
         }
+    }
 
-        Self { mailbox }
+    fn new_mailboxes(num_workers: usize) -> Vec<Mailbox<Option<T>>> {
+        (0..num_workers).map(|_| Mailbox::new()).collect()
+    }
+
+    fn num_workers(&self) -> usize {
+        self.mailbox.read().unwrap().len()
     }
 
     fn take_from_worker(&self, worker: usize) -> Option<T> {
-        self.mailbox[worker].take()
+        self.mailbox.read().unwrap()[worker].take()
     }
 
-    fn mailbox(&self, worker: usize) -> &Mailbox<Option<T>> {
-        &self.mailbox[worker]
+    /// Allocate mailboxes for a new runtime with `num_workers` workers.  The
+    /// client keeps reading from the current mailboxes until
+    /// [`Self::commit_resize`] is called.
+    fn prepare_resize(&self, num_workers: usize) {
+        *self.next.write().unwrap() = Some(Self::new_mailboxes(num_workers));
+    }
+
+    /// Switch to the mailboxes allocated by [`Self::prepare_resize`].  Values
+    /// that have not been read yet are discarded.
+    fn commit_resize(&self) {
+        if let Some(next) = self.next.write().unwrap().take() {
+            *self.mailbox.write().unwrap() = next;
+        }
+    }
+}
+
+impl<T> OutputHandleInternal<T>
+where
+    T: Clone,
+{
+    /// Returns the mailbox that the output operator in `worker` writes to.
+    ///
+    /// While the handle is being resized, returns the mailbox of the new
+    /// runtime.
+    fn mailbox(&self, worker: usize) -> Mailbox<Option<T>> {
+        match &*self.next.read().unwrap() {
+            Some(next) => next[worker].clone(),
+            None => self.mailbox.read().unwrap()[worker].clone(),
+        }
     }
 }
 
@@ -133,17 +190,39 @@ impl<T> OutputHandle<T>
 where
     T: Send + Clone + 'static,
 {
-    fn new() -> Self {
+    fn new(enabled: bool) -> Self {
         match Runtime::runtime() {
-            None => Self(Arc::new(OutputHandleInternal::new(1))),
+            None => Self(Arc::new(OutputHandleInternal::new(1, enabled))),
             Some(runtime) => {
-                let output_id = runtime.sequence_next(Runtime::worker_index());
+                let output_id = runtime.handle_sequence_next(Runtime::worker_index());
 
                 runtime
                     .local_store()
                     .entry(OutputId::new(output_id))
                     .or_insert_with(|| {
-                        Self(Arc::new(OutputHandleInternal::new(runtime.num_workers())))
+                        // After rescaling, reuse the handle created by the
+                        // previous runtime, so that clients can keep using it.
+                        let inherited = runtime.predecessor().and_then(|predecessor| {
+                            predecessor
+                                .local_store()
+                                .get(&OutputId::new(output_id))
+                                .map(|handle| handle.value().clone())
+                        });
+
+                        match inherited {
+                            Some(handle) => {
+                                handle.0.prepare_resize(runtime.num_workers());
+                                let handle_clone = handle.clone();
+                                runtime.on_handles_commit(Box::new(move || {
+                                    handle_clone.0.commit_resize()
+                                }));
+                                handle
+                            }
+                            None => Self(Arc::new(OutputHandleInternal::new(
+                                runtime.num_workers(),
+                                enabled,
+                            ))),
+                        }
                     })
                     .value()
                     .clone()
@@ -151,10 +230,27 @@ where
         }
     }
 
-    fn mailbox(&self, worker: usize) -> &Mailbox<Option<T>> {
+    fn mailbox(&self, worker: usize) -> Mailbox<Option<T>> {
         self.0.mailbox(worker)
     }
 
+    /// Enable or disable the handle.
+    ///
+    /// Handles created with [`Stream::output`] are enabled by default;
+    /// handles created with [`Stream::tap`] are disabled by default.  The
+    /// circuit doesn't write to a disabled handle.  The new setting takes
+    /// effect immediately, so if the handle is enabled in the middle of a
+    /// clock cycle, only some of the workers may have written to it by the
+    /// end of the cycle.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.0.enabled.store(enabled, Ordering::Release);
+    }
+
+    /// Returns `true` if the handle is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.0.enabled.load(Ordering::Acquire)
+    }
+
     /// Read the value produced by `worker` worker thread during the last
     /// clock cycle.
     ///
@@ -182,7 +278,7 @@ where
     /// worker thread in order and storing all none-`None`
     /// results in a vector.
     pub fn take_from_all(&self) -> Vec<T> {
-        let num_workers = self.0.mailbox.len();
+        let num_workers = self.0.num_workers();
         let mut res = Vec::with_capacity(num_workers);
 
         for worker in 0..num_workers {
@@ -232,28 +328,47 @@ where
 /// an `OutputHandle`.
 struct Output<T> {
     mailbox: Mailbox<Option<T>>,
+    handle: OutputHandle<T>,
+    // `true` if the operator was created by `Stream::tap`.
+    tap: bool,
 }
 
 impl<T> Output<T>
 where
     T: Clone + Send + 'static,
 {
-    fn new() -> (Self, OutputHandle<T>) {
-        let handle = OutputHandle::new();
-        let mailbox = handle.mailbox(Runtime::worker_index()).clone();
+    fn new(tap: bool) -> (Self, OutputHandle<T>) {
+        let handle = OutputHandle::new(!tap);
+        let mailbox = handle.mailbox(Runtime::worker_index());
 
-        let output = Self { mailbox };
+        let output = Self {
+            mailbox,
+            handle: handle.clone(),
+            tap,
+        };
 
         (output, handle)
     }
 }
 
+impl<T> Output<T> {
+    /// Returns `true` if the current value of the stream should be written to
+    /// the handle.
+    fn active(&self) -> bool {
+        self.handle.0.enabled.load(Ordering::Acquire)
+    }
+}
+
 impl<T> Operator for Output<T>
 where
     T: 'static,
 {
     fn name(&self) -> Cow<'static, str> {
-        Cow::from("Output")
+        if self.tap {
+            Cow::from("Tap")
+        } else {
+            Cow::from("Output")
+        }
     }
 
     fn fixedpoint(&self, _scope: Scope) -> bool {
@@ -266,15 +381,25 @@ where
     T: Clone + 'static,
 {
     fn eval(&mut self, val: &T) {
-        self.mailbox.set(Some(val.clone()));
+        if self.active() {
+            self.mailbox.set(Some(val.clone()));
+        }
     }
 
     fn eval_owned(&mut self, val: T) {
-        self.mailbox.set(Some(val));
+        if self.active() {
+            self.mailbox.set(Some(val));
+        }
     }
 
     fn input_preference(&self) -> OwnershipPreference {
-        OwnershipPreference::PREFER_OWNED
+        // A tap is usually disabled and shouldn't force other consumers of the
+        // stream to work with a copy.
+        if self.tap {
+            OwnershipPreference::INDIFFERENT
+        } else {
+            OwnershipPreference::PREFER_OWNED
+        }
     }
 }
 
@@ -308,4 +433,36 @@ mod test {
 
         dbsp.kill().unwrap();
     }
+
+    #[test]
+    fn test_tap() {
+        let (mut dbsp, (mut input, tap)) = Runtime::init_circuit(4, |circuit| {
+            let (zset, zset_handle) = circuit.add_input_zset::<u64, isize>();
+            let tap = zset.tap();
+
+            (zset_handle, tap)
+        })
+        .unwrap();
+
+        // Disabled taps don't receive data.
+        assert!(!tap.is_enabled());
+        input.append(&mut vec![(1, 1), (2, 1)]);
+        dbsp.step().unwrap();
+        assert!(tap.take_from_all().is_empty());
+
+        tap.set_enabled(true);
+        input.append(&mut vec![(3, 1), (4, 1)]);
+        dbsp.step().unwrap();
+        assert_eq!(
+            tap.consolidate(),
+            OrdZSet::from_tuples((), vec![(3, 1), (4, 1)])
+        );
+
+        tap.set_enabled(false);
+        input.append(&mut vec![(5, 1)]);
+        dbsp.step().unwrap();
+        assert!(tap.take_from_all().is_empty());
+
+        dbsp.kill().unwrap();
+    }
 }
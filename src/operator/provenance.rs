@@ -0,0 +1,484 @@
+//! Provenance tracing for debugging circuits.
+//!
+//! When provenance tracing is enabled (see
+//! [`Runtime::init_circuit_with_provenance`](`crate::Runtime::init_circuit_with_provenance`)),
+//! the `map`, `filter`, `index`, `join`, `aggregate`, and `distinct` operators
+//! of the top-level circuit record, for each record they output, the input records
+//! it was derived from.  Records are identified by the hash of their value:
+//! `key` for Z-sets and `(key, value)` for indexed Z-sets.
+//! [`DBSPHandle::provenance`](`crate::DBSPHandle::provenance`) follows these
+//! edges backward from an output record to the records that contributed to
+//! it.  Operators that shard their inputs record lineage against the
+//! unsharded input streams, so lineage paths are the same regardless of the
+//! number of workers.
+//!
+//! Lineage edges are accumulated for the entire lifetime of the circuit and
+//! are never garbage collected, and the `join` and `aggregate` operators
+//! maintain an additional copy of their inputs, so this mode is only meant for
+//! tests and debugging runs over small datasets.  Operators inside nested
+//! circuits are not traced.
+
+use crate::{
+    algebra::{AddAssignByRef, HasZero, IndexedZSet, ZRingValue},
+    circuit::{Circuit, GlobalNodeId, Stream},
+    circuit_cache_key, default_hash,
+    trace::{cursor::Cursor, BatchReader},
+};
+use std::{
+    any::TypeId,
+    cell::RefCell,
+    collections::{BTreeSet, HashMap, HashSet},
+    rc::Rc,
+};
+
+circuit_cache_key!(ProvenanceId(() => Rc<Provenance>));
+
+/// Identifies a record by the hash of its value (see [`record_id`]).
+pub(crate) type RecordId = u64;
+
+/// Records that record `output` of the stream produced by `node` was
+/// derived from record `input` of the stream produced by `input_node`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct LineageEdge {
+    node: GlobalNodeId,
+    operator: &'static str,
+    output: RecordId,
+    input_node: GlobalNodeId,
+    input: RecordId,
+    // `Debug` representation of the input record, used to report the
+    // record to the user.
+    input_label: String,
+}
+
+/// Lineage edges recorded by the operators of a circuit in one worker.
+#[derive(Default)]
+pub(crate) struct Provenance {
+    edges: RefCell<HashSet<LineageEdge>>,
+}
+
+impl Provenance {
+    fn record(
+        &self,
+        node: &GlobalNodeId,
+        operator: &'static str,
+        output: RecordId,
+        input_node: &GlobalNodeId,
+        input: &Record,
+    ) {
+        self.edges.borrow_mut().insert(LineageEdge {
+            node: node.clone(),
+            operator,
+            output,
+            input_node: input_node.clone(),
+            input: input.id,
+            input_label: input.label.clone(),
+        });
+    }
+
+    /// All edges recorded so far.
+    pub(crate) fn edges(&self) -> Vec<LineageEdge> {
+        self.edges.borrow().iter().cloned().collect()
+    }
+}
+
+/// Lineage of an output record.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lineage {
+    /// Contributing record, formatted using its `Debug` implementation.
+    ///
+    /// The formatted record is only meant for display: records are matched
+    /// by value.
+    pub record: String,
+    /// Node that produced `record`: an input operator or an operator that
+    /// does not propagate lineage.
+    pub source: GlobalNodeId,
+    /// Operators that derived the output record from `record`, from source
+    /// to output, as `(node id, operator name)` pairs.
+    pub path: Vec<(GlobalNodeId, &'static str)>,
+}
+
+/// Follow `edges` backward from all streams where `record` occurs last
+/// (i.e., is not passed on unmodified to another traced operator).
+pub(crate) fn lineage(edges: Vec<LineageEdge>, record: RecordId) -> Vec<Lineage> {
+    let mut incoming: HashMap<(&GlobalNodeId, RecordId), Vec<&LineageEdge>> = HashMap::new();
+    let mut consumed = HashSet::new();
+
+    for edge in edges.iter() {
+        incoming
+            .entry((&edge.node, edge.output))
+            .or_default()
+            .push(edge);
+        consumed.insert((&edge.input_node, edge.input));
+    }
+
+    let outputs: BTreeSet<&GlobalNodeId> = edges
+        .iter()
+        .filter(|edge| edge.output == record && !consumed.contains(&(&edge.node, record)))
+        .map(|edge| &edge.node)
+        .collect();
+
+    let mut result = Vec::new();
+    for node in outputs {
+        for edge in incoming[&(node, record)].iter() {
+            walk(&incoming, edge, &mut Vec::new(), &mut result);
+        }
+    }
+
+    result
+}
+
+/// Extend `path` with `edge` and follow edges backward from its input record.
+fn walk<'a>(
+    incoming: &HashMap<(&'a GlobalNodeId, RecordId), Vec<&'a LineageEdge>>,
+    edge: &'a LineageEdge,
+    path: &mut Vec<&'a LineageEdge>,
+    result: &mut Vec<Lineage>,
+) {
+    // Guard against cycles.
+    if path
+        .iter()
+        .any(|other| other.node == edge.node && other.output == edge.output)
+    {
+        return;
+    }
+
+    path.push(edge);
+
+    match incoming.get(&(&edge.input_node, edge.input)) {
+        None => result.push(Lineage {
+            record: edge.input_label.clone(),
+            source: edge.input_node.clone(),
+            path: path
+                .iter()
+                .rev()
+                .map(|edge| (edge.node.clone(), edge.operator))
+                .collect(),
+        }),
+        Some(edges) => {
+            for edge in edges.iter() {
+                walk(incoming, edge, path, result);
+            }
+        }
+    }
+
+    path.pop();
+}
+
+/// A record of a batch, as it appears in lineage edges.
+pub(crate) struct Record {
+    id: RecordId,
+    label: String,
+}
+
+/// Identifier of a record of batch type `B` with key `key` and value `val`:
+/// the hash of `key` for Z-sets or of the `(key, val)` tuple for indexed
+/// Z-sets.
+pub(crate) fn record_id<B>(key: &B::Key, val: &B::Val) -> RecordId
+where
+    B: BatchReader,
+{
+    if TypeId::of::<B::Val>() == TypeId::of::<()>() {
+        default_hash(key)
+    } else {
+        default_hash(&(key, val))
+    }
+}
+
+/// Identify and format a record of batch type `B`.
+pub(crate) fn make_record<B>(key: &B::Key, val: &B::Val) -> Record
+where
+    B: BatchReader,
+{
+    let label = if TypeId::of::<B::Val>() == TypeId::of::<()>() {
+        format!("{key:?}")
+    } else {
+        format!("({key:?}, {val:?})")
+    };
+
+    Record {
+        id: record_id::<B>(key, val),
+        label,
+    }
+}
+
+/// Identify and format all records in `batch`.
+fn make_records<B>(batch: &B) -> Vec<Record>
+where
+    B: BatchReader<Time = ()>,
+{
+    let mut records = Vec::new();
+    let mut cursor = batch.cursor();
+
+    while cursor.key_valid() {
+        while cursor.val_valid() {
+            records.push(make_record::<B>(cursor.key(), cursor.val()));
+            cursor.step_val();
+        }
+        cursor.step_key();
+    }
+
+    records
+}
+
+impl Circuit<()> {
+    /// Enable provenance tracing for this circuit.
+    ///
+    /// Must be invoked before adding any operators to the circuit.
+    pub(crate) fn enable_provenance(&self) -> Rc<Provenance> {
+        let provenance = Rc::new(Provenance::default());
+        self.cache_insert(ProvenanceId::new(()), provenance.clone());
+        provenance
+    }
+}
+
+impl<P> Circuit<P>
+where
+    P: Clone + 'static,
+{
+    /// Returns the provenance store of the circuit or `None` if provenance
+    /// tracing is not enabled.
+    pub(crate) fn provenance(&self) -> Option<Rc<Provenance>> {
+        self.cache_get(&ProvenanceId::new(()))
+    }
+}
+
+impl<P, B> Stream<Circuit<P>, B>
+where
+    P: Clone + 'static,
+    B: BatchReader<Time = ()> + Clone + 'static,
+{
+    /// Record that each record in `self` was derived from the identical record
+    /// in the stream produced by `input`.
+    pub(crate) fn record_identity_lineage(&self, operator: &'static str, input: &GlobalNodeId) {
+        if let Some(provenance) = self.circuit().provenance() {
+            let node = self.origin_node_id().clone();
+            let input = input.clone();
+
+            self.inspect(move |batch| {
+                for record in make_records(batch) {
+                    provenance.record(&node, operator, record.id, &input, &record);
+                }
+            });
+        }
+    }
+
+    /// Record that each record in `self` was transformed into records
+    /// computed by `derive` in the stream produced by `output`.  `derive`
+    /// returns the identifiers of output records (see [`record_id`]).
+    pub(crate) fn record_lineage<F>(&self, operator: &'static str, output: &GlobalNodeId, derive: F)
+    where
+        F: Fn(&B::Key, &B::Val) -> Vec<RecordId> + 'static,
+    {
+        if let Some(provenance) = self.circuit().provenance() {
+            let node = output.clone();
+            let input = self.origin_node_id().clone();
+
+            self.inspect(move |batch| {
+                let mut cursor = batch.cursor();
+
+                while cursor.key_valid() {
+                    while cursor.val_valid() {
+                        let record = make_record::<B>(cursor.key(), cursor.val());
+                        for output in derive(cursor.key(), cursor.val()) {
+                            provenance.record(&node, operator, output, &input, &record);
+                        }
+                        cursor.step_val();
+                    }
+                    cursor.step_key();
+                }
+            });
+        }
+    }
+}
+
+impl<P, I1> Stream<Circuit<P>, I1>
+where
+    P: Clone + 'static,
+    I1: IndexedZSet,
+{
+    /// Record lineage of the output of a join of sharded streams `self` and
+    /// `other`.
+    ///
+    /// Whenever a key changes in either input, applies `join_func` to all
+    /// pairs of values with this key accumulated so far and records that each
+    /// output record was derived from both input records.  Input records are
+    /// attributed to `left_node` and `right_node`, the streams that `self`
+    /// and `other` were sharded from, since exchange operators do not record
+    /// lineage.
+    pub(crate) fn record_join_lineage<I2, Z, F, It>(
+        &self,
+        other: &Stream<Circuit<P>, I2>,
+        left_node: &GlobalNodeId,
+        right_node: &GlobalNodeId,
+        output: &GlobalNodeId,
+        join_func: F,
+    ) where
+        I2: IndexedZSet<Key = I1::Key>,
+        Z: BatchReader,
+        F: Fn(&I1::Key, &I1::Val, &I2::Val) -> It + 'static,
+        It: IntoIterator<Item = (Z::Key, Z::Val)>,
+    {
+        if let Some(provenance) = self.circuit().provenance() {
+            let node = output.clone();
+            let left_node = left_node.clone();
+            let right_node = right_node.clone();
+            let left_integral = RefCell::new(I1::zero());
+            let right_integral = RefCell::new(I2::zero());
+
+            self.apply2(other, move |left_delta, right_delta| {
+                left_integral.borrow_mut().add_assign_by_ref(left_delta);
+                right_integral.borrow_mut().add_assign_by_ref(right_delta);
+
+                let mut keys = BTreeSet::new();
+                let mut cursor = left_delta.cursor();
+                while cursor.key_valid() {
+                    keys.insert(cursor.key().clone());
+                    cursor.step_key();
+                }
+                let mut cursor = right_delta.cursor();
+                while cursor.key_valid() {
+                    keys.insert(cursor.key().clone());
+                    cursor.step_key();
+                }
+
+                let left_integral = left_integral.borrow();
+                let right_integral = right_integral.borrow();
+                let mut left_cursor = left_integral.cursor();
+                let mut right_cursor = right_integral.cursor();
+
+                for key in keys.iter() {
+                    left_cursor.seek_key(key);
+                    right_cursor.seek_key(key);
+                    if !(left_cursor.key_valid()
+                        && left_cursor.key() == key
+                        && right_cursor.key_valid()
+                        && right_cursor.key() == key)
+                    {
+                        continue;
+                    }
+
+                    let mut right_vals = Vec::new();
+                    while right_cursor.val_valid() {
+                        let val = right_cursor.val().clone();
+                        right_vals.push((make_record::<I2>(key, &val), val));
+                        right_cursor.step_val();
+                    }
+
+                    while left_cursor.val_valid() {
+                        let left_val = left_cursor.val();
+                        let left_record = make_record::<I1>(key, left_val);
+
+                        for (right_record, right_val) in right_vals.iter() {
+                            for (k, v) in join_func(key, left_val, right_val) {
+                                let record = record_id::<Z>(&k, &v);
+                                provenance.record(&node, "Join", record, &left_node, &left_record);
+                                provenance.record(&node, "Join", record, &right_node, right_record);
+                            }
+                        }
+                        left_cursor.step_val();
+                    }
+                }
+            });
+        }
+    }
+
+    /// Record lineage of `output`, computed by aggregating sharded stream
+    /// `self`: each aggregate value inserted into `output` is derived from all
+    /// values with the same key accumulated in `self` so far.  Input records
+    /// are attributed to `input`, the stream that `self` was sharded from.
+    pub(crate) fn record_aggregate_lineage<O>(
+        &self,
+        input: &GlobalNodeId,
+        output: &Stream<Circuit<P>, O>,
+    ) where
+        O: BatchReader<Key = I1::Key, Time = ()> + Clone + 'static,
+        O::R: ZRingValue,
+    {
+        if let Some(provenance) = self.circuit().provenance() {
+            let node = output.origin_node_id().clone();
+            let input = input.clone();
+            let integral = RefCell::new(I1::zero());
+
+            self.apply2(output, move |delta, output| {
+                integral.borrow_mut().add_assign_by_ref(delta);
+
+                let integral = integral.borrow();
+                let mut input_cursor = integral.cursor();
+                let mut output_cursor = output.cursor();
+
+                while output_cursor.key_valid() {
+                    let key = output_cursor.key();
+                    input_cursor.seek_key(key);
+
+                    if input_cursor.key_valid() && input_cursor.key() == key {
+                        let mut inputs = Vec::new();
+                        while input_cursor.val_valid() {
+                            inputs.push(make_record::<I1>(key, input_cursor.val()));
+                            input_cursor.step_val();
+                        }
+
+                        while output_cursor.val_valid() {
+                            // Retractions of old aggregate values were recorded when
+                            // the values were inserted.
+                            if output_cursor.weight().ge0() {
+                                let record = record_id::<O>(key, output_cursor.val());
+                                for input_record in inputs.iter() {
+                                    provenance.record(
+                                        &node,
+                                        "Aggregate",
+                                        record,
+                                        &input,
+                                        input_record,
+                                    );
+                                }
+                            }
+                            output_cursor.step_val();
+                        }
+                    }
+                    output_cursor.step_key();
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{lineage, LineageEdge};
+    use crate::circuit::{GlobalNodeId, NodeId};
+
+    fn edge(node: usize, output: u64, input_node: usize, input: u64) -> LineageEdge {
+        LineageEdge {
+            node: GlobalNodeId::root().child(NodeId::new(node)),
+            operator: "Map",
+            output,
+            input_node: GlobalNodeId::root().child(NodeId::new(input_node)),
+            input,
+            input_label: input.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_lineage_traversal() {
+        // 0 --Map--> 1 --Map--> 2; record 3 also flows from 1 to 3 unmodified.
+        let edges = vec![
+            edge(1, 2, 0, 1),
+            edge(2, 3, 1, 2),
+            edge(1, 3, 0, 2),
+            edge(3, 3, 1, 3),
+        ];
+
+        let mut lineage = lineage(edges, 3);
+        lineage.sort_by(|l1, l2| l1.record.cmp(&l2.record));
+
+        assert_eq!(lineage.len(), 2);
+        assert_eq!(lineage[0].record, "1");
+        assert_eq!(lineage[0].path.len(), 2);
+        assert_eq!(lineage[1].record, "2");
+        assert_eq!(lineage[1].path.len(), 2);
+        assert_eq!(
+            lineage[1].path.last().unwrap().0,
+            GlobalNodeId::root().child(NodeId::new(3))
+        );
+    }
+}
@@ -336,6 +336,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn is_stateful(&self) -> bool {
+        true
+    }
 }
 
 impl<TS, V, Z, IT, OT, Agg, O> TernaryOperator<Z, IT, OT, O>
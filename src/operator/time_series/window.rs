@@ -3,9 +3,10 @@
 use crate::{
     algebra::{IndexedZSet, NegByRef},
     circuit::{
-        operator_traits::{Operator, TernaryOperator},
+        operator_traits::{Operator, OperatorState, StatePartitioner, TernaryOperator},
         Circuit, OwnershipPreference, Scope, Stream,
     },
+    operator::trace::{replicate_state, restore_replicated},
     trace::{cursor::Cursor, ord::OrdZSet, Batch, BatchReader, Spine},
 };
 use std::{borrow::Cow, cmp::max, marker::PhantomData};
@@ -115,6 +116,20 @@ where
         // Do we have meaningful examples of using windows inside nested scopes?
         panic!("'Window' operator used in fixedpoint iteration")
     }
+
+    fn state_partitioner(&self) -> Option<StatePartitioner> {
+        Some(replicate_state::<Option<(B::Key, B::Key)>>())
+    }
+
+    fn take_state(&mut self) -> Option<OperatorState> {
+        Some(Box::new(self.window.take()))
+    }
+
+    fn restore_state(&mut self, states: Vec<OperatorState>) {
+        if let Some(window) = restore_replicated(states) {
+            self.window = window;
+        }
+    }
 }
 
 impl<B> TernaryOperator<Spine<B>, B, (B::Key, B::Key), OrdZSet<B::Val, B::R>> for Window<B>
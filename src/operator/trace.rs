@@ -1,15 +1,18 @@
 use crate::{
     circuit::{
         metadata::{MetaItem, OperatorMeta},
-        operator_traits::{BinaryOperator, Operator, StrictOperator, StrictUnaryOperator},
+        operator_traits::{
+            BinaryOperator, Operator, OperatorState, StatePartitioner, StrictOperator,
+            StrictUnaryOperator,
+        },
         Circuit, ExportId, ExportStream, GlobalNodeId, OwnershipPreference, Scope, Stream,
     },
-    circuit_cache_key,
+    circuit_cache_key, default_hash,
     trace::{cursor::Cursor, Batch, BatchReader, Builder, Spine, Trace},
     Timestamp,
 };
 use size_of::SizeOf;
-use std::{borrow::Cow, marker::PhantomData};
+use std::{borrow::Cow, collections::BTreeMap, hash::Hash, marker::PhantomData, sync::Arc};
 
 circuit_cache_key!(TraceId<B, D>(GlobalNodeId => Stream<B, D>));
 circuit_cache_key!(DelayedTraceId<B, D>(GlobalNodeId => Stream<B, D>));
@@ -17,6 +20,45 @@ circuit_cache_key!(IntegrateTraceId<B, D>(GlobalNodeId => Stream<B, D>));
 
 // TODO: add infrastructure to compact the trace during slack time.
 
+/// Assigns keys of a trace to workers when the trace is redistributed across
+/// a different number of workers after rescaling:  `partitioner(key,
+/// num_workers)` returns the index of the worker that stores `key`.
+pub type KeyPartitioner<K> = fn(&K, usize) -> usize;
+
+/// Key partitioning used by [`Stream::shard`].
+pub(crate) fn shard_partitioner<K>(key: &K, num_workers: usize) -> usize
+where
+    K: Hash,
+{
+    default_hash(key) as usize % num_workers
+}
+
+/// State partitioner for operators whose state is identical in all workers:
+/// sends a copy of the state to each worker.
+pub(crate) fn replicate_state<S>() -> StatePartitioner
+where
+    S: Clone + Send + 'static,
+{
+    Arc::new(|_worker: usize, state: OperatorState, num_workers: usize| {
+        let state = *state.downcast::<S>().expect("operator state type mismatch");
+        (0..num_workers)
+            .map(|_| Box::new(state.clone()) as OperatorState)
+            .collect()
+    })
+}
+
+/// Restore state replicated by [`replicate_state`].  Returns `None` if no
+/// state was received.
+pub(crate) fn restore_replicated<S>(states: Vec<OperatorState>) -> Option<S>
+where
+    S: Ord + 'static,
+{
+    states
+        .into_iter()
+        .map(|state| *state.downcast::<S>().expect("operator state type mismatch"))
+        .max()
+}
+
 /// Add `timestamp` to all tuples in the input batch.
 ///
 /// Given an input batch without timing information (`BatchReader::Time = ()`),
@@ -74,8 +116,12 @@ where
                 let circuit = self.circuit();
 
                 circuit.region("trace", || {
+                    let mut z1trace = Z1Trace::new(false, circuit.root_scope());
+                    if self.has_sharded_version() {
+                        z1trace = z1trace.with_partitioner(shard_partitioner);
+                    }
                     let (ExportStream { local, export }, z1feedback) =
-                        circuit.add_feedback_with_export(Z1Trace::new(false, circuit.root_scope()));
+                        circuit.add_feedback_with_export(z1trace);
                     let trace = circuit.add_binary_operator_with_preference(
                         <TraceAppend<T, B>>::new(),
                         (&local, OwnershipPreference::STRONGLY_PREFER_OWNED),
@@ -114,8 +160,12 @@ where
                 let circuit = self.circuit();
 
                 circuit.region("integrate_trace", || {
+                    let mut z1trace = Z1Trace::new(true, circuit.root_scope());
+                    if self.has_sharded_version() {
+                        z1trace = z1trace.with_partitioner(shard_partitioner);
+                    }
                     let (ExportStream { local, export }, z1feedback) =
-                        circuit.add_feedback_with_export(Z1Trace::new(true, circuit.root_scope()));
+                        circuit.add_feedback_with_export(z1trace);
 
                     let trace = circuit.add_binary_operator_with_preference(
                         UntimedTraceAppend::<Spine<B>>::new(),
@@ -201,6 +251,10 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn preserves_sharding(&self) -> bool {
+        true
+    }
 }
 
 impl<T> BinaryOperator<T, T::Batch, T> for UntimedTraceAppend<T>
@@ -280,6 +334,24 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn preserves_sharding(&self) -> bool {
+        true
+    }
+
+    fn state_partitioner(&self) -> Option<StatePartitioner> {
+        Some(replicate_state::<T::Time>())
+    }
+
+    fn take_state(&mut self) -> Option<OperatorState> {
+        Some(Box::new(self.time.clone()))
+    }
+
+    fn restore_state(&mut self, states: Vec<OperatorState>) {
+        if let Some(time) = restore_replicated(states) {
+            self.time = time;
+        }
+    }
 }
 
 impl<T, B> BinaryOperator<T, B, T> for TraceAppend<T, B>
@@ -329,6 +401,10 @@ pub struct Z1Trace<T: Trace> {
     dirty: Vec<bool>,
     root_scope: Scope,
     reset_on_clock_start: bool,
+    // Assigns keys to workers when the circuit is rescaled.  `None` if the
+    // trace is not partitioned by key, in which case the entire contents of
+    // the trace in worker `i` goes to worker `i % num_workers`.
+    partitioner: Option<KeyPartitioner<T::Key>>,
 }
 
 impl<T> Z1Trace<T>
@@ -342,10 +418,27 @@ where
             dirty: vec![false; root_scope as usize + 1],
             root_scope,
             reset_on_clock_start,
+            partitioner: None,
         }
     }
+
+    /// Partition the trace across workers using `partitioner` when the
+    /// circuit is rescaled.  Must match the partitioning of the stream
+    /// that the trace is built from.
+    pub fn with_partitioner(mut self, partitioner: KeyPartitioner<T::Key>) -> Self {
+        self.partitioner = Some(partitioner);
+        self
+    }
 }
 
+/// Contents of a trace as a flat list of updates.
+type TraceTuples<T> = Vec<(
+    <T as BatchReader>::Key,
+    <T as BatchReader>::Val,
+    <T as BatchReader>::Time,
+    <T as BatchReader>::R,
+)>;
+
 impl<T> Operator for Z1Trace<T>
 where
     T: Trace,
@@ -397,6 +490,80 @@ where
     fn fixedpoint(&self, scope: Scope) -> bool {
         !self.dirty[scope as usize]
     }
+
+    fn preserves_sharding(&self) -> bool {
+        true
+    }
+
+    fn state_partitioner(&self) -> Option<StatePartitioner> {
+        let key_partitioner = self.partitioner;
+
+        let partitioner: StatePartitioner = Arc::new(
+            move |worker: usize, state: OperatorState, num_workers: usize| {
+                let tuples = *state
+                    .downcast::<TraceTuples<T>>()
+                    .expect("operator state type mismatch");
+                let mut parts: Vec<TraceTuples<T>> = (0..num_workers).map(|_| Vec::new()).collect();
+
+                match key_partitioner {
+                    Some(key_partitioner) => {
+                        for tuple in tuples {
+                            parts[key_partitioner(&tuple.0, num_workers)].push(tuple);
+                        }
+                    }
+                    None => parts[worker % num_workers] = tuples,
+                }
+
+                parts
+                    .into_iter()
+                    .map(|part| Box::new(part) as OperatorState)
+                    .collect()
+            },
+        );
+
+        Some(partitioner)
+    }
+
+    fn take_state(&mut self) -> Option<OperatorState> {
+        let trace = self.trace.replace(T::new(None))?;
+
+        let mut tuples: TraceTuples<T> = Vec::new();
+        let mut cursor = trace.cursor();
+        while cursor.key_valid() {
+            while cursor.val_valid() {
+                let key = cursor.key().clone();
+                let val = cursor.val().clone();
+                cursor.map_times(|time, weight| {
+                    tuples.push((key.clone(), val.clone(), time.clone(), weight.clone()))
+                });
+                cursor.step_val();
+            }
+            cursor.step_key();
+        }
+
+        Some(Box::new(tuples))
+    }
+
+    fn restore_state(&mut self, states: Vec<OperatorState>) {
+        let mut batches: BTreeMap<T::Time, Vec<_>> = BTreeMap::new();
+
+        for state in states {
+            let tuples = *state
+                .downcast::<TraceTuples<T>>()
+                .expect("operator state type mismatch");
+            for (key, val, time, weight) in tuples {
+                batches
+                    .entry(time)
+                    .or_default()
+                    .push((<T::Batch as Batch>::item_from(key, val), weight));
+            }
+        }
+
+        let trace = self.trace.get_or_insert_with(|| T::new(None));
+        for (time, tuples) in batches {
+            trace.insert(<T::Batch as Batch>::from_tuples(time, tuples));
+        }
+    }
 }
 
 impl<T> StrictOperator<T> for Z1Trace<T>
@@ -1,17 +1,29 @@
 use crate::{
     algebra::{AddAssignByRef, HasOne, HasZero, PartialOrder, ZRingValue},
     circuit::{
-        operator_traits::{BinaryOperator, Operator},
+        operator_traits::{BinaryOperator, Operator, OperatorState, StatePartitioner},
         ExportId, ExportStream, OwnershipPreference, Scope,
     },
-    operator::trace::{DelayedTraceId, TraceAppend, TraceId, Z1Trace},
+    default_hash,
+    operator::trace::{
+        replicate_state, restore_replicated, shard_partitioner, DelayedTraceId, TraceAppend,
+        TraceId, Z1Trace,
+    },
     trace::{
         consolidation::consolidate, cursor::Cursor, Batch, BatchReader, Builder, Spine, Trace,
     },
     utils::VecExt,
     Circuit, DBData, DBTimestamp, Stream, Timestamp,
 };
-use std::{borrow::Cow, marker::PhantomData, ops::Neg};
+use std::{borrow::Cow, hash::Hash, marker::PhantomData, ops::Neg};
+
+/// Key partitioning used by [`UpsertHandle`](`crate::UpsertHandle`).
+fn upsert_handle_partitioner<K>(key: &K, num_workers: usize) -> usize
+where
+    K: Hash,
+{
+    default_hash(key) as u32 as usize % num_workers
+}
 
 impl<P, K, V> Stream<Circuit<P>, Vec<(K, Option<V>)>>
 where
@@ -64,8 +76,17 @@ where
         //                    z1trace             └───────┘
         // ```
         circuit.region("upsert", || {
+            // Upserts that don't come from a sharded stream are routed to workers
+            // by `UpsertHandle`.
+            let z1trace = Z1Trace::new(false, circuit.root_scope()).with_partitioner(
+                if self.has_sharded_version() {
+                    shard_partitioner
+                } else {
+                    upsert_handle_partitioner
+                },
+            );
             let (ExportStream { local, export }, z1feedback) =
-                circuit.add_feedback_with_export(Z1Trace::new(false, circuit.root_scope()));
+                circuit.add_feedback_with_export(z1trace);
             local.mark_sharded_if(self);
 
             let delta = circuit.add_binary_operator(
@@ -137,6 +158,20 @@ where
     fn fixedpoint(&self, _scope: Scope) -> bool {
         true
     }
+
+    fn state_partitioner(&self) -> Option<StatePartitioner> {
+        Some(replicate_state::<T::Time>())
+    }
+
+    fn take_state(&mut self) -> Option<OperatorState> {
+        Some(Box::new(self.time.clone()))
+    }
+
+    fn restore_state(&mut self, states: Vec<OperatorState>) {
+        if let Some(time) = restore_replicated(states) {
+            self.time = time;
+        }
+    }
 }
 
 impl<T, B> BinaryOperator<T, Vec<(T::Key, Option<T::Val>)>, B> for Upsert<T, B>
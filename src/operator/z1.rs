@@ -207,6 +207,16 @@ where
             true
         }
     }
+
+    fn preserves_sharding(&self) -> bool {
+        true
+    }
+
+    fn state_transferable(&self) -> bool {
+        // The delayed value can be of any type and cannot be partitioned
+        // across workers.
+        false
+    }
 }
 
 impl<T> UnaryOperator<T, T> for Z1<T>
@@ -374,6 +384,10 @@ where
             false
         }
     }
+
+    fn preserves_sharding(&self) -> bool {
+        true
+    }
 }
 
 impl<T> UnaryOperator<T, T> for Z1Nested<T>
@@ -8,14 +8,22 @@
 
 use crate::circuit::{trace::SchedulerEvent, Circuit, GlobalNodeId};
 use hashbrown::HashMap;
+#[cfg(feature = "with-serde")]
+use serde::Serialize;
 use std::{
     cell::RefCell,
+    collections::VecDeque,
     rc::Rc,
     time::{Duration, Instant},
 };
 
+/// Maximal number of operator evaluations retained in the timeline.  Older
+/// evaluations are discarded.
+const MAX_TIMELINE_EVENTS: usize = 1 << 20;
+
 /// Per-operator CPU profile.
 #[derive(Clone, Default)]
+#[cfg_attr(feature = "with-serde", derive(Serialize))]
 pub struct OperatorCPUProfile {
     invocations: usize,
     total_time: Duration,
@@ -35,10 +43,22 @@ impl OperatorCPUProfile {
     }
 }
 
+/// A single evaluation of an operator recorded in the profiler timeline.
+#[derive(Clone, Debug)]
+pub struct EvalEvent {
+    /// Operator or subcircuit evaluated.
+    pub node: GlobalNodeId,
+    /// Time when the evaluation started.
+    pub start: Instant,
+    /// Duration of the evaluation.
+    pub duration: Duration,
+}
+
 #[derive(Default)]
 struct CPUProfilerInner {
     start_times: HashMap<GlobalNodeId, Instant>,
     operators: HashMap<GlobalNodeId, OperatorCPUProfile>,
+    timeline: VecDeque<EvalEvent>,
 }
 
 impl CPUProfilerInner {
@@ -57,6 +77,15 @@ impl CPUProfilerInner {
                         .or_insert_with(Default::default);
                     op_profile.invocations += 1;
                     op_profile.total_time += duration;
+
+                    if self.timeline.len() == MAX_TIMELINE_EVENTS {
+                        self.timeline.pop_front();
+                    }
+                    self.timeline.push_back(EvalEvent {
+                        node: node.global_id().clone(),
+                        start: start_time,
+                        duration,
+                    });
                 };
             }
             _ => (),
@@ -98,4 +127,16 @@ impl CPUProfiler {
             None
         }
     }
+
+    /// Returns the most recent operator evaluations in the order they
+    /// completed.
+    ///
+    /// The profiler retains up to 2^20 evaluations per worker.
+    pub fn timeline(&self) -> Vec<EvalEvent> {
+        if let Ok(this) = self.0.try_borrow() {
+            this.timeline.iter().cloned().collect()
+        } else {
+            Vec::new()
+        }
+    }
 }
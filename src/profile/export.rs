@@ -0,0 +1,119 @@
+//! Machine-readable profile formats.
+
+use crate::{
+    circuit::{metadata::OperatorMeta, GlobalNodeId},
+    profile::cpu::OperatorCPUProfile,
+};
+#[cfg(feature = "with-serde")]
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Structured profile of a circuit instantiated in one worker thread.
+///
+/// Unlike the graphviz profile, which renders operator metadata as text
+/// labels, this profile keeps metadata entries typed (see
+/// [`MetaItem`](`crate::circuit::metadata::MetaItem`)), making it suitable for
+/// automated analysis, e.g., comparing profiles between builds.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "with-serde", derive(Serialize))]
+pub struct WorkerProfile {
+    /// Index of the worker thread.
+    pub worker: usize,
+    /// Operators and subcircuits sorted by id.
+    pub nodes: Vec<NodeProfile>,
+    /// Streams connecting nodes.
+    pub edges: Vec<EdgeProfile>,
+}
+
+/// Profile of an operator or subcircuit.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "with-serde", derive(Serialize))]
+pub struct NodeProfile {
+    /// Global node id, i.e., the path from the root circuit to the node.
+    pub id: Vec<usize>,
+    pub name: String,
+    /// Source location where the operator was created, if known.
+    pub location: Option<String>,
+    pub kind: NodeProfileKind,
+    pub meta: OperatorMeta,
+    /// CPU usage of the node; `None` unless CPU profiling is enabled.
+    pub cpu: Option<OperatorCPUProfile>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(Serialize))]
+#[cfg_attr(feature = "with-serde", serde(tag = "type"))]
+pub enum NodeProfileKind {
+    Operator,
+    Circuit { iterative: bool },
+}
+
+/// A stream from the output of node `from` to an input of node `to`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(Serialize))]
+pub struct EdgeProfile {
+    pub from: Vec<usize>,
+    pub to: Vec<usize>,
+}
+
+/// An event in the [Chrome trace event
+/// format](https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU),
+/// which can be loaded in `chrome://tracing` or Perfetto.
+///
+/// Each operator evaluation is reported as a complete (`"X"`) event on the
+/// timeline of the worker thread that evaluated it.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "with-serde", derive(Serialize))]
+pub struct TraceEvent {
+    pub name: String,
+    pub cat: String,
+    /// Event type.
+    pub ph: String,
+    /// Start time in microseconds.
+    pub ts: f64,
+    /// Duration in microseconds.
+    #[cfg_attr(feature = "with-serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub dur: Option<f64>,
+    pub pid: u32,
+    /// Worker index.
+    pub tid: usize,
+    pub args: BTreeMap<String, String>,
+}
+
+impl TraceEvent {
+    /// Evaluation of operator `node` by `worker`.
+    pub(super) fn eval(name: &str, node: &GlobalNodeId, worker: usize, ts: f64, dur: f64) -> Self {
+        Self {
+            name: name.to_string(),
+            cat: "operator".to_string(),
+            ph: "X".to_string(),
+            ts,
+            dur: Some(dur),
+            pid: 0,
+            tid: worker,
+            args: BTreeMap::from([("node".to_string(), node.to_string())]),
+        }
+    }
+
+    /// Metadata event that names the timeline of `worker`.
+    pub(super) fn thread_name(worker: usize) -> Self {
+        Self {
+            name: "thread_name".to_string(),
+            cat: "__metadata".to_string(),
+            ph: "M".to_string(),
+            ts: 0.0,
+            dur: None,
+            pid: 0,
+            tid: worker,
+            args: BTreeMap::from([("name".to_string(), format!("worker {worker}"))]),
+        }
+    }
+}
+
+/// Contents of a Chrome trace file.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "with-serde", derive(Serialize))]
+pub struct ChromeTrace {
+    #[cfg_attr(feature = "with-serde", serde(rename = "traceEvents"))]
+    pub trace_events: Vec<TraceEvent>,
+}
@@ -3,21 +3,28 @@
 use crate::{
     circuit::{
         circuit_builder::Node,
+        description::CircuitDescription,
         metadata::{MetaItem, OperatorMeta},
         GlobalNodeId,
     },
-    monitor::TraceMonitor,
-    Circuit,
+    monitor::{GraphNode, GraphNodeKind, TraceMonitor},
+    Circuit, Runtime,
 };
-use std::{borrow::Cow, collections::HashMap, fmt::Write};
+use std::{borrow::Cow, collections::HashMap, fmt::Write, time::Instant};
 
 mod cpu;
-pub use cpu::CPUProfiler;
+mod export;
+
+pub use cpu::{CPUProfiler, EvalEvent, OperatorCPUProfile};
+pub use export::{
+    ChromeTrace, EdgeProfile, NodeProfile, NodeProfileKind, TraceEvent, WorkerProfile,
+};
 
 /// Rudimentary circuit profiler.
 ///
 /// Records circuit topology, operator metadata, and optionally CPU usage, and
-/// dumps them in graphviz (dot) format.
+/// dumps them in graphviz (dot) format, as a structured [`WorkerProfile`], or
+/// as a timeline of [`TraceEvent`]s.
 pub struct Profiler {
     cpu_profiler: CPUProfiler,
     monitor: TraceMonitor,
@@ -48,14 +55,7 @@ impl Profiler {
 
     /// Dump profile in graphviz format.
     pub fn dump_profile(&self) -> String {
-        let mut metadata = HashMap::<GlobalNodeId, OperatorMeta>::new();
-
-        // Collect node metadata.
-        self.circuit.map_nodes_recursive(&mut |node: &dyn Node| {
-            let mut meta = OperatorMeta::new();
-            node.metadata(&mut meta);
-            metadata.insert(node.global_id().clone(), meta);
-        });
+        let mut metadata = self.metadata();
 
         // Add CPU profiling info.
         for (node_id, meta) in metadata.iter_mut() {
@@ -92,4 +92,108 @@ impl Profiler {
 
         graph.to_dot()
     }
+
+    /// Operators and subcircuits of the circuit along with stream edges
+    /// between them (see [`TraceMonitor::graph`]).
+    pub fn circuit_graph(&self) -> (Vec<GraphNode>, Vec<(GlobalNodeId, GlobalNodeId)>) {
+        self.monitor.graph()
+    }
+
+    /// Description of the circuit (see [`TraceMonitor::description`]).
+    pub fn description(&self) -> CircuitDescription {
+        self.monitor.description(&self.circuit)
+    }
+
+    /// Structured profile of the circuit in the current worker.
+    pub fn profile(&self) -> WorkerProfile {
+        let mut metadata = self.metadata();
+        let (nodes, edges) = self.monitor.graph();
+
+        let nodes = nodes
+            .into_iter()
+            .map(|node| NodeProfile {
+                id: node_path(&node.id),
+                name: node.name.to_string(),
+                location: node.location.map(|location| {
+                    format!(
+                        "{}:{}:{}",
+                        location.file(),
+                        location.line(),
+                        location.column()
+                    )
+                }),
+                kind: match node.kind {
+                    GraphNodeKind::Operator => NodeProfileKind::Operator,
+                    GraphNodeKind::Circuit { iterative } => NodeProfileKind::Circuit { iterative },
+                },
+                meta: metadata.remove(&node.id).unwrap_or_default(),
+                cpu: self.cpu_profiler.operator_profile(&node.id),
+            })
+            .collect();
+
+        let edges = edges
+            .iter()
+            .map(|(from, to)| EdgeProfile {
+                from: node_path(from),
+                to: node_path(to),
+            })
+            .collect();
+
+        WorkerProfile {
+            worker: Runtime::worker_index(),
+            nodes,
+            edges,
+        }
+    }
+
+    /// Operator evaluations recorded by the CPU profiler in Chrome trace
+    /// format.
+    ///
+    /// Timestamps are relative to `epoch`, which should be shared by all
+    /// workers so that their timelines line up.  Returns only the metadata
+    /// event naming the worker's timeline unless CPU profiling is enabled.
+    pub fn trace_events(&self, epoch: Instant) -> Vec<TraceEvent> {
+        let worker = Runtime::worker_index();
+        let names: HashMap<GlobalNodeId, Cow<'static, str>> = self
+            .monitor
+            .graph()
+            .0
+            .into_iter()
+            .map(|node| (node.id, node.name))
+            .collect();
+
+        let mut events = vec![TraceEvent::thread_name(worker)];
+        for event in self.cpu_profiler.timeline() {
+            let name = names
+                .get(&event.node)
+                .map(|name| name.as_ref())
+                .unwrap_or("circuit");
+            events.push(TraceEvent::eval(
+                name,
+                &event.node,
+                worker,
+                event.start.saturating_duration_since(epoch).as_secs_f64() * 1e6,
+                event.duration.as_secs_f64() * 1e6,
+            ));
+        }
+
+        events
+    }
+
+    /// Collect metadata of all nodes in the circuit.
+    fn metadata(&self) -> HashMap<GlobalNodeId, OperatorMeta> {
+        let mut metadata = HashMap::<GlobalNodeId, OperatorMeta>::new();
+
+        self.circuit.map_nodes_recursive(&mut |node: &dyn Node| {
+            let mut meta = OperatorMeta::new();
+            node.metadata(&mut meta);
+            metadata.insert(node.global_id().clone(), meta);
+        });
+
+        metadata
+    }
+}
+
+fn node_path(id: &GlobalNodeId) -> Vec<usize> {
+    id.path().iter().map(|node_id| node_id.id()).collect()
 }
@@ -0,0 +1,316 @@
+//! Differential testing of incremental circuits.
+//!
+//! DBSP operators compute incremental updates: at each clock cycle, an
+//! operator receives changes to its inputs and outputs the corresponding
+//! change to its output.  A correct incremental query produces the same
+//! results as evaluating the query from scratch over the integral of its
+//! inputs, regardless of the number of workers and of how the input changes
+//! are split into clock cycles.
+//!
+//! [`DifferentialTest`] checks this property for a query supplied as a
+//! circuit constructor:
+//!
+//! * For each input change sequence produced by a `proptest` strategy, e.g.,
+//!   [`zset_changes`], the query is evaluated incrementally with each
+//!   configured number of workers and with several splittings of the input
+//!   sequence into clock cycles: as generated, one update per clock cycle, and
+//!   all updates in a single clock cycle.
+//!
+//! * After each input step, the integral of the outputs of the incremental
+//!   circuit is compared with the output of a fresh single-worker circuit fed
+//!   with the integral of all inputs so far in one clock cycle.
+//!
+//! * On failure, `proptest` shrinks the input sequence to a minimal failing
+//!   example.
+//!
+//! ```ignore
+//! use dbsp::testing::{zset_changes, DifferentialTest};
+//!
+//! DifferentialTest::new(zset_changes(0..10u64, 5, 10), |input| input.distinct())
+//!     .workers(&[1, 4])
+//!     .cases(16)
+//!     .run();
+//! ```
+//!
+//! The query has a single Z-set input.  Queries over multiple relations can
+//! tag records with the relation they belong to, e.g., using a tuple or an
+//! enum as the key type, and split the input stream using
+//! [`filter`](`crate::Stream::filter`) and [`map`](`crate::Stream::map`).
+//!
+//! This module is available with the `testing` feature.
+
+use crate::{
+    algebra::AddByRef, trace::Batch, CollectionHandle, DBData, OrdZSet, OutputHandle, Runtime,
+    Stream,
+};
+use proptest::{
+    collection,
+    prelude::*,
+    test_runner::{TestError, TestRunner},
+};
+use std::fmt::Debug;
+
+type RootCircuit = crate::Circuit<()>;
+
+/// A sequence of changes to a Z-set, one vector of `(key, weight)` updates
+/// per step.
+pub type ZSetChanges<K> = Vec<Vec<(K, isize)>>;
+
+/// A strategy that generates up to `max_steps` steps, each containing up to
+/// `max_updates` updates with keys drawn from `key` and weights in `-2..=2`.
+pub fn zset_changes<K, S>(
+    key: S,
+    max_steps: usize,
+    max_updates: usize,
+) -> impl Strategy<Value = ZSetChanges<K>>
+where
+    K: Debug,
+    S: Strategy<Value = K>,
+{
+    collection::vec(
+        collection::vec((key, -2..=2isize), 0..=max_updates),
+        0..=max_steps,
+    )
+}
+
+/// Differential test comparing incremental evaluation of a query with its
+/// from-scratch evaluation over integrated inputs (see the
+/// [module documentation](`self`)).
+pub struct DifferentialTest<S, F> {
+    strategy: S,
+    query: F,
+    workers: Vec<usize>,
+    config: ProptestConfig,
+}
+
+impl<S, F> DifferentialTest<S, F> {
+    /// Create a differential test for `query` on input sequences generated by
+    /// `strategy`.
+    ///
+    /// `query` builds the circuit under test: it takes the input stream and
+    /// returns the output stream.  It is invoked once per worker for each
+    /// tested circuit.
+    ///
+    /// By default, the test evaluates the query with 1, 2 and 4 workers on 32
+    /// generated input sequences.
+    pub fn new<K, O>(strategy: S, query: F) -> Self
+    where
+        S: Strategy<Value = ZSetChanges<K>>,
+        F: Fn(&Stream<RootCircuit, OrdZSet<K, isize>>) -> Stream<RootCircuit, O>,
+    {
+        Self {
+            strategy,
+            query,
+            workers: vec![1, 2, 4],
+            config: ProptestConfig::with_cases(32),
+        }
+    }
+
+    /// Numbers of workers to evaluate the incremental circuit with.
+    pub fn workers(mut self, workers: &[usize]) -> Self {
+        self.workers = workers.to_vec();
+        self
+    }
+
+    /// Number of input sequences to generate.
+    pub fn cases(mut self, cases: u32) -> Self {
+        self.config.cases = cases;
+        self
+    }
+
+    /// Use custom `proptest` configuration.
+    pub fn config(mut self, config: ProptestConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Run the test.
+    ///
+    /// # Panics
+    ///
+    /// Panics with the minimal failing input sequence found by `proptest` if
+    /// the incremental and from-scratch results differ for any input.
+    pub fn run<K, O>(&self)
+    where
+        S: Strategy<Value = ZSetChanges<K>>,
+        F: Fn(&Stream<RootCircuit, OrdZSet<K, isize>>) -> Stream<RootCircuit, O>
+            + Clone
+            + Send
+            + 'static,
+        K: DBData,
+        O: Batch<Time = ()> + AddByRef + Debug + Send,
+    {
+        let mut runner = TestRunner::new(self.config.clone());
+
+        match runner.run(&self.strategy, |changes| self.check(&changes)) {
+            Ok(()) => {}
+            Err(TestError::Fail(reason, changes)) => {
+                panic!("differential test failed: {reason}\nminimal failing input: {changes:?}")
+            }
+            Err(TestError::Abort(reason)) => panic!("differential test aborted: {reason}"),
+        }
+    }
+
+    /// Check a single input sequence, e.g., a regression case found by
+    /// [`Self::run`].
+    pub fn check<K, O>(&self, changes: &[Vec<(K, isize)>]) -> Result<(), TestCaseError>
+    where
+        F: Fn(&Stream<RootCircuit, OrdZSet<K, isize>>) -> Stream<RootCircuit, O>
+            + Clone
+            + Send
+            + 'static,
+        K: DBData,
+        O: Batch<Time = ()> + AddByRef + Debug + Send,
+    {
+        // Expected output after each input step.
+        let expected = (0..changes.len())
+            .map(|step| {
+                let integral = changes[..=step].iter().flatten().cloned().collect();
+                self.evaluate(1, vec![(integral, Some(step))])
+                    .map(|mut outputs| outputs.pop().unwrap().1)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for &workers in self.workers.iter() {
+            for (name, schedule) in schedules(changes) {
+                for (step, output) in self.evaluate(workers, schedule)? {
+                    prop_assert_eq!(
+                        &output,
+                        &expected[step],
+                        "incremental output after input step {} ({} workers, {}) differs from \
+                         from-scratch evaluation",
+                        step,
+                        workers,
+                        name
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Evaluate the query with `workers` workers, running one clock cycle
+    /// for each element of `schedule`.  Returns the integral of outputs after
+    /// each clock cycle that completes an input step, labeled with the index
+    /// of the step.
+    fn evaluate<K, O>(
+        &self,
+        workers: usize,
+        schedule: Schedule<K>,
+    ) -> Result<Vec<(usize, O)>, TestCaseError>
+    where
+        F: Fn(&Stream<RootCircuit, OrdZSet<K, isize>>) -> Stream<RootCircuit, O>
+            + Clone
+            + Send
+            + 'static,
+        K: DBData,
+        O: Batch<Time = ()> + AddByRef + Debug + Send,
+    {
+        let query = self.query.clone();
+        let (mut circuit, (mut input_handle, output_handle)): (
+            _,
+            (CollectionHandle<K, isize>, OutputHandle<O>),
+        ) = Runtime::init_circuit(workers, move |circuit| {
+            let (input, input_handle) = circuit.add_input_zset::<K, isize>();
+            (input_handle, query(&input).output())
+        })
+        .map_err(|e| TestCaseError::fail(format!("failed to create circuit: {e}")))?;
+
+        let mut integral = O::empty(());
+        let mut outputs = Vec::new();
+
+        for (mut updates, step) in schedule {
+            input_handle.append(&mut updates);
+            circuit
+                .step()
+                .map_err(|e| TestCaseError::fail(format!("circuit failed: {e}")))?;
+            integral = integral.add_by_ref(&output_handle.consolidate());
+
+            if let Some(step) = step {
+                outputs.push((step, integral.clone()));
+            }
+        }
+
+        circuit
+            .kill()
+            .map_err(|_| TestCaseError::fail("worker thread panicked"))?;
+
+        Ok(outputs)
+    }
+}
+
+/// A sequence of clock cycles, each labeled with the index of the input step
+/// completed by the clock cycle, if any.
+type Schedule<K> = Vec<(Vec<(K, isize)>, Option<usize>)>;
+
+/// Splittings of `changes` into clock cycles.
+fn schedules<K: Clone>(changes: &[Vec<(K, isize)>]) -> Vec<(&'static str, Schedule<K>)> {
+    let as_generated = changes
+        .iter()
+        .enumerate()
+        .map(|(step, updates)| (updates.clone(), Some(step)))
+        .collect();
+
+    let mut one_update_per_step = Vec::new();
+    for (step, updates) in changes.iter().enumerate() {
+        match updates.split_last() {
+            None => one_update_per_step.push((Vec::new(), Some(step))),
+            Some((last, init)) => {
+                one_update_per_step.extend(init.iter().map(|update| (vec![update.clone()], None)));
+                one_update_per_step.push((vec![last.clone()], Some(step)));
+            }
+        }
+    }
+
+    let mut schedules = vec![
+        ("as generated", as_generated),
+        ("one update per step", one_update_per_step),
+    ];
+    if !changes.is_empty() {
+        schedules.push((
+            "single step",
+            vec![(
+                changes.iter().flatten().cloned().collect(),
+                Some(changes.len() - 1),
+            )],
+        ));
+    }
+
+    schedules
+}
+
+#[cfg(test)]
+mod test {
+    use super::{zset_changes, DifferentialTest};
+    use crate::operator::Max;
+
+    #[test]
+    fn test_distinct() {
+        DifferentialTest::new(zset_changes(0..10u64, 5, 10), |input| input.distinct())
+            .cases(16)
+            .run();
+    }
+
+    #[test]
+    fn test_join_aggregate() {
+        DifferentialTest::new(zset_changes((0..5u64, 0..5u64), 4, 6), |input| {
+            let indexed = input.index_with(|&(k, v)| (k % 3, v));
+            indexed
+                .join::<(), _, _, _>(&indexed, |&k, &v1, &v2| (k, v1 + v2))
+                .index()
+                .aggregate::<(), _>(Max)
+        })
+        .cases(16)
+        .run();
+    }
+
+    #[test]
+    fn test_detects_non_incremental_query() {
+        // Integrating the input is not an incremental query: the incremental
+        // circuit outputs the integral of the integral of the input.
+        let test = DifferentialTest::new(zset_changes(0..10u64, 5, 10), |input| input.integrate());
+        assert!(test.check(&[vec![(1, 1)]]).is_ok());
+        assert!(test.check(&[vec![(1, 1)], vec![(2, 1)]]).is_err());
+    }
+}
@@ -13,8 +13,15 @@ mod tests;
 #[doc(hidden)]
 pub mod utils;
 
-use crate::algebra::{AddAssignByRef, HasZero, MonoidValue};
+use crate::{
+    algebra::{AddAssignByRef, HasZero, MonoidValue},
+    circuit::Runtime,
+};
+use rayon::slice::ParallelSliceMut;
 use std::{mem::replace, ops::AddAssign, ptr};
+
+/// Minimal length of a vector sorted in parallel by [`par_consolidate`].
+const PAR_CONSOLIDATE_MIN_LEN: usize = 1 << 16;
 use utils::{dedup_starting_at, retain_starting_at, shuffle_by_indices};
 
 /// Sorts and consolidates `vec`.
@@ -32,6 +39,34 @@ where
     }
 
     vec.sort_unstable_by(|(key1, _), (key2, _)| key1.cmp(key2));
+    consolidate_sorted(vec);
+}
+
+/// Sorts and consolidates `vec`, using the thread pool of the current
+/// runtime to sort large vectors (see
+/// [`Runtime::run_with_thread_pool`](`crate::Runtime::run_with_thread_pool`)).
+///
+/// Produces the same result as [`consolidate`].
+pub fn par_consolidate<T, R>(vec: &mut Vec<(T, R)>)
+where
+    T: Ord + Send,
+    R: MonoidValue + Send,
+{
+    if vec.len() < PAR_CONSOLIDATE_MIN_LEN || Runtime::thread_pool_size() == 0 {
+        consolidate(vec);
+        return;
+    }
+
+    Runtime::install(|| vec.par_sort_unstable_by(|(key1, _), (key2, _)| key1.cmp(key2)));
+    consolidate_sorted(vec);
+}
+
+/// Consolidates a sorted `vec`.
+fn consolidate_sorted<T, R>(vec: &mut Vec<(T, R)>)
+where
+    T: Ord,
+    R: MonoidValue,
+{
     // TODO: Combine the `.dedup_by()` and `.retain()` calls together
     vec.dedup_by(|(key1, data1), (key2, data2)| {
         if key1 == key2 {
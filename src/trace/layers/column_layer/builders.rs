@@ -37,6 +37,13 @@ impl<K, R> ColumnLayerBuilder<K, R> {
     }
 }
 
+impl<K, R> From<ColumnLayer<K, R>> for ColumnLayerBuilder<K, R> {
+    fn from(layer: ColumnLayer<K, R>) -> Self {
+        let (keys, diffs) = layer.into_parts();
+        Self { keys, diffs }
+    }
+}
+
 impl<K, R> Builder for ColumnLayerBuilder<K, R>
 where
     K: Ord + Clone,
@@ -10,11 +10,13 @@ pub mod column_layer;
 pub mod ordered;
 pub mod ordered_leaf;
 pub mod unordered;
+mod parallel;
 // pub mod hashed;
 // pub mod weighted;
 // pub mod unordered;
 
 pub use advance::{advance, advance_erased, advance_raw};
+pub(crate) use parallel::{par_merge_column_layers, par_merge_ordered_layers};
 
 use crate::algebra::HasZero;
 use size_of::SizeOf;
@@ -209,6 +211,8 @@ pub trait OrdOffset:
     + TryInto<usize>
     + HasZero
     + SizeOf
+    + Send
+    + Sync
     + Sized
     + 'static
 {
@@ -227,6 +231,8 @@ where
         + TryInto<usize>
         + HasZero
         + SizeOf
+        + Send
+        + Sync
         + Sized
         + 'static,
     <O as TryInto<usize>>::Error: Debug,
@@ -8,8 +8,9 @@ pub use consumer::{OrderedLayerConsumer, OrderedLayerValues};
 use crate::{
     algebra::{AddAssignByRef, AddByRef, NegByRef},
     trace::layers::{
-        advance, column_layer::ColumnLayer, Builder, Cursor, MergeBuilder, OrdOffset, Trie,
-        TupleBuilder,
+        advance,
+        column_layer::{ColumnLayer, ColumnLayerBuilder},
+        Builder, Cursor, MergeBuilder, OrdOffset, Trie, TupleBuilder,
     },
     utils::{assume, cast_uninit_vec},
     DBData, NumEntries,
@@ -294,6 +295,18 @@ pub struct OrderedBuilder<K, L, O = usize> {
     pub vals: L,
 }
 
+impl<K, V, R, O> From<OrderedLayer<K, ColumnLayer<V, R>, O>>
+    for OrderedBuilder<K, ColumnLayerBuilder<V, R>, O>
+{
+    fn from(layer: OrderedLayer<K, ColumnLayer<V, R>, O>) -> Self {
+        Self {
+            keys: layer.keys,
+            offs: layer.offs,
+            vals: layer.vals.into(),
+        }
+    }
+}
+
 impl<K, L, O> OrderedBuilder<K, L, O> {
     /// Performs one step of merging.
     pub fn merge_step(
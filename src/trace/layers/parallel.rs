@@ -0,0 +1,257 @@
+//! Parallel merging of trie layers.
+//!
+//! Merging two large layers is the most expensive part of trace maintenance.
+//! When the current runtime has a thread pool (see
+//! [`Runtime::run_with_thread_pool`]), the functions in this module split
+//! the key space of the two layers into disjoint ranges, merge the ranges in
+//! parallel, and concatenate the results.
+//!
+//! Tasks read their key ranges directly from the input layers instead of
+//! copying them.  Since the ranges are disjoint and the calling worker is
+//! blocked until all tasks complete, each key and weight is only accessed by
+//! one thread at a time, so they only need to be `Send` and the
+//! [`DBData`](`crate::DBData`) bound doesn't need to include `Sync`.
+
+use crate::{
+    algebra::{AddAssignByRef, HasZero},
+    circuit::Runtime,
+    trace::layers::{
+        column_layer::ColumnLayer, ordered::OrderedLayer, Builder, MergeBuilder, OrdOffset, Trie,
+    },
+};
+use std::ops::{AddAssign, Range};
+
+/// Minimal number of keys in a single parallel merge task.
+///
+/// Smaller merges are performed sequentially, as the cost of dispatching them
+/// to the thread pool would exceed any savings.
+const MIN_TASK_SIZE: usize = 1 << 14;
+
+/// The number of tasks to create per pool thread.  Using more tasks than
+/// threads helps balance the load when keys are not uniformly distributed
+/// between the two layers.
+const TASKS_PER_THREAD: usize = 4;
+
+/// Returns the number of tasks to split a merge of `keys` keys into, or
+/// `None` if the merge should be performed sequentially.
+fn num_tasks(keys: usize) -> Option<usize> {
+    let threads = Runtime::thread_pool_size();
+    let tasks = (threads * TASKS_PER_THREAD).min(keys / MIN_TASK_SIZE);
+
+    (tasks > 1).then_some(tasks)
+}
+
+/// Split two sorted arrays of keys into `tasks` pairs of ranges, such that
+/// all keys in the `i`th pair of ranges are smaller than keys in the
+/// `i+1`th pair.
+fn split_ranges<K>(keys1: &[K], keys2: &[K], tasks: usize) -> Vec<(Range<usize>, Range<usize>)>
+where
+    K: Ord,
+{
+    // Pick split points from the longer array.
+    let swap = keys1.len() < keys2.len();
+    let (long, short) = if swap { (keys2, keys1) } else { (keys1, keys2) };
+
+    let mut ranges = Vec::with_capacity(tasks);
+    let (mut long_start, mut short_start) = (0, 0);
+
+    for task in 1..=tasks {
+        let (long_end, short_end) = if task == tasks {
+            (long.len(), short.len())
+        } else {
+            let long_end = task * long.len() / tasks;
+            let split_key = &long[long_end];
+            (
+                long_end,
+                short_start + short[short_start..].partition_point(|key| key < split_key),
+            )
+        };
+
+        if swap {
+            ranges.push((short_start..short_end, long_start..long_end));
+        } else {
+            ranges.push((long_start..long_end, short_start..short_end));
+        }
+
+        long_start = long_end;
+        short_start = short_end;
+    }
+
+    ranges
+}
+
+/// The two layers being merged, shared by all tasks of a parallel merge.
+struct MergeInputs<'a, T>(&'a T, &'a T);
+
+impl<'a, T> MergeInputs<'a, T> {
+    // Tasks must access the layers through this method rather than the
+    // fields, so that closures capture the whole `MergeInputs` and rely on
+    // its `Sync` implementation below.
+    fn layers(&self) -> (&'a T, &'a T) {
+        (self.0, self.1)
+    }
+}
+
+// Safety: `par_merge` hands each task a disjoint pair of key ranges and
+// blocks the calling worker until all tasks complete.  Every key, value and
+// weight is therefore read by at most one thread at a time, which `Send` is
+// sufficient for.
+unsafe impl<T> Sync for MergeInputs<'_, T> where T: Send {}
+
+/// Merge `layer1` and `layer2`, whose top-level keys are `keys1` and
+/// `keys2`, in parallel.  Returns the merged parts in key order, or `None`
+/// if the merge should be performed sequentially.
+fn par_merge<T, K>(layer1: &T, layer2: &T, keys1: &[K], keys2: &[K]) -> Option<Vec<T>>
+where
+    T: Trie + Send,
+    K: Ord,
+{
+    let tasks = num_tasks(keys1.len() + keys2.len())?;
+    let ranges = split_ranges(keys1, keys2, tasks);
+    let inputs = MergeInputs(layer1, layer2);
+
+    Some(Runtime::map_parallel(ranges, |(range1, range2)| {
+        let (layer1, layer2) = inputs.layers();
+        let mut builder = T::MergeBuilder::with_key_capacity(range1.len() + range2.len());
+        builder.push_merge(
+            layer1.cursor_from(range1.start, range1.end),
+            layer2.cursor_from(range2.start, range2.end),
+        );
+        builder.done()
+    }))
+}
+
+/// Merge two column layers in parallel.
+///
+/// Returns `None` if the merge is too small to benefit from parallelism or
+/// the runtime has no thread pool, in which case the caller should fall back
+/// to sequential merging.
+pub(crate) fn par_merge_column_layers<K, R>(
+    layer1: &ColumnLayer<K, R>,
+    layer2: &ColumnLayer<K, R>,
+) -> Option<ColumnLayer<K, R>>
+where
+    K: Ord + Clone + Send,
+    R: Eq + HasZero + AddAssign + AddAssignByRef + Clone + Send,
+{
+    let parts = par_merge(layer1, layer2, &layer1.keys, &layer2.keys)?;
+
+    let mut result = ColumnLayer::with_capacity(parts.iter().map(ColumnLayer::len).sum());
+    for mut part in parts.into_iter() {
+        result.keys.append(&mut part.keys);
+        result.diffs.append(&mut part.diffs);
+    }
+
+    Some(result)
+}
+
+/// Merge two two-level ordered layers in parallel.
+///
+/// Returns `None` if the merge is too small to benefit from parallelism or
+/// the runtime has no thread pool, in which case the caller should fall back
+/// to sequential merging.
+pub(crate) fn par_merge_ordered_layers<K, V, R, O>(
+    layer1: &OrderedLayer<K, ColumnLayer<V, R>, O>,
+    layer2: &OrderedLayer<K, ColumnLayer<V, R>, O>,
+) -> Option<OrderedLayer<K, ColumnLayer<V, R>, O>>
+where
+    K: Ord + Clone + Send,
+    V: Ord + Clone + Send,
+    R: Eq + HasZero + AddAssign + AddAssignByRef + Clone + Send,
+    O: OrdOffset,
+{
+    let parts = par_merge(layer1, layer2, &layer1.keys, &layer2.keys)?;
+
+    let num_keys = parts.iter().map(|part| part.keys.len()).sum();
+    let num_vals = parts.iter().map(|part| part.vals.len()).sum();
+
+    let mut keys = Vec::with_capacity(num_keys);
+    let mut offs = Vec::with_capacity(num_keys + 1);
+    let mut vals = ColumnLayer::with_capacity(num_vals);
+    offs.push(O::zero());
+
+    for mut part in parts.into_iter() {
+        let basis = O::from_usize(vals.len());
+
+        keys.append(&mut part.keys);
+        offs.extend(part.offs.iter().skip(1).map(|off| *off + basis));
+        vals.keys.append(&mut part.vals.keys);
+        vals.diffs.append(&mut part.vals.diffs);
+    }
+
+    Some(OrderedLayer { keys, offs, vals })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{par_merge_column_layers, par_merge_ordered_layers, split_ranges};
+    use crate::{
+        circuit::Runtime,
+        trace::layers::{
+            column_layer::ColumnLayer, ordered::OrderedLayer, Builder, Trie, TupleBuilder,
+        },
+    };
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn parallel_merge() {
+        Runtime::run_with_thread_pool(1, 4, || {
+            let mut builder1 = <ColumnLayer<u64, i64> as Trie>::TupleBuilder::new();
+            let mut builder2 = <ColumnLayer<u64, i64> as Trie>::TupleBuilder::new();
+            builder1.extend_tuples((0..200_000).map(|x| (x * 2, 1)));
+            builder2.extend_tuples((0..100_000).map(|x| (x * 3, -1)));
+            let (layer1, layer2) = (builder1.done(), builder2.done());
+
+            let parallel = par_merge_column_layers(&layer1, &layer2).unwrap();
+            assert_eq!(parallel, layer1.merge(&layer2));
+
+            let mut builder1 =
+                <OrderedLayer<u64, ColumnLayer<u64, i64>> as Trie>::TupleBuilder::new();
+            let mut builder2 =
+                <OrderedLayer<u64, ColumnLayer<u64, i64>> as Trie>::TupleBuilder::new();
+            builder1.extend_tuples((0..200_000).map(|x| (x / 2, (x % 2, 1))));
+            builder2.extend_tuples((0..100_000).map(|x| (x * 3, (0, -1))));
+            let (layer1, layer2) = (builder1.done(), builder2.done());
+
+            let parallel = par_merge_ordered_layers(&layer1, &layer2).unwrap();
+            assert_eq!(parallel, layer1.merge(&layer2));
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn split_ranges_cover_inputs() {
+        let keys1: Vec<usize> = (0..1000).map(|x| x * 3).collect();
+        let keys2: Vec<usize> = (0..300).map(|x| x * 7).collect();
+
+        for tasks in 1..10 {
+            let ranges = split_ranges(&keys1, &keys2, tasks);
+            assert_eq!(ranges.len(), tasks);
+            assert_eq!(ranges[0].0.start, 0);
+            assert_eq!(ranges[0].1.start, 0);
+            assert_eq!(ranges[tasks - 1].0.end, keys1.len());
+            assert_eq!(ranges[tasks - 1].1.end, keys2.len());
+
+            for window in ranges.windows(2) {
+                let ((r1, r2), (next1, next2)) = (&window[0], &window[1]);
+                assert_eq!(r1.end, next1.start);
+                assert_eq!(r2.end, next2.start);
+
+                // All keys in the current pair of ranges are smaller than
+                // keys in the next pair.
+                let max = keys1[r1.clone()]
+                    .iter()
+                    .chain(keys2[r2.clone()].iter())
+                    .max();
+                let min = keys1[next1.clone()]
+                    .iter()
+                    .chain(keys2[next2.clone()].iter())
+                    .min();
+                if let (Some(max), Some(min)) = (max, min) {
+                    assert!(max < min);
+                }
+            }
+        }
+    }
+}
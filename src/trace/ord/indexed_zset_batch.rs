@@ -8,8 +8,8 @@ use crate::{
                 OrderedBuilder, OrderedCursor, OrderedLayer, OrderedLayerConsumer,
                 OrderedLayerValues,
             },
-            Builder as TrieBuilder, Cursor as TrieCursor, MergeBuilder, OrdOffset, Trie,
-            TupleBuilder,
+            par_merge_ordered_layers, Builder as TrieBuilder, Cursor as TrieCursor, MergeBuilder,
+            OrdOffset, Trie, TupleBuilder,
         },
         ord::merge_batcher::MergeBatcher,
         Batch, BatchReader, Builder, Consumer, Cursor, Merger, ValueConsumer,
@@ -349,9 +349,17 @@ where
         source2: &OrdIndexedZSet<K, V, R, O>,
         fuel: &mut isize,
     ) {
-        *fuel -= self
-            .result
-            .push_merge(source1.layer.cursor(), source2.layer.cursor()) as isize;
+        // Merges always run to completion, so we can hand large merges to the
+        // runtime's thread pool, if any.
+        if let Some(merged) = par_merge_ordered_layers(&source1.layer, &source2.layer) {
+            *fuel -= merged.keys() as isize;
+            self.result = merged.into();
+        } else {
+            *fuel -= self
+                .result
+                .push_merge(source1.layer.cursor(), source2.layer.cursor())
+                as isize;
+        }
         *fuel = max(*fuel, 1);
     }
 }
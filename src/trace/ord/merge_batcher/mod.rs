@@ -28,9 +28,9 @@ pub struct MergeBatcher<I: Ord, T: Ord, R: MonoidValue, B: Batch<Item = I, Time
 impl<I, T, R, B> Batcher<I, T, R, B> for MergeBatcher<I, T, R, B>
 where
     Self: SizeOf,
-    I: Ord + Clone,
+    I: Ord + Clone + Send,
     T: DBTimestamp,
-    R: MonoidValue,
+    R: MonoidValue + Send,
     B: Batch<Item = I, Time = T, R = R>,
 {
     fn new_batcher(time: T) -> Self {
@@ -152,7 +152,11 @@ impl<D: Ord, R: MonoidValue> MergeSorter<D, R> {
         empty
     }
 
-    pub fn push(&mut self, batch: &mut Vec<(D, R)>) {
+    pub fn push(&mut self, batch: &mut Vec<(D, R)>)
+    where
+        D: Send,
+        R: Send,
+    {
         // If the batch we're given is empty, do nothing
         if !batch.is_empty() {
             // TODO: Reason about possible unbounded stash growth. How to / should we return
@@ -171,7 +175,7 @@ impl<D: Ord, R: MonoidValue> MergeSorter<D, R> {
             };
 
             // Consolidate and push the batch we were given
-            consolidation::consolidate(&mut batch);
+            consolidation::par_consolidate(&mut batch);
             if !batch.is_empty() {
                 self.queue.push(vec![batch]);
 
@@ -7,7 +7,8 @@ use crate::{
                 ColumnLayer, ColumnLayerBuilder, ColumnLayerConsumer, ColumnLayerCursor,
                 ColumnLayerValues,
             },
-            Builder as TrieBuilder, Cursor as TrieCursor, MergeBuilder, Trie, TupleBuilder,
+            par_merge_column_layers, Builder as TrieBuilder, Cursor as TrieCursor, MergeBuilder,
+            Trie, TupleBuilder,
         },
         ord::merge_batcher::MergeBatcher,
         Batch, BatchReader, Builder, Consumer, Cursor, Merger, ValueConsumer,
@@ -281,9 +282,17 @@ where
     }
 
     fn work(&mut self, source1: &OrdZSet<K, R>, source2: &OrdZSet<K, R>, fuel: &mut isize) {
-        *fuel -= self
-            .result
-            .push_merge(source1.layer.cursor(), source2.layer.cursor()) as isize;
+        // Merges always run to completion, so we can hand large merges to the
+        // runtime's thread pool, if any.
+        if let Some(merged) = par_merge_column_layers(&source1.layer, &source2.layer) {
+            *fuel -= merged.len() as isize;
+            self.result = merged.into();
+        } else {
+            *fuel -= self
+                .result
+                .push_merge(source1.layer.cursor(), source2.layer.cursor())
+                as isize;
+        }
         *fuel = max(*fuel, 1);
     }
 }